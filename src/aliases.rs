@@ -0,0 +1,160 @@
+//! Old project names that point to another project after a merge
+//! (`DATA_DIR/aliases.json`). Requests to `/api/v1/projects/{alias}/...`
+//! and `/ui/{alias}/...` are served by the target project.
+//!
+//! Old run ids after a renumbering live in the project's
+//! `run_aliases.json`; they apply only while no run has that id.
+//! `/builds/{key}` of an external run key becomes `/runs/{id}` the same way.
+//! Run ids under a merged project's old name are looked up in the target's
+//! `merged_runs.json` first: they are ids of the merged project, not of
+//! the target.
+
+use http::Method;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::{merge::MERGED_RUNS_FILE, renumber::RUN_ALIASES_FILE, run_keys, storage, util::validate_project_name, volumes::Volumes};
+
+#[derive(Debug, Default)]
+pub struct Aliases {
+    map: RwLock<BTreeMap<String, String>>,
+}
+
+fn path(data_dir: &Path) -> PathBuf {
+    data_dir.join("aliases.json")
+}
+
+impl Aliases {
+    /// Startup only; a broken file is logged and ignored.
+    pub fn load(data_dir: &Path) -> Self {
+        let map = match std::fs::read(path(data_dir)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                tracing::warn!(error=%e, "aliases.json is broken, project aliases are ignored");
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self { map: RwLock::new(map) }
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        self.map.read().unwrap().get(name).cloned()
+    }
+
+    pub fn list(&self) -> BTreeMap<String, String> {
+        self.map.read().unwrap().clone()
+    }
+
+    /// Points `alias` at `target`; aliases of `alias` move to `target` too,
+    /// so lookups never chain.
+    pub async fn set(&self, data_dir: &Path, alias: &str, target: &str) -> anyhow::Result<()> {
+        let snapshot = {
+            let mut map = self.map.write().unwrap();
+            for t in map.values_mut().filter(|t| *t == alias) {
+                *t = target.to_string();
+            }
+            map.insert(alias.to_string(), target.to_string());
+            map.clone()
+        };
+        storage::write_json(&path(data_dir), &snapshot).await
+    }
+
+    /// False when there was no such alias.
+    pub async fn remove(&self, data_dir: &Path, alias: &str) -> anyhow::Result<bool> {
+        let snapshot = {
+            let mut map = self.map.write().unwrap();
+            if map.remove(alias).is_none() {
+                return Ok(false);
+            }
+            map.clone()
+        };
+        storage::write_json(&path(data_dir), &snapshot).await?;
+        Ok(true)
+    }
+
+    /// `path` with an aliased project segment and run id replaced by their
+    /// targets. Under an alias only reads and uploads go to the target.
+    pub async fn rewrite_path(&self, volumes: &Volumes, method: &Method, path: &str) -> Option<String> {
+        for prefix in ["/api/v1/projects/", "/ui/"] {
+            let Some(rest) = path.strip_prefix(prefix) else {
+                continue;
+            };
+            let (name, tail) = split_segment(rest);
+            let target = self.resolve(name);
+            // устаревший DELETE или правка настроек по старому имени не
+            // должны задеть проект, в который его слили
+            if target.is_some() && !alias_serves(method, tail) {
+                return None;
+            }
+            let project = target.as_deref().unwrap_or(name);
+            let mut run_tail = None;
+            if let Some(target) = &target {
+                run_tail = rewrite_merged_run(volumes, target, name, tail).await;
+            }
+            if run_tail.is_none() {
+                run_tail = rewrite_run(volumes, project, tail).await;
+            }
+            if run_tail.is_none() {
+                run_tail = rewrite_build(volumes, project, tail).await;
+            }
+            if target.is_none() && run_tail.is_none() {
+                return None;
+            }
+            return Some(format!("{prefix}{project}{}", run_tail.as_deref().unwrap_or(tail)));
+        }
+        None
+    }
+}
+
+/// Requests an alias passes on: reads, and uploads of a CI that still
+/// uses the old name.
+fn alias_serves(method: &Method, tail: &str) -> bool {
+    match *method {
+        Method::GET | Method::HEAD => true,
+        Method::POST | Method::PUT | Method::PATCH => tail == "/runs" || tail.starts_with("/uploads"),
+        _ => false,
+    }
+}
+
+/// Parsed JSON file next to the project, read off the async executor;
+/// None when missing or broken.
+async fn read_map<T: serde::de::DeserializeOwned>(volumes: &Volumes, project: &str, file: &str) -> Option<T> {
+    let bytes = tokio::fs::read(storage::project_dir(volumes, project).join(file)).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn split_segment(s: &str) -> (&str, &str) {
+    s.find('/').map_or((s, ""), |i| s.split_at(i))
+}
+
+/// `/builds/{key}...` as `/runs/{id}...` of the run with that key.
+async fn rewrite_build(volumes: &Volumes, project: &str, tail: &str) -> Option<String> {
+    let (key, rest) = split_segment(tail.strip_prefix("/builds/")?);
+    run_keys::validate_key(key).ok()?;
+    validate_project_name(volumes, project).ok()?;
+    let id = run_keys::resolve(&storage::project_dir(volumes, project), key).await?;
+    Some(format!("/runs/{id}{rest}"))
+}
+
+/// `/runs/{old}...` of a renumbered run as `/runs/{new}...`.
+async fn rewrite_run(volumes: &Volumes, project: &str, tail: &str) -> Option<String> {
+    let (id, rest) = split_segment(tail.strip_prefix("/runs/")?);
+    let id: u64 = id.parse().ok()?;
+    validate_project_name(volumes, project).ok()?;
+    if tokio::fs::metadata(storage::run_dir(volumes, project, id)).await.is_ok_and(|m| m.is_dir()) {
+        return None;
+    }
+    let map: BTreeMap<u64, u64> = read_map(volumes, project, RUN_ALIASES_FILE).await?;
+    Some(format!("/runs/{}{rest}", map.get(&id)?))
+}
+
+/// `/runs/{old}...` under the old name `alias` of a project merged into
+/// `project` as `/runs/{new}...`.
+async fn rewrite_merged_run(volumes: &Volumes, project: &str, alias: &str, tail: &str) -> Option<String> {
+    let (id, rest) = split_segment(tail.strip_prefix("/runs/")?);
+    let id: u64 = id.parse().ok()?;
+    validate_project_name(volumes, project).ok()?;
+    let map: crate::merge::MergedRuns = read_map(volumes, project, MERGED_RUNS_FILE).await?;
+    Some(format!("/runs/{}{rest}", map.get(alias)?.get(&id)?))
+}
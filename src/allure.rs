@@ -1,21 +1,153 @@
 use anyhow::Context;
-use std::path::Path;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 use tokio::{fs, process::Command};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-fn clip(s: &str, max: usize) -> String {
-    if s.len() <= max {
-        s.to_string()
+use crate::{
+    executor::{self, Backend, RemoteExecution, ResourceUsage},
+    iosched::IoNice,
+    trace,
+    util::clip,
+};
+
+/// The allure CLI and where it runs.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    pub bin: String,
+    pub flavor: Flavor,
+    pub backend: Backend,
+    /// Warm-start cache dir (`GENERATOR_WARM_START`).
+    pub warm_cache: Option<PathBuf>,
+    /// Run the local CLI under `ionice`.
+    pub ionice: Option<IoNice>,
+    /// Local CLI runs longer than this are killed (`GENERATOR_TIMEOUT_SECS`).
+    pub timeout: Duration,
+}
+
+impl Generator {
+    /// Env that lets successive CLI starts reuse compiled code: Node's
+    /// module compile cache (Allure 3) or an auto-created JVM class data
+    /// sharing archive (Allure 2). Neither CLI has a daemon mode that could
+    /// be fed successive runs, so a per-run process stays.
+    fn warm_env(&self) -> Vec<(String, String)> {
+        let Some(dir) = &self.warm_cache else {
+            return Vec::new();
+        };
+        match self.flavor {
+            Flavor::Allure3 => vec![("NODE_COMPILE_CACHE".into(), dir.join("node").display().to_string())],
+            Flavor::Allure2 => {
+                // старые JVM не знают AutoCreateSharedArchive — не падаем на них
+                let mut opts = std::env::var("JAVA_OPTS").unwrap_or_default();
+                opts.push_str(&format!(
+                    " -XX:+IgnoreUnrecognizedVMOptions -XX:+AutoCreateSharedArchive -XX:SharedArchiveFile={}",
+                    dir.join("allure2.jsa").display()
+                ));
+                vec![("JAVA_OPTS".into(), opts.trim().to_string())]
+            }
+        }
+    }
+}
+
+/// Primes the warm-start caches with one `allure --version`, so the first
+/// real generation does not pay for it. Local backend only.
+pub async fn warm_up(generator: &Generator) {
+    let Some(dir) = &generator.warm_cache else { return };
+    if !matches!(generator.backend, Backend::Local) {
+        return;
+    }
+    if let Err(e) = fs::create_dir_all(dir).await {
+        warn!(dir=%dir.display(), error=%e, "create warm-start cache dir failed");
+        return;
+    }
+
+    let started = std::time::Instant::now();
+    match executor::run(&generator.backend, &generator.bin, &["--version".into()], &generator.warm_env(), "warmup", generator.timeout).await {
+        Ok(out) if out.success() => {
+            info!(elapsed_ms = started.elapsed().as_millis() as u64, "allure warm-start cache primed")
+        }
+        Ok(out) => warn!(exit_code=?out.code, stderr=%clip(&out.stderr, 2000), "allure warm-up failed"),
+        Err(e) => warn!(error=%format!("{e:#}"), "allure warm-up failed"),
+    }
+}
+
+/// Which CLI `ALLURE_BIN` is: the npm Allure 3 one or the Java Allure 2 one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    Allure2,
+    Allure3,
+}
+
+impl FromStr for Flavor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allure2" | "2" => Ok(Self::Allure2),
+            "allure3" | "3" => Ok(Self::Allure3),
+            other => anyhow::bail!("expected auto|allure2|allure3, got {other:?}"),
+        }
+    }
+}
+
+/// Probes `<bin> --version`: "2.x" means Allure 2, anything else Allure 3.
+pub async fn detect_flavor(allure_bin: &str) -> Flavor {
+    let out = match Command::new(allure_bin).arg("--version").output().await {
+        Ok(o) => o,
+        Err(e) => {
+            warn!(allure_bin=%allure_bin, error=%e, "allure --version failed, assuming Allure 3");
+            return Flavor::Allure3;
+        }
+    };
+
+    let version = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    let flavor = if version.starts_with("2.") {
+        Flavor::Allure2
     } else {
-        format!("{}...[truncated {} bytes]", &s[..max], s.len().saturating_sub(max))
+        Flavor::Allure3
+    };
+    info!(allure_bin=%allure_bin, %version, ?flavor, "detected allure CLI");
+    flavor
+}
+
+/// Extra report configuration, passed to the CLI as a generated allurerc.json.
+#[derive(Debug, Clone, Default)]
+pub struct ReportConfig {
+    /// `plugins` section: plugin id -> { import?, options }.
+    pub plugins: serde_json::Map<String, serde_json::Value>,
+    /// `TZ` of the CLI process.
+    pub timezone: Option<String>,
+}
+
+impl ReportConfig {
+    fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
     }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({ "plugins": self.plugins })
+    }
+}
+
+/// Where and with what resources a report was generated.
+#[derive(Debug, Clone, Default)]
+pub struct Generation {
+    /// Container/job, when the backend is not local.
+    pub remote: Option<RemoteExecution>,
+    pub usage: Option<ResourceUsage>,
 }
 
+/// Runs `allure generate`.
 pub async fn generate_report(
-    allure_bin: &str,
+    generator: &Generator,
     results_dir: &Path, // .../runs/<id>/allure-results
     report_dir: &Path,  // .../runs/<id>/report
-) -> anyhow::Result<()> {
+    config: &ReportConfig,
+) -> anyhow::Result<Generation> {
+    let flavor = generator.flavor;
+
     if !results_dir.exists() {
         anyhow::bail!("results_dir does not exist: {}", results_dir.display());
     }
@@ -45,58 +177,144 @@ pub async fn generate_report(
     }
 
     info!(
-        allure_bin = %allure_bin,
+        allure_bin = %generator.bin,
         results_dir = %results_dir.display(),
         report_dir = %report_dir.display(),
         results_files = file_count,
-        "running allure generate (Allure 3 CLI)"
+        ?flavor,
+        "running allure generate"
     );
 
-    // ✅ Allure 3 CLI syntax:
-    // allure generate --cwd <dir> --output <report_dir> "<pattern>"
-    // Default pattern: ./**/allure-results
-    // Мы задаём cwd = run_dir и pattern на allure-results
-    let mut cmd = Command::new(allure_bin);
-    cmd.arg("generate")
-        .arg("--cwd")
-        .arg(run_dir)
-        .arg("--output")
-        .arg(report_dir)
-        .arg("./**/allure-results");
-
-    debug!(command = ?cmd, "spawn allure command");
-
-    let out = cmd
-        .output()
-        .await
-        .with_context(|| format!("spawn allure generate: {}", allure_bin))?;
+    let mut args: Vec<OsString> = match flavor {
+        // ✅ Allure 3 CLI syntax:
+        // allure generate --cwd <dir> --output <report_dir> "<pattern>"
+        // Default pattern: ./**/allure-results
+        // Мы задаём cwd = run_dir и pattern на allure-results
+        Flavor::Allure3 => vec![
+            "generate".into(),
+            "--cwd".into(),
+            run_dir.into(),
+            "--output".into(),
+            report_dir.into(),
+            "./**/allure-results".into(),
+        ],
+        // Allure 2 (Java): allure generate <results> -o <report> --clean
+        Flavor::Allure2 => vec![
+            "generate".into(),
+            results_dir.into(),
+            "-o".into(),
+            report_dir.into(),
+            "--clean".into(),
+        ],
+    };
+
+    let mut env = generator.warm_env();
+    if let Some(dir) = &generator.warm_cache {
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("create warm-start cache dir: {}", dir.display()))?;
+    }
+    if let Some(tz) = &config.timezone {
+        env.push(("TZ".to_string(), tz.clone()));
+    }
+    env.extend(trace::current().env());
 
-    let stdout = String::from_utf8_lossy(&out.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&out.stderr).to_string();
+    if flavor == Flavor::Allure2 && !config.is_empty() {
+        // у Allure 2 нет allurerc — плагины и язык из настроек не применяются
+        warn!(run_dir=%run_dir.display(), "report plugins/language are not supported by Allure 2, ignoring");
+    } else if !config.is_empty() {
+        let rc = run_dir.join("allurerc.json");
+        fs::write(&rc, serde_json::to_vec_pretty(&config.to_json())?)
+            .await
+            .with_context(|| format!("write {}", rc.display()))?;
+        args.push("--config".into());
+        args.push(rc.into());
+    }
+
+    // .../projects/<project>/runs/<id> -> "<project>-<id>"
+    let label = format!(
+        "{}-{}",
+        run_dir
+            .parent()
+            .and_then(|p| p.parent())
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        run_dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    );
+
+    let (bin, args) = match (&generator.ionice, &generator.backend) {
+        (Some(nice), Backend::Local) => {
+            let mut wrapped = nice.args();
+            wrapped.push(generator.bin.clone().into());
+            wrapped.extend(args);
+            ("ionice", wrapped)
+        }
+        _ => (generator.bin.as_str(), args),
+    };
+
+    let out = match executor::run(&generator.backend, bin, &args, &env, &label, generator.timeout).await {
+        Ok(out) => out,
+        Err(e) if e.is::<executor::TimedOut>() => {
+            write_log(run_dir, bin, &args, &format!("{e}"), "", "").await;
+            // недогенерированный отчёт не должен отдаваться как готовый
+            if let Err(rm) = fs::remove_dir_all(report_dir).await {
+                warn!(report_dir=%report_dir.display(), error=%rm, "failed to remove partial report");
+            }
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    let (stdout, stderr) = (&out.stdout, &out.stderr);
+    let outcome = match (out.code, &out.remote) {
+        (Some(code), Some(r)) => format!("exit code {code} ({} {})", r.backend, r.name),
+        (Some(code), None) => format!("exit code {code}"),
+        (None, _) => "killed by a signal".to_string(),
+    };
+    write_log(run_dir, bin, &args, &outcome, stdout, stderr).await;
 
-    if !out.status.success() {
-        let code = out.status.code().unwrap_or(-1);
+    if !out.success() {
+        let code = out.code.unwrap_or(-1);
 
         error!(
             exit_code = code,
-            stdout = %clip(&stdout, 20_000),
-            stderr = %clip(&stderr, 20_000),
+            stdout = %clip(stdout, 20_000),
+            stderr = %clip(stderr, 20_000),
             "allure generate failed"
         );
 
         anyhow::bail!(
-            "allure generate failed (exit_code={}) stdout={} stderr={}",
+            "allure generate failed (exit_code={}{}) stdout={} stderr={}",
             code,
-            clip(&stdout, 4000),
-            clip(&stderr, 4000)
+            out.remote
+                .as_ref()
+                .map(|r| format!(", {} {}", r.backend, r.name))
+                .unwrap_or_default(),
+            clip(stdout, 4000),
+            clip(stderr, 4000)
         );
     }
 
     debug!(
-        stdout = %clip(&stdout, 2000),
-        stderr = %clip(&stderr, 2000),
+        stdout = %clip(stdout, 2000),
+        stderr = %clip(stderr, 2000),
+        usage = ?out.usage,
         "allure generate succeeded"
     );
 
-    Ok(())
+    Ok(Generation { remote: out.remote, usage: Some(out.usage) })
+}
+
+/// Full output of the last generation, kept as `<run>/generate.log`:
+/// status.json only has a clipped copy.
+async fn write_log(run_dir: &Path, bin: &str, args: &[OsString], outcome: &str, stdout: &str, stderr: &str) {
+    let command: Vec<_> = std::iter::once(bin.into()).chain(args.iter().map(|a| a.to_string_lossy())).collect();
+    let text = format!(
+        "$ {}\n{outcome}\n\n--- stdout ---\n{stdout}\n--- stderr ---\n{stderr}",
+        command.join(" ")
+    );
+    let p = run_dir.join("generate.log");
+    if let Err(e) = fs::write(&p, text).await {
+        warn!(path=%p.display(), error=%e, "failed to write generate.log");
+    }
 }
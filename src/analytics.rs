@@ -0,0 +1,257 @@
+//! Aggregations over parsed results of several runs.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::{
+    results::{self, TestResult},
+    settings::{AnomalySettings, DurationRegressionSettings, HealthSettings},
+    storage::{Anomaly, AnomalyKind, RunStats},
+};
+
+/// Labels runs can be grouped by.
+pub const GROUP_LABELS: &[&str] = &[
+    "suite", "parentSuite", "subSuite", "feature", "epic", "story", "severity", "owner",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestDurations {
+    pub key: String,
+    pub name: String,
+    /// Number of runs the test had a duration in.
+    pub samples: usize,
+    pub avg_ms: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Duration statistics per test across runs, slowest (by average) first.
+pub fn slowest_tests(runs: &[Vec<TestResult>], limit: usize) -> Vec<TestDurations> {
+    let mut by_key: HashMap<&str, (&str, Vec<u64>)> = HashMap::new();
+    for run in runs {
+        for r in run {
+            if let Some(ms) = r.duration_ms() {
+                by_key
+                    .entry(r.key())
+                    .or_insert_with(|| (r.display_name(), Vec::new()))
+                    .1
+                    .push(ms);
+            }
+        }
+    }
+
+    let mut out: Vec<TestDurations> = by_key
+        .into_iter()
+        .map(|(key, (name, mut samples))| {
+            samples.sort_unstable();
+            TestDurations {
+                key: key.to_string(),
+                name: name.to_string(),
+                samples: samples.len(),
+                avg_ms: samples.iter().sum::<u64>() / samples.len() as u64,
+                p50_ms: percentile(&samples, 50),
+                p95_ms: percentile(&samples, 95),
+                max_ms: *samples.last().unwrap_or(&0),
+            }
+        })
+        .collect();
+
+    out.sort_by(|a, b| b.avg_ms.cmp(&a.avg_ms).then_with(|| a.key.cmp(&b.key)));
+    out.truncate(limit);
+    out
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestRef {
+    pub key: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DurationChange {
+    pub key: String,
+    pub name: String,
+    pub base_ms: u64,
+    pub head_ms: u64,
+    pub increase_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlakyChange {
+    pub key: String,
+    pub name: String,
+    pub base_flaky: bool,
+    pub head_flaky: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Comparison {
+    /// Failing in head, passing in base.
+    pub newly_failed: Vec<TestRef>,
+    /// Passing in head, failing in base.
+    pub fixed: Vec<TestRef>,
+    /// Marked flaky in one run but not in the other.
+    pub flaky_changed: Vec<FlakyChange>,
+    /// Slower than in base by more than the threshold.
+    pub duration_regressions: Vec<DurationChange>,
+}
+
+/// Compares two runs test by test (tests present in only one run are ignored).
+pub fn compare(
+    base: &[TestResult],
+    head: &[TestResult],
+    rules: &DurationRegressionSettings,
+) -> Comparison {
+    let base_by_key: HashMap<&str, &TestResult> = base.iter().map(|r| (r.key(), r)).collect();
+    let mut out = Comparison::default();
+
+    for h in head {
+        let Some(b) = base_by_key.get(h.key()) else { continue };
+        let test = || TestRef {
+            key: h.key().to_string(),
+            name: h.display_name().to_string(),
+        };
+
+        let passed = |r: &TestResult| r.status.as_deref() == Some("passed");
+        if h.is_failure() && passed(b) {
+            out.newly_failed.push(test());
+        } else if passed(h) && b.is_failure() {
+            out.fixed.push(test());
+        }
+
+        if h.is_flaky() != b.is_flaky() {
+            out.flaky_changed.push(FlakyChange {
+                key: h.key().to_string(),
+                name: h.display_name().to_string(),
+                base_flaky: b.is_flaky(),
+                head_flaky: h.is_flaky(),
+            });
+        }
+
+        if let (Some(base_ms), Some(head_ms)) = (b.duration_ms(), h.duration_ms()) {
+            if head_ms < rules.min_duration_ms || base_ms == 0 {
+                continue;
+            }
+            let increase_pct = (head_ms as f64 - base_ms as f64) * 100.0 / base_ms as f64;
+            if increase_pct > rules.threshold_pct {
+                out.duration_regressions.push(DurationChange {
+                    key: h.key().to_string(),
+                    name: h.display_name().to_string(),
+                    base_ms,
+                    head_ms,
+                    increase_pct,
+                });
+            }
+        }
+    }
+
+    out.duration_regressions
+        .sort_by(|a, b| b.increase_pct.total_cmp(&a.increase_pct));
+    out
+}
+
+/// Fewer previous runs than this give no baseline.
+const MIN_BASELINE_RUNS: usize = 3;
+
+/// Checks `head` against the median of `prev` (oldest first, the last
+/// `rules.window` are used): a suite that suddenly runs far fewer tests,
+/// skips many more or finishes in half the time usually means a broken
+/// CI job rather than a better product.
+pub fn anomalies(head: &RunStats, prev: &[RunStats], rules: &AnomalySettings) -> Vec<Anomaly> {
+    let prev = &prev[prev.len().saturating_sub(rules.window)..];
+    if rules.window == 0 || prev.len() < MIN_BASELINE_RUNS {
+        return Vec::new();
+    }
+    let median = |f: fn(&RunStats) -> f64| {
+        let mut v: Vec<f64> = prev.iter().map(f).collect();
+        v.sort_by(f64::total_cmp);
+        let mid = v.len() / 2;
+        if v.len().is_multiple_of(2) { (v[mid - 1] + v[mid]) / 2.0 } else { v[mid] }
+    };
+    let skipped_pct = |s: &RunStats| if s.total == 0 { 0.0 } else { s.skipped as f64 * 100.0 / s.total as f64 };
+    let mut out = Vec::new();
+
+    let (value, baseline) = (head.total as f64, median(|s| s.total as f64));
+    if baseline > 0.0 && value < baseline * (1.0 - rules.test_drop_pct / 100.0) {
+        out.push(Anomaly { kind: AnomalyKind::TestCountDrop, value, baseline });
+    }
+    let (value, baseline) = (skipped_pct(head), median(skipped_pct));
+    if value - baseline > rules.skipped_jump_pct {
+        let round = |x: f64| (x * 10.0).round() / 10.0;
+        out.push(Anomaly { kind: AnomalyKind::SkippedJump, value: round(value), baseline: round(baseline) });
+    }
+    // без таймингов (start/stop) у результатов длительность 0 — это не аномалия
+    let (value, baseline) = (head.duration_ms as f64, median(|s| s.duration_ms as f64));
+    if value > 0.0 && baseline > 0.0 && value < baseline * (1.0 - rules.duration_drop_pct / 100.0) {
+        out.push(Anomaly { kind: AnomalyKind::DurationDrop, value, baseline });
+    }
+    out
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupStats {
+    /// Label value, `(none)` for tests without the label.
+    pub group: String,
+    pub stats: RunStats,
+}
+
+/// Test counts grouped by a label, across all given runs; groups with the
+/// most failures first. A test with several values of the label is counted
+/// in each of them.
+pub fn group_stats(runs: &[Vec<TestResult>], label: &str) -> Vec<GroupStats> {
+    let mut groups: HashMap<String, Vec<TestResult>> = HashMap::new();
+    for r in runs.iter().flatten() {
+        let mut values: Vec<&str> = r.labels(label).collect();
+        if values.is_empty() {
+            values.push("(none)");
+        }
+        values.sort_unstable();
+        values.dedup();
+        for v in values {
+            groups.entry(v.to_string()).or_default().push(r.clone());
+        }
+    }
+
+    let mut out: Vec<GroupStats> = groups
+        .into_iter()
+        .map(|(group, tests)| GroupStats {
+            stats: results::stats(&tests),
+            group,
+        })
+        .collect();
+
+    out.sort_by(|a, b| {
+        let fails = |g: &GroupStats| g.stats.failed + g.stats.broken;
+        fails(b).cmp(&fails(a)).then_with(|| a.group.cmp(&b.group))
+    });
+    out
+}
+
+/// Severity-weighted pass rate in percent: every executed test contributes
+/// its severity weight, passed ones to the numerator. Skipped tests are
+/// ignored; `None` when nothing was executed.
+pub fn health_score(results: &[TestResult], health: &HealthSettings) -> Option<f64> {
+    let (mut passed, mut total) = (0.0, 0.0);
+    for r in results {
+        if r.status.as_deref() == Some("skipped") {
+            continue;
+        }
+        let severity = r.labels("severity").next().unwrap_or("normal");
+        let weight = health.weights.get(severity).copied().unwrap_or(1.0);
+        total += weight;
+        if r.status.as_deref() == Some("passed") {
+            passed += weight;
+        }
+    }
+    (total > 0.0).then(|| passed * 100.0 / total)
+}
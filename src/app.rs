@@ -5,7 +5,9 @@ use axum::{
 };
 use http::{header::HeaderName, Request};
 use std::time::Duration;
-use axum::routing::delete;
+use axum::body::Body;
+use tower::ServiceExt;
+use axum::{extract::DefaultBodyLimit, routing::{delete, put}};
 use tower_http::{
     classify::ServerErrorsFailureClass,
     request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer, RequestId},
@@ -13,8 +15,10 @@ use tower_http::{
 };
 use tracing::{info_span, Span};
 
-use crate::handlers::{api, ui};
+use crate::aliases::Aliases;
+use crate::handlers::{admin, api, ui, worker};
 use crate::state::AppState;
+use crate::volumes::Volumes;
 
 async fn root_redirect() -> impl IntoResponse {
     Redirect::temporary("/ui/")
@@ -59,37 +63,215 @@ impl OnFailure<ServerErrorsFailureClass> for MyOnFailure {
 }
 
 
+/// Full router: everything on one listener.
 pub fn router(state: AppState) -> Router {
-    let request_id_header = HeaderName::from_static("x-request-id");
+    finish(read_routes().merge(write_routes()), state)
+}
+
+/// UI, report files and the API the UI reads (`LISTEN` when
+/// `UPLOAD_LISTEN` is set).
+pub fn read_router(state: AppState) -> Router {
+    finish(read_routes().layer(axum::Extension(ui::ReadOnly)), state)
+}
+
+/// Uploads and everything else that changes data (`UPLOAD_LISTEN`).
+pub fn write_router(state: AppState) -> Router {
+    finish(write_routes(), state)
+}
 
+fn read_routes() -> Router<AppState> {
     Router::new()
         // Root -> /ui/
         .route("/", get(root_redirect))
+        .route("/metrics", get(api::metrics))
 
         // ======================
         // API
         // ======================
+        .route("/api/v1/projects", get(api::list_projects))
         .route("/api/v1/projects/summary", get(api::list_projects_summary))
+        .route("/api/v1/launches/{launch}", get(api::get_launch))
+        .route("/api/v1/projects/stale", get(api::list_stale_projects))
+        .route("/api/v1/uploads", get(api::list_uploads))
+        .route("/api/v1/uploads/{upload_id}/progress", get(api::upload_progress))
+        .route("/api/v1/uploads/{upload_id}/events", get(api::upload_events))
+        .route("/api/v1/jobs/{job_id}", get(api::get_job))
+        .route("/api/v1/generator", get(api::generator_health))
+        .route("/api/v1/federation", get(api::federation_directory))
+        .route("/api/v1/stats", get(api::storage_stats))
+        .route("/api/schemas", get(api::list_schemas))
+        .route("/api/schemas/{name}", get(api::get_schema))
+        .route("/api/v1/projects/{project}/runs", get(api::list_runs))
+        .route("/api/v1/projects/{project}/slowest", get(api::slowest_tests))
+        .route("/api/v1/projects/{project}/compare", get(api::compare_runs))
+        .route("/api/v1/projects/{project}/compare.md", get(api::compare_runs_markdown))
+        .route("/api/v1/projects/{project}/stats", get(api::project_stats))
+        .route("/api/v1/projects/{project}/badge/health.svg", get(api::health_badge))
+        .route("/api/v1/projects/{project}/muted", get(api::muted_budget))
+        .route("/api/v1/projects/{project}/report-size", get(api::report_size))
+        .route("/api/v1/projects/{project}/config", get(api::get_project_config))
+        .route("/api/v1/projects/{project}/categories", get(api::get_project_categories))
+        .route("/api/v1/projects/{project}/export/static", get(api::export_static))
+        // личное избранное, а не данные проекта — доступно с UI-адреса
+        .route("/api/v1/projects/{project}/star", put(api::star_project).delete(api::star_project))
+        .route("/api/v1/projects/{project}/runs/{run_id}", get(api::get_run))
+        .route("/api/v1/projects/{project}/runs/{run_id}/stats", get(api::run_stats))
+        .route("/api/v1/projects/{project}/runs/{run_id}/log", get(api::get_run_log))
+        .route("/api/v1/projects/{project}/runs/{run_id}/events", get(api::run_events))
+        .route("/api/v1/projects/{project}/runs/{run_id}/gate", get(api::run_gate))
+        .route("/api/v1/projects/{project}/runs/{run_id}/attachments/top", get(api::top_attachments))
+        .route("/api/v1/projects/{project}/runs/{run_id}/manifest", get(api::run_manifest))
+        .route("/api/v1/projects/{project}/runs/{run_id}/verify", get(api::verify_run))
+        .route("/api/v1/projects/{project}/runs/{run_id}/artifacts", get(api::list_artifacts))
+        .route("/api/v1/projects/{project}/runs/{run_id}/artifacts/{name}", get(api::get_artifact))
+        .route("/api/v1/projects/{project}/runs/{run_id}/backup", get(api::backup_run))
+        .route("/api/v1/projects/{project}/runs/{run_id}/archive", get(api::download_archive))
+
+        // ======================
+        // UI
+        // ======================
+        .route("/ui/", get(ui::ui_index))
+        .route("/ui/federation", get(ui::ui_federation))
+        .route("/ui/{project}/", get(ui::ui_project_home))
+        .route("/ui/{project}/runs/", get(ui::ui_project_runs))
+        .route("/ui/{project}/latest/", get(ui::ui_latest))
+        .route("/ui/{project}/compare", get(ui::ui_compare))
+
+        // Allure report static files
+        .route("/ui/{project}/runs/{run_id}/", get(ui::ui_run_index))
+        .route("/ui/{project}/runs/{run_id}/{*tail}", get(ui::ui_run_files))
+}
+
+fn write_routes() -> Router<AppState> {
+    Router::new()
+        // ======================
+        // API
+        // ======================
         .route("/api/v1/projects/{project}", delete(api::delete_project))
+        .route("/api/v1/projects/{project}/runs/{run_id}", delete(api::delete_run))
+        .route("/api/v1/projects/{project}/prune", post(api::prune_runs))
+        .route("/api/v1/projects/{project}/config", put(api::put_project_config))
+        .route(
+            "/api/v1/projects/{project}/categories",
+            put(api::put_project_categories).delete(api::delete_project_categories),
+        )
+        .route("/api/v1/uploads/{upload_id}", delete(api::abort_upload))
+        // размер архива ограничивают лимиты распаковки, а не 2 MiB axum по умолчанию;
+        // ошибки загрузок --- JSON с retryable, по нему CI решает, повторять ли
         .route(
             "/api/v1/projects/{project}/runs",
-            get(api::list_runs).post(api::upload_run),
+            post(api::upload_run)
+                .put(api::upload_run_raw)
+                .layer(DefaultBodyLimit::disable())
+                .layer(axum::middleware::map_response(api::structured_error)),
+        )
+        .route("/api/v1/projects/{project}/runs/import", post(api::import_run).layer(DefaultBodyLimit::disable()))
+        .route("/api/v1/projects/{project}/uploads", post(api::create_resumable))
+        .route(
+            "/api/v1/projects/{project}/uploads/{upload_id}",
+            get(api::resumable_status)
+                .patch(api::patch_resumable)
+                .delete(api::cancel_resumable)
+                .layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/api/v1/projects/{project}/uploads/{upload_id}/finalize",
+            post(api::finalize_resumable).layer(axum::middleware::map_response(api::structured_error)),
         )
         .route(
             "/api/v1/projects/{project}/runs/{run_id}/regenerate",
-            post(api::regenerate_run),
+            post(api::regenerate_run).layer(axum::middleware::map_response(api::structured_error)),
         )
 
         // ======================
-        // UI
+        // Admin
         // ======================
-        .route("/ui/", get(ui::ui_index))
-        .route("/ui/{project}/", get(ui::ui_project_page))
-        .route("/ui/{project}/latest/", get(ui::ui_latest))
+        .route("/api/v1/admin/purge", post(admin::purge))
+        .route("/api/v1/admin/audit", get(admin::audit_log))
+        .route("/api/v1/admin/usage", get(admin::usage_report))
+        .route("/api/v1/admin/retention/dry-run", get(admin::retention_dry_run))
+        .route("/api/v1/admin/locks", get(admin::list_locks))
+        .route("/api/v1/admin/locks/{project}/force-unlock", post(admin::force_unlock))
+        .route("/api/v1/admin/projects/duplicates", get(admin::project_duplicates))
+        .route("/api/v1/admin/projects/{project}/merge", post(admin::merge_project))
+        .route("/api/v1/admin/projects/{project}/renumber", post(admin::renumber_runs))
+        .route("/api/v1/admin/projects/{project}/next-run-id", post(admin::rebuild_next_run_id))
+        .route("/api/v1/admin/volumes", get(admin::list_volumes))
+        .route("/api/v1/admin/volumes/rebalance", post(admin::rebalance_volumes))
+        .route("/api/v1/admin/projects/{project}/volume", post(admin::set_project_volume))
+        .route("/api/v1/admin/storage-classes", get(admin::list_storage_classes))
+        .route(
+            "/api/v1/admin/storage-classes/{name}",
+            put(admin::put_storage_class).delete(admin::delete_storage_class),
+        )
+        .route("/api/v1/admin/projects/{project}/storage-class", put(admin::set_project_storage_class))
+        .route("/api/v1/admin/aliases", get(admin::list_aliases))
+        .route("/api/v1/admin/aliases/{alias}", delete(admin::delete_alias))
+        .route("/api/v1/admin/holds", get(admin::list_holds))
+        .route(
+            "/api/v1/admin/holds/{project}",
+            put(admin::set_project_hold).delete(admin::clear_project_hold),
+        )
+        .route(
+            "/api/v1/admin/holds/{project}/runs/{run_id}",
+            put(admin::set_run_hold).delete(admin::clear_run_hold),
+        )
 
-        // Allure report static files
-        .route("/ui/{project}/runs/{run_id}/", get(ui::ui_run_index))
-        .route("/ui/{project}/runs/{run_id}/{*tail}", get(ui::ui_run_files))
+        // ======================
+        // Remote workers
+        // ======================
+        .route("/api/v1/worker/claim", post(worker::claim))
+        .route("/api/v1/worker/jobs/{job_id}/results", get(worker::job_results))
+        .route(
+            "/api/v1/worker/jobs/{job_id}/report",
+            put(worker::job_report).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/api/v1/worker/jobs/{job_id}/fail", post(worker::job_fail))
+        .merge(test_hook_routes())
+}
+
+#[cfg(feature = "test-hooks")]
+fn test_hook_routes() -> Router<AppState> {
+    use crate::handlers::test_hooks;
+    Router::new().route(
+        "/api/v1/test-hooks",
+        get(test_hooks::get_faults)
+            .put(test_hooks::put_faults)
+            .delete(test_hooks::clear_faults),
+    )
+}
+
+#[cfg(not(feature = "test-hooks"))]
+fn test_hook_routes() -> Router<AppState> {
+    Router::new()
+}
+
+/// `STARTUP_CHECK=read-only` after a failed volume check: only safe
+/// methods go through.
+async fn reject_writes_if_read_only(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    match &state.read_only {
+        Some(reason) if !req.method().is_safe() => (
+            http::StatusCode::SERVICE_UNAVAILABLE,
+            format!("Service is read-only: {reason}"),
+        )
+            .into_response(),
+        _ => next.run(req).await,
+    }
+}
+
+fn finish(routes: Router<AppState>, state: AppState) -> Router {
+    let request_id_header = HeaderName::from_static("x-request-id");
+    let aliases = state.aliases.clone();
+    let volumes = state.volumes.clone();
+
+    let app = routes
+        .layer(axum::middleware::from_fn_with_state(state.clone(), reject_writes_if_read_only))
+        // request id и traceparent уходят дальше с хуками, уведомлениями и дочерними процессами
+        .layer(axum::middleware::from_fn(crate::trace::propagate))
         // request id: генерим и прокидываем обратно в response header
         .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
         .layer(SetRequestIdLayer::new(request_id_header.clone(), MakeRequestUuid))
@@ -106,5 +288,126 @@ pub fn router(state: AppState) -> Router {
                 .on_response(MyOnResponse)
                 .on_failure(MyOnFailure),
         )
-        .with_state(state)
+        .with_state(state);
+    // старое имя проекта после слияния и старый номер прогона после
+    // перенумерации: путь переписывается до роутинга
+    Router::new().fallback_service(tower::service_fn(move |req: Request<Body>| {
+        let (app, aliases, volumes) = (app.clone(), aliases.clone(), volumes.clone());
+        async move { app.oneshot(resolve_alias(&aliases, &volumes, req).await).await }
+    }))
+}
+
+async fn resolve_alias(aliases: &Aliases, volumes: &Volumes, mut req: Request<Body>) -> Request<Body> {
+    if let Some(path) = aliases.rewrite_path(volumes, req.method(), req.uri().path()).await {
+        let target = match req.uri().query() {
+            Some(q) => format!("{path}?{q}"),
+            None => path,
+        };
+        if let Ok(uri) = target.parse() {
+            *req.uri_mut() = uri;
+        }
+    }
+    req
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{allure::Flavor, config::Config, storage};
+    use http::{Method, StatusCode};
+
+    /// State over a fresh `DATA_DIR` holding project `demo` with one
+    /// successful run.
+    async fn state_with_run() -> AppState {
+        let root = std::env::temp_dir().join(format!("allure-router-test-{}", uuid::Uuid::new_v4()));
+        let mut config = Config::from_env().unwrap();
+        config.data_dir = root.join("data");
+        config.tmp_dir = root.join("tmp");
+        config.allure_bin = "true".into();
+        config.admin_token = None;
+        config.upload_listen = None;
+        let state = AppState::new(config, Flavor::Allure3);
+
+        storage::ensure_project_dirs(&state.volumes, "demo").await.unwrap();
+        let pdir = storage::project_dir(&state.volumes, "demo");
+        let run_id = storage::reserve_next_run_id(&pdir).await.unwrap();
+        let run_dir = storage::run_dir(&state.volumes, "demo", run_id);
+        tokio::fs::create_dir_all(run_dir.join("allure-results")).await.unwrap();
+        let result = r#"{"uuid":"a","name":"t","status":"passed","start":1,"stop":2}"#;
+        tokio::fs::write(run_dir.join("allure-results/a-result.json"), result).await.unwrap();
+        storage::write_json(&run_dir.join("meta.json"), &storage::Meta::default()).await.unwrap();
+        storage::write_run_status(&run_dir, storage::Status::Success, None).await;
+        storage::set_latest_run_id(&pdir, run_id).await.unwrap();
+        state
+    }
+
+    async fn call(state: &AppState, method: Method, uri: &str) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        let resp = router(state.clone()).oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn lists_runs() {
+        let state = state_with_run().await;
+        let (status, body) = call(&state, Method::GET, "/api/v1/projects/demo/runs").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.to_string().contains(r#""run_id":1"#), "{body}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn regenerates_run() {
+        let state = state_with_run().await;
+        let (status, body) = call(&state, Method::POST, "/api/v1/projects/demo/runs/1/regenerate").await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["status"], "success");
+        assert_eq!(storage::read_latest_run_id(&storage::project_dir(&state.volumes, "demo")).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn deletes_project() {
+        let state = state_with_run().await;
+        let (_, body) = call(&state, Method::GET, "/api/v1/projects").await;
+        assert!(body.to_string().contains("demo"), "{body}");
+        let (status, body) = call(&state, Method::DELETE, "/api/v1/projects/demo").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["deleted"], true);
+        assert!(!storage::project_dir(&state.volumes, "demo").exists());
+
+        let (_, body) = call(&state, Method::GET, "/api/v1/projects").await;
+        assert!(!body.to_string().contains("demo"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn alias_forwards_reads_only() {
+        let state = state_with_run().await;
+        state.aliases.set(&state.data_dir, "old", "demo").await.unwrap();
+        let (status, body) = call(&state, Method::GET, "/api/v1/projects/old/runs").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.to_string().contains(r#""run_id":1"#), "{body}");
+
+        call(&state, Method::DELETE, "/api/v1/projects/old/runs/1").await;
+        call(&state, Method::DELETE, "/api/v1/projects/old").await;
+        assert!(storage::run_dir(&state.volumes, "demo", 1).is_dir());
+    }
+
+    #[tokio::test]
+    async fn summarizes_projects() {
+        let state = state_with_run().await;
+        let (status, body) = call(&state, Method::GET, "/api/v1/projects/summary").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_projects"], 1);
+    }
+
+    #[tokio::test]
+    async fn wrong_method_is_405() {
+        let state = state_with_run().await;
+        let (status, _) = call(&state, Method::PATCH, "/api/v1/projects/demo/runs").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        let (status, _) = call(&state, Method::GET, "/api/v1/projects/demo/runs/1/regenerate").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
 }
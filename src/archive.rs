@@ -0,0 +1,725 @@
+//! Extraction of uploaded results archives — zip, tar.gz or tar.zst, told
+//! apart by their magic bytes — with the same path and size checks for
+//! all of them.
+
+use anyhow::Context;
+use flate2::read::MultiGzDecoder;
+use sha2::{Digest, Sha256};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::util::is_windows_reserved;
+
+/// Configured by `MAX_ZIP_FILES`, `MAX_TOTAL_UNCOMPRESSED`,
+/// `MAX_SINGLE_FILE` and `MAX_COMPRESSION_RATIO`, see
+/// [`crate::config::Config::unzip_limits`].
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Max number of files in the archive.
+    pub max_files: usize,
+    /// Max total uncompressed bytes across all files.
+    pub max_total_uncompressed: u64,
+    /// Max size of a single extracted file.
+    pub max_single_file: u64,
+    /// Max uncompressed/compressed ratio of an entry and of the whole
+    /// archive; 0 disables the check.
+    pub max_ratio: u64,
+}
+
+/// Less than this is never a bomb, whatever the ratio: a few hundred KiB
+/// of blank log compress a thousandfold.
+const RATIO_MIN_BYTES: u64 = 1024 * 1024;
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_files: 10_000,
+            max_total_uncompressed: 2 * 1024 * 1024 * 1024, // 2 GiB
+            max_single_file: 512 * 1024 * 1024,             // 512 MiB
+            max_ratio: 200,
+        }
+    }
+}
+
+impl Limits {
+    /// Rejects `what` when `uncompressed` bytes come out of `compressed`
+    /// at more than `max_ratio`.
+    fn check_ratio(&self, what: &str, uncompressed: u64, compressed: u64) -> anyhow::Result<()> {
+        if self.max_ratio == 0 || uncompressed < RATIO_MIN_BYTES {
+            return Ok(());
+        }
+        if uncompressed > compressed.max(1).saturating_mul(self.max_ratio) {
+            return Err(LimitExceeded(format!(
+                "{what} expands more than {}x (MAX_COMPRESSION_RATIO): {compressed} bytes to at least {uncompressed}",
+                self.max_ratio
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// An archive over one of the [`Limits`]; uploads answer it with 413.
+#[derive(Debug)]
+pub struct LimitExceeded(pub String);
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Whether `e` (or an error it wraps) is a [`LimitExceeded`].
+pub fn is_limit_exceeded(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| c.is::<LimitExceeded>())
+}
+
+/// Extraction progress, readable while the archive is being unpacked.
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// Uncompressed size declared by the archive; 0 until it is opened.
+    pub total: AtomicU64,
+    pub done: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Entries may be stored, deflated or zstd-compressed.
+    Zip,
+    TarGz,
+    TarZst,
+}
+
+impl Format {
+    /// By magic bytes: `PK` for zip, `1f 8b` for gzip, `28 b5 2f fd` for zstd.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => Some(Self::Zip),
+            [0x1f, 0x8b, ..] => Some(Self::TarGz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::TarZst),
+            _ => None,
+        }
+    }
+
+    /// By the first bytes of the file at `path`.
+    pub fn detect_file(path: &Path) -> anyhow::Result<Option<Self>> {
+        let mut head = Vec::with_capacity(4);
+        std::fs::File::open(path)
+            .and_then(|f| f.take(4).read_to_end(&mut head))
+            .with_context(|| format!("read {}", path.display()))?;
+        Ok(Self::detect(&head))
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+            Self::TarZst => "tar.zst",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+            Self::TarZst => "application/zstd",
+        }
+    }
+}
+
+const FORMATS: [Format; 3] = [Format::Zip, Format::TarGz, Format::TarZst];
+
+/// Name of a kept original upload: `results.zip` for the first part,
+/// `results.2.zip` for the second and so on.
+fn original_name(part: usize, format: Format) -> String {
+    match part {
+        1 => format!("results.{}", format.extension()),
+        n => format!("results.{n}.{}", format.extension()),
+    }
+}
+
+/// Keeps the uploaded archives in `run_dir` (`KEEP_UPLOAD_ARCHIVE`), so
+/// the run can be extracted again. Nothing is kept when a part is not an
+/// archive: its upload fails anyway.
+pub async fn keep_originals(run_dir: &Path, archives: &[PathBuf]) -> anyhow::Result<()> {
+    let Some(formats) = archives.iter().map(|a| Format::detect_file(a).ok().flatten()).collect::<Option<Vec<_>>>() else {
+        return Ok(());
+    };
+    for (i, (src, format)) in archives.iter().zip(formats).enumerate() {
+        let path = run_dir.join(original_name(i + 1, format));
+        tokio::fs::copy(src, &path).await.with_context(|| format!("write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Kept original archives of a run, in part order; empty when none were kept.
+pub fn originals(run_dir: &Path) -> Vec<(PathBuf, Format)> {
+    let mut out = Vec::new();
+    for part in 1.. {
+        let found = FORMATS
+            .iter()
+            .map(|f| (run_dir.join(original_name(part, *f)), *f))
+            .find(|(path, _)| path.is_file());
+        match found {
+            Some(x) => out.push(x),
+            None => break,
+        }
+    }
+    out
+}
+
+/// Lowercase hex SHA-256 of each archive file, hashed off the async runtime.
+pub async fn sha256(archives: &[PathBuf]) -> anyhow::Result<Vec<String>> {
+    let archives = archives.to_vec();
+    tokio::task::spawn_blocking(move || {
+        archives
+            .iter()
+            .map(|path| {
+                let mut hasher = Sha256::new();
+                std::fs::File::open(path)
+                    .and_then(|mut f| std::io::copy(&mut f, &mut hasher))
+                    .with_context(|| format!("hash {}", path.display()))?;
+                Ok(format!("{:x}", hasher.finalize()))
+            })
+            .collect()
+    })
+    .await
+    .context("hash archives")?
+}
+
+/// Extract zip safely into dest_dir:
+/// - rejects absolute paths
+/// - rejects ".." path traversal
+/// - rejects symlinks, devices and other special entries
+/// - limits number of files
+/// - limits uncompressed sizes (per-file and total)
+/// - limits the compression ratio (per-file and total)
+pub async fn unzip_safely(
+    zip_bytes: Vec<u8>,
+    dest_dir: PathBuf,
+    limits: Limits,
+) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        std::fs::create_dir_all(&dest_dir).context("create dest dir")?;
+        let len = zip_bytes.len() as u64;
+        unzip_safely_blocking(Cursor::new(zip_bytes), len, &dest_dir, limits, &mut Counters::default(), &Progress::default())
+    })
+    .await
+    .context("join unzip task")??;
+    Ok(())
+}
+
+/// Extracts zip, tar.gz or tar.zst archive files (see [`Format::detect`])
+/// into one `dest_dir` with the checks of [`unzip_safely`], reporting
+/// extracted bytes to `progress`. The limits cover all archives together;
+/// a file of a later archive replaces a same-named one of an earlier archive.
+pub async fn extract_with_progress(
+    archives: Vec<PathBuf>,
+    dest_dir: PathBuf,
+    limits: Limits,
+    progress: Arc<Progress>,
+) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let several = archives.len() > 1;
+        let mut parts = Vec::with_capacity(archives.len());
+        for (i, path) in archives.into_iter().enumerate() {
+            match Format::detect_file(&path)? {
+                Some(format) => parts.push((format, path)),
+                None if several => anyhow::bail!("part {}: not a zip, tar.gz or tar.zst archive", i + 1),
+                None => anyhow::bail!("not a zip, tar.gz or tar.zst archive"),
+            }
+        }
+
+        std::fs::create_dir_all(&dest_dir).context("create dest dir")?;
+        let mut counters = Counters::default();
+        for (i, (format, path)) in parts.iter().enumerate() {
+            let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+            let len = file.metadata().with_context(|| format!("stat {}", path.display()))?.len();
+            let file = BufReader::new(file);
+            let extracted = match format {
+                Format::Zip => unzip_safely_blocking(file, len, &dest_dir, limits, &mut counters, &progress),
+                Format::TarGz => untar_gz_blocking(file, len, &dest_dir, limits, &mut counters, &progress),
+                Format::TarZst => untar_zst_blocking(file, len, &dest_dir, limits, &mut counters, &progress),
+            };
+            if several {
+                extracted.with_context(|| format!("part {}", i + 1))?;
+            } else {
+                extracted?;
+            }
+        }
+        anyhow::Ok(())
+    })
+    .await
+    .context("join extract task")??;
+    Ok(())
+}
+
+/// Running totals checked against [`Limits`] while extracting.
+#[derive(Default)]
+struct Counters {
+    files: usize,
+    total_uncompressed: u64,
+    /// Size of the archives opened so far.
+    compressed: u64,
+}
+
+impl Counters {
+    fn add_file(&mut self, limits: &Limits) -> anyhow::Result<()> {
+        self.files += 1;
+        if self.files > limits.max_files {
+            return Err(LimitExceeded(format!("archive has more than {} files (MAX_ZIP_FILES)", limits.max_files)).into());
+        }
+        Ok(())
+    }
+}
+
+/// Destination of an entry inside `dest_dir`, or an error for paths that
+/// would land outside it.
+fn entry_path(dest_dir: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    let rel = sanitize_entry_path(name).with_context(|| format!("bad entry path: {}", name))?;
+    let out_path = dest_dir.join(&rel);
+    if !is_within_dir(&out_path, dest_dir)? {
+        anyhow::bail!("entry escapes destination: {}", name);
+    }
+    Ok(out_path)
+}
+
+/// Copies one file entry to `out_path` under the size limits; an entry
+/// whose `compressed` size is known is also held to `max_ratio`.
+fn write_entry(
+    reader: &mut impl Read,
+    out_path: &Path,
+    name: &str,
+    compressed: Option<u64>,
+    limits: &Limits,
+    counters: &mut Counters,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("mkdir {:?}", parent))?;
+    }
+
+    // права из архива не переносятся: файл создаётся по umask, так что
+    // setuid/setgid и исполняемый бит из записи на диск не попадают
+    let mut out = std::fs::File::create(out_path).with_context(|| format!("create {:?}", out_path))?;
+
+    let mut written: u64 = 0;
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        let n = reader.read(&mut buf).context("read archive entry")?;
+        if n == 0 {
+            break;
+        }
+
+        written = written.saturating_add(n as u64);
+        if written > limits.max_single_file {
+            return Err(LimitExceeded(format!("{name} exceeds {} bytes (MAX_SINGLE_FILE)", limits.max_single_file)).into());
+        }
+
+        // заявленным размерам не верим: проверяем то, что реально распаковалось
+        if let Some(compressed) = compressed {
+            limits.check_ratio(name, written, compressed)?;
+        }
+
+        counters.total_uncompressed = counters.total_uncompressed.saturating_add(n as u64);
+        limits.check_ratio("archive", counters.total_uncompressed, counters.compressed)?;
+        if counters.total_uncompressed > limits.max_total_uncompressed {
+            return Err(LimitExceeded(format!(
+                "archive unpacks to more than {} bytes (MAX_TOTAL_UNCOMPRESSED)",
+                limits.max_total_uncompressed
+            ))
+            .into());
+        }
+
+        out.write_all(&buf[..n]).context("write extracted file")?;
+        progress.done.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    out.flush().ok();
+    Ok(())
+}
+
+/// Kind of a non-regular entry by its unix mode, e.g. `symlink`; None
+/// for files, directories and entries without a file type.
+fn special_kind(mode: u32) -> Option<&'static str> {
+    match mode & 0o170000 {
+        0 | 0o100000 | 0o040000 => None,
+        0o120000 => Some("symlink"),
+        0o020000 | 0o060000 => Some("device"),
+        0o010000 => Some("fifo"),
+        0o140000 => Some("socket"),
+        _ => Some("unknown type"),
+    }
+}
+
+fn unzip_safely_blocking(
+    reader: impl Read + Seek,
+    len: u64,
+    dest_dir: &Path,
+    limits: Limits,
+    counters: &mut Counters,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    let mut archive = zip::ZipArchive::new(reader).context("open zip")?;
+
+    let mut declared_total: u64 = 0;
+    for i in 0..archive.len() {
+        declared_total = declared_total.saturating_add(archive.by_index_raw(i).context("read entry")?.size());
+    }
+    progress.total.fetch_add(declared_total, Ordering::Relaxed);
+    counters.compressed += len;
+    limits.check_ratio("archive", declared_total, len)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).context("read entry")?;
+        let name = file.name().to_string();
+
+        counters.add_file(&limits)?;
+        let out_path = entry_path(dest_dir, &name)?;
+        // без проверки симлинк распаковался бы обычным файлом с путём внутри
+        if let Some(kind) = file.unix_mode().and_then(special_kind) {
+            anyhow::bail!("{name}: {kind} entries are not allowed");
+        }
+
+        if file.is_dir() {
+            std::fs::create_dir_all(&out_path).with_context(|| format!("mkdir {:?}", out_path))?;
+            continue;
+        }
+
+        let declared = file.size();
+        if declared > limits.max_single_file {
+            return Err(LimitExceeded(format!("{name} is {declared} bytes, limit {} (MAX_SINGLE_FILE)", limits.max_single_file)).into());
+        }
+        let compressed = file.compressed_size();
+        limits.check_ratio(&name, declared, compressed)?;
+
+        write_entry(&mut file, &out_path, &name, Some(compressed), &limits, counters, progress)?;
+    }
+
+    Ok(())
+}
+
+const TAR_BLOCK: usize = 512;
+
+fn untar_gz_blocking(
+    mut reader: impl Read + Seek,
+    len: u64,
+    dest_dir: &Path,
+    limits: Limits,
+    counters: &mut Counters,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    // ISIZE из трейлера gzip — размер tar по модулю 2^32, для прогресса
+    // хватает; настоящий размер не меньше, так что годится и для проверки сжатия
+    counters.compressed += len;
+    if len >= 4 {
+        let mut isize = [0u8; 4];
+        reader.seek(SeekFrom::End(-4)).context("read gzip trailer")?;
+        reader.read_exact(&mut isize).context("read gzip trailer")?;
+        reader.rewind().context("read gzip")?;
+        let isize = u32::from_le_bytes(isize) as u64;
+        progress.total.fetch_add(isize, Ordering::Relaxed);
+        limits.check_ratio("archive", isize, len)?;
+    }
+    untar_blocking(MultiGzDecoder::new(reader), dest_dir, limits, counters, progress)
+}
+
+/// Longest zstd frame header.
+const ZSTD_FRAME_HEADER_MAX: u64 = 18;
+
+fn untar_zst_blocking(
+    mut reader: impl Read + Seek,
+    len: u64,
+    dest_dir: &Path,
+    limits: Limits,
+    counters: &mut Counters,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    // размер есть в заголовке кадра, если zstd сжимал файл, а не поток из pipe
+    counters.compressed += len;
+    let mut header = Vec::new();
+    (&mut reader).take(ZSTD_FRAME_HEADER_MAX).read_to_end(&mut header).context("read zstd frame header")?;
+    reader.rewind().context("read zstd")?;
+    if let Ok(Some(size)) = zstd::zstd_safe::get_frame_content_size(&header) {
+        progress.total.fetch_add(size, Ordering::Relaxed);
+        limits.check_ratio("archive", size, len)?;
+    }
+    let tar = zstd::stream::read::Decoder::new(reader).context("open zstd stream")?;
+    untar_blocking(tar, dest_dir, limits, counters, progress)
+}
+
+/// ustar / GNU / pax tar. Only regular files and directories are
+/// extracted; links and device entries are refused.
+fn untar_blocking(
+    mut tar: impl Read,
+    dest_dir: &Path,
+    limits: Limits,
+    counters: &mut Counters,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    // имя следующей записи из GNU long name (`L`) или pax-заголовка (`x`)
+    let mut long_name: Option<String> = None;
+    let mut header = [0u8; TAR_BLOCK];
+
+    loop {
+        if !read_block(&mut tar, &mut header)? || header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let size = tar_size(&header[124..136]).context("bad tar entry size")?;
+        let kind = header[156];
+
+        match kind {
+            b'L' | b'x' | b'g' => {
+                if size > 1024 * 1024 {
+                    anyhow::bail!("tar extended header too large ({size} bytes)");
+                }
+                let mut data = vec![0u8; size as usize];
+                tar.read_exact(&mut data).context("read tar extended header")?;
+                skip_padding(&mut tar, size)?;
+                match kind {
+                    b'L' => long_name = Some(c_string(&data)),
+                    b'x' => long_name = pax_path(&data).or(long_name),
+                    _ => {}
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let name = long_name.take().unwrap_or_else(|| ustar_name(&header));
+        // `tar czf r.tgz -C results .` начинается с записи `./` — это сам dest_dir
+        if kind == b'5' && name.split('/').all(|p| p.is_empty() || p == ".") {
+            continue;
+        }
+        counters.add_file(&limits)?;
+        let out_path = entry_path(dest_dir, &name)?;
+
+        match kind {
+            b'0' | 0 | b'7' => {
+                if size > limits.max_single_file {
+                    return Err(LimitExceeded(format!("{name} is {size} bytes, limit {} (MAX_SINGLE_FILE)", limits.max_single_file)).into());
+                }
+                let mut entry = (&mut tar).take(size);
+                write_entry(&mut entry, &out_path, &name, None, &limits, counters, progress)?;
+                if entry.limit() != 0 {
+                    anyhow::bail!("tar truncated in {}", name);
+                }
+                skip_padding(&mut tar, size)?;
+            }
+            b'5' => {
+                std::fs::create_dir_all(&out_path).with_context(|| format!("mkdir {:?}", out_path))?;
+                skip(&mut tar, size)?;
+            }
+            b'1' => anyhow::bail!("{name}: hardlink entries are not allowed"),
+            b'2' => anyhow::bail!("{name}: symlink entries are not allowed"),
+            b'3' | b'4' => anyhow::bail!("{name}: device entries are not allowed"),
+            b'6' => anyhow::bail!("{name}: fifo entries are not allowed"),
+            other => anyhow::bail!("tar entry {} has unsupported type {:?}", name, other as char),
+        }
+    }
+
+    Ok(())
+}
+
+/// False at a clean end of the stream (no trailing zero blocks).
+fn read_block(r: &mut impl Read, block: &mut [u8; TAR_BLOCK]) -> anyhow::Result<bool> {
+    let mut filled = 0;
+    while filled < TAR_BLOCK {
+        let n = r.read(&mut block[filled..]).context("read tar")?;
+        if n == 0 {
+            if filled == 0 {
+                return Ok(false);
+            }
+            anyhow::bail!("tar truncated in a header");
+        }
+        filled += n;
+    }
+    Ok(true)
+}
+
+fn skip(r: &mut impl Read, n: u64) -> anyhow::Result<()> {
+    let skipped = std::io::copy(&mut r.take(n), &mut std::io::sink()).context("read tar")?;
+    if skipped != n {
+        anyhow::bail!("tar truncated");
+    }
+    Ok(())
+}
+
+fn skip_padding(r: &mut impl Read, size: u64) -> anyhow::Result<()> {
+    skip(r, (TAR_BLOCK as u64 - size % TAR_BLOCK as u64) % TAR_BLOCK as u64)
+}
+
+/// Octal ASCII, or big-endian binary when the high bit is set (GNU, for
+/// sizes over 8 GiB).
+fn tar_size(field: &[u8]) -> anyhow::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        let mut n: u64 = (field[0] & 0x7f) as u64;
+        for b in &field[1..] {
+            n = n.checked_shl(8).context("size overflows")? | *b as u64;
+        }
+        return Ok(n);
+    }
+    let text = std::str::from_utf8(field).context("not ASCII")?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).with_context(|| format!("not octal: {text:?}"))
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// `prefix/name` of a ustar header.
+fn ustar_name(header: &[u8; TAR_BLOCK]) -> String {
+    let name = c_string(&header[0..100]);
+    let prefix = if &header[257..262] == b"ustar" { c_string(&header[345..500]) } else { String::new() };
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{prefix}/{name}")
+    }
+}
+
+/// `path` from pax records (`<len> path=<value>\n`).
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines()
+        .filter_map(|record| record.split_once(' ').map(|(_, kv)| kv))
+        .find_map(|kv| kv.strip_prefix("path="))
+        .map(str::to_string)
+}
+
+fn sanitize_entry_path(name: &str) -> anyhow::Result<PathBuf> {
+    let name = name.replace('\\', "/");
+
+    if name.starts_with('/') {
+        anyhow::bail!("absolute path not allowed");
+    }
+    if name.len() >= 2 && name.as_bytes()[1] == b':' {
+        anyhow::bail!("drive letter path not allowed");
+    }
+
+    let mut out = PathBuf::new();
+    for part in name.split('/') {
+        if part.is_empty() || part == "." {
+            continue;
+        }
+        if part == ".." {
+            anyhow::bail!("path traversal not allowed");
+        }
+        if cfg!(windows) {
+            check_windows_component(part)?;
+        }
+        out.push(part);
+    }
+
+    if out.as_os_str().is_empty() {
+        anyhow::bail!("empty entry path");
+    }
+
+    Ok(out)
+}
+
+/// Names Windows can't create or silently maps elsewhere: `a.txt:x` is an
+/// alternate data stream, `a.` is `a`, `nul.txt` is a device.
+fn check_windows_component(part: &str) -> anyhow::Result<()> {
+    if part.chars().any(|c| c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*')) {
+        anyhow::bail!("character not allowed on Windows");
+    }
+    if part.ends_with('.') || part.ends_with(' ') {
+        anyhow::bail!("trailing dot or space not allowed on Windows");
+    }
+    if is_windows_reserved(part) {
+        anyhow::bail!("reserved device name not allowed on Windows");
+    }
+    Ok(())
+}
+
+fn is_within_dir(out_path: &Path, base: &Path) -> anyhow::Result<bool> {
+    let out = normalize_lexical(out_path);
+    let base = normalize_lexical(base);
+    Ok(out.starts_with(&base))
+}
+
+fn normalize_lexical(p: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in p.components() {
+        use std::path::Component;
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// Packs `dir` recursively into an in-memory zip (entry paths relative to `dir`).
+pub async fn zip_dir(dir: PathBuf) -> anyhow::Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || zip_dir_blocking(&dir))
+        .await
+        .context("join zip task")?
+}
+
+fn zip_dir_blocking(dir: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    add_dir(&mut writer, dir, "")?;
+    Ok(writer.finish().context("finish zip")?.into_inner())
+}
+
+/// Adds the files under `dir` to `writer` as `<prefix><relative path>`.
+pub fn add_dir<W: Write + std::io::Seek>(
+    writer: &mut zip::ZipWriter<W>,
+    dir: &Path,
+    prefix: &str,
+) -> anyhow::Result<()> {
+    let options = zip::write::SimpleFileOptions::default();
+
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        for entry in std::fs::read_dir(&current).with_context(|| format!("read dir {:?}", current))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+
+            let rel = path.strip_prefix(dir).context("strip prefix")?;
+            let name = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            writer.start_file(format!("{prefix}{name}"), options).context("start zip entry")?;
+            let mut file = std::fs::File::open(&path).with_context(|| format!("open {:?}", path))?;
+            std::io::copy(&mut file, writer).context("write zip entry")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_components() {
+        for part in ["0-result.json", "a b.txt", "report.tar.gz", "console.log"] {
+            assert!(check_windows_component(part).is_ok(), "{part}");
+        }
+        for part in ["a.txt:stream", "a<b", "a|b", "what?", "star*", "quote\"", "tab\t", "dot.", "space ", "nul.txt", "COM1", "lpt3.log"] {
+            assert!(check_windows_component(part).is_err(), "{part}");
+        }
+    }
+}
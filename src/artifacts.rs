@@ -0,0 +1,125 @@
+//! Downloadable run artifacts for CI: the results archive, the report
+//! archive and a summary JSON, each with a sha256 digest. Archives are
+//! built on first request and cached in `<run>/artifacts/` until the run is
+//! generated again.
+
+use anyhow::Context;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::{archive, state::AppState, storage};
+
+pub const NAMES: [&str; 3] = ["results.zip", "report.zip", "summary.json"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+    pub content_type: &'static str,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    project: String,
+    run_id: u64,
+    status: Option<storage::Status>,
+    error: Option<String>,
+    created_at: Option<u64>,
+    #[serde(flatten)]
+    info: storage::RunInfo,
+}
+
+fn cache_dir(run_dir: &Path) -> PathBuf {
+    run_dir.join("artifacts")
+}
+
+/// Drops cached artifacts, e.g. after their sources were changed in place.
+pub async fn invalidate(run_dir: &Path) {
+    let _ = tokio::fs::remove_dir_all(cache_dir(run_dir)).await;
+}
+
+/// Artifacts that exist for the run (no report archive before generation).
+pub async fn list(state: &AppState, project: &str, run_id: u64) -> anyhow::Result<Vec<Artifact>> {
+    let mut out = Vec::new();
+    for name in NAMES {
+        if let Some((_, a)) = ensure(state, project, run_id, name).await? {
+            out.push(a);
+        }
+    }
+    Ok(out)
+}
+
+/// Path and metadata of an artifact, building it if the cache is stale.
+/// None for unknown names and missing sources.
+pub async fn ensure(state: &AppState, project: &str, run_id: u64, name: &str) -> anyhow::Result<Option<(PathBuf, Artifact)>> {
+    if !NAMES.contains(&name) {
+        return Ok(None);
+    }
+    let run_dir = storage::run_dir(&state.volumes, project, run_id);
+    let path = cache_dir(&run_dir).join(name);
+    let digest_path = cache_dir(&run_dir).join(format!("{name}.sha256"));
+
+    if is_fresh(&run_dir, &path).await {
+        if let Ok(sha256) = tokio::fs::read_to_string(&digest_path).await {
+            let size = tokio::fs::metadata(&path).await?.len();
+            return Ok(Some((path, artifact(name, size, sha256.trim().to_string()))));
+        }
+    }
+
+    let bytes = match name {
+        "results.zip" | "report.zip" => {
+            let src = run_dir.join(if name == "results.zip" { "allure-results" } else { "report" });
+            if !src.is_dir() {
+                return Ok(None);
+            }
+            archive::zip_dir(src).await?
+        }
+        _ => {
+            if !run_dir.is_dir() {
+                return Ok(None);
+            }
+            let st = storage::read_run_status(&run_dir).await;
+            let summary = Summary {
+                project: project.to_string(),
+                run_id,
+                status: st.as_ref().map(|s| s.status),
+                error: st.and_then(|s| s.error),
+                created_at: storage::run_created_at(&run_dir).await,
+                info: storage::read_run_info(&run_dir).await,
+            };
+            serde_json::to_vec_pretty(&summary)?
+        }
+    };
+
+    let sha256 = format!("{:x}", Sha256::digest(&bytes));
+    tokio::fs::create_dir_all(cache_dir(&run_dir)).await.context("create artifacts dir")?;
+    // параллельные запросы собирают одно и то же — побеждает последний rename
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    tokio::fs::write(&tmp, &bytes).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+    tokio::fs::write(&digest_path, &sha256).await?;
+
+    Ok(Some((path, artifact(name, bytes.len() as u64, sha256))))
+}
+
+/// Cached copy is newer than the last generation (`status.json`).
+async fn is_fresh(run_dir: &Path, path: &Path) -> bool {
+    let Ok(cached) = tokio::fs::metadata(path).await.and_then(|m| m.modified()) else {
+        return false;
+    };
+    match tokio::fs::metadata(run_dir.join("status.json")).await.and_then(|m| m.modified()) {
+        Ok(generated) => cached >= generated,
+        Err(_) => false,
+    }
+}
+
+fn artifact(name: &str, size: u64, sha256: String) -> Artifact {
+    Artifact {
+        name: name.to_string(),
+        size,
+        sha256,
+        content_type: if name.ends_with(".json") { "application/json" } else { "application/zip" },
+    }
+}
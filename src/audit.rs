@@ -0,0 +1,53 @@
+//! Append-only audit log of admin and destructive operations
+//! (`DATA_DIR/audit.log`, one JSON object per line).
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::util::now_unix;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: u64,
+    pub action: String,
+    pub details: serde_json::Value,
+}
+
+fn log_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("audit.log")
+}
+
+pub async fn record(data_dir: &Path, action: &str, details: serde_json::Value) -> anyhow::Result<()> {
+    let entry = AuditEntry { at: now_unix(), action: action.to_string(), details };
+    let mut line = serde_json::to_vec(&entry)?;
+    line.push(b'\n');
+
+    let path = log_path(data_dir);
+    let mut f = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .with_context(|| format!("open {}", path.display()))?;
+    // одна запись на строку — append одним write не перемешивается
+    f.write_all(&line).await?;
+    f.flush().await?;
+    Ok(())
+}
+
+/// Last `limit` entries, newest first.
+pub async fn read_recent(data_dir: &Path, limit: usize) -> anyhow::Result<Vec<AuditEntry>> {
+    Ok(read_all(data_dir).await?.into_iter().rev().take(limit).collect())
+}
+
+/// All entries, oldest first.
+pub async fn read_all(data_dir: &Path) -> anyhow::Result<Vec<AuditEntry>> {
+    let text = match fs::read_to_string(log_path(data_dir)).await {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(text.lines().filter_map(|l| serde_json::from_str(l).ok()).collect())
+}
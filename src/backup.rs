@@ -0,0 +1,169 @@
+//! Backup of a single run — results, report and metadata in one zip — and
+//! its import as a new run, possibly on another instance. The archive
+//! carries `backup.json` next to `allure-results/` and `report/`.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    archive::{self, Limits},
+    state::AppState,
+    storage, summary,
+    util::{self, now_unix},
+};
+
+const MANIFEST: &str = "backup.json";
+/// Bumped when the archive layout changes incompatibly.
+const FORMAT: u32 = 1;
+
+/// `backup.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    pub project: String,
+    pub run_id: u64,
+    pub created_at: Option<u64>,
+    pub status: Option<storage::RunStatus>,
+    pub meta: storage::Meta,
+    #[serde(default)]
+    pub info: storage::RunInfo,
+}
+
+/// Writes the backup zip to a temp file under `TMP_DIR` and returns its
+/// path; the caller removes it.
+pub async fn build(state: &AppState, project: &str, run_id: u64) -> anyhow::Result<PathBuf> {
+    let run_dir = storage::run_dir(&state.volumes, project, run_id);
+    let manifest = Manifest {
+        format: FORMAT,
+        project: project.to_string(),
+        run_id,
+        created_at: storage::run_created_at(&run_dir).await,
+        status: storage::read_run_status(&run_dir).await,
+        meta: storage::read_run_meta(&run_dir).await.unwrap_or_default(),
+        info: storage::read_run_info(&run_dir).await,
+    };
+
+    let tmp_dir = &state.config.tmp_dir;
+    tokio::fs::create_dir_all(tmp_dir).await.context("create tmp dir")?;
+    let out = tmp_dir.join(format!("backup-{project}-{run_id}-{}-{}.zip", now_unix(), std::process::id()));
+
+    let path = out.clone();
+    let written = tokio::task::spawn_blocking(move || write_zip(&path, &run_dir, &manifest))
+        .await
+        .context("join backup task")?;
+    if let Err(e) = written {
+        let _ = tokio::fs::remove_file(&out).await;
+        return Err(e);
+    }
+    Ok(out)
+}
+
+fn write_zip(path: &Path, run_dir: &Path, manifest: &Manifest) -> anyhow::Result<()> {
+    let file = std::fs::File::create(path).with_context(|| format!("create {}", path.display()))?;
+    let mut zip = zip::ZipWriter::new(std::io::BufWriter::new(file));
+    let options = zip::write::SimpleFileOptions::default();
+
+    zip.start_file(MANIFEST, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+
+    let log = run_dir.join("generate.log");
+    if log.is_file() {
+        zip.start_file("generate.log", options)?;
+        std::io::copy(&mut std::fs::File::open(&log)?, &mut zip)?;
+    }
+    for dir in ["allure-results", "report"] {
+        if run_dir.join(dir).is_dir() {
+            archive::add_dir(&mut zip, &run_dir.join(dir), &format!("{dir}/"))?;
+        }
+    }
+
+    zip.finish().context("finish zip")?.flush()?;
+    Ok(())
+}
+
+/// A backup unpacked into a staging dir under `TMP_DIR`; the caller
+/// removes `dir`.
+pub struct Unpacked {
+    pub dir: PathBuf,
+    pub manifest: Manifest,
+}
+
+/// Extracts and checks a backup archive. Errors mean a bad archive.
+pub async fn unpack(state: &AppState, bytes: Vec<u8>) -> anyhow::Result<Unpacked> {
+    let dir = util::staging_dir(&state.config.tmp_dir, "import").await?;
+    match unpack_into(&dir, bytes).await {
+        Ok(manifest) => Ok(Unpacked { dir, manifest }),
+        Err(e) => {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            Err(e)
+        }
+    }
+}
+
+async fn unpack_into(dir: &Path, bytes: Vec<u8>) -> anyhow::Result<Manifest> {
+    // в бэкапе кроме результатов лежит отчёт allure2 — по файлу на тест
+    let limits = Limits { max_files: 100_000, ..Limits::default() };
+    archive::unzip_safely(bytes, dir.to_path_buf(), limits).await?;
+
+    let text = tokio::fs::read_to_string(dir.join(MANIFEST))
+        .await
+        .with_context(|| format!("no {MANIFEST}: not a run backup"))?;
+    let manifest: Manifest = serde_json::from_str(&text).with_context(|| format!("parse {MANIFEST}"))?;
+    if manifest.format != FORMAT {
+        anyhow::bail!("unsupported backup format {} (expected {FORMAT})", manifest.format);
+    }
+    if !dir.join("allure-results").is_dir() {
+        anyhow::bail!("backup has no allure-results");
+    }
+    Ok(manifest)
+}
+
+/// Moves an unpacked backup into `run_dir` of a freshly reserved run.
+/// The caller holds the project lock. Returns whether the backup had a
+/// report; without one the run still needs generating.
+pub async fn restore(unpacked: &Unpacked, run_dir: &Path) -> anyhow::Result<bool> {
+    let m = &unpacked.manifest;
+    tokio::fs::create_dir_all(run_dir).await.context("mkdir run_dir")?;
+
+    let has_report = unpacked.dir.join("report").join("index.html").is_file();
+    let mut dirs = vec!["allure-results"];
+    if has_report {
+        dirs.push("report");
+    }
+    for dir in dirs {
+        util::move_dir(&unpacked.dir.join(dir), &run_dir.join(dir)).await?;
+    }
+    let log = unpacked.dir.join("generate.log");
+    if log.is_file() {
+        tokio::fs::copy(&log, run_dir.join("generate.log")).await.context("copy generate.log")?;
+    }
+
+    if has_report {
+        if let Some(status) = &m.status {
+            storage::write_json(&run_dir.join("status.json"), status).await?;
+        }
+        storage::write_json(&run_dir.join("run_info.json"), &m.info).await?;
+        if let Some(s) = summary::of_report(&run_dir.join("report"), m.info.stats.as_ref()).await {
+            summary::write(run_dir, &s).await?;
+        }
+    }
+
+    let meta_path = run_dir.join("meta.json");
+    storage::write_json(&meta_path, &m.meta).await?;
+    // время загрузки прогона — mtime meta.json (см. storage::run_created_at)
+    if let Some(ts) = m.created_at {
+        let path = meta_path.clone();
+        tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(ts))
+        })
+        .await
+        .context("join touch task")?
+        .context("set meta.json mtime")?;
+    }
+    Ok(has_report)
+}
@@ -0,0 +1,43 @@
+//! Minimal shields-style SVG badges.
+
+use crate::util::html_escape as escape;
+
+/// Renders a two-part flat badge; `color` is any SVG color.
+pub fn render(label: &str, value: &str, color: &str) -> String {
+    // ~6.5px на символ при font-size 11 — для бейджа достаточно
+    let lw = text_width(label);
+    let vw = text_width(value);
+    let w = lw + vw;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="20" role="img" aria-label="{label}: {value}">
+<title>{label}: {value}</title>
+<rect width="{lw}" height="20" fill="#555"/>
+<rect x="{lw}" width="{vw}" height="20" fill="{color}"/>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{lx}" y="14">{label}</text>
+<text x="{vx}" y="14">{value}</text>
+</g>
+</svg>"##,
+        label = escape(label),
+        value = escape(value),
+        lx = lw / 2,
+        vx = lw + vw / 2,
+    )
+}
+
+/// Badge color for a 0..=100 score.
+pub fn score_color(score: f64) -> &'static str {
+    if score >= 95.0 {
+        "#2ecc71"
+    } else if score >= 80.0 {
+        "#ffb020"
+    } else {
+        "#ff5a5f"
+    }
+}
+
+fn text_width(s: &str) -> usize {
+    s.chars().count() * 13 / 2 + 12
+}
+
@@ -0,0 +1,134 @@
+//! Circuit breaker around report generation: after N consecutive
+//! generation failures of the same class new generations are refused
+//! (503) until a probe of the allure CLI passes again.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{executor, state::AppState, util::now_unix};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BreakerState {
+    pub open: bool,
+    pub consecutive_failures: u32,
+    /// Class of the last failures, e.g. `spawn` or `exit_code=1`.
+    pub error_class: Option<String>,
+    pub last_error: Option<String>,
+    pub open_since: Option<u64>,
+}
+
+pub struct Breaker {
+    threshold: u32,
+    state: Mutex<BreakerState>,
+}
+
+impl Breaker {
+    /// `threshold` 0 disables the breaker.
+    pub fn new(threshold: u32) -> Self {
+        Self { threshold, state: Mutex::new(BreakerState::default()) }
+    }
+
+    pub fn snapshot(&self) -> BreakerState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Error to refuse a new generation with, when open.
+    pub fn open_error(&self) -> Option<String> {
+        let st = self.state.lock().unwrap();
+        st.open.then(|| {
+            format!(
+                "generator unhealthy: {} consecutive failures ({})",
+                st.consecutive_failures,
+                st.error_class.as_deref().unwrap_or("unknown")
+            )
+        })
+    }
+
+    /// Records the outcome of an `allure generate`.
+    pub fn record(&self, result: &Result<impl Sized, String>) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut st = self.state.lock().unwrap();
+        match result {
+            Ok(_) => {
+                if st.open {
+                    info!("generator recovered, closing circuit breaker");
+                }
+                *st = BreakerState::default();
+            }
+            Err(e) => {
+                let class = error_class(e);
+                if st.error_class.as_deref() == Some(class.as_str()) {
+                    st.consecutive_failures += 1;
+                } else {
+                    st.consecutive_failures = 1;
+                    st.error_class = Some(class);
+                }
+                st.last_error = Some(crate::util::clip(e, 2000));
+                if !st.open && st.consecutive_failures >= self.threshold {
+                    warn!(failures = st.consecutive_failures, class = ?st.error_class, "opening generator circuit breaker");
+                    st.open = true;
+                    st.open_since = Some(now_unix());
+                }
+            }
+        }
+    }
+
+    fn close(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+}
+
+/// Coarse class of a generation error, so unrelated failures (a broken
+/// results archive here, a timeout there) do not add up.
+fn error_class(err: &str) -> String {
+    if err.contains("spawn allure") || err.contains("No such file") {
+        return "spawn".into();
+    }
+    if err.contains("timed out") {
+        return "timeout".into();
+    }
+    if let Some(pos) = err.find("exit_code=") {
+        let code: String = err[pos + "exit_code=".len()..]
+            .chars()
+            .take_while(|c| *c == '-' || c.is_ascii_digit())
+            .collect();
+        return format!("exit_code={code}");
+    }
+    "other".into()
+}
+
+/// Whether a generation error may pass when the same results are sent
+/// again: the generator could not start, was cut short or killed, or no
+/// worker took the job. A plain nonzero exit of allure fails again.
+pub fn is_transient(err: &str) -> bool {
+    let class = error_class(err);
+    matches!(class.as_str(), "spawn" | "timeout" | "exit_code=-1" | "exit_code=137")
+        || err.starts_with("worker job dropped")
+        || err.starts_with("no worker finished")
+}
+
+/// Probes the CLI every `interval` while the breaker is open.
+pub fn spawn_probe(state: AppState, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if state.breaker.open_error().is_none() {
+                continue;
+            }
+
+            let generator = &state.generator;
+            match executor::run(&generator.backend, &generator.bin, &["--version".into()], &[], "probe", generator.timeout).await {
+                Ok(out) if out.success() => {
+                    info!("generator probe passed, closing circuit breaker");
+                    state.breaker.close();
+                }
+                Ok(out) => warn!(exit_code=?out.code, "generator probe failed"),
+                Err(e) => warn!(error=%format!("{e:#}"), "generator probe failed"),
+            }
+        }
+    });
+}
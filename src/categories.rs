@@ -0,0 +1,115 @@
+//! Defect categories merged into a run's results before generation: the
+//! project's own `categories.json` (`PUT .../categories`) and built-in
+//! presets for common frameworks (`categories.presets` in project.json).
+
+use anyhow::Context;
+use serde_json::Value;
+use std::path::Path;
+
+/// The project's categories, next to `project.json`.
+pub const PROJECT_FILE: &str = "categories.json";
+
+const PRESETS: &[(&str, &str)] = &[
+    ("junit", include_str!("categories/junit.json")),
+    ("pytest", include_str!("categories/pytest.json")),
+    ("selenium", include_str!("categories/selenium.json")),
+];
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    PRESETS.iter().map(|(name, _)| *name)
+}
+
+fn preset(name: &str) -> Option<Vec<Value>> {
+    let (_, json) = PRESETS.iter().find(|(n, _)| *n == name)?;
+    // файлы пресетов встроены в бинарь и валидны
+    serde_json::from_str(json).ok()
+}
+
+fn category_name(c: &Value) -> Option<&str> {
+    c.get("name").and_then(Value::as_str)
+}
+
+/// An Allure `categories.json`: an array of categories with unique
+/// non-empty names, string `messageRegex` / `traceRegex` and known
+/// `matchedStatuses`.
+pub fn validate(doc: &Value) -> Result<(), String> {
+    let Some(categories) = doc.as_array() else {
+        return Err("expected an array of categories".into());
+    };
+    let mut names = std::collections::HashSet::new();
+    for (i, c) in categories.iter().enumerate() {
+        if !c.is_object() {
+            return Err(format!("category {i}: expected an object"));
+        }
+        let name = category_name(c).filter(|n| !n.trim().is_empty());
+        let Some(name) = name else {
+            return Err(format!("category {i}: 'name' must be a non-empty string"));
+        };
+        if !names.insert(name) {
+            return Err(format!("duplicate category '{name}'"));
+        }
+        for key in ["messageRegex", "traceRegex"] {
+            match c.get(key) {
+                None | Some(Value::Null) => {}
+                // regex применяет allure (Java): синтаксис Rust-регулярок тут не судья
+                Some(Value::String(_)) => {}
+                Some(_) => return Err(format!("category '{name}': '{key}' must be a string")),
+            }
+        }
+        if let Some(statuses) = c.get("matchedStatuses") {
+            const STATUSES: &[&str] = &["failed", "broken", "passed", "skipped", "unknown"];
+            let valid = statuses.as_array().is_some_and(|a| a.iter().all(|s| s.as_str().is_some_and(|s| STATUSES.contains(&s))));
+            if !valid {
+                return Err(format!("category '{name}': 'matchedStatuses' must list {}", STATUSES.join(", ")));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The project's categories; None when it has none.
+pub async fn read_project(project_dir: &Path) -> anyhow::Result<Option<Vec<Value>>> {
+    let path = project_dir.join(PROJECT_FILE);
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).map(Some).with_context(|| format!("parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("read {}", path.display())),
+    }
+}
+
+/// Appends the project's categories, then those of `presets`, to
+/// `categories.json` in `results_dir`. Categories shipped with the results
+/// come first and win on name clashes, so applying them again is a no-op.
+/// Returns the number of categories added and the unknown preset names.
+pub async fn apply(results_dir: &Path, project: &[Value], presets: &[String]) -> anyhow::Result<(usize, Vec<String>)> {
+    if project.is_empty() && presets.is_empty() {
+        return Ok((0, Vec::new()));
+    }
+    let path = results_dir.join("categories.json");
+    let mut categories: Vec<Value> = match tokio::fs::read(&path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("parse {}", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+
+    let mut added = 0;
+    let mut add = |category: Value| {
+        let taken = categories.iter().any(|c| category_name(c) == category_name(&category));
+        if !taken {
+            categories.push(category);
+            added += 1;
+        }
+    };
+    project.iter().cloned().for_each(&mut add);
+    let mut unknown = Vec::new();
+    for name in presets {
+        match preset(name) {
+            Some(preset) => preset.into_iter().for_each(&mut add),
+            None => unknown.push(name.clone()),
+        }
+    }
+    if added > 0 {
+        crate::storage::write_json(&path, &categories).await?;
+    }
+    Ok((added, unknown))
+}
@@ -0,0 +1,294 @@
+//! CI context of a run: the standard variables of GitHub Actions, GitLab
+//! CI and Jenkins sent in `meta.ci_env` (or just `meta.build_url`) become
+//! the report's executor (`executor.json`) and `ci.*` entries of
+//! `environment.properties`; `meta.environment`, `branch` and `commit`
+//! become its other entries.
+
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::storage::Meta;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Github,
+    Gitlab,
+    Jenkins,
+}
+
+impl std::str::FromStr for Provider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::Github),
+            "gitlab" => Ok(Self::Gitlab),
+            "jenkins" => Ok(Self::Jenkins),
+            _ => anyhow::bail!("expected github|gitlab|jenkins, got {s:?}"),
+        }
+    }
+}
+
+impl Provider {
+    fn id(self) -> &'static str {
+        match self {
+            Self::Github => "github",
+            Self::Gitlab => "gitlab",
+            Self::Jenkins => "jenkins",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Github => "GitHub Actions",
+            Self::Gitlab => "GitLab CI",
+            Self::Jenkins => "Jenkins",
+        }
+    }
+
+    /// Variables kept from `ci_env`; anything else (tokens included) is
+    /// dropped on upload.
+    pub fn variables(self) -> &'static [&'static str] {
+        match self {
+            Self::Github => &[
+                "GITHUB_SERVER_URL",
+                "GITHUB_REPOSITORY",
+                "GITHUB_RUN_ID",
+                "GITHUB_RUN_NUMBER",
+                "GITHUB_RUN_ATTEMPT",
+                "GITHUB_WORKFLOW",
+                "GITHUB_JOB",
+                "GITHUB_ACTOR",
+                "GITHUB_REF_NAME",
+                "GITHUB_SHA",
+                "GITHUB_EVENT_NAME",
+            ],
+            Self::Gitlab => &[
+                "CI_SERVER_URL",
+                "CI_PROJECT_PATH",
+                "CI_PIPELINE_ID",
+                "CI_PIPELINE_IID",
+                "CI_PIPELINE_URL",
+                "CI_PIPELINE_SOURCE",
+                "CI_JOB_NAME",
+                "CI_JOB_URL",
+                "GITLAB_USER_LOGIN",
+                "CI_COMMIT_REF_NAME",
+                "CI_COMMIT_SHA",
+            ],
+            Self::Jenkins => &[
+                "JENKINS_URL",
+                "JOB_NAME",
+                "JOB_URL",
+                "BUILD_NUMBER",
+                "BUILD_URL",
+                "BUILD_USER_ID",
+                "BRANCH_NAME",
+                "GIT_BRANCH",
+                "GIT_COMMIT",
+            ],
+        }
+    }
+}
+
+/// What the report shows about the CI job that uploaded the run.
+#[derive(Debug)]
+pub struct CiContext {
+    pub provider: Provider,
+    pub run_url: Option<String>,
+    pub actor: Option<String>,
+    pub git_ref: Option<String>,
+    pub job: Option<String>,
+    pub build_number: Option<String>,
+    pub commit: Option<String>,
+}
+
+impl CiContext {
+    pub fn from_env(provider: Provider, env: &BTreeMap<String, String>) -> Self {
+        let var = |k: &str| env.get(k).filter(|v| !v.is_empty()).cloned();
+        match provider {
+            Provider::Github => Self {
+                provider,
+                run_url: match (var("GITHUB_SERVER_URL"), var("GITHUB_REPOSITORY"), var("GITHUB_RUN_ID")) {
+                    (Some(server), Some(repo), Some(id)) => Some(format!("{server}/{repo}/actions/runs/{id}")),
+                    _ => None,
+                },
+                actor: var("GITHUB_ACTOR"),
+                git_ref: var("GITHUB_REF_NAME"),
+                job: match (var("GITHUB_WORKFLOW"), var("GITHUB_JOB")) {
+                    (Some(w), Some(j)) => Some(format!("{w} / {j}")),
+                    (w, j) => w.or(j),
+                },
+                build_number: var("GITHUB_RUN_NUMBER"),
+                commit: var("GITHUB_SHA"),
+            },
+            Provider::Gitlab => Self {
+                provider,
+                run_url: var("CI_PIPELINE_URL").or_else(|| var("CI_JOB_URL")),
+                actor: var("GITLAB_USER_LOGIN"),
+                git_ref: var("CI_COMMIT_REF_NAME"),
+                job: var("CI_JOB_NAME"),
+                build_number: var("CI_PIPELINE_IID").or_else(|| var("CI_PIPELINE_ID")),
+                commit: var("CI_COMMIT_SHA"),
+            },
+            Provider::Jenkins => Self {
+                provider,
+                run_url: var("BUILD_URL"),
+                actor: var("BUILD_USER_ID"),
+                // GIT_BRANCH у git-плагина вида origin/main
+                git_ref: var("BRANCH_NAME").or_else(|| {
+                    var("GIT_BRANCH").map(|b| b.strip_prefix("origin/").map(str::to_string).unwrap_or(b))
+                }),
+                job: var("JOB_NAME"),
+                build_number: var("BUILD_NUMBER"),
+                commit: var("GIT_COMMIT"),
+            },
+        }
+    }
+
+    /// `None` when the run was not uploaded with a CI hint.
+    pub fn of_meta(meta: &Meta) -> Option<Self> {
+        let provider = meta.ci.as_deref()?.parse().ok()?;
+        Some(Self::from_env(provider, &meta.ci_env))
+    }
+}
+
+/// Drops variables the provider does not define; an unknown provider is
+/// an error.
+pub fn keep_known(provider: &str, env: &mut BTreeMap<String, String>) -> anyhow::Result<()> {
+    let provider: Provider = provider.parse()?;
+    env.retain(|k, _| provider.variables().contains(&k.as_str()));
+    Ok(())
+}
+
+/// Allure's `executor.json`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Executor<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a str,
+    build_name: Option<String>,
+    build_order: u64,
+    build_url: Option<&'a str>,
+    report_url: &'a str,
+    report_name: &'a str,
+}
+
+/// Writes `executor.json` (unless the results have their own) and adds
+/// missing `ci.*` keys to `environment.properties`. Nothing to do for a
+/// run with neither `ci` nor `build_url`.
+pub async fn write_results(
+    results_dir: &Path,
+    meta: &Meta,
+    project: &str,
+    run_id: u64,
+    report_url: &str,
+) -> anyhow::Result<()> {
+    let ctx = CiContext::of_meta(meta);
+    let build_url = meta.build_url.as_deref().or(ctx.as_ref().and_then(|c| c.run_url.as_deref()));
+    if ctx.is_none() && build_url.is_none() {
+        return Ok(());
+    }
+
+    let executor_path = results_dir.join("executor.json");
+    if !executor_path.exists() {
+        let build_name = ctx.as_ref().and_then(|ctx| match (&ctx.job, &ctx.build_number) {
+            (Some(job), Some(n)) => Some(format!("{job} #{n}")),
+            (job, n) => job.clone().or_else(|| n.as_ref().map(|n| format!("#{n}"))),
+        });
+        let executor = Executor {
+            name: ctx.as_ref().map_or("CI", |c| c.provider.title()),
+            kind: ctx.as_ref().map_or("ci", |c| c.provider.id()),
+            build_name,
+            build_order: run_id,
+            build_url,
+            report_url,
+            report_name: project,
+        };
+        crate::storage::write_json(&executor_path, &executor).await?;
+    }
+
+    let Some(ctx) = ctx else {
+        return Ok(());
+    };
+    let provider = ctx.provider;
+    let entries = [
+        ("ci.provider", Some(provider.id())),
+        ("ci.run_url", ctx.run_url.as_deref()),
+        ("ci.actor", ctx.actor.as_deref()),
+        ("ci.ref", ctx.git_ref.as_deref()),
+        ("ci.job", ctx.job.as_deref()),
+        ("ci.commit", ctx.commit.as_deref()),
+    ];
+    append_properties(&results_dir.join("environment.properties"), &entries).await
+}
+
+/// Keys of `meta.environment`: non-empty, without whitespace, `=` or `:`.
+pub fn validate_environment(env: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    for key in env.keys() {
+        if key.is_empty() || key.len() > 128 || key.chars().any(|c| c.is_whitespace() || c == '=' || c == ':') {
+            anyhow::bail!("bad key {key:?}: 1-128 characters without whitespace, '=' or ':'");
+        }
+    }
+    Ok(())
+}
+
+/// Adds `meta.environment` and the run's `branch` and `commit` to
+/// `environment.properties`; keys the results already set are kept.
+pub async fn write_environment(results_dir: &Path, meta: &Meta) -> anyhow::Result<()> {
+    let mut entries: Vec<(&str, Option<&str>)> =
+        meta.environment.iter().map(|(k, v)| (k.as_str(), Some(v.as_str()))).collect();
+    for (key, value) in [("branch", &meta.branch), ("commit", &meta.commit)] {
+        if !meta.environment.contains_key(key) {
+            entries.push((key, value.as_deref()));
+        }
+    }
+    append_properties(&results_dir.join("environment.properties"), &entries).await
+}
+
+/// Appends `key=value` lines whose keys are not in the file yet.
+async fn append_properties(path: &Path, entries: &[(&str, Option<&str>)]) -> anyhow::Result<()> {
+    let mut text = match tokio::fs::read_to_string(path).await {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    let existing: Vec<&str> = text
+        .lines()
+        .filter_map(|l| l.split_once(['=', ':']).map(|(k, _)| k.trim()))
+        .collect();
+    let missing: Vec<String> = entries
+        .iter()
+        .filter(|(k, _)| !existing.contains(k))
+        .filter_map(|(k, v)| v.map(|v| format!("{k}={}", escape_property(v))))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    for line in missing {
+        text.push_str(&line);
+        text.push('\n');
+    }
+    tokio::fs::write(path, text).await.with_context(|| format!("write {}", path.display()))
+}
+
+/// Escapes a `.properties` value (`\`, line breaks, leading spaces).
+fn escape_property(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    for (i, c) in v.chars().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            ' ' if i == 0 => out.push_str("\\ "),
+            c => out.push(c),
+        }
+    }
+    out
+}
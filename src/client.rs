@@ -0,0 +1,396 @@
+//! Typed async client of the service API (`--features client`), for Rust
+//! CI tooling that uploads results and fetches reports.
+//!
+//! ```no_run
+//! # async fn demo() -> Result<(), allure3_docker_service::client::Error> {
+//! use allure3_docker_service::client::{Client, UploadMeta};
+//!
+//! let client = Client::new("http://allure:8080");
+//! let meta = UploadMeta { branch: Some("main".into()), ..Default::default() };
+//! let upload = client.upload("backend", "allure-results.zip".as_ref(), Some(&meta)).await?;
+//! client.download_report("backend", upload.run_id, "report.zip".as_ref()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use reqwest::{multipart, Body, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::Path, time::Duration};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Connection, TLS or body decoding failure.
+    Http(reqwest::Error),
+    /// The service answered with a non-success status.
+    Api { status: StatusCode, message: String },
+    Io(std::io::Error),
+    /// [`Client::wait_for_run`] gave up.
+    Timeout { project: String, run_id: u64 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "request failed: {e}"),
+            Self::Api { status, message } => write!(f, "{status}: {message}"),
+            Self::Io(e) => write!(f, "io: {e}"),
+            Self::Timeout { project, run_id } => write!(f, "run {project}/{run_id} did not finish in time"),
+        }
+    }
+}
+
+impl Error {
+    /// Whether the same request may pass when sent again: a connection
+    /// failure, or an error the service marks `retryable`.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Http(e) => e.is_connect() || e.is_timeout(),
+            Self::Api { status, message } => serde_json::from_str::<serde_json::Value>(message)
+                .ok()
+                .and_then(|v| v.get("retryable")?.as_bool())
+                // сервис старше поля retryable
+                .unwrap_or_else(|| status.is_server_error()),
+            Self::Io(_) | Self::Timeout { .. } => false,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The `meta` part of an upload.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UploadMeta {
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    pub trigger: Option<String>,
+    pub started_at: Option<String>,
+    /// Cross-project launch id.
+    pub launch: Option<String>,
+    /// External key of the run; the report is also at `/ui/{project}/builds/{key}/`.
+    pub run_key: Option<String>,
+    /// `github`, `gitlab` or `jenkins`.
+    pub ci: Option<String>,
+    /// Link of the CI job the report points back to.
+    pub build_url: Option<String>,
+    /// Standard variables of that CI; the report shows them as its executor.
+    #[serde(default)]
+    pub ci_env: BTreeMap<String, String>,
+    /// Shown in the report's Environment widget, e.g. `stage` or `browser`.
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+}
+
+/// Variables the service understands, per CI; nothing else (tokens
+/// included) leaves the CI job.
+const CI_VARIABLES: &[(&str, &str, &[&str])] = &[
+    (
+        "github",
+        "GITHUB_ACTIONS",
+        &[
+            "GITHUB_SERVER_URL",
+            "GITHUB_REPOSITORY",
+            "GITHUB_RUN_ID",
+            "GITHUB_RUN_NUMBER",
+            "GITHUB_RUN_ATTEMPT",
+            "GITHUB_WORKFLOW",
+            "GITHUB_JOB",
+            "GITHUB_ACTOR",
+            "GITHUB_REF_NAME",
+            "GITHUB_SHA",
+            "GITHUB_EVENT_NAME",
+        ],
+    ),
+    (
+        "gitlab",
+        "GITLAB_CI",
+        &[
+            "CI_SERVER_URL",
+            "CI_PROJECT_PATH",
+            "CI_PIPELINE_ID",
+            "CI_PIPELINE_IID",
+            "CI_PIPELINE_URL",
+            "CI_PIPELINE_SOURCE",
+            "CI_JOB_NAME",
+            "CI_JOB_URL",
+            "GITLAB_USER_LOGIN",
+            "CI_COMMIT_REF_NAME",
+            "CI_COMMIT_SHA",
+        ],
+    ),
+    (
+        "jenkins",
+        "JENKINS_URL",
+        &[
+            "JENKINS_URL",
+            "JOB_NAME",
+            "JOB_URL",
+            "BUILD_NUMBER",
+            "BUILD_URL",
+            "BUILD_USER_ID",
+            "BRANCH_NAME",
+            "GIT_BRANCH",
+            "GIT_COMMIT",
+        ],
+    ),
+];
+
+impl UploadMeta {
+    /// Detects GitHub Actions, GitLab CI or Jenkins from the process
+    /// environment and fills `ci` and `ci_env`; empty meta elsewhere.
+    pub fn from_ci_env() -> Self {
+        let Some((ci, _, vars)) = CI_VARIABLES.iter().find(|(_, marker, _)| std::env::var_os(marker).is_some()) else {
+            return Self::default();
+        };
+        let ci_env = vars
+            .iter()
+            .filter_map(|k| std::env::var(k).ok().map(|v| (k.to_string(), v)))
+            .collect();
+        Self { ci: Some(ci.to_string()), ci_env, ..Self::default() }
+    }
+}
+
+/// `status` of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Queued,
+    Extracting,
+    Generating,
+    Success,
+    Failed,
+    Interrupted,
+    Rejected,
+    Incomplete,
+    Pruned,
+    /// A status added by a newer service.
+    #[serde(other)]
+    Unknown,
+}
+
+impl RunStatus {
+    /// The run will not change on its own any more.
+    pub fn is_final(self) -> bool {
+        !matches!(self, Self::Queued | Self::Extracting | Self::Generating)
+    }
+}
+
+/// Response of `POST /api/v1/projects/{project}/runs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Upload {
+    pub project: String,
+    pub run_id: u64,
+    pub ui_url: String,
+    pub latest_url: String,
+    /// `success`, `failed` or `incomplete`.
+    pub status: RunStatus,
+    pub error: Option<String>,
+    pub run_key: Option<String>,
+    /// None when generation failed.
+    #[serde(default)]
+    pub summary: Option<RunSummary>,
+    #[serde(default)]
+    pub evicted_runs: Vec<u64>,
+}
+
+/// Test counts of a generated report.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RunSummary {
+    pub total: u64,
+    pub passed: u64,
+    pub failed: u64,
+    pub broken: u64,
+    pub skipped: u64,
+    pub unknown: u64,
+    pub duration_ms: u64,
+    /// passed / (total - skipped), in percent.
+    pub pass_rate: Option<f64>,
+}
+
+impl RunSummary {
+    /// No failed or broken tests.
+    pub fn is_green(&self) -> bool {
+        self.failed == 0 && self.broken == 0
+    }
+}
+
+/// `GET /api/v1/projects/{project}/runs/{run_id}`, without the analysis
+/// details.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Run {
+    pub project: String,
+    pub run_id: u64,
+    /// None while the archive is still being received.
+    pub status: Option<RunStatus>,
+    pub error: Option<String>,
+    pub ui_url: String,
+    pub meta: Option<UploadMeta>,
+    #[serde(default)]
+    pub sealed: bool,
+}
+
+impl Run {
+    pub fn is_finished(&self) -> bool {
+        self.status.is_some_and(RunStatus::is_final)
+    }
+}
+
+/// An item of `GET /api/v1/projects/{project}/runs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunItem {
+    pub run_id: u64,
+    pub status: Option<RunStatus>,
+    pub error: Option<String>,
+    pub ui_url: String,
+    pub run_key: Option<String>,
+    pub external_url: Option<String>,
+    #[serde(default)]
+    pub health_score: Option<f64>,
+    #[serde(default)]
+    pub summary: Option<RunSummary>,
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RunsResp {
+    runs: Vec<RunItem>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl Client {
+    /// `base_url` is the service root, e.g. `https://allure.example.com`.
+    pub fn new(base_url: &str) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Reuses a configured client (timeouts, proxies, default headers of
+    /// an auth proxy).
+    pub fn with_http_client(base_url: &str, http: reqwest::Client) -> Self {
+        Self { base_url: base_url.trim_end_matches('/').to_string(), http }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    /// Uploads a zip of `allure-results`; the service answers once the
+    /// report is generated.
+    pub async fn upload(&self, project: &str, results_zip: &Path, meta: Option<&UploadMeta>) -> Result<Upload> {
+        let file = tokio::fs::File::open(results_zip).await?;
+        let len = file.metadata().await?.len();
+        let results = multipart::Part::stream_with_length(Body::wrap_stream(ReaderStream::new(file)), len)
+            .file_name("allure-results.zip")
+            .mime_str("application/zip")?;
+        let mut form = multipart::Form::new().part("results", results);
+        if let Some(meta) = meta {
+            let json = serde_json::to_string(meta).map_err(std::io::Error::other)?;
+            form = form.text("meta", json);
+        }
+
+        let resp = self
+            .http
+            .post(self.url(&format!("/api/v1/projects/{project}/runs")))
+            .multipart(form)
+            .send()
+            .await?;
+        json(resp).await
+    }
+
+    pub async fn list_projects(&self) -> Result<Vec<String>> {
+        json(self.http.get(self.url("/api/v1/projects")).send().await?).await
+    }
+
+    /// Newest first.
+    pub async fn list_runs(&self, project: &str) -> Result<Vec<RunItem>> {
+        let resp = self.http.get(self.url(&format!("/api/v1/projects/{project}/runs"))).send().await?;
+        Ok(json::<RunsResp>(resp).await?.runs)
+    }
+
+    pub async fn run(&self, project: &str, run_id: u64) -> Result<Run> {
+        let resp = self
+            .http
+            .get(self.url(&format!("/api/v1/projects/{project}/runs/{run_id}")))
+            .send()
+            .await?;
+        json(resp).await
+    }
+
+    /// Polls the run until its report is generated, e.g. after an upload
+    /// whose connection dropped or a regeneration started elsewhere.
+    pub async fn wait_for_run(&self, project: &str, run_id: u64, poll: Duration, timeout: Duration) -> Result<Run> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let run = self.run(project, run_id).await?;
+            if run.is_finished() {
+                return Ok(run);
+            }
+            if tokio::time::Instant::now() + poll > deadline {
+                return Err(Error::Timeout { project: project.to_string(), run_id });
+            }
+            tokio::time::sleep(poll).await;
+        }
+    }
+
+    /// Saves the zipped report of the run to `dest`; returns its size.
+    pub async fn download_report(&self, project: &str, run_id: u64, dest: &Path) -> Result<u64> {
+        let resp = self
+            .http
+            .get(self.url(&format!("/api/v1/projects/{project}/runs/{run_id}/artifacts/report.zip")))
+            .send()
+            .await?;
+        let mut resp = check(resp).await?;
+
+        let mut file = tokio::fs::File::create(dest).await?;
+        let mut written = 0;
+        while let Some(chunk) = resp.chunk().await? {
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+        Ok(written)
+    }
+}
+
+async fn check(resp: Response) -> Result<Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    // ошибки сервис отдаёт текстом или JSON с полем error — отдаём как есть
+    let message = resp.text().await.unwrap_or_default();
+    Err(Error::Api { status, message })
+}
+
+async fn json<T: DeserializeOwned>(resp: Response) -> Result<T> {
+    Ok(check(resp).await?.json().await?)
+}
@@ -0,0 +1,171 @@
+//! Scheduled summary digests (daily/weekly) per project and org-wide.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{
+    config::DigestSchedule,
+    notify, results, settings,
+    state::AppState,
+    storage,
+    util::now_unix,
+};
+
+const DAY: u64 = 86_400;
+
+pub fn spawn(state: AppState) {
+    let schedule = state.config.digest_schedule;
+    if schedule == DigestSchedule::Off {
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            let now = now_unix();
+            let wait = secs_until_next(now, schedule, state.config.digest_hour);
+            info!(?schedule, in_secs = wait, "next digest scheduled");
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+
+            let period = match schedule {
+                DigestSchedule::Weekly => 7 * DAY,
+                _ => DAY,
+            };
+            send_digests(&state, now_unix().saturating_sub(period)).await;
+        }
+    });
+}
+
+/// Seconds until the next `hour`:00 UTC (on a Monday for weekly digests).
+pub fn secs_until_next(now: u64, schedule: DigestSchedule, hour: u64) -> u64 {
+    let mut next = now - now % DAY + hour * 3600;
+    if next <= now {
+        next += DAY;
+    }
+    if schedule == DigestSchedule::Weekly {
+        // 1970-01-01 was a Thursday: (days + 3) % 7 == 0 on Mondays
+        while !(next / DAY + 3).is_multiple_of(7) {
+            next += DAY;
+        }
+    }
+    next - now
+}
+
+async fn send_digests(state: &AppState, since: u64) {
+    let projects = match storage::list_projects(&state.volumes).await {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(error=%e, "digest: list projects failed");
+            return;
+        }
+    };
+
+    let mut org_text = String::new();
+    for project in projects {
+        let text = match project_digest(state, &project, since).await {
+            Ok(Some(t)) => t,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!(project=%project, error=%format!("{e:#}"), "digest: build failed");
+                continue;
+            }
+        };
+
+        let ps = settings::load(&storage::project_dir(&state.volumes, &project))
+            .await
+            .unwrap_or_default();
+        if ps.digest && !ps.notifications.is_empty() {
+            let subject = format!("Allure digest: {project}");
+            if let Err(e) = notify::send(state, &ps.notifications, &subject, &text).await {
+                warn!(project=%project, error=%format!("{e:#}"), "digest: send failed");
+            }
+        }
+
+        org_text.push_str(&text);
+        org_text.push('\n');
+    }
+
+    if !org_text.is_empty() && !state.config.notify.is_empty() {
+        if let Err(e) = notify::send(state, &state.config.notify, "Allure digest", &org_text).await {
+            warn!(error=%format!("{e:#}"), "digest: send org digest failed");
+        }
+    }
+}
+
+/// Digest of the runs uploaded since `since`; `None` when there were none.
+pub async fn project_digest(state: &AppState, project: &str, since: u64) -> anyhow::Result<Option<String>> {
+    let mut in_window = Vec::new();
+    let mut before: Option<u64> = None;
+    for id in storage::list_run_ids(&state.volumes, project).await? {
+        let created = storage::run_created_at(&storage::run_dir(&state.volumes, project, id)).await;
+        if created.unwrap_or(0) >= since {
+            in_window.push(id);
+        } else {
+            before = Some(id);
+        }
+    }
+
+    let Some(&latest) = in_window.last() else {
+        return Ok(None);
+    };
+
+    let mut text = String::new();
+    writeln!(text, "*{}* — {} run(s)", project, in_window.len())?;
+
+    let mut rates = Vec::new();
+    for id in &in_window {
+        let info = storage::read_run_info(&storage::run_dir(&state.volumes, project, *id)).await;
+        if let Some(rate) = info.stats.and_then(|s| s.pass_rate()) {
+            rates.push(format!("{rate:.1}%"));
+        }
+    }
+    if !rates.is_empty() {
+        writeln!(text, "Pass rate: {}", rates.join(" → "))?;
+    }
+
+    let latest_results = results::load_run(&state.volumes, project, latest).await;
+
+    let flaky_before: HashSet<String> = match before {
+        Some(id) => results::load_run(&state.volumes, project, id)
+            .await
+            .iter()
+            .filter(|r| r.is_flaky())
+            .map(|r| r.key().to_string())
+            .collect(),
+        None => HashSet::new(),
+    };
+    let new_flaky: Vec<&str> = latest_results
+        .iter()
+        .filter(|r| r.is_flaky() && !flaky_before.contains(r.key()))
+        .map(|r| r.display_name())
+        .collect();
+    if !new_flaky.is_empty() {
+        writeln!(text, "New flaky: {}", list_head(&new_flaky, 10))?;
+    }
+
+    let mut longest: Vec<_> = latest_results
+        .iter()
+        .filter_map(|r| Some((r.duration_ms()?, r.display_name())))
+        .collect();
+    longest.sort_by_key(|x| std::cmp::Reverse(x.0));
+    if !longest.is_empty() {
+        let items: Vec<String> = longest
+            .iter()
+            .take(5)
+            .map(|(ms, name)| format!("{} ({:.1}s)", name, *ms as f64 / 1000.0))
+            .collect();
+        writeln!(text, "Longest: {}", items.join(", "))?;
+    }
+
+    writeln!(text, "Latest report: {}", state.run_url(project, latest))?;
+    Ok(Some(text))
+}
+
+fn list_head(items: &[&str], max: usize) -> String {
+    let mut s = items.iter().take(max).copied().collect::<Vec<_>>().join(", ");
+    if items.len() > max {
+        write!(s, " and {} more", items.len() - max).ok();
+    }
+    s
+}
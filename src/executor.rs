@@ -0,0 +1,409 @@
+//! Where the allure CLI runs: in-process child, a Docker container, or a
+//! Kubernetes Job. Remote backends must see `DATA_DIR` at the same path.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, info, warn};
+
+use crate::util::now_unix;
+
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Local,
+    Docker(RemoteSettings),
+    Kubernetes(RemoteSettings),
+}
+
+/// Settings of the docker/kubernetes backends (`GENERATOR_*` env).
+#[derive(Debug, Clone)]
+pub struct RemoteSettings {
+    pub image: String,
+    /// Docker: volume name or host path; Kubernetes: PVC name.
+    /// Mounted at `data_dir` inside the generator container.
+    pub data_volume: String,
+    pub data_dir: PathBuf,
+    /// e.g. `2g` (docker) / `2Gi` (kubernetes)
+    pub memory: Option<String>,
+    pub cpus: Option<String>,
+    pub namespace: String,
+    pub timeout_secs: u64,
+}
+
+/// Remote container/job that produced a report, kept in run_info.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteExecution {
+    pub backend: String,
+    pub name: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+}
+
+/// Resources used by one CLI run.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub wall_ms: u64,
+    /// User + system CPU time (local backend only).
+    pub cpu_ms: Option<u64>,
+    /// Peak resident set size (local backend only).
+    pub peak_rss_bytes: Option<u64>,
+}
+
+pub struct CliOutput {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub remote: Option<RemoteExecution>,
+    pub usage: ResourceUsage,
+}
+
+impl CliOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// A local CLI run went over the timeout and was killed.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub bin: String,
+    pub secs: u64,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} timed out after {}s and was killed (GENERATOR_TIMEOUT_SECS)", self.bin, self.secs)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Runs `bin args` with extra `env` on the backend; `label` names the
+/// container/job (e.g. `demo-42`). A local process still running after
+/// `timeout` is killed together with its children (remote backends use
+/// their own `timeout_secs`).
+pub async fn run(
+    backend: &Backend,
+    bin: &str,
+    args: &[OsString],
+    env: &[(String, String)],
+    label: &str,
+    timeout: Duration,
+) -> anyhow::Result<CliOutput> {
+    let started = std::time::Instant::now();
+    let mut out = match backend {
+        // по истечении таймаута future бросается, KillOnDrop убивает группу
+        Backend::Local => tokio::time::timeout(timeout, run_local(bin, args, env))
+            .await
+            .map_err(|_| TimedOut { bin: bin.to_string(), secs: timeout.as_secs() })??,
+        Backend::Docker(rs) => run_docker(rs, bin, args, env, label).await?,
+        Backend::Kubernetes(rs) => run_kubernetes(rs, bin, args, env, label).await?,
+    };
+    out.usage.wall_ms = started.elapsed().as_millis() as u64;
+    Ok(out)
+}
+
+/// Kills the child's process group if the run is abandoned (aborted upload,
+/// timeout) before the child was reaped: Node workers and the JVM of the
+/// CLI go with it.
+#[cfg(unix)]
+struct KillOnDrop {
+    pid: libc::pid_t,
+    reaped: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(unix)]
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        if !self.reaped.load(std::sync::atomic::Ordering::Acquire) {
+            // SAFETY: pid is our own child that has not been reaped yet and
+            // leads its own process group
+            unsafe { libc::kill(-self.pid, libc::SIGKILL) };
+        }
+    }
+}
+
+/// Runs the CLI as a child and reaps it with `wait4`, which also gives its
+/// CPU time and peak RSS.
+#[cfg(unix)]
+async fn run_local(bin: &str, args: &[OsString], env: &[(String, String)]) -> anyhow::Result<CliOutput> {
+    use std::io::Read;
+    use std::os::unix::process::CommandExt;
+
+    let mut cmd = std::process::Command::new(bin);
+    cmd.process_group(0)
+        .args(args)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!(command = ?cmd, "spawn allure command");
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("spawn allure generate: {}", bin))?;
+
+    let guard = KillOnDrop {
+        pid: child.id() as libc::pid_t,
+        reaped: Default::default(),
+    };
+    let (pid, reaped) = (guard.pid, guard.reaped.clone());
+    let mut stdout = child.stdout.take().context("child stdout")?;
+    let mut stderr = child.stderr.take().context("child stderr")?;
+
+    let (status, ru, stdout, stderr) = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let out_reader = std::thread::spawn(move || {
+            let mut b = Vec::new();
+            let _ = stdout.read_to_end(&mut b);
+            b
+        });
+        let err_reader = std::thread::spawn(move || {
+            let mut b = Vec::new();
+            let _ = stderr.read_to_end(&mut b);
+            b
+        });
+
+        let mut status: libc::c_int = 0;
+        // SAFETY: rusage is plain data, zeroed is a valid value
+        let mut ru: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: pid is our child, status/ru point to live locals
+            let r = unsafe { libc::wait4(pid, &mut status, 0, &mut ru) };
+            if r == pid {
+                break;
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err).context("wait4 allure process");
+            }
+        }
+        reaped.store(true, std::sync::atomic::Ordering::Release);
+        drop(child);
+
+        let out = out_reader.join().unwrap_or_default();
+        let err = err_reader.join().unwrap_or_default();
+        Ok((status, ru, out, err))
+    })
+    .await
+    .context("join allure process task")??;
+    drop(guard);
+
+    let code = libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status));
+    let ms = |tv: libc::timeval| tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000;
+    // ru_maxrss: килобайты на Linux, байты на macOS
+    let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+
+    Ok(CliOutput {
+        code,
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        stderr: String::from_utf8_lossy(&stderr).to_string(),
+        remote: None,
+        usage: ResourceUsage {
+            wall_ms: 0,
+            cpu_ms: Some(ms(ru.ru_utime) + ms(ru.ru_stime)),
+            peak_rss_bytes: Some(ru.ru_maxrss as u64 * rss_unit),
+        },
+    })
+}
+
+#[cfg(not(unix))]
+async fn run_local(bin: &str, args: &[OsString], env: &[(String, String)]) -> anyhow::Result<CliOutput> {
+    let mut cmd = Command::new(bin);
+    cmd.args(args).envs(env.iter().map(|(k, v)| (k, v))).kill_on_drop(true);
+    debug!(command = ?cmd, "spawn allure command");
+    let out = cmd
+        .output()
+        .await
+        .with_context(|| format!("spawn allure generate: {}", bin))?;
+    Ok(CliOutput {
+        code: out.status.code(),
+        stdout: String::from_utf8_lossy(&out.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&out.stderr).to_string(),
+        remote: None,
+        usage: ResourceUsage::default(),
+    })
+}
+/// DNS-1123 friendly, unique enough name for a container/job.
+fn job_name(label: &str) -> String {
+    let mut name: String = format!("allure-gen-{}", label)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let suffix = format!("-{}", now_unix());
+    name.truncate(63 - suffix.len());
+    name.trim_end_matches('-').to_string() + &suffix
+}
+
+async fn run_docker(
+    rs: &RemoteSettings,
+    bin: &str,
+    args: &[OsString],
+    env: &[(String, String)],
+    label: &str,
+) -> anyhow::Result<CliOutput> {
+    let name = job_name(label);
+    let started_at = now_unix();
+
+    let mut cmd = Command::new("docker");
+    cmd.arg("run")
+        .arg("--rm")
+        .arg("--name")
+        .arg(&name)
+        .arg("-v")
+        .arg(format!("{}:{}", rs.data_volume, rs.data_dir.display()));
+    if let Some(m) = &rs.memory {
+        cmd.arg("--memory").arg(m);
+    }
+    if let Some(c) = &rs.cpus {
+        cmd.arg("--cpus").arg(c);
+    }
+    for (k, v) in env {
+        cmd.arg("-e").arg(format!("{k}={v}"));
+    }
+    cmd.arg(&rs.image).arg(bin).args(args).kill_on_drop(true);
+
+    info!(container = %name, image = %rs.image, "running allure in docker");
+    debug!(command = ?cmd, "spawn docker run");
+
+    let out = tokio::time::timeout(Duration::from_secs(rs.timeout_secs), cmd.output())
+        .await
+        .map_err(|_| anyhow::anyhow!("docker container {} timed out after {}s", name, rs.timeout_secs))?
+        .context("spawn docker run")?;
+
+    Ok(CliOutput {
+        code: out.status.code(),
+        stdout: String::from_utf8_lossy(&out.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&out.stderr).to_string(),
+        usage: ResourceUsage::default(),
+        remote: Some(RemoteExecution {
+            backend: "docker".into(),
+            name,
+            started_at,
+            finished_at: now_unix(),
+        }),
+    })
+}
+
+async fn kubectl(ns: &str, args: &[&str], stdin: Option<&[u8]>) -> anyhow::Result<String> {
+    let mut cmd = Command::new("kubectl");
+    cmd.arg("-n")
+        .arg(ns)
+        .args(args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().context("spawn kubectl")?;
+    if let (Some(data), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(data).await.context("write kubectl stdin")?;
+    }
+    let out = child.wait_with_output().await.context("wait kubectl")?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "kubectl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+async fn run_kubernetes(
+    rs: &RemoteSettings,
+    bin: &str,
+    args: &[OsString],
+    env: &[(String, String)],
+    label: &str,
+) -> anyhow::Result<CliOutput> {
+    let name = job_name(label);
+    let started_at = now_unix();
+
+    let mut command = vec![bin.to_string()];
+    command.extend(args.iter().map(|a| a.to_string_lossy().to_string()));
+
+    let mut limits = serde_json::Map::new();
+    if let Some(m) = &rs.memory {
+        limits.insert("memory".into(), m.clone().into());
+    }
+    if let Some(c) = &rs.cpus {
+        limits.insert("cpu".into(), c.clone().into());
+    }
+
+    let manifest = serde_json::json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": { "name": name, "labels": { "app": "allure3-docker-service" } },
+        "spec": {
+            "backoffLimit": 0,
+            "ttlSecondsAfterFinished": 600,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [{
+                        "name": "allure",
+                        "image": rs.image,
+                        "command": command,
+                        "env": env.iter().map(|(k, v)| serde_json::json!({ "name": k, "value": v })).collect::<Vec<_>>(),
+                        "resources": { "limits": limits, "requests": limits },
+                        "volumeMounts": [{ "name": "data", "mountPath": rs.data_dir }],
+                    }],
+                    "volumes": [{ "name": "data", "persistentVolumeClaim": { "claimName": rs.data_volume } }],
+                }
+            }
+        }
+    });
+
+    info!(job = %name, namespace = %rs.namespace, image = %rs.image, "creating kubernetes job");
+    kubectl(&rs.namespace, &["apply", "-f", "-"], Some(&serde_json::to_vec(&manifest)?)).await?;
+
+    let job = format!("job/{name}");
+    let deadline = started_at + rs.timeout_secs;
+    let code = loop {
+        let st = kubectl(
+            &rs.namespace,
+            &["get", &job, "-o", "jsonpath={.status.succeeded},{.status.failed}"],
+            None,
+        )
+        .await?;
+        let (ok, failed) = st.trim().split_once(',').unwrap_or(("", ""));
+        if ok.parse::<u32>().unwrap_or(0) > 0 {
+            break Some(0);
+        }
+        if failed.parse::<u32>().unwrap_or(0) > 0 {
+            break Some(1);
+        }
+        if now_unix() >= deadline {
+            break None;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    };
+
+    let logs = kubectl(&rs.namespace, &["logs", &job], None)
+        .await
+        .unwrap_or_else(|e| format!("<logs unavailable: {e:#}>"));
+
+    if let Err(e) = kubectl(&rs.namespace, &["delete", &job, "--wait=false"], None).await {
+        warn!(job = %name, error = %format!("{e:#}"), "delete kubernetes job failed");
+    }
+
+    if code.is_none() {
+        anyhow::bail!("kubernetes job {} timed out after {}s", name, rs.timeout_secs);
+    }
+
+    Ok(CliOutput {
+        code,
+        // логи пода — общий поток stdout/stderr
+        stdout: logs,
+        stderr: String::new(),
+        usage: ResourceUsage::default(),
+        remote: Some(RemoteExecution {
+            backend: "kubernetes".into(),
+            name,
+            started_at,
+            finished_at: now_unix(),
+        }),
+    })
+}
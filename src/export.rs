@@ -0,0 +1,142 @@
+//! Static copy of a project — an index of runs plus the generated reports —
+//! for publishing on GitHub Pages or an S3 website outside the service.
+
+use anyhow::Context;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{settings, state::AppState, storage, util::{format_date, html_escape as escape, now_unix}, volumes::Volumes};
+
+struct ExportedRun {
+    run_id: u64,
+    status: Option<storage::Status>,
+    created_at: Option<u64>,
+    stats: Option<storage::RunStats>,
+}
+
+/// Writes the export zip to a temp file under `TMP_DIR` and returns its
+/// path; the caller removes it.
+pub async fn build(state: &AppState, project: &str) -> anyhow::Result<PathBuf> {
+    let pdir = storage::project_dir(&state.volumes, project);
+    let ps = settings::load(&pdir).await.unwrap_or_default();
+
+    let mut ids = storage::list_run_ids(&state.volumes, project).await?;
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+
+    // только прогоны со сгенерированным отчётом
+    let mut runs = Vec::new();
+    for run_id in ids {
+        let rdir = storage::run_dir(&state.volumes, project, run_id);
+        if !rdir.join("report").join("index.html").is_file() {
+            continue;
+        }
+        runs.push(ExportedRun {
+            run_id,
+            status: storage::read_run_status(&rdir).await.map(|s| s.status),
+            created_at: storage::run_created_at(&rdir).await,
+            stats: storage::read_run_info(&rdir).await.stats,
+        });
+    }
+
+    let index = render_index(project, ps.description.as_deref(), &runs);
+    let tmp_dir = &state.config.tmp_dir;
+    tokio::fs::create_dir_all(tmp_dir).await.context("create tmp dir")?;
+    let out = tmp_dir.join(format!("export-{project}-{}-{}.zip", now_unix(), std::process::id()));
+
+    let volumes = state.volumes.clone();
+    let project = project.to_string();
+    let path = out.clone();
+    let written = tokio::task::spawn_blocking(move || write_zip(&path, &volumes, &project, &index, &runs))
+        .await
+        .context("join export task")?;
+    if let Err(e) = written {
+        let _ = tokio::fs::remove_file(&out).await;
+        return Err(e);
+    }
+    Ok(out)
+}
+
+fn write_zip(path: &Path, volumes: &Volumes, project: &str, index: &str, runs: &[ExportedRun]) -> anyhow::Result<()> {
+    let file = std::fs::File::create(path).with_context(|| format!("create {}", path.display()))?;
+    let mut zip = zip::ZipWriter::new(std::io::BufWriter::new(file));
+    let options = zip::write::SimpleFileOptions::default();
+
+    zip.start_file("index.html", options)?;
+    zip.write_all(index.as_bytes())?;
+    // GitHub Pages иначе прогоняет сайт через Jekyll и прячет файлы на "_"
+    zip.start_file(".nojekyll", options)?;
+
+    if let Some(latest) = runs.first() {
+        zip.start_file("latest/index.html", options)?;
+        zip.write_all(
+            format!(
+                r#"<!doctype html><meta charset="utf-8"><meta http-equiv="refresh" content="0; url=../runs/{0}/index.html"><a href="../runs/{0}/index.html">Latest report</a>"#,
+                latest.run_id
+            )
+            .as_bytes(),
+        )?;
+    }
+
+    for run in runs {
+        let report = storage::run_dir(volumes, project, run.run_id).join("report");
+        crate::archive::add_dir(&mut zip, &report, &format!("runs/{}/", run.run_id))?;
+    }
+
+    zip.finish().context("finish zip")?.flush()?;
+    Ok(())
+}
+
+fn render_index(project: &str, description: Option<&str>, runs: &[ExportedRun]) -> String {
+    let rows: String = runs
+        .iter()
+        .map(|r| {
+            let stats = r
+                .stats
+                .as_ref()
+                .map(|s| format!("{} / {} / {}", s.passed, s.failed + s.broken, s.total))
+                .unwrap_or_default();
+            format!(
+                r#"<tr><td><a href="runs/{id}/index.html">#{id}</a></td><td>{status}</td><td>{created}</td><td>{stats}</td></tr>"#,
+                id = r.run_id,
+                status = escape(r.status.map_or("unknown", storage::Status::as_str)),
+                created = r.created_at.map(format_date).unwrap_or_default(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let description = description
+        .map(|d| format!("<p>{}</p>", escape(d)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8"/>
+<meta name="viewport" content="width=device-width, initial-scale=1"/>
+<title>{project} — Allure reports</title>
+<style>
+body{{font-family:system-ui,Segoe UI,Roboto,Arial;margin:0 auto;max-width:900px;padding:24px 16px;}}
+table{{border-collapse:collapse;width:100%;}}
+th,td{{text-align:left;padding:8px;border-bottom:1px solid #ddd;}}
+</style>
+</head>
+<body>
+<main>
+<h1>{project}</h1>
+{description}
+<p><a href="latest/index.html">Latest report</a> · exported {exported}</p>
+<table>
+<thead><tr><th scope="col">Run</th><th scope="col">Status</th><th scope="col">Uploaded (UTC)</th><th scope="col">Passed / failed / total</th></tr></thead>
+<tbody>
+{rows}
+</tbody>
+</table>
+</main>
+</body>
+</html>
+"#,
+        project = escape(project),
+        exported = format_date(now_unix()),
+    )
+}
@@ -0,0 +1,89 @@
+//! Starred projects per user. A user is whoever the auth proxy puts into
+//! `USER_HEADER`; without one, a random `allure_uid` cookie.
+
+use http::HeaderMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::{state::AppState, storage};
+
+const COOKIE: &str = "allure_uid";
+/// One year.
+const COOKIE_MAX_AGE: u64 = 365 * 24 * 3600;
+
+/// Who is asking, plus a `Set-Cookie` value when a new cookie id was issued.
+pub struct Viewer {
+    key: String,
+    pub set_cookie: Option<String>,
+}
+
+impl Viewer {
+    pub fn from_headers(headers: &HeaderMap, user_header: &str) -> Self {
+        let user = headers
+            .get(user_header)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|u| !u.is_empty());
+        if let Some(user) = user {
+            return Self { key: format!("user:{}", crate::util::clip(user, 200)), set_cookie: None };
+        }
+
+        match cookie(headers, COOKIE).filter(|id| valid_id(id)) {
+            Some(id) => Self { key: format!("cookie:{id}"), set_cookie: None },
+            None => {
+                let id = uuid::Uuid::new_v4().simple().to_string();
+                Self {
+                    key: format!("cookie:{id}"),
+                    set_cookie: Some(format!(
+                        "{COOKIE}={id}; Path=/; Max-Age={COOKIE_MAX_AGE}; SameSite=Lax; HttpOnly"
+                    )),
+                }
+            }
+        }
+    }
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(http::header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|kv| kv.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+fn valid_id(id: &str) -> bool {
+    (8..=64).contains(&id.len()) && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn path(data_dir: &Path) -> PathBuf {
+    data_dir.join("favorites.json")
+}
+
+async fn load_all(data_dir: &Path) -> BTreeMap<String, BTreeSet<String>> {
+    let Ok(s) = tokio::fs::read_to_string(path(data_dir)).await else {
+        return BTreeMap::new();
+    };
+    serde_json::from_str(&s).unwrap_or_default()
+}
+
+pub async fn starred(state: &AppState, viewer: &Viewer) -> BTreeSet<String> {
+    load_all(&state.data_dir).await.remove(&viewer.key).unwrap_or_default()
+}
+
+pub async fn set_starred(state: &AppState, viewer: &Viewer, project: &str, on: bool) -> anyhow::Result<()> {
+    let _guard = state.favorites_lock.lock().await;
+    let mut all = load_all(&state.data_dir).await;
+    let set = all.entry(viewer.key.clone()).or_default();
+    if on {
+        set.insert(project.to_string());
+    } else {
+        set.remove(project);
+    }
+    if set.is_empty() {
+        all.remove(&viewer.key);
+    }
+    storage::write_json(&path(&state.data_dir), &all).await
+}
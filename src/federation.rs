@@ -0,0 +1,160 @@
+//! Directory of report servers (`FEDERATION_PEERS`): this instance and
+//! its peers' projects in one list, for orgs running an instance per
+//! business unit. Peers are asked for `/api/v1/projects/summary` on every
+//! request; an unreachable peer is listed with its error.
+
+use anyhow::Context;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::{state::AppState, storage};
+
+/// Per-peer request timeout: one slow instance must not stall the page.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub name: String,
+    /// Base URL without the trailing slash.
+    pub url: String,
+}
+
+impl FromStr for Peer {
+    type Err = anyhow::Error;
+
+    /// `name=https://allure.unit.example.com`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, url)) = s.split_once('=') else {
+            anyhow::bail!("expected name=url, got {s:?}");
+        };
+        let (name, url) = (name.trim(), url.trim().trim_end_matches('/'));
+        if name.is_empty() {
+            anyhow::bail!("empty peer name in {s:?}");
+        }
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            anyhow::bail!("peer url must be http(s), got {url:?}");
+        }
+        Ok(Self { name: name.to_string(), url: url.to_string() })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Directory {
+    pub instances: Vec<Instance>,
+    pub total_projects: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Instance {
+    pub name: String,
+    /// None for this instance without `PUBLIC_URL`.
+    pub url: Option<String>,
+    pub local: bool,
+    /// Why the peer's projects are missing.
+    pub error: Option<String>,
+    pub projects: Vec<FederatedProject>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FederatedProject {
+    pub project: String,
+    #[serde(default)]
+    pub runs_count: usize,
+    #[serde(default)]
+    pub latest_status: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Project page on its instance; filled in here, not by the peer.
+    #[serde(default)]
+    pub ui_url: String,
+}
+
+/// The part of a peer's `/api/v1/projects/summary` the directory needs.
+#[derive(Deserialize)]
+struct PeerSummary {
+    projects: Vec<FederatedProject>,
+}
+
+/// Case-insensitive match on the project name and description.
+fn matches(p: &FederatedProject, q: &str) -> bool {
+    q.is_empty()
+        || p.project.to_lowercase().contains(q)
+        || p.description.as_deref().is_some_and(|d| d.to_lowercase().contains(q))
+}
+
+/// All instances with their projects matching `q`.
+pub async fn directory(state: &AppState, q: &str) -> Directory {
+    let q = q.trim().to_lowercase();
+    let mut instances = vec![local(state).await];
+    instances.extend(join_all(state.config.federation_peers.iter().map(|p| peer(state, p))).await);
+
+    for i in &mut instances {
+        i.projects.retain(|p| matches(p, &q));
+    }
+    let total_projects = instances.iter().map(|i| i.projects.len()).sum();
+    Directory { instances, total_projects }
+}
+
+async fn local(state: &AppState) -> Instance {
+    let base = state.config.public_url.clone();
+    let (projects, error) = match storage::list_project_summaries(&state.volumes).await {
+        Ok(list) => (
+            list.into_iter()
+                .map(|s| FederatedProject {
+                    ui_url: format!("{}/ui/{}/", base.as_deref().unwrap_or(""), s.project),
+                    project: s.project,
+                    runs_count: s.runs_count,
+                    latest_status: s.latest_status.map(|st| st.as_str().to_string()),
+                    description: s.description,
+                })
+                .collect(),
+            None,
+        ),
+        Err(e) => (Vec::new(), Some(format!("list projects: {e:#}"))),
+    };
+    Instance { name: state.config.federation_name.clone(), url: base, local: true, error, projects }
+}
+
+async fn peer(state: &AppState, peer: &Peer) -> Instance {
+    let mut instance = Instance {
+        name: peer.name.clone(),
+        url: Some(peer.url.clone()),
+        local: false,
+        error: None,
+        projects: Vec::new(),
+    };
+    match fetch(state, peer).await {
+        Ok(projects) => instance.projects = projects,
+        Err(e) => {
+            warn!(peer=%peer.name, error=%format!("{e:#}"), "federation peer unavailable");
+            instance.error = Some(format!("{e:#}"));
+        }
+    }
+    instance
+}
+
+async fn fetch(state: &AppState, peer: &Peer) -> anyhow::Result<Vec<FederatedProject>> {
+    let summary: PeerSummary = state
+        .http
+        .get(format!("{}/api/v1/projects/summary", peer.url))
+        .timeout(PEER_TIMEOUT)
+        .send()
+        .await
+        .context("send request")?
+        .error_for_status()
+        .context("bad response")?
+        .json()
+        .await
+        .context("parse summary")?;
+    Ok(summary
+        .projects
+        .into_iter()
+        .map(|mut p| {
+            p.ui_url = format!("{}/ui/{}/", peer.url, p.project);
+            p
+        })
+        .collect())
+}
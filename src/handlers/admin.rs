@@ -0,0 +1,651 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    audit, hold, merge,
+    purge::{self, PurgeRequest},
+    renumber, retention, settings,
+    state::AppState,
+    storage,
+    storage_classes::{self, StorageClass},
+    usage,
+    util::{validate_name, validate_project_name},
+    volumes,
+};
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    /// `30d`, `12h`.
+    pub window: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// Project that receives the runs.
+    pub into: String,
+    /// Only show the plan.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct RenumberRequest {
+    /// First id of the compacted range.
+    #[serde(default = "first_run_id")]
+    pub start: u64,
+    /// Only show the plan.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn first_run_id() -> u64 {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct HoldRequest {
+    pub reason: String,
+}
+
+/// Admin endpoints exist only when `ADMIN_TOKEN` is configured.
+fn auth_error(state: &AppState, headers: &HeaderMap) -> Option<(StatusCode, &'static str)> {
+    super::bearer_error(headers, state.config.admin_token.as_ref(), "Admin API is disabled")
+}
+
+pub async fn purge(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<PurgeRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    if req.projects.is_empty() {
+        return (StatusCode::BAD_REQUEST, "'projects' is required (use [\"*\"] for all)").into_response();
+    }
+
+    // под legal hold purge не выполняется вовсе, а не частично
+    if !req.dry_run {
+        let preview = PurgeRequest { dry_run: true, ..req.clone() };
+        match purge::run(&state, &preview).await {
+            Ok(r) if !r.held.is_empty() => {
+                let body = serde_json::json!({ "error": "Runs in scope are under legal hold", "held": r.held });
+                return (StatusCode::LOCKED, Json(body)).into_response();
+            }
+            Ok(_) => {}
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("purge: {e:#}")).into_response(),
+        }
+    }
+
+    let report = match purge::run(&state, &req).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("purge: {e:#}")).into_response(),
+    };
+
+    if !req.dry_run {
+        info!(runs = report.runs.len(), files = report.files, bytes = report.bytes, "purge done");
+        let details = serde_json::json!({ "request": req, "runs": report.runs, "files": report.files, "bytes": report.bytes });
+        if let Err(e) = audit::record(&state.data_dir, "purge", details).await {
+            warn!(error=%format!("{e:#}"), "write audit log failed");
+        }
+    }
+
+    Json(report).into_response()
+}
+
+pub async fn audit_log(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<AuditQuery>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+
+    match audit::read_recent(&state.data_dir, q.limit.unwrap_or(100)).await {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("read audit log: {e:#}")).into_response(),
+    }
+}
+
+/// Uploads and generation minutes by token, project and day.
+pub async fn usage_report(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<UsageQuery>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let window = q.window.unwrap_or_else(|| "30d".to_string());
+    let Some(window_secs) = usage::parse_window(&window) else {
+        return (StatusCode::BAD_REQUEST, "Invalid window: expected e.g. 30d or 12h, at most 366d").into_response();
+    };
+    match usage::report(&state.data_dir, &window, window_secs).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("usage: {e:#}")).into_response(),
+    }
+}
+
+/// What retention would remove now, per project; deletes nothing.
+pub async fn retention_dry_run(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    Json(retention::dry_run(&state).await).into_response()
+}
+
+pub async fn list_locks(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    Json(state.project_locks.list()).into_response()
+}
+
+pub async fn force_unlock(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project): Path<String>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+
+    let Some(released) = state.project_locks.force_unlock(&project) else {
+        return (StatusCode::NOT_FOUND, "Lock is not held").into_response();
+    };
+    warn!(project=%project, holder=?released.holder, held_secs=?released.held_secs, "project lock force-unlocked");
+
+    if let Err(e) = audit::record(&state.data_dir, "force_unlock", serde_json::json!(released)).await {
+        warn!(error=%format!("{e:#}"), "write audit log failed");
+    }
+    Json(released).into_response()
+}
+
+pub async fn list_holds(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    match hold::list(&state.volumes).await {
+        Ok(holds) => Json(holds).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("list holds: {e:#}")).into_response(),
+    }
+}
+
+pub async fn set_project_hold(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project): Path<String>,
+    Json(req): Json<HoldRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    change_hold(&state, &project, None, Some(&req.reason)).await
+}
+
+pub async fn clear_project_hold(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project): Path<String>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    change_hold(&state, &project, None, None).await
+}
+
+pub async fn set_run_hold(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((project, run_id)): Path<(String, u64)>,
+    Json(req): Json<HoldRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    change_hold(&state, &project, Some(run_id), Some(&req.reason)).await
+}
+
+pub async fn clear_run_hold(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((project, run_id)): Path<(String, u64)>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    change_hold(&state, &project, Some(run_id), None).await
+}
+
+/// Sets (`reason` given) or lifts a hold and records it in the audit log.
+async fn change_hold(state: &AppState, project_raw: &str, run_id: Option<u64>, reason: Option<&str>) -> Response {
+    let project = match validate_project_name(&state.volumes, project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let dir = match run_id {
+        Some(id) => storage::run_dir(&state.volumes, &project, id),
+        None => storage::project_dir(&state.volumes, &project),
+    };
+    if !dir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    }
+
+    let (action, result) = match reason {
+        Some(reason) if reason.trim().is_empty() => {
+            return (StatusCode::BAD_REQUEST, "'reason' is required").into_response();
+        }
+        Some(reason) => ("legal_hold_set", hold::set(&dir, reason).await.map(|_| true)),
+        None => ("legal_hold_clear", hold::clear(&dir).await),
+    };
+    let changed = match result {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("legal hold: {e:#}")).into_response(),
+    };
+    if !changed {
+        return (StatusCode::NOT_FOUND, "No legal hold").into_response();
+    }
+
+    warn!(project=%project, run_id=?run_id, action, "legal hold changed");
+    let details = serde_json::json!({ "project": project, "run_id": run_id, "reason": reason });
+    if let Err(e) = audit::record(&state.data_dir, action, details).await {
+        warn!(error=%format!("{e:#}"), "write audit log failed");
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+pub async fn project_duplicates(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    match merge::duplicates(&state).await {
+        Ok(groups) => Json(groups).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("duplicates: {e:#}")).into_response(),
+    }
+}
+
+pub async fn merge_project(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project_raw): Path<String>,
+    Json(req): Json<MergeRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let from = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let into = match validate_project_name(&state.volumes, &req.into) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid 'into': {e}")).into_response(),
+    };
+    if from == into {
+        return (StatusCode::BAD_REQUEST, "Cannot merge a project into itself").into_response();
+    }
+
+    // два лока — всегда в одном порядке, чтобы встречные слияния не зависли
+    let (first, second) = if from < into { (&from, &into) } else { (&into, &from) };
+    let _first = state.lock_project(first, "merge").await;
+    let _second = state.lock_project(second, "merge").await;
+
+    for p in [&from, &into] {
+        if !storage::project_dir(&state.volumes, p).is_dir() {
+            return (StatusCode::NOT_FOUND, format!("Project not found: {p}")).into_response();
+        }
+    }
+    match hold::project_held(&state.volumes, &from).await {
+        Ok(true) => return (StatusCode::LOCKED, "Project is under legal hold").into_response(),
+        Ok(false) => {}
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("legal hold: {e:#}")).into_response(),
+    }
+    // задача генерации ссылается на from/run_id: перенесённый прогон так и остался бы в очереди
+    if state.jobs.has_unfinished(&from) {
+        return (StatusCode::CONFLICT, "Project has queued generation jobs").into_response();
+    }
+
+    let report = match merge::merge(&state, &from, &into, req.dry_run).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("merge: {e:#}")).into_response(),
+    };
+    if !req.dry_run {
+        let details = serde_json::to_value(&report).unwrap_or_default();
+        if let Err(e) = audit::record(&state.data_dir, "project_merged", details).await {
+            warn!(error=%format!("{e:#}"), "write audit log failed");
+        }
+    }
+    Json(report).into_response()
+}
+
+pub async fn renumber_runs(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project_raw): Path<String>,
+    Json(req): Json<RenumberRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    if req.start == 0 {
+        return (StatusCode::BAD_REQUEST, "start must be at least 1").into_response();
+    }
+
+    let _guard = state.lock_project(&project, "renumber").await;
+    if !storage::project_dir(&state.volumes, &project).is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+    // номера прогонов под hold и в очереди генерации менять нельзя
+    match hold::project_held(&state.volumes, &project).await {
+        Ok(true) => return (StatusCode::LOCKED, "Project is under legal hold").into_response(),
+        Ok(false) => {}
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("legal hold: {e:#}")).into_response(),
+    }
+    if state.jobs.has_unfinished(&project) {
+        return (StatusCode::CONFLICT, "Project has queued generation jobs").into_response();
+    }
+
+    let run_ids = match storage::list_run_ids(&state.volumes, &project).await {
+        Ok(ids) => ids,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e:#}")).into_response(),
+    };
+    let moves = match renumber::plan(&run_ids, req.start) {
+        Ok(m) => m,
+        Err(e) => return (StatusCode::CONFLICT, e).into_response(),
+    };
+    let report = match renumber::apply(&state, &project, moves, req.dry_run).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("renumber: {e:#}")).into_response(),
+    };
+    if !req.dry_run && !report.runs.is_empty() {
+        let details = serde_json::to_value(&report).unwrap_or_default();
+        if let Err(e) = audit::record(&state.data_dir, "runs_renumbered", details).await {
+            warn!(error=%format!("{e:#}"), "write audit log failed");
+        }
+    }
+    Json(report).into_response()
+}
+
+pub async fn rebuild_next_run_id(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project_raw): Path<String>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let _guard = state.lock_project(&project, "renumber").await;
+    if !storage::project_dir(&state.volumes, &project).is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+    match renumber::rebuild_next_run_id(&state, &project).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("next_run_id: {e:#}")).into_response(),
+    }
+}
+
+pub async fn list_aliases(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    Json(state.aliases.list()).into_response()
+}
+
+/// Frees the old name; requests to it stop reaching the merged project.
+pub async fn delete_alias(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(alias): Path<String>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    match state.aliases.remove(&state.data_dir, &alias).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "No such alias").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("aliases: {e:#}")).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct VolumeRequest {
+    /// Volume to pin the project to; null drops the pin.
+    pub volume: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RebalanceRequest {
+    /// Only show the plan.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct RebalanceReport {
+    dry_run: bool,
+    moves: Vec<volumes::Move>,
+    failed: Vec<FailedMove>,
+}
+
+#[derive(Serialize)]
+struct FailedMove {
+    #[serde(flatten)]
+    planned: volumes::Move,
+    error: String,
+}
+
+const NO_VOLUMES: (StatusCode, &str) = (StatusCode::NOT_FOUND, "Data volumes are not configured");
+
+pub async fn list_volumes(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let vols = &state.volumes;
+    if !vols.configured() {
+        return NO_VOLUMES.into_response();
+    }
+    Json(serde_json::json!({
+        "volumes": vols.list(),
+        "pins": vols.pins(),
+        "pending_moves": vols.plan(),
+    }))
+    .into_response()
+}
+
+/// Pins the project to a volume and moves it there right away.
+pub async fn set_project_volume(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project_raw): Path<String>,
+    Json(req): Json<VolumeRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let vols = &state.volumes;
+    if !vols.configured() {
+        return NO_VOLUMES.into_response();
+    }
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let _guard = state.lock_project(&project, "move").await;
+    if let Err(e) = vols.set_pin(&state.data_dir, &project, req.volume.as_deref()).await {
+        return (StatusCode::BAD_REQUEST, format!("{e:#}")).into_response();
+    }
+    // пина достаточно для ещё не созданного проекта — он появится сразу на нужном томе
+    let moved = if storage::project_dir(&state.volumes, &project).is_dir() {
+        match vols.relocate(&project).await {
+            Ok(moved) => moved,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("move project: {e:#}")).into_response(),
+        }
+    } else {
+        false
+    };
+    let volume = vols.name_of(&project).to_string();
+
+    let details = serde_json::json!({ "project": project, "pin": req.volume, "volume": volume, "moved": moved });
+    if let Err(e) = audit::record(&state.data_dir, "project_volume_set", details).await {
+        warn!(error=%format!("{e:#}"), "write audit log failed");
+    }
+    Json(serde_json::json!({ "project": project, "volume": volume, "moved": moved })).into_response()
+}
+
+/// Moves every project that is not on its pinned or hashed volume.
+pub async fn rebalance_volumes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<RebalanceRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let vols = &state.volumes;
+    if !vols.configured() {
+        return NO_VOLUMES.into_response();
+    }
+
+    let plan = vols.plan();
+    if req.dry_run {
+        return Json(RebalanceReport { dry_run: true, moves: plan, failed: Vec::new() }).into_response();
+    }
+    let (mut moves, mut failed) = (Vec::new(), Vec::new());
+    for m in plan {
+        let _guard = state.lock_project(&m.project, "move").await;
+        match vols.relocate(&m.project).await {
+            Ok(true) => moves.push(m),
+            Ok(false) => {}
+            Err(e) => {
+                warn!(project=%m.project, error=%format!("{e:#}"), "move project to volume failed");
+                failed.push(FailedMove { planned: m, error: format!("{e:#}") });
+            }
+        }
+    }
+    info!(moved = moves.len(), failed = failed.len(), "volumes rebalanced");
+
+    let report = RebalanceReport { dry_run: false, moves, failed };
+    if !report.moves.is_empty() || !report.failed.is_empty() {
+        let details = serde_json::to_value(&report).unwrap_or_default();
+        if let Err(e) = audit::record(&state.data_dir, "volumes_rebalanced", details).await {
+            warn!(error=%format!("{e:#}"), "write audit log failed");
+        }
+    }
+    Json(report).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct StorageClassRequest {
+    /// Class to assign; null leaves only the project's own settings.
+    pub storage_class: Option<String>,
+}
+
+pub async fn list_storage_classes(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    Json(storage_classes::list()).into_response()
+}
+
+/// Defines or replaces a class; its projects pick it up on the next read.
+pub async fn put_storage_class(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name_raw): Path<String>,
+    Json(class): Json<StorageClass>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let name = match validate_name(&name_raw) {
+        Ok(n) => n,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid storage class: {e}")).into_response(),
+    };
+    if class.retention.as_ref().is_some_and(|r| r.max_runs == Some(0)) {
+        return (StatusCode::BAD_REQUEST, "'retention.max_runs' must be at least 1").into_response();
+    }
+    let details = serde_json::json!({ "storage_class": name, "class": class });
+    if let Err(e) = storage_classes::set(&state.data_dir, &name, Some(class)).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("storage classes: {e:#}")).into_response();
+    }
+    if let Err(e) = audit::record(&state.data_dir, "storage_class_set", details).await {
+        warn!(error=%format!("{e:#}"), "write audit log failed");
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+pub async fn delete_storage_class(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    if !storage_classes::exists(&name) {
+        return (StatusCode::NOT_FOUND, "No such storage class").into_response();
+    }
+    if let Err(e) = storage_classes::set(&state.data_dir, &name, None).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("storage classes: {e:#}")).into_response();
+    }
+    if let Err(e) = audit::record(&state.data_dir, "storage_class_deleted", serde_json::json!({ "storage_class": name })).await {
+        warn!(error=%format!("{e:#}"), "write audit log failed");
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Assigns a storage class to the project; sections set in its
+/// `project.json` still win over the class.
+pub async fn set_project_storage_class(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(project_raw): Path<String>,
+    Json(req): Json<StorageClassRequest>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    if let Some(name) = req.storage_class.as_deref().filter(|n| !storage_classes::exists(n)) {
+        return (StatusCode::BAD_REQUEST, format!("Unknown storage class '{name}'")).into_response();
+    }
+    let pdir = storage::project_dir(&state.volumes, &project);
+    if !pdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+
+    let _guard = state.lock_project(&project, "config").await;
+    if let Err(e) = settings::update_section(&pdir, "storage_class", &req.storage_class).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("project settings: {e:#}")).into_response();
+    }
+    let details = serde_json::json!({ "project": project, "storage_class": req.storage_class });
+    if let Err(e) = audit::record(&state.data_dir, "project_storage_class_set", details.clone()).await {
+        warn!(error=%format!("{e:#}"), "write audit log failed");
+    }
+    Json(details).into_response()
+}
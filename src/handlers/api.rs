@@ -1,36 +1,100 @@
 use axum::{
-    extract::{Multipart, Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Body,
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
-use tracing::{error, info, warn};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
 
 use crate::{
-    allure,
+    analytics, artifacts, audit, backup, badge, breaker, categories, ci, export,
+    favorites::{self, Viewer},
+    federation,
+    hold,
+    hooks::{self, HookContext, HookEvent},
+    idempotency,
+    jobs::JobStatus,
+    metrics,
+    pipeline::{self, Generated},
+    progress::{Phase, RunEvent, UploadTracker},
+    quota,
+    resumable,
+    retention,
+    run_keys,
+    schema,
+    seal,
+    results,
+    settings,
     state::AppState,
-    storage,
-    unzip::{self, UnzipLimits},
-    util::sanitize_name,
+    storage::{self, Status},
+    summary,
+    archive,
+    uploads::{Stage, UploadInfo, UploadSession},
+    usage,
+    util::{self, parse_timestamp, validate_new_project_name, validate_project_name},
 };
 
-#[derive(Deserialize, Serialize, Default)]
-pub struct Meta {
-    pub branch: Option<String>,
-    pub commit: Option<String>,
-    pub trigger: Option<String>,
-    pub started_at: Option<String>,
-}
-
 #[derive(Serialize)]
 pub struct UploadResp {
     pub project: String,
     pub run_id: u64,
     pub ui_url: String,
     pub latest_url: String,
-    pub status: String,        // "success" | "failed"
+    pub status: Status,
     pub error: Option<String>, // error text if failed
+    pub run_key: Option<String>,
+    /// Test counts of the report; None when generation failed.
+    pub summary: Option<summary::RunSummary>,
+    /// A failed generation may pass when the upload is sent again.
+    pub retryable: bool,
+    /// Old runs deleted to fit the project's disk quota.
+    pub evicted_runs: Vec<u64>,
+}
+
+/// Error body of uploads and regenerations.
+#[derive(Serialize)]
+pub struct ErrorResp {
+    pub error: String,
+    /// The same request may pass when sent again (after `Retry-After`).
+    pub retryable: bool,
+}
+
+/// 202 answer of an `?async=true` upload: the report is generated by a
+/// background job.
+#[derive(Serialize)]
+pub struct UploadAcceptedResp {
+    pub project: String,
+    pub run_id: u64,
+    pub job_id: String,
+    pub job_url: String,
+    pub ui_url: String,
+    pub latest_url: String,
+    pub status: JobStatus,
+    pub run_key: Option<String>,
+    pub evicted_runs: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    /// Client-chosen id to follow the upload's progress by.
+    pub upload_id: Option<String>,
+    /// Answer 202 once the results are stored and generate in the background.
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+    /// `github`, `gitlab` or `jenkins`; same as `ci` in meta.
+    pub ci: Option<String>,
+    /// Expected SHA-256 of the archive (hex; comma-separated, one per part).
+    /// Also the `X-Archive-SHA256` header or a `sha256` multipart field.
+    pub sha256: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -40,18 +104,258 @@ pub struct ProjectsSummaryResp {
     pub projects: Vec<storage::ProjectSummary>,
 }
 
+#[derive(Deserialize)]
+pub struct SummaryQuery {
+    /// Only projects starred by the caller.
+    #[serde(default)]
+    pub starred: bool,
+}
+
+#[derive(Deserialize)]
+pub struct FederationQuery {
+    /// Substring of the project name or description.
+    #[serde(default)]
+    pub q: String,
+}
+
+#[derive(Deserialize)]
+pub struct StaleQuery {
+    pub days: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct StorageStatsQuery {
+    /// Walk the volume now instead of serving the cached result.
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Serialize)]
+pub struct StaleProjectsResp {
+    pub min_idle_days: u64,
+    pub total_disk_bytes: u64,
+    pub projects: Vec<storage::StaleProject>,
+}
+
+#[derive(Serialize)]
+pub struct LaunchResp {
+    pub launch: String,
+    /// "success" when every project's latest run in the launch succeeded,
+    /// "failed" when any failed, "unknown" otherwise.
+    pub status: String,
+    pub projects_count: usize,
+    pub projects: Vec<LaunchProject>,
+}
+
+#[derive(Serialize)]
+pub struct LaunchProject {
+    pub project: String,
+    pub run_ids: Vec<u64>,
+    pub latest_run_id: u64,
+    pub status: Option<Status>,
+    pub error: Option<String>,
+    pub ui_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct SlowestQuery {
+    /// How many recent runs to look at.
+    pub window: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SlowestResp {
+    pub project: String,
+    pub run_ids: Vec<u64>,
+    pub tests: Vec<analytics::TestDurations>,
+}
+
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    pub base: Option<u64>,
+    pub head: Option<u64>,
+    pub threshold_pct: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct CompareResp {
+    pub project: String,
+    pub base_run_id: u64,
+    pub head_run_id: u64,
+    pub base_url: String,
+    pub head_url: String,
+    pub threshold_pct: f64,
+    #[serde(flatten)]
+    pub comparison: analytics::Comparison,
+}
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    pub group_by: Option<String>,
+    /// Only for the project-level endpoint: number of recent runs.
+    pub window: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct StatsResp {
+    pub project: String,
+    pub run_ids: Vec<u64>,
+    pub group_by: String,
+    pub groups: Vec<analytics::GroupStats>,
+}
+
+#[derive(Deserialize)]
+pub struct GateQuery {
+    pub min_score: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct GateResp {
+    pub project: String,
+    pub run_id: u64,
+    pub passed: bool,
+    pub health_score: Option<f64>,
+    pub min_score: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct MutedQuery {
+    pub window: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct MutedResp {
+    pub project: String,
+    pub max_muted: Option<usize>,
+    /// Muted count of the latest run.
+    pub current: Option<usize>,
+    pub over_budget: bool,
+    /// Oldest first.
+    pub history: Vec<MutedPoint>,
+}
+
+#[derive(Serialize)]
+pub struct MutedPoint {
+    pub run_id: u64,
+    pub muted: usize,
+}
+
+#[derive(Serialize)]
+pub struct ReportSizeResp {
+    pub project: String,
+    pub max_bytes: Option<u64>,
+    pub max_growth_pct: Option<f64>,
+    /// The latest measured run.
+    pub current: Option<ReportSizePoint>,
+    /// Oldest first.
+    pub history: Vec<ReportSizePoint>,
+}
+
+#[derive(Serialize)]
+pub struct ReportSizePoint {
+    pub run_id: u64,
+    #[serde(flatten)]
+    pub size: storage::ReportSize,
+}
+
+#[derive(Deserialize)]
+pub struct TopAttachmentsQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct TopAttachmentsResp {
+    pub project: String,
+    pub run_id: u64,
+    /// All attachment files of the run.
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub attachments: Vec<results::AttachmentUsage>,
+}
+
 #[derive(Serialize)]
 pub struct DeleteResp {
     pub deleted: bool,
     pub project: String,
 }
 
+#[derive(Deserialize)]
+pub struct PruneRequest {
+    /// Defaults to the project's retention settings.
+    pub keep_last: Option<usize>,
+    /// Only report what would be removed.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct PruneResp {
+    pub project: String,
+    /// Removed run ids, oldest first.
+    pub removed: Vec<u64>,
+    /// Older runs kept because of a legal hold.
+    pub held: Vec<u64>,
+    pub bytes: u64,
+    pub latest_run_id: Option<u64>,
+    /// Nothing was removed: `removed` is what would have been.
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ProjectConfigRequest {
+    #[serde(default)]
+    pub retention: settings::RetentionSettings,
+}
+
+#[derive(Serialize)]
+pub struct ProjectConfigResp {
+    pub project: String,
+    /// As stored in `project.json`.
+    pub retention: settings::RetentionSettings,
+    /// With the service-wide defaults applied.
+    pub effective: retention::Limits,
+}
+
+#[derive(Serialize)]
+pub struct DeleteRunResp {
+    pub deleted: bool,
+    pub project: String,
+    pub run_id: u64,
+    /// Size of the removed run directory.
+    pub bytes: u64,
+    /// Latest run after the removal.
+    pub latest_run_id: Option<u64>,
+}
+
 #[derive(Serialize)]
 pub struct RegenerateResp {
     pub project: String,
     pub run_id: u64,
-    pub status: String,        // "success" | "failed"
+    pub status: Status,
     pub error: Option<String>,
+    pub retryable: bool,
+}
+
+/// Answer of `POST /api/v1/projects/{project}/runs/import`.
+#[derive(Serialize)]
+pub struct ImportResp {
+    pub project: String,
+    pub run_id: u64,
+    pub imported_from: ImportedFrom,
+    pub ui_url: String,
+    /// Run status from the backup, or `queued` when it had no report.
+    pub status: Option<Status>,
+    pub job_id: Option<String>,
+    /// Old runs deleted to fit the project's disk quota.
+    pub evicted_runs: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ImportedFrom {
+    pub project: String,
+    pub run_id: u64,
+    pub created_at: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -63,13 +367,56 @@ pub struct RunsResp {
 #[derive(Serialize)]
 pub struct RunItem {
     pub run_id: u64,
-    pub status: Option<String>, // success/failed/None
+    pub status: Option<Status>,
+    pub error: Option<String>,
+    pub ui_url: String,
+    /// External key from the upload's meta.
+    pub run_key: Option<String>,
+    /// Copy in the project's sync bucket, if any.
+    pub external_url: Option<String>,
+    pub views: u64,
+    pub last_viewed_at: Option<u64>,
+    pub issues: Vec<storage::IssueRef>,
+    pub health_score: Option<f64>,
+    /// Test counts of the report.
+    pub summary: Option<summary::RunSummary>,
+    /// Only in `as_of` listings: the run existed then but is gone now.
+    pub deleted_at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct RunsQuery {
+    /// `2024-01-31`, `2024-01-31T12:00:00Z` or unix seconds.
+    pub as_of: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RunDetailResp {
+    pub project: String,
+    pub run_id: u64,
+    pub status: Option<Status>,
     pub error: Option<String>,
     pub ui_url: String,
+    pub meta: Option<storage::Meta>,
+    pub info: storage::RunInfo,
+    pub views: storage::RunViews,
+    pub sealed: bool,
+}
+
+/// Project names only; `/summary` carries the per-project details.
+pub async fn list_projects(State(state): State<AppState>) -> impl IntoResponse {
+    match storage::list_projects(&state.volumes).await {
+        Ok(names) => (StatusCode::OK, Json(names)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("list projects: {e:#}")).into_response(),
+    }
 }
 
-pub async fn list_projects_summary(State(state): State<AppState>) -> impl IntoResponse {
-    let summaries = match storage::list_project_summaries(&state.data_dir).await {
+pub async fn list_projects_summary(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<SummaryQuery>,
+) -> impl IntoResponse {
+    let mut summaries = match storage::list_project_summaries(&state.volumes).await {
         Ok(x) => x,
         Err(e) => {
             return (
@@ -83,7 +430,18 @@ pub async fn list_projects_summary(State(state): State<AppState>) -> impl IntoRe
     let total_projects = summaries.len();
     let total_runs = summaries.iter().map(|p| p.runs_count).sum::<usize>();
 
-    (
+    let viewer = Viewer::from_headers(&headers, &state.config.user_header);
+    let starred = favorites::starred(&state, &viewer).await;
+    for p in &mut summaries {
+        p.starred = starred.contains(&p.project);
+    }
+    if q.starred {
+        summaries.retain(|p| p.starred);
+    }
+    // избранные — первыми, внутри групп порядок по имени сохраняется
+    summaries.sort_by_key(|p| !p.starred);
+
+    let mut resp = (
         StatusCode::OK,
         Json(ProjectsSummaryResp {
             total_projects,
@@ -91,215 +449,2282 @@ pub async fn list_projects_summary(State(state): State<AppState>) -> impl IntoRe
             projects: summaries,
         }),
     )
-        .into_response()
+        .into_response();
+    set_viewer_cookie(&mut resp, &viewer);
+    resp
 }
 
-pub async fn list_runs(
+/// Projects of this instance and its `FEDERATION_PEERS`.
+pub async fn federation_directory(State(state): State<AppState>, Query(q): Query<FederationQuery>) -> impl IntoResponse {
+    if state.config.federation_peers.is_empty() {
+        return (StatusCode::NOT_FOUND, "Federation is not configured").into_response();
+    }
+    Json(federation::directory(&state, &q.q).await).into_response()
+}
+
+/// PUT/DELETE /api/v1/projects/{project}/star
+pub async fn star_project(
     State(state): State<AppState>,
     Path(project_raw): Path<String>,
+    method: http::Method,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let project = match sanitize_name(&project_raw) {
-        Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Invalid project").into_response(),
-    };
-
-    // list ids
-    let mut ids = match storage::list_run_ids(&state.data_dir, &project).await {
-        Ok(v) => v,
-        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e}")).into_response(),
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
     };
+    let on = method == http::Method::PUT;
+    if on && !storage::project_dir(&state.volumes, &project).is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
 
-    // newest first
-    ids.sort_unstable_by(|a, b| b.cmp(a));
-
-    let mut runs = Vec::with_capacity(ids.len());
-    for id in ids {
-        let rdir = storage::run_dir(&state.data_dir, &project, id);
-        let st = storage::read_run_status(&rdir).await;
-
-        runs.push(RunItem {
-            run_id: id,
-            status: st.as_ref().map(|x| x.status.clone()),
-            error: st.and_then(|x| x.error),
-            ui_url: format!("/ui/{}/runs/{}/", project, id),
-        });
+    let viewer = Viewer::from_headers(&headers, &state.config.user_header);
+    if let Err(e) = favorites::set_starred(&state, &viewer, &project, on).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("save favorites: {e}")).into_response();
     }
 
-    (StatusCode::OK, Json(RunsResp { project, runs })).into_response()
+    let mut resp = StatusCode::NO_CONTENT.into_response();
+    set_viewer_cookie(&mut resp, &viewer);
+    resp
 }
 
-pub async fn delete_project(
+/// GET /api/v1/projects/{project}/export/static — zip with a static site
+/// (runs index + reports).
+pub async fn export_static(
     State(state): State<AppState>,
     Path(project_raw): Path<String>,
 ) -> impl IntoResponse {
-    let project = match sanitize_name(&project_raw) {
-        Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Invalid project").into_response(),
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
     };
+    if !storage::project_dir(&state.volumes, &project).is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
 
-    let lock = state.project_lock(&project);
-    let _guard = lock.lock().await;
+    let path = match export::build(&state, &project).await {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("export: {e:#}")).into_response(),
+    };
+    let file = tokio::fs::File::open(&path).await;
+    // открытый файл дочитывается и после unlink
+    let _ = tokio::fs::remove_file(&path).await;
+    let file = match file {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("open export: {e}")).into_response(),
+    };
+    let len = file.metadata().await.map(|m| m.len()).ok();
 
-    if let Err(e) = storage::delete_project(&state.data_dir, &project).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, format!("delete project: {e}")).into_response();
+    let mut resp = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)).into_response();
+    let headers = resp.headers_mut();
+    headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/zip"));
+    if let Ok(v) = format!("attachment; filename=\"{project}-static.zip\"").parse() {
+        headers.insert(header::CONTENT_DISPOSITION, v);
     }
-
-    (StatusCode::OK, Json(DeleteResp { deleted: true, project })).into_response()
+    if let Some(len) = len {
+        headers.insert(header::CONTENT_LENGTH, len.into());
+    }
+    resp
 }
 
-pub async fn regenerate_run(
+/// GET /api/v1/projects/{project}/runs/{run_id}/backup — zip with the
+/// run's results, report and metadata, importable with `runs/import`.
+pub async fn backup_run(
     State(state): State<AppState>,
     Path((project_raw, run_id)): Path<(String, u64)>,
 ) -> impl IntoResponse {
-    let project = match sanitize_name(&project_raw) {
-        Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Invalid project").into_response(),
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
     };
+    if !storage::run_dir(&state.volumes, &project, run_id).join("allure-results").is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
 
-    let lock = state.project_lock(&project);
-    let _guard = lock.lock().await;
+    let path = match backup::build(&state, &project, run_id).await {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("backup: {e:#}")).into_response(),
+    };
+    let file = tokio::fs::File::open(&path).await;
+    // открытый файл дочитывается и после unlink
+    let _ = tokio::fs::remove_file(&path).await;
+    let file = match file {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("open backup: {e}")).into_response(),
+    };
+    let len = file.metadata().await.map(|m| m.len()).ok();
 
-    let run_dir = storage::run_dir(&state.data_dir, &project, run_id);
-    let results_dir = run_dir.join("allure-results");
-    let report_dir = run_dir.join("report");
+    let mut resp = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)).into_response();
+    let headers = resp.headers_mut();
+    headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/zip"));
+    if let Ok(v) = format!("attachment; filename=\"{project}-{run_id}-backup.zip\"").parse() {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    if let Some(len) = len {
+        headers.insert(header::CONTENT_LENGTH, len.into());
+    }
+    resp
+}
 
-    let _ = tokio::fs::remove_dir_all(&report_dir).await;
+/// POST /api/v1/projects/{project}/runs/import — recreates a run from a
+/// `backup` archive (the zip is the body) under the next run id, keeping
+/// its metadata and upload time. A backup without a report is queued for
+/// generation.
+pub async fn import_run(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    if body.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Empty body: expected a run backup zip").into_response();
+    }
 
-    match allure::generate_report(&state.allure_bin, &results_dir, &report_dir).await {
-        Ok(()) => {
-            let _ = storage::write_json(
-                &run_dir.join("status.json"),
-                &storage::RunStatus { status: "success".into(), error: None },
-            )
-                .await;
+    let unpacked = match backup::unpack(&state, body.to_vec()).await {
+        Ok(u) => u,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("bad backup: {e:#}")).into_response(),
+    };
+    let resp = import_unpacked(&state, &project, &unpacked).await;
+    let _ = tokio::fs::remove_dir_all(&unpacked.dir).await;
+    resp
+}
 
-            let pdir = storage::project_dir(&state.data_dir, &project);
-            let latest = storage::read_latest_run_id(&pdir).await;
-            if latest.is_none() || latest == Some(run_id) {
-                let _ = storage::set_latest_run_id(&pdir, run_id).await;
-            }
+async fn import_unpacked(state: &AppState, project: &str, unpacked: &backup::Unpacked) -> Response {
+    let _guard = state.lock_project(project, "import").await;
 
-            (StatusCode::OK, Json(RegenerateResp {
-                project,
-                run_id,
-                status: "success".into(),
-                error: None,
-            })).into_response()
-        }
-        Err(e) => {
-            let err_text = e.to_string();
-            error!(project=%project, run_id=run_id, error=%err_text, "regenerate failed");
+    if let Err(e) = storage::ensure_project_dirs(&state.volumes, project).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("ensure project dirs: {e}")).into_response();
+    }
+    let project_dir = storage::project_dir(&state.volumes, project);
+    let run_id = match storage::reserve_next_run_id(&project_dir).await {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("reserve next_run_id: {e}")).into_response(),
+    };
 
-            let _ = storage::write_json(
-                &run_dir.join("status.json"),
-                &storage::RunStatus { status: "failed".into(), error: Some(err_text.clone()) },
-            )
-                .await;
+    let room = match quota::make_room(state, project, run_id, &unpacked.dir).await {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("quota: {e:#}")).into_response(),
+    };
+    if let Some(exceeded) = room.exceeded {
+        return (StatusCode::INSUFFICIENT_STORAGE, Json(exceeded)).into_response();
+    }
 
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(RegenerateResp {
-                project,
-                run_id,
-                status: "failed".into(),
-                error: Some(err_text),
-            })).into_response()
+    let run_dir = storage::run_dir(&state.volumes, project, run_id);
+    let has_report = match backup::restore(unpacked, &run_dir).await {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = tokio::fs::remove_dir_all(&run_dir).await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("restore run: {e:#}")).into_response();
         }
-    }
-}
+    };
 
-pub async fn upload_run(
-    State(state): State<AppState>,
-    Path(project_raw): Path<String>,
-    mut mp: Multipart,
-) -> impl IntoResponse {
-    let project = match sanitize_name(&project_raw) {
-        Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Invalid project name").into_response(),
+    let m = &unpacked.manifest;
+    let (status, job_id) = if has_report {
+        // импортированный прогон — не свежая загрузка: latest двигаем, только если его не было
+        if storage::read_latest_run_id(&project_dir).await.is_none() {
+            let _ = storage::set_latest_run_id(&project_dir, run_id).await;
+        }
+        (m.status.as_ref().map(|s| s.status), None)
+    } else {
+        match state.jobs.enqueue(&state.volumes, project, run_id).await {
+            Ok(job) => (Some(Status::Queued), Some(job.id)),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("enqueue generation: {e:#}")).into_response(),
+        }
     };
+    info!(project=%project, run_id=run_id, from_project=%m.project, from_run_id=m.run_id, "imported run");
 
-    let lock = state.project_lock(&project);
-    let _guard = lock.lock().await;
+    (StatusCode::CREATED, Json(ImportResp {
+        project: project.to_string(),
+        run_id,
+        imported_from: ImportedFrom { project: m.project.clone(), run_id: m.run_id, created_at: m.created_at },
+        ui_url: format!("/ui/{}/runs/{}/", project, run_id),
+        status,
+        job_id,
+        evicted_runs: room.evicted,
+    }))
+    .into_response()
+}
 
-    if let Err(e) = storage::ensure_project_dirs(&state.data_dir, &project).await {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("ensure project dirs: {e}"),
-        )
-            .into_response();
+fn set_viewer_cookie(resp: &mut Response, viewer: &Viewer) {
+    if let Some(cookie) = viewer.set_cookie.as_deref().and_then(|c| c.parse().ok()) {
+        resp.headers_mut().insert(header::SET_COOKIE, cookie);
     }
-    let project_dir = storage::project_dir(&state.data_dir, &project);
+}
 
-    let run_id = match storage::reserve_next_run_id(&project_dir).await {
-        Ok(id) => id,
+pub async fn list_stale_projects(
+    State(state): State<AppState>,
+    Query(q): Query<StaleQuery>,
+) -> impl IntoResponse {
+    let min_idle_days = q.days.unwrap_or(state.config.stale_after_days);
+
+    let projects = match storage::list_stale_projects(&state.volumes, min_idle_days).await {
+        Ok(x) => x,
         Err(e) => {
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("reserve next_run_id: {e}"),
+                format!("list stale projects: {e}"),
             )
                 .into_response()
         }
     };
 
-    let run_dir = storage::run_dir(&state.data_dir, &project, run_id);
-    let results_dir = run_dir.join("allure-results");
-    let report_dir = run_dir.join("report");
+    let total_disk_bytes = projects.iter().map(|p| p.disk_bytes).sum::<u64>();
 
-    if let Err(e) = tokio::fs::create_dir_all(&results_dir).await {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("mkdir results_dir: {e}"),
-        )
-            .into_response();
-    }
+    (
+        StatusCode::OK,
+        Json(StaleProjectsResp {
+            min_idle_days,
+            total_disk_bytes,
+            projects,
+        }),
+    )
+        .into_response()
+}
 
-    let mut zip_bytes: Option<Vec<u8>> = None;
-    let mut meta: Meta = Meta::default();
+pub async fn list_schemas() -> impl IntoResponse {
+    let index: Vec<_> = schema::names()
+        .map(|name| serde_json::json!({ "name": name, "url": format!("/api/schemas/{name}") }))
+        .collect();
+    Json(index)
+}
 
-    while let Ok(Some(field)) = mp.next_field().await {
-        let name = field.name().unwrap_or("").to_string();
-        if name == "results" {
-            match field.bytes().await {
-                Ok(b) => zip_bytes = Some(b.to_vec()),
-                Err(e) => return (StatusCode::BAD_REQUEST, format!("read results: {e}")).into_response(),
-            }
-        } else if name == "meta" {
-            if let Ok(t) = field.text().await {
-                if let Ok(m) = serde_json::from_str::<Meta>(&t) {
-                    meta = m;
-                }
-            }
-        }
+/// `/api/schemas/UploadResp` or `/api/schemas/UploadResp.json`.
+pub async fn get_schema(Path(name): Path<String>) -> impl IntoResponse {
+    match schema::document(name.trim_end_matches(".json")) {
+        Some(doc) => ([(header::CONTENT_TYPE, "application/schema+json")], Json(doc)).into_response(),
+        None => (StatusCode::NOT_FOUND, "Unknown schema").into_response(),
     }
+}
 
-    let zip_bytes = match zip_bytes {
-        Some(b) => b,
-        None => return (StatusCode::BAD_REQUEST, "Missing multipart field 'results'").into_response(),
-    };
+/// Largest runs listed by `GET /api/v1/stats`.
+const STATS_TOP_RUNS: usize = 20;
 
-    if let Err(e) = storage::write_json(&run_dir.join("meta.json"), &meta).await {
-        return (StatusCode::INTERNAL_SERVER_ERROR, format!("write meta.json: {e}")).into_response();
+pub async fn storage_stats(State(state): State<AppState>, Query(q): Query<StorageStatsQuery>) -> impl IntoResponse {
+    let mut cached = state.disk_usage.lock().await;
+    let fresh = cached
+        .as_ref()
+        .filter(|u| !q.refresh && util::now_unix() < u.computed_at + state.config.stats_cache_secs);
+    if let Some(usage) = fresh {
+        return Json(usage).into_response();
     }
 
-    let limits = UnzipLimits::default();
-    if let Err(e) = unzip::unzip_safely(zip_bytes, results_dir.clone(), limits).await {
-        warn!(project=%project, run_id=run_id, error=%e, "failed to unzip results");
+    match storage::disk_usage(&state.volumes, STATS_TOP_RUNS).await {
+        Ok(usage) => {
+            let resp = Json(&usage).into_response();
+            *cached = Some(usage);
+            resp
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("disk usage: {e:#}")).into_response(),
+    }
+}
 
-        let _ = storage::write_json(
-            &run_dir.join("status.json"),
-            &storage::RunStatus { status: "failed".into(), error: Some(format!("bad zip: {e}")) },
+pub async fn get_launch(
+    State(state): State<AppState>,
+    Path(launch_raw): Path<String>,
+) -> impl IntoResponse {
+    let launch = match validate_new_project_name(&launch_raw) {
+        Ok(l) => l,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid launch: {e}")).into_response(),
+    };
+
+    let runs = match storage::list_launch_runs(&state.volumes, &launch).await {
+        Ok(x) => x,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list launch runs: {e}")).into_response(),
+    };
+
+    let mut projects = Vec::with_capacity(runs.len());
+    for (project, run_ids) in runs {
+        // run_ids отсортированы, статус проекта в запуске — по последнему прогону
+        let latest_run_id = *run_ids.last().expect("non-empty run list");
+        let st = storage::read_run_status(&storage::run_dir(&state.volumes, &project, latest_run_id)).await;
+
+        projects.push(LaunchProject {
+            ui_url: format!("/ui/{}/runs/{}/", project, latest_run_id),
+            project,
+            run_ids,
+            latest_run_id,
+            status: st.as_ref().map(|x| x.status),
+            error: st.and_then(|x| x.error),
+        });
+    }
+
+    if projects.is_empty() {
+        return (StatusCode::NOT_FOUND, "Launch not found").into_response();
+    }
+
+    let status = if projects.iter().any(|p| p.status.is_some_and(Status::is_failure)) {
+        "failed"
+    } else if projects.iter().all(|p| p.status == Some(Status::Success)) {
+        "success"
+    } else {
+        "unknown"
+    };
+
+    (
+        StatusCode::OK,
+        Json(LaunchResp {
+            launch,
+            status: status.into(),
+            projects_count: projects.len(),
+            projects,
+        }),
+    )
+        .into_response()
+}
+
+pub async fn list_runs(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<RunsQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let as_of = match q.as_of.as_deref().map(parse_timestamp) {
+        None => None,
+        Some(Some(ts)) => Some(ts),
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid as_of (expected YYYY-MM-DD, RFC 3339 UTC or unix seconds)").into_response(),
+    };
+
+    // list ids
+    let mut ids = match storage::list_run_ids(&state.volumes, &project).await {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e}")).into_response(),
+    };
+
+    // newest first
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut keys = run_keys::by_run(&storage::project_dir(&state.volumes, &project)).await;
+    let mut runs = Vec::with_capacity(ids.len());
+    for id in ids {
+        let rdir = storage::run_dir(&state.volumes, &project, id);
+        if let Some(ts) = as_of {
+            if storage::run_created_at(&rdir).await.is_none_or(|c| c > ts) {
+                continue;
+            }
+        }
+        let st = storage::read_run_status(&rdir).await;
+        let views = storage::read_run_views(&rdir).await;
+        let info = storage::read_run_info(&rdir).await;
+
+        runs.push(RunItem {
+            run_id: id,
+            status: st.as_ref().map(|x| x.status),
+            error: st.and_then(|x| x.error),
+            ui_url: format!("/ui/{}/runs/{}/", project, id),
+            run_key: keys.remove(&id),
+            external_url: info.external_url,
+            views: views.views,
+            last_viewed_at: views.last_viewed_at,
+            issues: info.issues,
+            health_score: info.health_score,
+            summary: summary::read(&rdir).await,
+            deleted_at: None,
+        });
+    }
+
+    if let Some(ts) = as_of {
+        match deleted_runs_at(&state, &project, ts).await {
+            Ok(gone) => {
+                for (tomb, deleted_at) in gone {
+                    if runs.iter().any(|r| r.run_id == tomb.run_id) {
+                        continue;
+                    }
+                    runs.push(RunItem {
+                        run_id: tomb.run_id,
+                        status: Some(Status::Pruned),
+                        error: None,
+                        ui_url: format!("/ui/{}/runs/{}/", project, tomb.run_id),
+                        run_key: None,
+                        external_url: None,
+                        views: 0,
+                        last_viewed_at: None,
+                        issues: Vec::new(),
+                        health_score: None,
+                        summary: None,
+                        deleted_at: Some(deleted_at),
+                    });
+                }
+                runs.sort_unstable_by_key(|r| std::cmp::Reverse(r.run_id));
+            }
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("read audit log: {e:#}")).into_response(),
+        }
+    }
+
+    (StatusCode::OK, Json(RunsResp { project, runs })).into_response()
+}
+
+/// Runs of the project that existed at `ts` and were deleted later, from
+/// the tombstones in the audit log, with their deletion time.
+async fn deleted_runs_at(state: &AppState, project: &str, ts: u64) -> anyhow::Result<Vec<(storage::Tombstone, u64)>> {
+    let mut out = Vec::new();
+    for entry in audit::read_all(&state.data_dir).await? {
+        if entry.at <= ts {
+            continue;
+        }
+        let tombs: Vec<storage::Tombstone> = match entry.action.as_str() {
+            "run_deleted" => serde_json::from_value(entry.details).into_iter().collect(),
+            "project_deleted" => entry
+                .details
+                .get("runs")
+                .cloned()
+                .and_then(|r| serde_json::from_value(r).ok())
+                .unwrap_or_default(),
+            _ => continue,
+        };
+        out.extend(
+            tombs
+                .into_iter()
+                .filter(|t| t.project == project && t.created_at.is_some_and(|c| c <= ts))
+                .map(|t| (t, entry.at)),
+        );
+    }
+    Ok(out)
+}
+
+pub async fn slowest_tests(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<SlowestQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let window = q.window.unwrap_or(10).clamp(1, 100);
+    let limit = q.limit.unwrap_or(20).clamp(1, 500);
+
+    let ids = match storage::list_run_ids(&state.volumes, &project).await {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e}")).into_response(),
+    };
+    let run_ids: Vec<u64> = ids.iter().rev().take(window).copied().collect();
+
+    let mut runs = Vec::with_capacity(run_ids.len());
+    for id in &run_ids {
+        runs.push(results::load_run(&state.volumes, &project, *id).await);
+    }
+
+    let tests = analytics::slowest_tests(&runs, limit);
+    (StatusCode::OK, Json(SlowestResp { project, run_ids, tests })).into_response()
+}
+
+fn stats_group_by(q: &StatsQuery) -> Result<String, String> {
+    let group_by = q.group_by.clone().unwrap_or_else(|| "suite".to_string());
+    if analytics::GROUP_LABELS.contains(&group_by.as_str()) {
+        Ok(group_by)
+    } else {
+        Err(format!("group_by must be one of: {}", analytics::GROUP_LABELS.join(", ")))
+    }
+}
+
+async fn stats_response(state: &AppState, project: String, run_ids: Vec<u64>, group_by: String) -> Response {
+    let mut runs = Vec::with_capacity(run_ids.len());
+    for id in &run_ids {
+        runs.push(results::load_run(&state.volumes, &project, *id).await);
+    }
+    let groups = analytics::group_stats(&runs, &group_by);
+
+    (StatusCode::OK, Json(StatsResp { project, run_ids, group_by, groups })).into_response()
+}
+
+/// /api/v1/projects/{project}/runs/{run_id}/stats?group_by=feature
+pub async fn run_stats(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+    Query(q): Query<StatsQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let group_by = match stats_group_by(&q) {
+        Ok(g) => g,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+
+    if !storage::run_dir(&state.volumes, &project, run_id).is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+
+    stats_response(&state, project, vec![run_id], group_by).await
+}
+
+/// /api/v1/projects/{project}/stats?group_by=feature&window=10
+pub async fn project_stats(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<StatsQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let group_by = match stats_group_by(&q) {
+        Ok(g) => g,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+    let window = q.window.unwrap_or(10).clamp(1, 100);
+
+    let ids = match storage::list_run_ids(&state.volumes, &project).await {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e}")).into_response(),
+    };
+    let run_ids: Vec<u64> = ids.iter().rev().take(window).copied().collect();
+
+    stats_response(&state, project, run_ids, group_by).await
+}
+
+/// /api/v1/projects/{project}/muted?window=30 — muted count over recent runs
+pub async fn muted_budget(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<MutedQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let window = q.window.unwrap_or(30).clamp(1, 500);
+
+    let ps = match settings::load(&storage::project_dir(&state.volumes, &project)).await {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("project settings: {e:#}")).into_response(),
+    };
+    let ids = match storage::list_run_ids(&state.volumes, &project).await {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e}")).into_response(),
+    };
+
+    let mut history = Vec::new();
+    for id in ids.iter().skip(ids.len().saturating_sub(window)) {
+        let info = storage::read_run_info(&storage::run_dir(&state.volumes, &project, *id)).await;
+        if let Some(st) = info.stats {
+            history.push(MutedPoint { run_id: *id, muted: st.muted });
+        }
+    }
+
+    let max_muted = ps.muted_budget.max_muted;
+    let current = history.last().map(|p| p.muted);
+    let over_budget = matches!((max_muted, current), (Some(max), Some(cur)) if cur > max);
+
+    (
+        StatusCode::OK,
+        Json(MutedResp { project, max_muted, current, over_budget, history }),
+    )
+        .into_response()
+}
+
+pub async fn report_size(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<MutedQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let window = q.window.unwrap_or(30).clamp(1, 500);
+
+    let ps = match settings::load(&storage::project_dir(&state.volumes, &project)).await {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("project settings: {e:#}")).into_response(),
+    };
+    let ids = match storage::list_run_ids(&state.volumes, &project).await {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e}")).into_response(),
+    };
+
+    let mut history = Vec::new();
+    for id in ids.iter().skip(ids.len().saturating_sub(window)) {
+        let info = storage::read_run_info(&storage::run_dir(&state.volumes, &project, *id)).await;
+        if let Some(size) = info.report_size {
+            history.push(ReportSizePoint { run_id: *id, size });
+        }
+    }
+    let current = history.last().map(|p| ReportSizePoint { run_id: p.run_id, size: p.size.clone() });
+
+    (
+        StatusCode::OK,
+        Json(ReportSizeResp {
+            project,
+            max_bytes: ps.report_size.max_bytes,
+            max_growth_pct: ps.report_size.max_growth_pct,
+            current,
+            history,
+        }),
+    )
+        .into_response()
+}
+
+/// /api/v1/projects/{project}/badge/health.svg — health score of the latest run
+pub async fn health_badge(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let pdir = storage::project_dir(&state.volumes, &project);
+    let score = match storage::read_latest_run_id(&pdir).await {
+        Some(id) => storage::read_run_info(&storage::run_dir(&state.volumes, &project, id))
+            .await
+            .health_score,
+        None => None,
+    };
+
+    let svg = match score {
+        Some(s) => badge::render("health", &format!("{s:.0}%"), badge::score_color(s)),
+        None => badge::render("health", "unknown", "#9f9f9f"),
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        svg,
+    )
+        .into_response()
+}
+
+/// /api/v1/projects/{project}/runs/{run_id}/gate — 200 when the run's health
+/// score meets the threshold, 412 otherwise (so `curl -f` fails the CI step).
+pub async fn run_gate(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+    Query(q): Query<GateQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let rdir = storage::run_dir(&state.volumes, &project, run_id);
+    if !rdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+
+    let ps = match settings::load(&storage::project_dir(&state.volumes, &project)).await {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("project settings: {e:#}")).into_response(),
+    };
+    let min_score = q.min_score.or(ps.health.min_score);
+    let health_score = storage::read_run_info(&rdir).await.health_score;
+
+    let passed = match (min_score, health_score) {
+        (None, _) => true,
+        (Some(min), Some(score)) => score >= min,
+        (Some(_), None) => false,
+    };
+
+    let code = if passed { StatusCode::OK } else { StatusCode::PRECONDITION_FAILED };
+    (code, Json(GateResp { project, run_id, passed, health_score, min_score })).into_response()
+}
+
+/// Resolves `base`/`head` of a comparison: head defaults to the latest run,
+/// base to the project's baseline or else the run before head.
+pub(crate) async fn resolve_compare_pair(
+    state: &AppState,
+    project: &str,
+    ps: &settings::ProjectSettings,
+    base: Option<u64>,
+    head: Option<u64>,
+) -> Result<(u64, u64), Response> {
+    let ids = storage::list_run_ids(&state.volumes, project)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("list runs: {e}")).into_response())?;
+
+    let pdir = storage::project_dir(&state.volumes, project);
+    let head = match head {
+        Some(h) => h,
+        None => match storage::read_latest_run_id(&pdir).await.or(ids.last().copied()) {
+            Some(h) => h,
+            None => return Err((StatusCode::NOT_FOUND, "No runs yet").into_response()),
+        },
+    };
+
+    let base = base
+        .or(ps.baseline_run_id.filter(|b| *b != head))
+        .or_else(|| ids.iter().rev().find(|id| **id < head).copied());
+    let base = match base {
+        Some(b) => b,
+        None => return Err((StatusCode::NOT_FOUND, "No run to compare with").into_response()),
+    };
+
+    for id in [base, head] {
+        if !ids.contains(&id) {
+            return Err((StatusCode::NOT_FOUND, format!("Run {id} not found")).into_response());
+        }
+    }
+    Ok((base, head))
+}
+
+/// Resolves the pair and compares it, for both the JSON and the Markdown
+/// representation.
+async fn load_comparison(state: &AppState, project_raw: &str, q: CompareQuery) -> Result<CompareResp, Response> {
+    let project = match validate_project_name(&state.volumes, project_raw) {
+        Ok(p) => p,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response()),
+    };
+
+    let ps = match settings::load(&storage::project_dir(&state.volumes, &project)).await {
+        Ok(s) => s,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("project settings: {e:#}")).into_response()),
+    };
+
+    let (base, head) = resolve_compare_pair(state, &project, &ps, q.base, q.head).await?;
+
+    let mut rules = ps.duration_regression.clone();
+    if let Some(t) = q.threshold_pct {
+        rules.threshold_pct = t;
+    }
+
+    let base_results = results::load_run(&state.volumes, &project, base).await;
+    let head_results = results::load_run(&state.volumes, &project, head).await;
+    let comparison = analytics::compare(&base_results, &head_results, &rules);
+
+    Ok(CompareResp {
+        base_url: state.run_url(&project, base),
+        head_url: state.run_url(&project, head),
+        project,
+        base_run_id: base,
+        head_run_id: head,
+        threshold_pct: rules.threshold_pct,
+        comparison,
+    })
+}
+
+pub async fn compare_runs(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<CompareQuery>,
+) -> impl IntoResponse {
+    match load_comparison(&state, &project_raw, q).await {
+        Ok(resp) => (StatusCode::OK, Json(resp)).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// The comparison as a Markdown comment for a pull request.
+pub async fn compare_runs_markdown(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<CompareQuery>,
+) -> impl IntoResponse {
+    match load_comparison(&state, &project_raw, q).await {
+        Ok(resp) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            compare_markdown(&resp),
+        )
+            .into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// Test name in a table cell: `|` and line breaks would break the row.
+fn md_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+fn compare_markdown(resp: &CompareResp) -> String {
+    use std::fmt::Write;
+
+    let c = &resp.comparison;
+    let mut md = format!(
+        "### Allure: {} [#{}]({}) → [#{}]({})\n\n",
+        md_cell(&resp.project),
+        resp.base_run_id,
+        resp.base_url,
+        resp.head_run_id,
+        resp.head_url
+    );
+    let total = c.newly_failed.len() + c.fixed.len() + c.flaky_changed.len() + c.duration_regressions.len();
+    if total == 0 {
+        md.push_str("No changes: no new failures, fixes or duration regressions.\n");
+        return md;
+    }
+
+    let _ = writeln!(
+        md,
+        "❌ {} newly failed · ✅ {} fixed · 🔀 {} flaky changed · 🐢 {} slower than +{}%\n",
+        c.newly_failed.len(),
+        c.fixed.len(),
+        c.flaky_changed.len(),
+        c.duration_regressions.len(),
+        resp.threshold_pct
+    );
+    md.push_str("| | Test | Change |\n|---|---|---|\n");
+    for t in &c.newly_failed {
+        let _ = writeln!(md, "| ❌ | {} | passed → failed |", md_cell(&t.name));
+    }
+    for t in &c.fixed {
+        let _ = writeln!(md, "| ✅ | {} | failed → passed |", md_cell(&t.name));
+    }
+    let flaky = |f: bool| if f { "flaky" } else { "stable" };
+    for t in &c.flaky_changed {
+        let _ = writeln!(md, "| 🔀 | {} | {} → {} |", md_cell(&t.name), flaky(t.base_flaky), flaky(t.head_flaky));
+    }
+    for t in &c.duration_regressions {
+        let _ = writeln!(
+            md,
+            "| 🐢 | {} | {} ms → {} ms (+{:.0}%) |",
+            md_cell(&t.name),
+            t.base_ms,
+            t.head_ms,
+            t.increase_pct
+        );
+    }
+    md
+}
+
+/// `generate.log` of the run: command line and full output of the last
+/// `allure generate`.
+pub async fn get_run_log(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let rdir = storage::run_dir(&state.volumes, &project, run_id);
+    if !rdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+    match tokio::fs::read(rdir.join("generate.log")).await {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], bytes).into_response(),
+        // прогоны до появления лога, генерация на воркере или ещё не начата
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            (StatusCode::NOT_FOUND, "No generation log for this run").into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("read generate.log: {e}")).into_response(),
+    }
+}
+
+pub async fn get_run(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let rdir = storage::run_dir(&state.volumes, &project, run_id);
+    if !rdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+
+    let st = storage::read_run_status(&rdir).await;
+    let meta = storage::read_run_meta(&rdir).await;
+
+    let resp = RunDetailResp {
+        ui_url: format!("/ui/{}/runs/{}/", project, run_id),
+        project,
+        run_id,
+        status: st.as_ref().map(|x| x.status),
+        error: st.and_then(|x| x.error),
+        meta,
+        info: storage::read_run_info(&rdir).await,
+        views: storage::read_run_views(&rdir).await,
+        sealed: seal::is_sealed(&rdir),
+    };
+
+    (StatusCode::OK, Json(resp)).into_response()
+}
+
+pub async fn list_artifacts(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    if !storage::run_dir(&state.volumes, &project, run_id).is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+
+    match artifacts::list(&state, &project, run_id).await {
+        Ok(items) => (StatusCode::OK, Json(items)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("artifacts: {e:#}")).into_response(),
+    }
+}
+
+pub async fn top_attachments(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+    Query(q): Query<TopAttachmentsQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let limit = q.limit.unwrap_or(20).clamp(1, 500);
+
+    let results_dir = storage::run_dir(&state.volumes, &project, run_id).join("allure-results");
+    if !results_dir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+    let mut all = match results::attachment_usage(&results_dir).await {
+        Ok(a) => a,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("attachments: {e:#}")).into_response(),
+    };
+
+    let total_files = all.len();
+    let total_bytes = all.iter().map(|a| a.size).sum();
+    all.truncate(limit);
+    (StatusCode::OK, Json(TopAttachmentsResp { project, run_id, total_files, total_bytes, attachments: all })).into_response()
+}
+
+pub async fn run_manifest(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let rdir = storage::run_dir(&state.volumes, &project, run_id);
+    match seal::read_manifest(&rdir).await {
+        Ok(Some(m)) => (StatusCode::OK, Json(m)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Run is not sealed").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("manifest: {e:#}")).into_response(),
+    }
+}
+
+/// Re-hashes a sealed run; 200 either way, `ok` tells whether it matches.
+pub async fn verify_run(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let rdir = storage::run_dir(&state.volumes, &project, run_id);
+    let manifest = match seal::read_manifest(&rdir).await {
+        Ok(Some(m)) => m,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Run is not sealed").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("manifest: {e:#}")).into_response(),
+    };
+    match seal::verify(&rdir, &manifest).await {
+        Ok(v) => {
+            if !v.ok {
+                warn!(project=%project, run_id=run_id, modified=v.modified.len(), missing=v.missing.len(), added=v.added.len(), "sealed run does not match its manifest");
+            }
+            (StatusCode::OK, Json(v)).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("verify: {e:#}")).into_response(),
+    }
+}
+
+/// GET/HEAD of one artifact; the sha256 doubles as a strong ETag.
+pub async fn get_artifact(
+    State(state): State<AppState>,
+    Path((project_raw, run_id, name)): Path<(String, u64, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let (path, artifact) = match artifacts::ensure(&state, &project, run_id, &name).await {
+        Ok(Some(found)) => found,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Artifact not found").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("artifact: {e:#}")).into_response(),
+    };
+
+    let etag = format!("\"{}\"", artifact.sha256);
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+
+    let mut resp = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        match tokio::fs::File::open(&path).await {
+            Ok(file) => {
+                let mut r = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)).into_response();
+                r.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(artifact.content_type));
+                r.headers_mut().insert(header::CONTENT_LENGTH, artifact.size.into());
+                r
+            }
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("open artifact: {e}")).into_response(),
+        }
+    };
+    let h = resp.headers_mut();
+    if let Ok(v) = etag.parse() {
+        h.insert(header::ETAG, v);
+    }
+    if let Ok(v) = artifact.sha256.parse() {
+        h.insert("x-checksum-sha256", v);
+    }
+    // содержимое меняется при регенерации — клиент перепроверяет по ETag
+    h.insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-cache"));
+    if let Ok(v) = format!("attachment; filename=\"{project}-{run_id}-{name}\"").parse() {
+        h.insert(header::CONTENT_DISPOSITION, v);
+    }
+    resp
+}
+
+pub async fn delete_project(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let _guard = state.lock_project(&project, "delete").await;
+
+    match hold::project_held(&state.volumes, &project).await {
+        Ok(false) => {}
+        Ok(true) => return (StatusCode::LOCKED, "Project is under legal hold").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("legal hold: {e:#}")).into_response(),
+    }
+
+    // настройки читаем до удаления — вместе с проектом пропадёт и project.json
+    let pdir = storage::project_dir(&state.volumes, &project);
+    let post_delete = settings::load(&pdir)
+        .await
+        .map(|s| s.hooks.post_delete)
+        .unwrap_or_default();
+
+    if let Err(e) = storage::delete_project(&state.volumes, &project).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("delete project: {e}")).into_response();
+    }
+
+    let ctx = HookContext {
+        event: HookEvent::PostDelete,
+        project: project.clone(),
+        run_id: None,
+        run_dir: None,
+        status: None,
+        error: None,
+        report_url: None,
+    };
+    if let Err(e) = hooks::run_hooks(&state, &post_delete, &ctx).await {
+        warn!(project=%project, error=%format!("{e:#}"), "post-delete hook failed");
+    }
+
+    (StatusCode::OK, Json(DeleteResp { deleted: true, project })).into_response()
+}
+
+pub async fn delete_run(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    let _guard = state.lock_project(&project, "delete").await;
+
+    let rdir = storage::run_dir(&state.volumes, &project, run_id);
+    if !rdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+    if hold::run_held(&state.volumes, &project, run_id) {
+        return (StatusCode::LOCKED, "Run is under legal hold").into_response();
+    }
+
+    let bytes = storage::dir_size(&rdir).await.unwrap_or(0);
+    if let Err(e) = storage::delete_run(&state.volumes, &project, run_id).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("delete run: {e:#}")).into_response();
+    }
+    info!(project=%project, run_id=run_id, bytes, "run deleted");
+
+    let pdir = storage::project_dir(&state.volumes, &project);
+    (StatusCode::OK, Json(DeleteRunResp {
+        deleted: true,
+        project,
+        run_id,
+        bytes,
+        latest_run_id: storage::read_latest_run_id(&pdir).await,
+    }))
+    .into_response()
+}
+
+/// Deletes all but the newest `keep_last` runs, or applies the project's
+/// retention settings when `keep_last` is not given.
+pub async fn prune_runs(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    req: Option<Json<PruneRequest>>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let pdir = storage::project_dir(&state.volumes, &project);
+    if !pdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+
+    let req = req.map(|Json(r)| r);
+    let dry_run = req.as_ref().is_some_and(|r| r.dry_run);
+    let limits = match req.and_then(|r| r.keep_last) {
+        Some(0) => return (StatusCode::BAD_REQUEST, "'keep_last' must be at least 1").into_response(),
+        Some(n) => retention::Limits { max_runs: Some(n), max_age_days: None },
+        None => retention::limits_for(&state, &project).await,
+    };
+    if limits.is_empty() {
+        return (StatusCode::BAD_REQUEST, "'keep_last' is required: the project has no retention configured").into_response();
+    }
+
+    let _guard = state.lock_project(&project, "prune").await;
+
+    let pruned = if dry_run {
+        retention::plan(&state, &project, limits).await
+    } else {
+        retention::prune(&state, &project, limits).await
+    };
+    let pruned = match pruned {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(project=%project, error=%format!("{e:#}"), "prune failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")).into_response();
+        }
+    };
+
+    (StatusCode::OK, Json(PruneResp {
+        project,
+        removed: pruned.removed,
+        held: pruned.held,
+        bytes: pruned.bytes,
+        latest_run_id: storage::read_latest_run_id(&pdir).await,
+        dry_run,
+    }))
+    .into_response()
+}
+
+pub async fn get_project_config(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let pdir = storage::project_dir(&state.volumes, &project);
+    if !pdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+    project_config_response(&state, project).await
+}
+
+/// Replaces the project's retention settings; other `project.json` keys stay.
+pub async fn put_project_config(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Json(req): Json<ProjectConfigRequest>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    if req.retention.max_runs == Some(0) {
+        return (StatusCode::BAD_REQUEST, "'max_runs' must be at least 1").into_response();
+    }
+    let pdir = storage::project_dir(&state.volumes, &project);
+    if !pdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+
+    let _guard = state.lock_project(&project, "config").await;
+    if let Err(e) = settings::update_section(&pdir, "retention", &req.retention).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("project settings: {e:#}")).into_response();
+    }
+    info!(project=%project, max_runs=?req.retention.max_runs, max_age_days=?req.retention.max_age_days, "retention settings changed");
+    project_config_response(&state, project).await
+}
+
+/// The project's `categories.json`; 404 while it has none.
+pub async fn get_project_categories(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let pdir = storage::project_dir(&state.volumes, &project);
+    if !pdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+    match categories::read_project(&pdir).await {
+        Ok(Some(c)) => Json(c).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Project has no categories.json").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")).into_response(),
+    }
+}
+
+/// Replaces the project's `categories.json`, merged into every run
+/// generated from now on.
+pub async fn put_project_categories(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Json(doc): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    if let Err(e) = categories::validate(&doc) {
+        return (StatusCode::BAD_REQUEST, format!("Invalid categories.json: {e}")).into_response();
+    }
+    let pdir = storage::project_dir(&state.volumes, &project);
+    if !pdir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Project not found").into_response();
+    }
+
+    let _guard = state.lock_project(&project, "categories").await;
+    if let Err(e) = storage::write_json(&pdir.join(categories::PROJECT_FILE), &doc).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("write categories.json: {e:#}")).into_response();
+    }
+    let count = doc.as_array().map_or(0, Vec::len);
+    info!(project=%project, categories=count, "project categories changed");
+    Json(doc).into_response()
+}
+
+pub async fn delete_project_categories(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let pdir = storage::project_dir(&state.volumes, &project);
+    let _guard = state.lock_project(&project, "categories").await;
+    match tokio::fs::remove_file(pdir.join(categories::PROJECT_FILE)).await {
+        Ok(()) => {
+            info!(project=%project, "project categories removed");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            (StatusCode::NOT_FOUND, "Project has no categories.json").into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("remove categories.json: {e}")).into_response(),
+    }
+}
+
+/// Replaces the run's `allure-results` with a fresh extraction of its kept
+/// original archives; the caller holds the project lock.
+async fn reextract_run(state: &AppState, run_dir: &std::path::Path) -> Result<(), Response> {
+    let originals = archive::originals(run_dir);
+    if originals.is_empty() {
+        return Err((StatusCode::CONFLICT, "The original archive of this run is not kept").into_response());
+    }
+    let archives = originals.into_iter().map(|(path, _)| path).collect();
+    let staging = match util::staging_dir(&state.config.tmp_dir, "reextract").await {
+        Ok(d) => d,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("staging dir: {e:#}")).into_response()),
+    };
+    let progress = std::sync::Arc::new(archive::Progress::default());
+    if let Err(e) = archive::extract_with_progress(archives, staging.clone(), state.config.unzip_limits, progress).await {
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+        let status = if archive::is_limit_exceeded(&e) { StatusCode::PAYLOAD_TOO_LARGE } else { StatusCode::BAD_REQUEST };
+        return Err((status, format!("bad archive: {e:#}")).into_response());
+    }
+
+    let results_dir = run_dir.join("allure-results");
+    let _ = tokio::fs::remove_dir_all(&results_dir).await;
+    let moved = util::move_dir(&staging, &results_dir).await;
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    if let Err(e) = moved {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("move results: {e:#}")).into_response());
+    }
+    // счётчики обрезки и редактирования копились по старым результатам
+    let _ = tokio::fs::remove_file(run_dir.join("run_info.json")).await;
+    Ok(())
+}
+
+/// GET /api/v1/projects/{project}/runs/{run_id}/archive?part=1 — the kept
+/// original upload archive.
+pub async fn download_archive(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+    Query(q): Query<ArchiveQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+    if !run_dir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+    let originals = archive::originals(&run_dir);
+    let part = q.part.unwrap_or(1);
+    let Some((path, format)) = part.checked_sub(1).and_then(|i| originals.get(i)) else {
+        return (StatusCode::NOT_FOUND, "Original archive is not kept").into_response();
+    };
+    let file = match tokio::fs::File::open(path).await {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("open archive: {e}")).into_response(),
+    };
+    let len = file.metadata().await.map(|m| m.len()).ok();
+
+    let mut resp = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)).into_response();
+    let headers = resp.headers_mut();
+    headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static(format.content_type()));
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("results");
+    if let Ok(v) = format!("attachment; filename=\"{project}-{run_id}-{name}\"").parse() {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    if let Some(len) = len {
+        headers.insert(header::CONTENT_LENGTH, len.into());
+    }
+    headers.insert("x-archive-parts", originals.len().into());
+    resp
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveQuery {
+    /// 1-based part of a multi-part upload.
+    pub part: Option<usize>,
+}
+
+async fn project_config_response(state: &AppState, project: String) -> Response {
+    let ps = match settings::load(&storage::project_dir(&state.volumes, &project)).await {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("project settings: {e:#}")).into_response(),
+    };
+    let effective = retention::limits_for(state, &project).await;
+    (StatusCode::OK, Json(ProjectConfigResp { project, retention: ps.retention, effective })).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateQuery {
+    /// Extract the kept original archive again before generating.
+    #[serde(default)]
+    pub reextract: bool,
+}
+
+pub async fn regenerate_run(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+    Query(q): Query<RegenerateQuery>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    if let Some(err) = state.breaker.open_error() {
+        return generator_unhealthy(&state, err);
+    }
+
+    let _guard = state.lock_project(&project, "regenerate").await;
+
+    let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+    if seal::is_sealed(&run_dir) {
+        return (StatusCode::CONFLICT, "Run is sealed").into_response();
+    }
+    if q.reextract {
+        if let Err(resp) = reextract_run(&state, &run_dir).await {
+            return resp;
+        }
+        info!(project=%project, run_id=run_id, "run extracted again from the original archive");
+    }
+    let _ = tokio::fs::remove_dir_all(run_dir.join("report")).await;
+
+    match pipeline::generate_run(&state, &project, run_id).await {
+        Ok(Generated::Incomplete(reason)) => (StatusCode::OK, Json(RegenerateResp {
+            project,
+            run_id,
+            status: Status::Incomplete,
+            error: Some(reason),
+            retryable: false,
+        })).into_response(),
+        Ok(Generated::Complete) => {
+            let pdir = storage::project_dir(&state.volumes, &project);
+            let latest = storage::read_latest_run_id(&pdir).await;
+            if latest.is_none() || latest == Some(run_id) {
+                let _ = storage::set_latest_run_id(&pdir, run_id).await;
+            }
+
+            (StatusCode::OK, Json(RegenerateResp {
+                project,
+                run_id,
+                status: Status::Success,
+                error: None,
+                retryable: false,
+            })).into_response()
+        }
+        Err(err_text) => {
+            let retryable = breaker::is_transient(&err_text);
+            let resp = Json(RegenerateResp {
+                project,
+                run_id,
+                status: Status::Failed,
+                error: Some(err_text),
+                retryable,
+            });
+            generation_failed(retryable, resp)
+        }
+    }
+}
+
+/// Where an upload's archive and metadata come from.
+enum UploadBody {
+    /// `results` (one or more archives) and `meta` (JSON) fields.
+    Multipart(Multipart),
+    /// The zip itself; metadata from headers and query.
+    Raw { body: Body, meta: Box<storage::Meta> },
+    /// A completed resumable upload of an already reserved run.
+    Resumable { run_id: u64 },
+}
+
+/// Metadata of a raw upload as query parameters; they override the
+/// `X-Allure-Meta` header field by field.
+#[derive(Deserialize)]
+pub struct RawMetaQuery {
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    pub trigger: Option<String>,
+    pub started_at: Option<String>,
+    pub launch: Option<String>,
+    pub run_key: Option<String>,
+    pub build_url: Option<String>,
+}
+
+pub async fn upload_run(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<UploadQuery>,
+    headers: HeaderMap,
+    mp: Multipart,
+) -> impl IntoResponse {
+    start_upload(state, project_raw, q, headers, UploadBody::Multipart(mp)).await
+}
+
+/// PUT with the zip as the whole body, for clients that cannot build a
+/// multipart request.
+pub async fn upload_run_raw(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(q): Query<UploadQuery>,
+    Query(mq): Query<RawMetaQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> impl IntoResponse {
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or("");
+    if content_type.starts_with("multipart/") {
+        return (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Multipart uploads go to POST; PUT takes the zip as the body")
+            .into_response();
+    }
+
+    let mut meta = match raw_meta(&headers, mq) {
+        Ok(m) => m,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    // meta известна из заголовков: с ошибкой отказываем, не занимая номер прогона
+    if let Err(e) = validate_meta(&mut meta, q.ci.as_deref()) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+    start_upload(state, project_raw, q, headers, UploadBody::Raw { body, meta: Box::new(meta) }).await
+}
+
+/// Metadata of a request without a multipart `meta` field; the error is
+/// for a 400.
+fn raw_meta(headers: &HeaderMap, mq: RawMetaQuery) -> Result<storage::Meta, String> {
+    let mut meta = match headers.get("x-allure-meta").map(|v| v.to_str().map(serde_json::from_str::<storage::Meta>)) {
+        None => storage::Meta::default(),
+        Some(Ok(Ok(m))) => m,
+        Some(Ok(Err(e))) => return Err(format!("Invalid X-Allure-Meta: {e}")),
+        Some(Err(_)) => return Err("Invalid X-Allure-Meta: not ASCII".to_string()),
+    };
+    meta.branch = mq.branch.or(meta.branch);
+    meta.commit = mq.commit.or(meta.commit);
+    meta.trigger = mq.trigger.or(meta.trigger);
+    meta.started_at = mq.started_at.or(meta.started_at);
+    meta.launch = mq.launch.or(meta.launch);
+    meta.run_key = mq.run_key.or(meta.run_key);
+    meta.build_url = mq.build_url.or(meta.build_url);
+    Ok(meta)
+}
+
+/// Answer to creating a resumable upload.
+#[derive(Serialize)]
+pub struct ResumableResp {
+    pub project: String,
+    pub run_id: u64,
+    pub upload_id: String,
+    /// PATCH chunks here, then POST `{upload_url}/finalize`.
+    pub upload_url: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+fn resumable_url(project: &str, upload_id: &str) -> String {
+    format!("/api/v1/projects/{project}/uploads/{upload_id}")
+}
+
+/// Run dir of an open resumable upload, or 404.
+async fn resumable_session(state: &AppState, project_raw: &str, upload_id: &str) -> Result<(String, std::path::PathBuf, resumable::Session), Response> {
+    let project = validate_project_name(&state.volumes, project_raw)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid project name: {e}")).into_response())?;
+    let not_found = || (StatusCode::NOT_FOUND, "No such upload session").into_response();
+    let run_id = resumable::parse_id(upload_id).ok_or_else(not_found)?;
+    let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+    let session = resumable::load(&run_dir, upload_id).await.ok_or_else(not_found)?;
+    Ok((project, run_dir, session))
+}
+
+/// Creates a resumable upload (`Upload-Length` — archive size in bytes)
+/// and reserves its run.
+pub async fn create_resumable(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    Query(mq): Query<RawMetaQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project name: {e}")).into_response(),
+    };
+    let length = match headers.get("upload-length").map(|v| v.to_str().ok().and_then(|v| v.parse::<u64>().ok())) {
+        None => return (StatusCode::BAD_REQUEST, "Missing Upload-Length header").into_response(),
+        Some(None) | Some(Some(0)) => return (StatusCode::BAD_REQUEST, "Invalid Upload-Length: expected a positive number").into_response(),
+        Some(Some(n)) => n,
+    };
+    let max = state.config.max_archive_bytes;
+    if length > max {
+        return (StatusCode::PAYLOAD_TOO_LARGE, format!("Upload-Length exceeds {max} bytes (MAX_ARCHIVE_BYTES)")).into_response();
+    }
+    let meta = match raw_meta(&headers, mq) {
+        Ok(m) => m,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let (run_id, session) = match resumable::create(&state, &project, length, meta).await {
+        Ok(x) => x,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("create upload: {e:#}")).into_response(),
+    };
+    info!(project=%project, run_id=run_id, upload_id=%session.id, length=length, "resumable upload created");
+    let upload_url = resumable_url(&project, &session.id);
+    let resp = ResumableResp { project, run_id, upload_id: session.id, upload_url: upload_url.clone(), offset: 0, length };
+    (
+        StatusCode::CREATED,
+        [(header::LOCATION, upload_url), (header::HeaderName::from_static("upload-offset"), "0".to_string())],
+        Json(resp),
+    )
+        .into_response()
+}
+
+/// Where to resume: `Upload-Offset` (also answers HEAD).
+pub async fn resumable_status(
+    State(state): State<AppState>,
+    Path((project_raw, upload_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let (project, run_dir, session) = match resumable_session(&state, &project_raw, &upload_id).await {
+        Ok(x) => x,
+        Err(resp) => return resp,
+    };
+    let offset = resumable::offset(&run_dir).await;
+    let resp = ResumableResp {
+        upload_url: resumable_url(&project, &session.id),
+        run_id: resumable::parse_id(&session.id).unwrap_or_default(),
+        project,
+        upload_id: session.id,
+        offset,
+        length: session.length,
+    };
+    (
+        [
+            ("upload-offset", offset.to_string()),
+            ("upload-length", resp.length.to_string()),
+            ("cache-control", "no-store".to_string()),
+        ],
+        Json(resp),
+    )
+        .into_response()
+}
+
+/// Appends the body at `Upload-Offset`, which must be the current offset.
+pub async fn patch_resumable(
+    State(state): State<AppState>,
+    Path((project_raw, upload_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Body,
+) -> impl IntoResponse {
+    let (project, run_dir, session) = match resumable_session(&state, &project_raw, &upload_id).await {
+        Ok(x) => x,
+        Err(resp) => return resp,
+    };
+    let Some(client_offset) = headers.get("upload-offset").and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<u64>().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing or invalid Upload-Offset header").into_response();
+    };
+    // один кусок за раз: параллельный PATCH или finalize получат 409
+    let Some(upload) = state.uploads.start(upload_id.clone(), &project, Some(session.length)) else {
+        return (StatusCode::CONFLICT, "Upload with this upload_id is already in progress").into_response();
+    };
+    let tracked = upload.session.clone();
+    tracked.set_run_id(resumable::parse_id(&upload_id).unwrap_or_default());
+    tracked.set_stage(Stage::Receiving);
+
+    let offset = resumable::offset(&run_dir).await;
+    if client_offset != offset {
+        return (StatusCode::CONFLICT, [("upload-offset", offset.to_string())], format!("Upload-Offset mismatch: server has {offset} bytes"))
+            .into_response();
+    }
+    tracked.add_bytes(offset);
+
+    let write = async {
+        let mut file = resumable::open_part(&run_dir).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))?;
+        let mut remaining = session.length - offset;
+        let mut stream = body.into_data_stream();
+        let mut result = Ok(());
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                // принятое до обрыва остаётся в upload.part — клиент продолжит с него
+                Err(e) => {
+                    result = Err((StatusCode::BAD_REQUEST, format!("read body: {e}")));
+                    break;
+                }
+            };
+            let take = chunk.len().min(remaining as usize);
+            if let Err(e) = file.write_all(&chunk[..take]).await {
+                result = Err((StatusCode::INTERNAL_SERVER_ERROR, format!("write upload.part: {e}")));
+                break;
+            }
+            remaining -= take as u64;
+            tracked.add_bytes(take as u64);
+            if take < chunk.len() {
+                result = Err((StatusCode::PAYLOAD_TOO_LARGE, "Chunk goes past Upload-Length".to_string()));
+                break;
+            }
+        }
+        let _ = file.flush().await;
+        result
+    };
+    let result = tokio::select! {
+        r = write => r,
+        _ = tracked.aborted() => Err((StatusCode::CONFLICT, "Upload aborted".to_string())),
+    };
+
+    let offset = resumable::offset(&run_dir).await;
+    let offset_header = [("upload-offset", offset.to_string())];
+    match result {
+        Ok(()) => (StatusCode::NO_CONTENT, offset_header).into_response(),
+        Err((status, msg)) => (status, offset_header, msg).into_response(),
+    }
+}
+
+/// Extracts and generates a completely received resumable upload; the
+/// answer is that of a regular upload.
+pub async fn finalize_resumable(
+    State(state): State<AppState>,
+    Path((project_raw, upload_id)): Path<(String, String)>,
+    Query(mut q): Query<UploadQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let (_, run_dir, session) = match resumable_session(&state, &project_raw, &upload_id).await {
+        Ok(x) => x,
+        Err(resp) => return resp,
+    };
+    let offset = resumable::offset(&run_dir).await;
+    if offset != session.length {
+        return (
+            StatusCode::CONFLICT,
+            [("upload-offset", offset.to_string())],
+            format!("upload incomplete: {offset} of {} bytes", session.length),
+        )
+            .into_response();
+    }
+    let run_id = resumable::parse_id(&upload_id).unwrap_or_default();
+    q.upload_id = Some(upload_id);
+    start_upload(state, project_raw, q, headers, UploadBody::Resumable { run_id }).await
+}
+
+/// Abandons a resumable upload together with its reserved run.
+pub async fn cancel_resumable(
+    State(state): State<AppState>,
+    Path((project_raw, upload_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let (project, run_dir, _) = match resumable_session(&state, &project_raw, &upload_id).await {
+        Ok(x) => x,
+        Err(resp) => return resp,
+    };
+    state.uploads.abort(&upload_id);
+    let _guard = state.lock_project(&project, "delete").await;
+    if let Err(e) = tokio::fs::remove_dir_all(&run_dir).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("remove run: {e}")).into_response();
+    }
+    info!(project=%project, upload_id=%upload_id, "resumable upload cancelled");
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn start_upload(
+    state: AppState,
+    project_raw: String,
+    mut q: UploadQuery,
+    headers: HeaderMap,
+    body: UploadBody,
+) -> Response {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project name: {e}")).into_response(),
+    };
+
+    if q.sha256.is_none() {
+        match headers.get(SHA256_HEADER).map(|v| v.to_str()) {
+            None => {}
+            Some(Ok(v)) => q.sha256 = Some(v.to_string()),
+            Some(Err(_)) => return (StatusCode::BAD_REQUEST, "Invalid X-Archive-SHA256: not ASCII").into_response(),
+        }
+    }
+    // битую контрольную сумму отвергаем до приёма архива
+    if let Some(Err(e)) = q.sha256.as_deref().map(parse_sha256) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+
+    let idempotency_key = match headers.get(idempotency::HEADER).map(|v| v.to_str()) {
+        None => None,
+        Some(Ok(key)) => match idempotency::validate_key(key) {
+            Ok(()) => Some(key.to_string()),
+            Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+        },
+        Some(Err(_)) => return (StatusCode::BAD_REQUEST, "Invalid Idempotency-Key: not ASCII").into_response(),
+    };
+
+    // не принимаем архив, который всё равно не сгенерируется
+    if let Some(err) = state.breaker.open_error() {
+        return generator_unhealthy(&state, err);
+    }
+
+    // тело ещё не читалось: на Expect: 100-continue клиент получит отказ
+    // вместо «100 Continue» и архив не отправит
+    let content_length: Option<u64> = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let max_body = match &body {
+        UploadBody::Multipart(_) => state.config.max_archive_bytes.saturating_add(MULTIPART_OVERHEAD),
+        _ => state.config.max_archive_bytes,
+    };
+    if content_length.is_some_and(|len| len > max_body) {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Archive exceeds {} bytes (MAX_ARCHIVE_BYTES)", state.config.max_archive_bytes),
         )
-            .await;
+            .into_response();
+    }
+    match quota::full(&state, &project, content_length.unwrap_or(0)).await {
+        Ok(None) => {}
+        Ok(Some(exceeded)) => {
+            warn!(project=%project, used=exceeded.used_bytes, quota=exceeded.quota_bytes, "upload rejected by quota before transfer");
+            return (StatusCode::INSUFFICIENT_STORAGE, Json(exceeded)).into_response();
+        }
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("quota: {e:#}")).into_response(),
+    }
 
-        return (StatusCode::BAD_REQUEST, format!("bad zip: {e}")).into_response();
+    // id загрузки задаёт клиент (чтобы следить за прогрессом), иначе это
+    // x-request-id, чтобы её было легко найти в логах
+    if let Some(id) = &q.upload_id {
+        if !valid_upload_id(id) {
+            return (StatusCode::BAD_REQUEST, "Invalid upload_id: 1-128 ASCII letters, digits, '-', '_' or '.'")
+                .into_response();
+        }
     }
+    let upload_id = q
+        .upload_id
+        .clone()
+        .or_else(|| headers.get("x-request-id").and_then(|v| v.to_str().ok()).map(|v| v.to_string()))
+        .unwrap_or_else(|| format!("{}-{}", project, crate::util::now_unix()));
+    let Some(upload) = state.uploads.start(upload_id, &project, content_length) else {
+        return (StatusCode::CONFLICT, "Upload with this upload_id is already in progress").into_response();
+    };
+    let session = upload.session.clone();
 
-    match allure::generate_report(&state.allure_bin, &results_dir, &report_dir).await {
-        Ok(()) => {
-            let _ = storage::write_json(
-                &run_dir.join("status.json"),
-                &storage::RunStatus { status: "success".into(), error: None },
+    let token = usage::token_of(&headers);
+    let resp = tokio::select! {
+        resp = process_upload(&state, &project, body, &session, &q, idempotency_key.as_deref()) => resp,
+        _ = session.aborted() => {
+            // future загрузки уже брошен: лок проекта и процесс allure освобождены
+            warn!(project=%project, upload_id=%session.id, "upload aborted");
+            if let Some(run_id) = session.run_id() {
+                let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+                storage::write_run_status(&run_dir, Status::Interrupted, Some("upload aborted".into())).await;
+            }
+            (StatusCode::CONFLICT, "Upload aborted").into_response()
+        }
+    };
+    // в учёт идут загрузки, результаты которых остались в прогоне
+    if let Some(run_id) = session.run_id() {
+        if storage::run_dir(&state.volumes, &project, run_id).join("allure-results").is_dir() {
+            usage::record_upload(&state.data_dir, &project, run_id, token, session.bytes_received()).await;
+        }
+    }
+    resp
+}
+
+const SHA256_HEADER: &str = "x-archive-sha256";
+
+/// Expected digests: lowercase hex, one per archive part.
+fn parse_sha256(s: &str) -> Result<Vec<String>, String> {
+    s.split(',')
+        .map(|d| {
+            let d = d.trim().to_ascii_lowercase();
+            if d.len() == 64 && d.bytes().all(|b| b.is_ascii_hexdigit()) {
+                Ok(d)
+            } else {
+                Err(format!("Invalid sha256 '{d}': expected 64 hex digits"))
+            }
+        })
+        .collect()
+}
+
+/// Room for the meta field and part headers on top of `MAX_ARCHIVE_BYTES`
+/// in the `Content-Length` of a multipart upload.
+const MULTIPART_OVERHEAD: u64 = 1024 * 1024;
+
+/// Checks of an upload's meta. They run as soon as the meta is known ---
+/// before a raw upload gets a run id, on the `meta` field of a multipart
+/// one --- so a bad upload is refused before its archive is transferred.
+struct MetaCheck<'a> {
+    state: &'a AppState,
+    project: &'a str,
+    run_id: u64,
+    run_dir: &'a std::path::Path,
+    /// `?ci=`, wins over `meta.ci`.
+    ci: Option<&'a str>,
+}
+
+/// Checks of an upload's meta that need no run; `ci` (`?ci=`) wins over
+/// `meta.ci`. The error is for a 400.
+fn validate_meta(meta: &mut storage::Meta, ci: Option<&str>) -> Result<(), String> {
+    if let Some(launch) = &meta.launch {
+        if let Err(e) = validate_new_project_name(launch) {
+            return Err(format!("Invalid launch: {e}"));
+        }
+    }
+    if let Some(url) = &meta.build_url {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err("Invalid build_url: expected an http(s) URL".into());
+        }
+    }
+    if let Err(e) = ci::validate_environment(&meta.environment) {
+        return Err(format!("Invalid environment: {e}"));
+    }
+
+    if let Some(ci) = ci {
+        meta.ci = Some(ci.to_string());
+    }
+    if let Some(provider) = &meta.ci {
+        if let Err(e) = ci::keep_known(provider, &mut meta.ci_env) {
+            return Err(format!("Invalid ci: {e}"));
+        }
+        // ветка и коммит из переменных CI, если клиент не передал их явно
+        if let Some(ctx) = ci::CiContext::of_meta(meta) {
+            meta.branch = meta.branch.take().or(ctx.git_ref);
+            meta.commit = meta.commit.take().or(ctx.commit);
+        }
+    }
+    if let Some(key) = &meta.run_key {
+        if let Err(e) = run_keys::validate_key(key) {
+            return Err(e.to_string());
+        }
+    }
+    Ok(())
+}
+
+impl MetaCheck<'_> {
+    async fn run(&self, meta: &mut storage::Meta) -> Result<(), Response> {
+        validate_meta(meta, self.ci).map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+        self.claim_key(meta).await
+    }
+
+    /// Gives the run its `run_key`; last, so a meta with an error does
+    /// not hold the key.
+    async fn claim_key(&self, meta: &storage::Meta) -> Result<(), Response> {
+        if let Some(key) = &meta.run_key {
+            match run_keys::claim(&self.state.volumes, self.project, key, self.run_id).await {
+                Ok(Ok(())) => {}
+                Ok(Err(holder)) => {
+                    return Err((StatusCode::CONFLICT, format!("run_key '{key}' is already used by run {holder}")).into_response());
+                }
+                Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("claim run_key: {e:#}")).into_response()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Streams the archives of an upload into `spool` (`part-1`, `part-2`, ...)
+/// and reads its metadata and `sha256` field. The meta is checked as soon
+/// as it is known; a body over `max_bytes` is cut off at the limit.
+async fn receive(
+    body: UploadBody,
+    session: &UploadSession,
+    spool: &std::path::Path,
+    max_bytes: u64,
+    check: &MetaCheck<'_>,
+) -> Result<(Vec<PathBuf>, storage::Meta, Option<String>), Response> {
+    let too_large =
+        || (StatusCode::PAYLOAD_TOO_LARGE, format!("Archive exceeds {max_bytes} bytes (MAX_ARCHIVE_BYTES)")).into_response();
+    let write_failed = |e: std::io::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("spool archive: {e}")).into_response();
+    let mut received: u64 = 0;
+    match body {
+        UploadBody::Multipart(mut mp) => {
+            let mut archives: Vec<PathBuf> = Vec::new();
+            let mut meta: Option<storage::Meta> = None;
+            let mut sha256: Vec<String> = Vec::new();
+
+            while let Ok(Some(field)) = mp.next_field().await {
+                let name = field.name().unwrap_or("").to_string();
+                // частями, если архив целиком упирается в лимиты CI-артефактов
+                if name == "results" || name == "results[]" {
+                    let mut field = field;
+                    let path = spool.join(format!("part-{}", archives.len() + 1));
+                    let mut file = tokio::fs::File::create(&path).await.map_err(write_failed)?;
+                    loop {
+                        match field.chunk().await {
+                            Ok(Some(chunk)) => {
+                                session.add_bytes(chunk.len() as u64);
+                                received += chunk.len() as u64;
+                                if received > max_bytes {
+                                    return Err(too_large());
+                                }
+                                file.write_all(&chunk).await.map_err(write_failed)?;
+                            }
+                            Ok(None) => break,
+                            Err(e) => return Err((StatusCode::BAD_REQUEST, format!("read results: {e}")).into_response()),
+                        }
+                    }
+                    file.flush().await.map_err(write_failed)?;
+                    archives.push(path);
+                } else if name == "meta" {
+                    if meta.is_some() {
+                        return Err((StatusCode::BAD_REQUEST, "Duplicate multipart field 'meta'").into_response());
+                    }
+                    let text = match field.text().await {
+                        Ok(t) => t,
+                        Err(e) => return Err((StatusCode::BAD_REQUEST, format!("read meta: {e}")).into_response()),
+                    };
+                    let mut m = match serde_json::from_str::<storage::Meta>(&text) {
+                        Ok(m) => m,
+                        Err(e) => return Err((StatusCode::BAD_REQUEST, format!("Invalid meta: {e}")).into_response()),
+                    };
+                    check.run(&mut m).await?;
+                    meta = Some(m);
+                } else if name == "sha256" {
+                    match field.text().await {
+                        Ok(t) => sha256.push(t),
+                        Err(e) => return Err((StatusCode::BAD_REQUEST, format!("read sha256: {e}")).into_response()),
+                    }
+                }
+            }
+
+            if archives.is_empty() {
+                return Err((StatusCode::BAD_REQUEST, "Missing multipart field 'results'").into_response());
+            }
+            let meta = match meta {
+                Some(m) => m,
+                None => {
+                    let mut m = storage::Meta::default();
+                    check.run(&mut m).await?;
+                    m
+                }
+            };
+            Ok((archives, meta, (!sha256.is_empty()).then(|| sha256.join(","))))
+        }
+        UploadBody::Raw { body, meta } => {
+            // meta уже проверена в upload_run_raw; ключ занимаем до чтения
+            // тела: клиент с Expect: 100-continue архив не отправит
+            let meta = *meta;
+            check.claim_key(&meta).await?;
+            let path = spool.join("part-1");
+            let mut file = tokio::fs::File::create(&path).await.map_err(write_failed)?;
+            let mut stream = body.into_data_stream();
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        session.add_bytes(chunk.len() as u64);
+                        received += chunk.len() as u64;
+                        if received > max_bytes {
+                            return Err(too_large());
+                        }
+                        file.write_all(&chunk).await.map_err(write_failed)?;
+                    }
+                    Err(e) => return Err((StatusCode::BAD_REQUEST, format!("read body: {e}")).into_response()),
+                }
+            }
+            file.flush().await.map_err(write_failed)?;
+            if received == 0 {
+                return Err((StatusCode::BAD_REQUEST, "Empty body: expected a zip archive").into_response());
+            }
+            Ok((vec![path], meta, None))
+        }
+        UploadBody::Resumable { .. } => {
+            let path = spool.join("part-1");
+            match resumable::take(check.run_dir, &path).await {
+                Ok((len, mut meta)) => {
+                    session.add_bytes(len);
+                    check.run(&mut meta).await?;
+                    Ok((vec![path], meta, None))
+                }
+                Err(e) => Err((StatusCode::CONFLICT, format!("{e:#}")).into_response()),
+            }
+        }
+    }
+}
+
+async fn process_upload(
+    state: &AppState,
+    project: &str,
+    body: UploadBody,
+    session: &UploadSession,
+    q: &UploadQuery,
+    idempotency_key: Option<&str>,
+) -> Response {
+    let _guard = state.lock_project(project, "upload").await;
+    // повтор той же загрузки из CI ждёт лок и получает ответ первой
+    if let Some(key) = idempotency_key {
+        if let Some(resp) = idempotency::replay(&state.volumes, project, key).await {
+            return resp;
+        }
+    }
+    let resp = store_upload(state, project, body, session, q).await;
+    match idempotency_key {
+        Some(key) => idempotency::remember(&state.volumes, project, key, session.run_id(), resp).await,
+        None => resp,
+    }
+}
+
+/// An upload refused before its results are stored leaves no empty,
+/// status-less run behind. A resumable upload whose session is still open
+/// keeps its run to be finalized again.
+async fn refuse_upload(run_dir: &std::path::Path, resumable: bool, resp: Response) -> Response {
+    if !resumable {
+        let _ = tokio::fs::remove_dir_all(run_dir).await;
+    } else if !resumable::is_open(run_dir) {
+        storage::write_run_status(run_dir, Status::Rejected, Some("upload refused".into())).await;
+    }
+    resp
+}
+
+/// The upload itself; the caller holds the project lock.
+async fn store_upload(
+    state: &AppState,
+    project: &str,
+    body: UploadBody,
+    session: &UploadSession,
+    q: &UploadQuery,
+) -> Response {
+    let project = project.to_string();
+    session.set_stage(Stage::Receiving);
+
+    if let Err(e) = storage::ensure_project_dirs(&state.volumes, &project).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("ensure project dirs: {e}"),
+        )
+            .into_response();
+    }
+    let project_dir = storage::project_dir(&state.volumes, &project);
+
+    let reserved = match &body {
+        UploadBody::Resumable { run_id } => Ok(*run_id),
+        _ => storage::reserve_next_run_id(&project_dir).await,
+    };
+    let run_id = match reserved {
+        Ok(id) => id,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("reserve next_run_id: {e}"),
             )
-                .await;
+                .into_response()
+        }
+    };
+
+    session.set_run_id(run_id);
+    let tracker = UploadTracker::new(&state.progress, &project, run_id);
+    tracker.publish(Phase::Receiving);
+
+    let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+    let results_dir = run_dir.join("allure-results");
+
+    if let Err(e) = tokio::fs::create_dir_all(&run_dir).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("mkdir run_dir: {e}"),
+        )
+            .into_response();
+    }
+
+    // архив пишется на диск по мере приёма, а не копится в памяти
+    let spool = match util::staging_dir(&state.config.tmp_dir, "upload-spool").await {
+        Ok(d) => util::StagingGuard::new(d),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("spool dir: {e:#}")).into_response(),
+    };
+    let check = MetaCheck { state, project: &project, run_id, run_dir: &run_dir, ci: q.ci.as_deref() };
+    let resumable = matches!(body, UploadBody::Resumable { .. });
+    let (archives, mut meta, field_sha256) =
+        match receive(body, session, spool.path(), state.config.max_archive_bytes, &check).await {
+            Ok(x) => x,
+            Err(resp) => return refuse_upload(&run_dir, resumable, resp).await,
+        };
+    let expected = match q.sha256.as_deref().or(field_sha256.as_deref()).map(parse_sha256).transpose() {
+        Ok(e) => e,
+        Err(e) => return refuse_upload(&run_dir, resumable, (StatusCode::BAD_REQUEST, e).into_response()).await,
+    };
+    let digests = match archive::sha256(&archives).await {
+        Ok(x) => x,
+        Err(e) => {
+            let resp = (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")).into_response();
+            return refuse_upload(&run_dir, resumable, resp).await;
+        }
+    };
+    if let Some(expected) = expected {
+        if expected.len() != digests.len() {
+            let msg = format!("sha256 has {} digests for {} archives", expected.len(), digests.len());
+            return refuse_upload(&run_dir, resumable, (StatusCode::BAD_REQUEST, msg).into_response()).await;
+        }
+        // обрезанный по дороге архив иначе дал бы пустой отчёт
+        if let Some((i, (want, got))) = expected.iter().zip(&digests).enumerate().find(|(_, (w, g))| w != g) {
+            let part = if digests.len() > 1 { format!(" of archive {}", i + 1) } else { String::new() };
+            let msg = format!("SHA-256 mismatch{part}: expected {want}, received {got}");
+            warn!(project=%project, run_id=run_id, received=session.bytes_received(), "{msg}");
+            storage::write_run_status(&run_dir, Status::Rejected, Some(msg.clone())).await;
+            return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response();
+        }
+    }
+
+    tracker.publish(Phase::Received);
+
+    meta.sha256 = digests;
+    if let Err(e) = storage::write_json(&run_dir.join("meta.json"), &meta).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("write meta.json: {e}")).into_response();
+    }
+
+    if state.config.keep_upload_archive {
+        // исходный архив редактирование секретов не проходит — у таких проектов не храним
+        match settings::load(&project_dir).await {
+            Ok(ps) if ps.redaction.patterns.is_empty() => {
+                if let Err(e) = archive::keep_originals(&run_dir, &archives).await {
+                    warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "keeping original archive failed");
+                }
+            }
+            _ => info!(project=%project, run_id=run_id, "project redacts secrets, original archive is not kept"),
+        }
+    }
+
+    session.set_stage(Stage::Extracting);
+    tracker.publish(Phase::Unzipping);
+    storage::write_run_status(&run_dir, Status::Extracting, None).await;
+    // распаковка идёт в TMP_DIR, в прогон попадает только целиком распакованный архив
+    let staging = match util::staging_dir(&state.config.tmp_dir, "upload").await {
+        Ok(d) => d,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("staging dir: {e:#}")).into_response(),
+    };
+    let extracted =
+        archive::extract_with_progress(archives, staging.clone(), state.config.unzip_limits, session.extraction()).await;
+    let room = match extracted {
+        Ok(()) => quota::make_room(state, &project, run_id, &staging).await,
+        Err(_) => Ok(quota::Room::default()),
+    };
+    let room = match room {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = tokio::fs::remove_dir_all(&staging).await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("quota: {e:#}")).into_response();
+        }
+    };
+    if let Some(exceeded) = room.exceeded {
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+        // отклонённая загрузка не должна занимать место — прогон убираем целиком
+        let _ = tokio::fs::remove_dir_all(&run_dir).await;
+        warn!(project=%project, run_id=run_id, used=exceeded.used_bytes, upload=exceeded.upload_bytes, quota=exceeded.quota_bytes, "upload rejected by quota");
+        return (StatusCode::INSUFFICIENT_STORAGE, Json(exceeded)).into_response();
+    }
+    let extracted = match extracted {
+        Ok(()) => util::move_dir(&staging, &results_dir).await,
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    if let Err(e) = extracted {
+        warn!(project=%project, run_id=run_id, error=%e, "failed to unzip results");
+
+        storage::write_run_status(&run_dir, Status::Rejected, Some(format!("bad archive: {e:#}"))).await;
+
+        let status = if archive::is_limit_exceeded(&e) { StatusCode::PAYLOAD_TOO_LARGE } else { StatusCode::BAD_REQUEST };
+        return (status, format!("bad archive: {e:#}")).into_response();
+    }
+
+    // перегруженный хост не запускает allure прямо в запросе
+    let shed = if q.run_async { None } else { state.shedder.check().await };
+    if let Some(reason) = &shed {
+        state.shedder.shed_upload();
+        info!(project=%project, run_id=run_id, reason=%reason, "host overloaded, queueing generation of upload");
+    }
+
+    if q.run_async || shed.is_some() {
+        // генерирует фоновая задача под тем же локом проекта
+        let job = match state.jobs.enqueue(&state.volumes, &project, run_id).await {
+            Ok(j) => j,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("enqueue generation: {e:#}")).into_response(),
+        };
+        tracker.publish(Phase::Queued);
+        tracker.hand_over();
+        info!(project=%project, run_id=run_id, job_id=%job.id, "uploaded run, generation queued");
+        let resp = UploadAcceptedResp {
+            project: project.clone(),
+            run_id,
+            job_url: format!("/api/v1/jobs/{}", job.id),
+            job_id: job.id,
+            ui_url: format!("/ui/{}/runs/{}/", project, run_id),
+            latest_url: format!("/ui/{}/latest/", project),
+            status: job.status,
+            run_key: meta.run_key.clone(),
+            evicted_runs: room.evicted,
+        };
+        return (StatusCode::ACCEPTED, Json(resp)).into_response();
+    }
+
+    session.set_stage(Stage::Generating);
+    tracker.hand_over();
+    match pipeline::generate_run(state, &project, run_id).await {
+        Ok(Generated::Incomplete(reason)) => {
+            info!(project=%project, run_id=run_id, "uploaded run is incomplete, latest is kept");
+            let resp = UploadResp {
+                project: project.clone(),
+                run_id,
+                ui_url: format!("/ui/{}/runs/{}/", project, run_id),
+                latest_url: format!("/ui/{}/latest/", project),
+                status: Status::Incomplete,
+                error: Some(reason),
+                retryable: false,
+                run_key: meta.run_key.clone(),
+                summary: summary::read(&run_dir).await,
+                evicted_runs: room.evicted,
+            };
 
+            (StatusCode::OK, Json(resp)).into_response()
+        }
+        Ok(Generated::Complete) => {
             if let Err(e) = storage::set_latest_run_id(&project_dir, run_id).await {
                 warn!(project=%project, run_id=run_id, error=%e, "set latest_run_id failed");
             }
@@ -311,32 +2736,241 @@ pub async fn upload_run(
                 run_id,
                 ui_url: format!("/ui/{}/runs/{}/", project, run_id),
                 latest_url: format!("/ui/{}/latest/", project),
-                status: "success".into(),
+                status: Status::Success,
                 error: None,
+                retryable: false,
+                run_key: meta.run_key.clone(),
+                summary: summary::read(&run_dir).await,
+                evicted_runs: room.evicted,
             };
 
             (StatusCode::OK, Json(resp)).into_response()
         }
-        Err(e) => {
-            let err_text = e.to_string();
-            error!(project=%project, run_id=run_id, error=%err_text, "report generation failed");
-
-            let _ = storage::write_json(
-                &run_dir.join("status.json"),
-                &storage::RunStatus { status: "failed".into(), error: Some(err_text.clone()) },
-            )
-                .await;
-
+        Err(err_text) => {
+            let retryable = breaker::is_transient(&err_text);
             let resp = UploadResp {
                 project: project.clone(),
                 run_id,
                 ui_url: format!("/ui/{}/runs/{}/", project, run_id),
                 latest_url: format!("/ui/{}/latest/", project),
-                status: "failed".into(),
+                status: Status::Failed,
                 error: Some(err_text),
+                retryable,
+                run_key: meta.run_key.clone(),
+                summary: None,
+                evicted_runs: room.evicted,
             };
 
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(resp)).into_response()
+            generation_failed(retryable, Json(resp))
+        }
+    }
+}
+
+pub async fn get_job(State(state): State<AppState>, Path(job_id): Path<String>) -> impl IntoResponse {
+    match state.jobs.get(&job_id) {
+        Some(job) => Json(job).into_response(),
+        None => (StatusCode::NOT_FOUND, "Job not found").into_response(),
+    }
+}
+
+pub async fn list_uploads(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.uploads.list())
+}
+
+fn valid_upload_id(id: &str) -> bool {
+    (1..=128).contains(&id.len()) && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+pub async fn upload_progress(State(state): State<AppState>, Path(upload_id): Path<String>) -> impl IntoResponse {
+    match state.uploads.get(&upload_id) {
+        Some(info) => Json(info).into_response(),
+        None => (StatusCode::NOT_FOUND, "Upload not found").into_response(),
+    }
+}
+
+/// How long the event stream waits for an upload that has not started yet
+/// (the client subscribes before sending the archive).
+const UPLOAD_EVENTS_WAIT: Duration = Duration::from_secs(30);
+const UPLOAD_EVENTS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// SSE: `progress` events with the upload's state while it changes, then
+/// `done` with the last state once the upload has finished.
+pub async fn upload_events(State(state): State<AppState>, Path(upload_id): Path<String>) -> Response {
+    let deadline = tokio::time::Instant::now() + UPLOAD_EVENTS_WAIT;
+    while state.uploads.get(&upload_id).is_none() {
+        if tokio::time::Instant::now() >= deadline {
+            return (StatusCode::NOT_FOUND, "Upload not found").into_response();
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+
+    let uploads = state.uploads.clone();
+    let events = futures_util::stream::unfold(Some(None), move |last: Option<Option<UploadInfo>>| {
+        let uploads = uploads.clone();
+        let upload_id = upload_id.clone();
+        async move {
+            let last = last?;
+            loop {
+                match uploads.get(&upload_id) {
+                    Some(info) if last.as_ref().is_some_and(|l| same_progress(l, &info)) => {
+                        tokio::time::sleep(UPLOAD_EVENTS_INTERVAL).await;
+                    }
+                    Some(info) => {
+                        let event = Event::default().event("progress").json_data(&info);
+                        return Some((event, Some(Some(info))));
+                    }
+                    None => {
+                        let event = Event::default().event("done").json_data(&last);
+                        return Some((event, None));
+                    }
+                }
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// Phases of one run as Server-Sent Events named after the phase
+/// (`receiving`, `received`, `unzipping`, `queued`, `generating`, `done`, `failed`).
+/// A finished run gets a single `done`/`failed` event from `status.json`.
+pub async fn run_events(
+    State(state): State<AppState>,
+    Path((project_raw, run_id)): Path<(String, u64)>,
+) -> Response {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+    if !run_dir.is_dir() {
+        return (StatusCode::NOT_FOUND, "Run not found").into_response();
+    }
+
+    let progress = state.progress.clone();
+    let deadline = tokio::time::Instant::now() + UPLOAD_EVENTS_WAIT;
+    let events = futures_util::stream::unfold(Some(None), move |watching: Option<Option<_>>| {
+        let progress = progress.clone();
+        let project = project.clone();
+        let run_dir = run_dir.clone();
+        async move {
+            let mut watching: Option<tokio::sync::watch::Receiver<RunEvent>> = watching?;
+            loop {
+                if let Some(rx) = watching.as_mut() {
+                    if rx.changed().await.is_ok() {
+                        let ev = rx.borrow_and_update().clone();
+                        let next = (!ev.phase.is_final()).then_some(watching);
+                        return Some((run_event(&ev), next));
+                    }
+                    // канал закрыт: итог уже в status.json
+                    watching = None;
+                }
+                if let Some(mut rx) = progress.subscribe(&project, run_id) {
+                    let ev = rx.borrow_and_update().clone();
+                    let next = (!ev.phase.is_final()).then_some(Some(rx));
+                    return Some((run_event(&ev), next));
+                }
+                if let Some(st) = storage::read_run_status(&run_dir).await.filter(|st| st.status.is_final()) {
+                    let phase = if st.status.is_failure() { Phase::Failed } else { Phase::Done };
+                    let ev = RunEvent { project: project.clone(), run_id, phase, error: st.error, at: util::now_unix() };
+                    return Some((run_event(&ev), None));
+                }
+                // прогон без итога и без активной загрузки (например, брошенный)
+                if tokio::time::Instant::now() >= deadline {
+                    return None;
+                }
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
         }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+fn run_event(ev: &RunEvent) -> Result<Event, axum::Error> {
+    Event::default().event(ev.phase.as_str()).json_data(ev)
+}
+
+/// Ignores the ticking `elapsed_secs`.
+fn same_progress(a: &UploadInfo, b: &UploadInfo) -> bool {
+    (a.stage, a.run_id, a.bytes_received, a.bytes_to_extract, a.bytes_extracted)
+        == (b.stage, b.run_id, b.bytes_received, b.bytes_to_extract, b.bytes_extracted)
+}
+
+pub async fn abort_upload(State(state): State<AppState>, Path(upload_id): Path<String>) -> impl IntoResponse {
+    if !state.uploads.abort(&upload_id) {
+        return (StatusCode::NOT_FOUND, "Upload not found").into_response();
+    }
+    info!(upload_id=%upload_id, "upload abort requested");
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// How long a CI job should wait before it sends a transiently failed
+/// upload again.
+const GENERATION_RETRY_AFTER_SECS: u64 = 30;
+
+/// 503 with `Retry-After` when the generator could not run or was cut
+/// short, 422 when it failed on the results themselves.
+fn generation_failed(retryable: bool, body: impl IntoResponse) -> Response {
+    if retryable {
+        (StatusCode::SERVICE_UNAVAILABLE, [(header::RETRY_AFTER, GENERATION_RETRY_AFTER_SECS.to_string())], body)
+            .into_response()
+    } else {
+        (StatusCode::UNPROCESSABLE_ENTITY, body).into_response()
+    }
+}
+
+/// Statuses on which a CI job may send the same upload again.
+fn retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504)
+}
+
+/// Route layer of uploads and regenerations: plain-text errors become
+/// [`ErrorResp`], so CI retry logic does not have to parse messages.
+pub async fn structured_error(resp: Response) -> Response {
+    let is_text = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/plain"));
+    if resp.status().is_success() || !is_text {
+        return resp;
     }
+    let (mut parts, body) = resp.into_parts();
+    let error = match axum::body::to_bytes(body, 64 * 1024).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(e) => format!("read error response: {e}"),
+    };
+    let retryable = retryable_status(parts.status);
+    parts.headers.remove(header::CONTENT_TYPE);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let mut resp = Json(ErrorResp { error, retryable }).into_response();
+    resp.headers_mut().extend(parts.headers);
+    *resp.status_mut() = parts.status;
+    resp
+}
+
+/// 503 while the generation circuit breaker is open.
+fn generator_unhealthy(state: &AppState, err: String) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, state.config.breaker_probe_secs.to_string())],
+        err,
+    )
+        .into_response()
+}
+
+pub async fn generator_health(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.breaker.snapshot())
+}
+
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut out = state.metrics.render();
+    let slots = &state.generation_slots;
+    metrics::gauge(&mut out, "allure_generations_max", "MAX_CONCURRENT_GENERATIONS.", slots.size() as u64);
+    metrics::gauge(&mut out, "allure_generations_running", "Local allure generate processes running.", slots.running() as u64);
+    metrics::gauge(&mut out, "allure_generations_waiting", "Generations waiting for a free slot.", slots.waiting() as u64);
+    let shedder = &state.shedder;
+    metrics::gauge(&mut out, "allure_overloaded", "1 while generations are shed (SHED_MAX_LOAD, SHED_MIN_AVAILABLE_MB).", shedder.overloaded() as u64);
+    metrics::gauge(&mut out, "allure_generations_deferred", "Queued generations waiting for the host to recover.", shedder.deferred() as u64);
+    metrics::counter_value(&mut out, "allure_uploads_shed_total", "Uploads answered 202 and queued because of overload.", shedder.shed_uploads());
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
 }
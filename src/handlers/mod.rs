@@ -1,2 +1,30 @@
+use axum::http::{header, HeaderMap, StatusCode};
+
+use crate::config::Secret;
+
+pub mod admin;
 pub mod api;
+#[cfg(feature = "test-hooks")]
+pub mod test_hooks;
 pub mod ui;
+pub mod worker;
+
+/// Checks `Authorization: Bearer <token>`; an endpoint guarded by an
+/// unconfigured token is reported as disabled (404).
+pub(crate) fn bearer_error(
+    headers: &HeaderMap,
+    token: Option<&Secret>,
+    disabled: &'static str,
+) -> Option<(StatusCode, &'static str)> {
+    let Some(token) = token else {
+        return Some((StatusCode::NOT_FOUND, disabled));
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if given != Some(token.0.as_str()) {
+        return Some((StatusCode::UNAUTHORIZED, "Invalid token"));
+    }
+    None
+}
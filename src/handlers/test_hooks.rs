@@ -0,0 +1,20 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use tracing::warn;
+
+use crate::test_hooks::{self, Faults};
+
+pub async fn get_faults() -> impl IntoResponse {
+    Json(test_hooks::current())
+}
+
+/// Replaces all faults at once; omitted fields are reset.
+pub async fn put_faults(Json(faults): Json<Faults>) -> impl IntoResponse {
+    warn!(?faults, "test hooks: faults injected");
+    test_hooks::set(faults);
+    Json(test_hooks::current())
+}
+
+pub async fn clear_faults() -> impl IntoResponse {
+    test_hooks::set(Faults::default());
+    StatusCode::NO_CONTENT
+}
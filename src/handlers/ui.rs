@@ -1,58 +1,181 @@
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{Request, StatusCode, Uri},
-    response::{Html, IntoResponse, Redirect},
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, Request, StatusCode, Uri},
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension,
 };
+use serde::Deserialize;
 use tower::ServiceExt;
 use tower_http::services::ServeDir;
+use tracing::warn;
 
-use crate::{state::AppState, storage, util::sanitize_name};
+use crate::{
+    settings::{self, Landing},
+    sniff,
+    state::AppState,
+    storage,
+    util::validate_project_name,
+};
 
 const PROJECTS_HTML: &str = include_str!("../ui_pages/projects.html");
 const PROJECT_HTML: &str = include_str!("../ui_pages/project.html");
+const COMPARE_HTML: &str = include_str!("../ui_pages/compare.html");
+const FEDERATION_HTML: &str = include_str!("../ui_pages/federation.html");
+
+/// Injected into the report index in embed mode: reports the document
+/// height to the embedding page, so the iframe can be sized to fit.
+const EMBED_SCRIPT: &str = r#"<script>
+(function(){
+  if(window.parent === window) return;
+  var last = 0;
+  function post(){
+    var h = Math.max(document.documentElement.scrollHeight, document.body ? document.body.scrollHeight : 0);
+    if(h === last) return;
+    last = h;
+    window.parent.postMessage({ type: "allure-report:height", height: h, url: location.href }, "*");
+  }
+  if(window.ResizeObserver) new ResizeObserver(post).observe(document.documentElement);
+  window.addEventListener("load", post);
+  window.addEventListener("hashchange", function(){ setTimeout(post, 50); });
+  setInterval(post, 1000);
+})();
+</script>"#;
+
+#[derive(Deserialize, Default)]
+pub struct ReportQuery {
+    /// Serve the report for an iframe (`?embed=true`).
+    #[serde(default)]
+    pub embed: bool,
+}
+
+/// Set on the UI listener of a split setup (`UPLOAD_LISTEN` or a systemd
+/// `upload` socket): it has no write routes, so the pages hide Delete and
+/// Regenerate.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnly;
 
-pub async fn ui_index(State(_state): State<AppState>) -> impl IntoResponse {
-    Html(PROJECTS_HTML).into_response()
+fn read_only(marker: Option<Extension<ReadOnly>>) -> &'static str {
+    if marker.is_some() { "true" } else { "false" }
 }
 
-/// /ui/{project}/ — страница проекта (список прогонов)
-pub async fn ui_project_page(Path(project_raw): Path<String>) -> impl IntoResponse {
-    let project = match sanitize_name(&project_raw) {
-        Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Invalid project").into_response(),
+pub async fn ui_index(State(_state): State<AppState>, marker: Option<Extension<ReadOnly>>) -> impl IntoResponse {
+    Html(PROJECTS_HTML.replace("__READ_ONLY__", read_only(marker))).into_response()
+}
+
+/// /ui/federation — проекты всех инстансов из `FEDERATION_PEERS`
+pub async fn ui_federation(State(state): State<AppState>) -> impl IntoResponse {
+    if state.config.federation_peers.is_empty() {
+        return (StatusCode::NOT_FOUND, "Federation is not configured").into_response();
+    }
+    Html(FEDERATION_HTML).into_response()
+}
+
+/// /ui/{project}/ — домашняя страница проекта, по `landing` из настроек:
+/// список прогонов, последний отчёт или дашборд
+pub async fn ui_project_home(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    marker: Option<Extension<ReadOnly>>,
+) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
     };
 
+    let project_dir = storage::project_dir(&state.volumes, &project);
+    let landing = match settings::load(&project_dir).await {
+        Ok(ps) => ps.landing,
+        Err(e) => {
+            warn!(project=%project, error=%format!("{e:#}"), "project settings unreadable, showing runs list");
+            Landing::Runs
+        }
+    };
+    match landing {
+        Landing::Runs => runs_page(&project, read_only(marker)),
+        // без прогонов редиректить некуда
+        Landing::Latest => match storage::read_latest_run_id(&project_dir).await {
+            Some(_) => Redirect::temporary(&format!("/ui/{project}/latest/")).into_response(),
+            None => runs_page(&project, read_only(marker)),
+        },
+        Landing::Dashboard => Redirect::temporary(&format!("/ui/?q={project}")).into_response(),
+    }
+}
+
+/// /ui/{project}/runs/ — список прогонов независимо от `landing`
+pub async fn ui_project_runs(
+    State(state): State<AppState>,
+    Path(project_raw): Path<String>,
+    marker: Option<Extension<ReadOnly>>,
+) -> impl IntoResponse {
+    match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => runs_page(&p, read_only(marker)),
+        Err(e) => (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    }
+}
+
+fn runs_page(project: &str, read_only: &str) -> Response {
     // Подстановка __PROJECT__ в HTML (простая и быстрая)
-    let html = PROJECT_HTML.replace("__PROJECT__", &project);
-    Html(html).into_response()
+    Html(PROJECT_HTML.replace("__PROJECT__", project).replace("__READ_ONLY__", read_only)).into_response()
+}
+
+/// /ui/{project}/compare?base=&head= — разница двух прогонов; страница
+/// сама берёт данные из `/api/v1/projects/{project}/compare`
+pub async fn ui_compare(State(state): State<AppState>, Path(project_raw): Path<String>) -> impl IntoResponse {
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+
+    Html(COMPARE_HTML.replace("__PROJECT__", &project)).into_response()
 }
 
 pub async fn ui_latest(
     State(state): State<AppState>,
     Path(project_raw): Path<String>,
+    Query(q): Query<ReportQuery>,
 ) -> impl IntoResponse {
-    let project = match sanitize_name(&project_raw) {
-        Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Invalid project").into_response(),
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
     };
 
-    let project_dir = storage::project_dir(&state.data_dir, &project);
+    let project_dir = storage::project_dir(&state.volumes, &project);
     let run_id = match storage::read_latest_run_id(&project_dir).await {
         Some(id) => id,
         None => return (StatusCode::NOT_FOUND, "No runs yet").into_response(),
     };
 
-    Redirect::temporary(&format!("/ui/{}/runs/{}/", project, run_id)).into_response()
+    let embed = if q.embed { "?embed=true" } else { "" };
+    Redirect::temporary(&format!("/ui/{}/runs/{}/{}", project, run_id, embed)).into_response()
 }
 
 /// /ui/{project}/runs/{run_id}/
-/// Отдаём index.html (через ServeDir)
+/// Отдаём index.html (через ServeDir) и считаем просмотр отчёта
 pub async fn ui_run_index(
     State(state): State<AppState>,
     Path((project_raw, run_id)): Path<(String, u64)>,
+    Query(q): Query<ReportQuery>,
 ) -> impl IntoResponse {
-    serve_report_path(state, project_raw, run_id, "").await
+    let resp = if q.embed {
+        serve_embedded_index(&state, &project_raw, run_id).await
+    } else {
+        serve_report_path(state.clone(), project_raw.clone(), run_id, "")
+            .await
+            .into_response()
+    };
+
+    if resp.status().is_success() {
+        if let Ok(project) = validate_project_name(&state.volumes, &project_raw) {
+            let rdir = storage::run_dir(&state.volumes, &project, run_id);
+            let _guard = state.views_lock.lock().await;
+            if let Err(e) = storage::record_run_view(&rdir).await {
+                warn!(project=%project, run_id=run_id, error=%e, "record report view failed");
+            }
+        }
+    }
+
+    resp
 }
 
 /// /ui/{project}/runs/{run_id}/{*tail}
@@ -69,12 +192,12 @@ async fn serve_report_path(
     run_id: u64,
     tail: &str,
 ) -> impl IntoResponse {
-    let project = match sanitize_name(&project_raw) {
-        Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Invalid project").into_response(),
+    let project = match validate_project_name(&state.volumes, &project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
     };
 
-    let report_dir = storage::run_dir(&state.data_dir, &project, run_id).join("report");
+    let report_dir = storage::run_dir(&state.volumes, &project, run_id).join("report");
 
     let rel_path = if tail.is_empty() { "/".to_string() } else { format!("/{}", tail) };
 
@@ -90,10 +213,63 @@ async fn serve_report_path(
         .body(Body::empty())
         .unwrap();
 
-    let service = ServeDir::new(report_dir).append_index_html_on_directories(true);
+    let service = ServeDir::new(&report_dir).append_index_html_on_directories(true);
 
-    match service.oneshot(req).await {
+    let mut resp = match service.oneshot(req).await {
         Ok(resp) => resp.into_response(),
-        Err(_) => (StatusCode::NOT_FOUND, "Not found").into_response(),
+        Err(_) => return (StatusCode::NOT_FOUND, "Not found").into_response(),
+    };
+    if sniff::is_attachment(tail) && resp.status().is_success() {
+        sniff::apply(&report_dir.join(tail), resp.headers_mut()).await;
+    }
+    // CSP нужен только документам, настройки не читаем на каждый js/png
+    let is_html = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/html"));
+    if is_html {
+        set_frame_ancestors(&state, &project, &mut resp).await;
+    }
+    resp
+}
+
+/// Report index with the height-reporting script, for `?embed=true`.
+async fn serve_embedded_index(state: &AppState, project_raw: &str, run_id: u64) -> Response {
+    let project = match validate_project_name(&state.volumes, project_raw) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid project: {e}")).into_response(),
+    };
+    let index = storage::run_dir(&state.volumes, &project, run_id).join("report").join("index.html");
+    let html = match tokio::fs::read_to_string(&index).await {
+        Ok(h) => h,
+        Err(_) => return (StatusCode::NOT_FOUND, "Not found").into_response(),
+    };
+
+    let html = match html.rfind("</body>") {
+        Some(pos) => format!("{}{}{}", &html[..pos], EMBED_SCRIPT, &html[pos..]),
+        None => format!("{html}{EMBED_SCRIPT}"),
+    };
+    let mut resp = Html(html).into_response();
+    resp.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    set_frame_ancestors(state, &project, &mut resp).await;
+    resp
+}
+
+/// Reports may be framed by the service itself and by the project's
+/// `embed.frame_ancestors`.
+async fn set_frame_ancestors(state: &AppState, project: &str, resp: &mut Response) {
+    let pdir = storage::project_dir(&state.volumes, project);
+    let ps = settings::load(&pdir).await.unwrap_or_default();
+    let mut sources = vec!["'self'".to_string()];
+    sources.extend(
+        ps.embed
+            .frame_ancestors
+            .into_iter()
+            // один источник — одно слово, без разделителей директив
+            .filter(|src| !src.is_empty() && !src.contains(|c: char| c == ';' || c == ',' || c.is_whitespace())),
+    );
+    if let Ok(v) = HeaderValue::from_str(&format!("frame-ancestors {}", sources.join(" "))) {
+        resp.headers_mut().insert(header::CONTENT_SECURITY_POLICY, v);
     }
 }
@@ -0,0 +1,118 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::{
+    archive,
+    state::AppState,
+    storage,
+    worker::{self, FailReq},
+};
+
+#[derive(Deserialize)]
+pub struct ClaimQuery {
+    /// Long-poll seconds, capped at 60.
+    pub wait: Option<u64>,
+}
+
+/// Worker endpoints exist only when `WORKER_TOKEN` is configured.
+fn auth_error(state: &AppState, headers: &HeaderMap) -> Option<(StatusCode, &'static str)> {
+    super::bearer_error(headers, state.config.worker_token.as_ref(), "Remote workers are disabled")
+}
+
+pub async fn claim(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<ClaimQuery>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+
+    let worker_name = headers
+        .get("x-worker-name")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+    let wait = Duration::from_secs(q.wait.unwrap_or(0).min(60));
+
+    match state
+        .work_queue
+        .claim(&worker_name, state.config.worker_lease_secs, wait)
+        .await
+    {
+        Some(job) => Json(job).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+pub async fn job_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+
+    let Some((project, run_id)) = state.work_queue.claimed_run(&job_id) else {
+        return (StatusCode::NOT_FOUND, "Job not found").into_response();
+    };
+    let results_dir = storage::run_dir(&state.volumes, &project, run_id).join("allure-results");
+
+    match archive::zip_dir(results_dir).await {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "application/zip")], bytes).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("zip results: {e:#}")).into_response(),
+    }
+}
+
+pub async fn job_report(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    body: Bytes,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+
+    let Some((project, run_id)) = state.work_queue.claimed_run(&job_id) else {
+        return (StatusCode::NOT_FOUND, "Job not found").into_response();
+    };
+
+    let result = worker::store_report(&state, &project, run_id, body.to_vec())
+        .await
+        .map_err(|e| format!("store worker report: {e:#}"));
+    if let Err(e) = &result {
+        warn!(job_id=%job_id, project=%project, run_id=run_id, error=%e, "worker report rejected");
+    }
+    let status = if result.is_ok() { StatusCode::NO_CONTENT } else { StatusCode::BAD_REQUEST };
+
+    if !state.work_queue.complete(&job_id, result) {
+        return (StatusCode::NOT_FOUND, "Job not found").into_response();
+    }
+    status.into_response()
+}
+
+pub async fn job_fail(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    Json(req): Json<FailReq>,
+) -> impl IntoResponse {
+    if let Some(err) = auth_error(&state, &headers) {
+        return err.into_response();
+    }
+
+    if !state.work_queue.complete(&job_id, Err(req.error)) {
+        return (StatusCode::NOT_FOUND, "Job not found").into_response();
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,79 @@
+//! Legal hold: a marker on a project or a single run that blocks deletion,
+//! purges and retention until an admin lifts it.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{storage, util::now_unix, volumes::Volumes};
+
+/// Marker file in the project dir or in a run dir.
+const MARKER: &str = "legal_hold.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hold {
+    pub reason: String,
+    pub set_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HoldEntry {
+    pub project: String,
+    /// None for a hold on the whole project.
+    pub run_id: Option<u64>,
+    #[serde(flatten)]
+    pub hold: Hold,
+}
+
+pub async fn get(dir: &Path) -> Option<Hold> {
+    let bytes = tokio::fs::read(dir.join(MARKER)).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+pub async fn set(dir: &Path, reason: &str) -> anyhow::Result<Hold> {
+    let hold = Hold { reason: reason.to_string(), set_at: now_unix() };
+    storage::write_json(&dir.join(MARKER), &hold).await?;
+    Ok(hold)
+}
+
+/// Lifts the hold; false when there was none.
+pub async fn clear(dir: &Path) -> anyhow::Result<bool> {
+    let p = dir.join(MARKER);
+    match tokio::fs::remove_file(&p).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("remove {}", p.display())),
+    }
+}
+
+/// The run is held by itself or through its project.
+pub fn run_held(volumes: &Volumes, project: &str, run_id: u64) -> bool {
+    storage::project_dir(volumes, project).join(MARKER).is_file()
+        || storage::run_dir(volumes, project, run_id).join(MARKER).is_file()
+}
+
+/// The project or any of its runs is held.
+pub async fn project_held(volumes: &Volumes, project: &str) -> anyhow::Result<bool> {
+    if storage::project_dir(volumes, project).join(MARKER).is_file() {
+        return Ok(true);
+    }
+    let ids = storage::list_run_ids(volumes, project).await?;
+    Ok(ids
+        .into_iter()
+        .any(|id| storage::run_dir(volumes, project, id).join(MARKER).is_file()))
+}
+
+pub async fn list(volumes: &Volumes) -> anyhow::Result<Vec<HoldEntry>> {
+    let mut out = Vec::new();
+    for project in storage::list_projects(volumes).await? {
+        if let Some(hold) = get(&storage::project_dir(volumes, &project)).await {
+            out.push(HoldEntry { project: project.clone(), run_id: None, hold });
+        }
+        for run_id in storage::list_run_ids(volumes, &project).await? {
+            if let Some(hold) = get(&storage::run_dir(volumes, &project, run_id)).await {
+                out.push(HoldEntry { project: project.clone(), run_id: Some(run_id), hold });
+            }
+        }
+    }
+    Ok(out)
+}
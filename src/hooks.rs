@@ -0,0 +1,213 @@
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{path::PathBuf, process::Stdio, time::Duration};
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, info};
+
+use crate::{config::Secret, state::AppState, trace, util::{clip, now_unix, sanitize_name}};
+
+/// Version of the hook payload; bumped when fields change incompatibly.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const SIGNATURE_HEADER: &str = "X-Allure-Signature";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    PreGenerate,
+    PostGenerate,
+    PostDelete,
+}
+
+impl HookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HookEvent::PreGenerate => "pre_generate",
+            HookEvent::PostGenerate => "post_generate",
+            HookEvent::PostDelete => "post_delete",
+        }
+    }
+}
+
+/// Hooks configured for a project (`hooks` in project.json).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    pub pre_generate: Vec<Hook>,
+    pub post_generate: Vec<Hook>,
+    pub post_delete: Vec<Hook>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Hook {
+    /// Executable from `HOOKS_DIR` (by file name only). Runs with a cleared
+    /// environment, cwd = run dir, and gets the context JSON on stdin.
+    Exec {
+        name: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// POST of the context JSON to the given URL. With `secret` the body
+    /// is signed: `X-Allure-Signature: sha256=<hex HMAC-SHA256>`.
+    Http {
+        url: String,
+        #[serde(default)]
+        secret: Option<Secret>,
+    },
+}
+
+/// What a hook gets to know about the event.
+#[derive(Debug, Clone, Serialize)]
+pub struct HookContext {
+    pub event: HookEvent,
+    pub project: String,
+    pub run_id: Option<u64>,
+    pub run_dir: Option<PathBuf>,
+    pub status: Option<String>,
+    pub error: Option<String>,
+    pub report_url: Option<String>,
+}
+
+/// What a hook receives: the context plus delivery fields.
+#[derive(Serialize)]
+struct Payload<'a> {
+    schema_version: u32,
+    /// One id per event, shared by all its hooks; receivers deduplicate by it.
+    event_id: &'a str,
+    sent_at: u64,
+    #[serde(flatten)]
+    ctx: &'a HookContext,
+    /// `request_id`, `job_id` and `traceparent` of the work that fired the event.
+    #[serde(flatten)]
+    trace: trace::Correlation,
+    /// How to check the signature; absent for unsigned deliveries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<SignatureInfo>,
+}
+
+#[derive(Serialize)]
+struct SignatureInfo {
+    header: &'static str,
+    algorithm: &'static str,
+    verify: &'static str,
+}
+
+const SIGNATURE_INFO: SignatureInfo = SignatureInfo {
+    header: SIGNATURE_HEADER,
+    algorithm: "hmac-sha256",
+    verify: "Compute HMAC-SHA256 over the raw request body (exact bytes, before JSON parsing) with the \
+             webhook secret, hex-encode it and compare with the header value after \"sha256=\" in \
+             constant time. Reject stale sent_at to stop replays and skip already seen event_id values.",
+};
+
+/// Runs the hooks one by one, stopping at the first failure.
+pub async fn run_hooks(state: &AppState, hooks: &[Hook], ctx: &HookContext) -> anyhow::Result<()> {
+    let event_id = uuid::Uuid::new_v4().to_string();
+    for hook in hooks {
+        debug!(event = ctx.event.as_str(), project = %ctx.project, ?hook, "running hook");
+        let payload = Payload {
+            schema_version: SCHEMA_VERSION,
+            event_id: &event_id,
+            sent_at: now_unix(),
+            ctx,
+            trace: trace::current(),
+            signature: matches!(hook, Hook::Http { secret: Some(_), .. }).then_some(SIGNATURE_INFO),
+        };
+        match hook {
+            Hook::Exec { name, args } => run_exec(state, name, args, &payload).await,
+            Hook::Http { url, secret } => run_http(state, url, secret.as_ref(), &payload).await,
+        }
+        .with_context(|| format!("{} hook {:?}", ctx.event.as_str(), hook))?;
+    }
+
+    if !hooks.is_empty() {
+        info!(event = ctx.event.as_str(), project = %ctx.project, run_id = ?ctx.run_id, count = hooks.len(), "hooks done");
+    }
+    Ok(())
+}
+
+async fn run_exec(state: &AppState, name: &str, args: &[String], payload: &Payload<'_>) -> anyhow::Result<()> {
+    let ctx = payload.ctx;
+    let hooks_dir = state
+        .config
+        .hooks_dir
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("exec hooks are disabled (HOOKS_DIR is not set)"))?;
+    let name = sanitize_name(name).ok_or_else(|| anyhow::anyhow!("invalid hook name: {name}"))?;
+
+    let mut cmd = Command::new(hooks_dir.join(&name));
+    cmd.args(args)
+        .env_clear()
+        .env("PATH", "/usr/local/bin:/usr/bin:/bin")
+        .env("HOOK_EVENT", ctx.event.as_str())
+        .env("HOOK_PROJECT", &ctx.project)
+        .env("HOOK_EVENT_ID", payload.event_id)
+        .envs(payload.trace.env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(id) = ctx.run_id {
+        cmd.env("HOOK_RUN_ID", id.to_string());
+    }
+    if let Some(dir) = &ctx.run_dir {
+        cmd.env("HOOK_RUN_DIR", dir).current_dir(dir);
+    }
+
+    let mut child = cmd.spawn().context("spawn hook")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let body = serde_json::to_vec(payload)?;
+        // a hook may ignore stdin and exit early, that's not an error
+        let _ = stdin.write_all(&body).await;
+    }
+
+    let timeout = Duration::from_secs(state.config.hook_timeout_secs);
+    let out = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {}s", timeout.as_secs()))?
+        .context("wait hook")?;
+
+    if !out.status.success() {
+        anyhow::bail!(
+            "exit_code={} stderr={}",
+            out.status.code().unwrap_or(-1),
+            clip(&String::from_utf8_lossy(&out.stderr), 2000)
+        );
+    }
+    Ok(())
+}
+
+async fn run_http(state: &AppState, url: &str, secret: Option<&Secret>, payload: &Payload<'_>) -> anyhow::Result<()> {
+    // подписываем ровно те байты, что уходят в теле
+    let body = serde_json::to_vec(payload)?;
+    let mut req = state
+        .http
+        .post(url)
+        .timeout(Duration::from_secs(state.config.hook_timeout_secs))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Allure-Event", payload.ctx.event.as_str())
+        .header("X-Allure-Event-Id", payload.event_id);
+    for (name, value) in payload.trace.headers() {
+        req = req.header(name, value);
+    }
+    if let Some(secret) = secret {
+        req = req.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+    }
+    req.body(body)
+        .send()
+        .await
+        .context("send request")?
+        .error_for_status()
+        .context("bad response")?;
+    Ok(())
+}
+
+/// Hex HMAC-SHA256 of `body`.
+fn sign(secret: &Secret, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.0.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("{:x}", mac.finalize().into_bytes())
+}
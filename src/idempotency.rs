@@ -0,0 +1,100 @@
+//! `Idempotency-Key` of uploads: a CI job that retries an upload whose
+//! response it never got would otherwise create a second run. The
+//! answer of a successful upload is kept in the project's
+//! `idempotency.json` for [`KEY_TTL_SECS`]; a repeated key gets it back
+//! instead of a new run. Failed uploads are not kept, so their retry
+//! uploads again.
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::{storage, util::now_unix, volumes::Volumes};
+
+pub const HEADER: &str = "idempotency-key";
+/// Set on a replayed answer.
+const REPLAYED: &str = "idempotent-replayed";
+const KEY_TTL_SECS: u64 = 24 * 3600;
+/// Upload answers are small JSON documents.
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    run_id: u64,
+    created_at: u64,
+    status: u16,
+    /// JSON as sent, so the replay is byte for byte the same.
+    body: String,
+}
+
+fn path(project_dir: &Path) -> PathBuf {
+    project_dir.join("idempotency.json")
+}
+
+/// 1..=255 printable ASCII characters.
+pub fn validate_key(key: &str) -> Result<(), &'static str> {
+    if key.is_empty() || key.len() > 255 || !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err("Invalid Idempotency-Key: 1-255 printable ASCII characters");
+    }
+    Ok(())
+}
+
+async fn load(project_dir: &Path) -> BTreeMap<String, Entry> {
+    match tokio::fs::read(path(project_dir)).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!(path=%path(project_dir).display(), error=%e, "idempotency.json is broken, keys are forgotten");
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// The kept answer for `key`, while its run still exists. The caller
+/// holds the project lock.
+pub async fn replay(volumes: &Volumes, project: &str, key: &str) -> Option<Response> {
+    let entries = load(&storage::project_dir(volumes, project)).await;
+    let entry = entries.get(key).filter(|e| now_unix().saturating_sub(e.created_at) < KEY_TTL_SECS)?;
+    // удалённый прогон не возвращаем — загрузка пойдёт заново
+    if !storage::run_dir(volumes, project, entry.run_id).is_dir() {
+        return None;
+    }
+    info!(project=%project, run_id=entry.run_id, key=%key, "idempotent upload replayed");
+    let status = StatusCode::from_u16(entry.status).unwrap_or(StatusCode::OK);
+    let mut resp = (status, [(header::CONTENT_TYPE, "application/json")], entry.body.clone()).into_response();
+    resp.headers_mut().insert(REPLAYED, HeaderValue::from_static("true"));
+    Some(resp)
+}
+
+/// Keeps a successful answer under `key` and gives it back unchanged.
+/// The caller holds the project lock.
+pub async fn remember(volumes: &Volumes, project: &str, key: &str, run_id: Option<u64>, resp: Response) -> Response {
+    let Some(run_id) = run_id.filter(|_| resp.status().is_success()) else {
+        return resp;
+    };
+    let (parts, body) = resp.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_BODY).await {
+        Ok(b) => b,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("read upload response: {e}")).into_response(),
+    };
+    if let Err(e) = store(volumes, project, key, run_id, parts.status, &bytes).await {
+        warn!(project=%project, key=%key, error=%format!("{e:#}"), "save idempotency key failed");
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+async fn store(volumes: &Volumes, project: &str, key: &str, run_id: u64, status: StatusCode, body: &[u8]) -> anyhow::Result<()> {
+    let project_dir = storage::project_dir(volumes, project);
+    let body = String::from_utf8(body.to_vec()).context("upload response is not UTF-8")?;
+    let now = now_unix();
+    let mut entries = load(&project_dir).await;
+    entries.retain(|_, e| now.saturating_sub(e.created_at) < KEY_TTL_SECS);
+    entries.insert(key.to_string(), Entry { run_id, created_at: now, status: status.as_u16(), body });
+    storage::write_json(&path(&project_dir), &entries).await
+}
@@ -0,0 +1,119 @@
+//! Startup check of the data volume: writable, known layout version,
+//! enough free space. Problems are reported before the first upload
+//! stumbles over them.
+
+use std::path::Path;
+
+use crate::config::Config;
+
+/// Version of the `DATA_DIR` layout written by this build.
+const LAYOUT_VERSION: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Layout {
+    version: u32,
+}
+
+/// Human-readable problems with the data volume; empty when all is well.
+pub async fn check(config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    let data_dir = &config.data_dir;
+
+    if let Err(e) = tokio::fs::create_dir_all(data_dir).await {
+        problems.push(format!(
+            "DATA_DIR {} cannot be created ({e}); check that the volume is mounted{}",
+            data_dir.display(),
+            owner_hint()
+        ));
+        return problems;
+    }
+    let mut dirs = vec![("DATA_DIR".to_string(), data_dir), ("TMP_DIR".to_string(), &config.tmp_dir)];
+    dirs.extend(config.data_volumes.iter().map(|v| (format!("data volume {}", v.name), &v.root)));
+    for (name, dir) in dirs {
+        if let Err(e) = probe_write(dir).await {
+            problems.push(format!(
+                "{name} {} is not writable ({e}); mount it read-write{}",
+                dir.display(),
+                owner_hint()
+            ));
+        }
+    }
+
+    let projects = data_dir.join("projects");
+    if projects.exists() && !projects.is_dir() {
+        problems.push(format!("{} is not a directory; DATA_DIR does not look like a data volume of this service", projects.display()));
+    }
+
+    match read_layout(data_dir).await {
+        Ok(Some(v)) if v > LAYOUT_VERSION => problems.push(format!(
+            "DATA_DIR layout version {v} was written by a newer release (this one supports {LAYOUT_VERSION}); upgrade the service or restore a matching backup"
+        )),
+        Ok(Some(_)) => {}
+        // том без layout.json — новый или от релиза до версионирования
+        Ok(None) => {
+            if problems.is_empty() {
+                if let Err(e) = crate::storage::write_json(&data_dir.join("layout.json"), &Layout { version: LAYOUT_VERSION }).await {
+                    problems.push(format!("cannot write {}: {e:#}", data_dir.join("layout.json").display()));
+                }
+            }
+        }
+        Err(e) => problems.push(format!("{}: {e:#}; fix or remove the file", data_dir.join("layout.json").display())),
+    }
+
+    if config.min_free_bytes > 0 {
+        if let Some(free) = free_bytes(data_dir) {
+            if free < config.min_free_bytes {
+                problems.push(format!(
+                    "only {} MiB free on the DATA_DIR volume, MIN_FREE_BYTES requires {} MiB; free up space (e.g. RETENTION_MAX_RUNS) or lower MIN_FREE_BYTES",
+                    free >> 20,
+                    config.min_free_bytes >> 20
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+async fn probe_write(dir: &Path) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+    tokio::fs::write(&probe, b"ok").await?;
+    tokio::fs::remove_file(&probe).await
+}
+
+async fn read_layout(data_dir: &Path) -> anyhow::Result<Option<u32>> {
+    match tokio::fs::read(data_dir.join("layout.json")).await {
+        Ok(bytes) => Ok(Some(serde_json::from_slice::<Layout>(&bytes)?.version)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(unix)]
+fn owner_hint() -> String {
+    format!(" and owned by uid {}", unsafe { libc::geteuid() })
+}
+
+#[cfg(not(unix))]
+fn owner_hint() -> String {
+    String::new()
+}
+
+#[cfg(unix)]
+// ширина полей statvfs зависит от платформы
+#[allow(clippy::unnecessary_cast)]
+pub fn free_bytes(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut st) } != 0 {
+        return None;
+    }
+    Some(st.f_bavail as u64 * st.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_bytes(_dir: &Path) -> Option<u64> {
+    None
+}
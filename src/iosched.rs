@@ -0,0 +1,77 @@
+//! IO-aware scheduling of generations on shared volumes: IO priority of
+//! the allure process and serialization under IO pressure.
+
+use std::ffi::OsString;
+use std::str::FromStr;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::info;
+
+/// `ionice` class of local allure processes (`GENERATOR_IONICE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoNice {
+    Idle,
+    /// Priority 0 (highest) ..= 7 (lowest).
+    BestEffort(u8),
+}
+
+impl FromStr for IoNice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "idle" => Ok(Self::Idle),
+            None if s == "best-effort" => Ok(Self::BestEffort(7)),
+            Some(("best-effort", level)) => match level.parse::<u8>() {
+                Ok(l) if l <= 7 => Ok(Self::BestEffort(l)),
+                _ => anyhow::bail!("best-effort level must be 0..=7, got {level:?}"),
+            },
+            _ => anyhow::bail!("expected idle|best-effort[:0-7], got {s:?}"),
+        }
+    }
+}
+
+impl IoNice {
+    /// `ionice` arguments before the wrapped command.
+    pub fn args(&self) -> Vec<OsString> {
+        match self {
+            Self::Idle => vec!["-c".into(), "3".into()],
+            Self::BestEffort(l) => vec!["-c".into(), "2".into(), "-n".into(), l.to_string().into()],
+        }
+    }
+}
+
+/// Serializes generations while IO pressure is above the threshold.
+pub struct IoScheduler {
+    /// `some avg10` of `/proc/pressure/io`, percent.
+    threshold: Option<f64>,
+    serial: Mutex<()>,
+}
+
+impl IoScheduler {
+    pub fn new(threshold: Option<f64>) -> Self {
+        Self { threshold, serial: Mutex::new(()) }
+    }
+
+    /// Waits for the serial slot when IO is under pressure; generations run
+    /// concurrently otherwise (and when PSI is unavailable).
+    pub async fn admit(&self) -> Option<MutexGuard<'_, ()>> {
+        let threshold = self.threshold?;
+        let pressure = io_pressure().await?;
+        if pressure < threshold {
+            return None;
+        }
+        info!(pressure, threshold, "IO pressure high, serializing generation");
+        Some(self.serial.lock().await)
+    }
+}
+
+/// `some avg10` from `/proc/pressure/io` (Linux PSI).
+async fn io_pressure() -> Option<f64> {
+    let text = tokio::fs::read_to_string("/proc/pressure/io").await.ok()?;
+    text.lines()
+        .find(|l| l.starts_with("some "))?
+        .split_whitespace()
+        .find_map(|kv| kv.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
@@ -0,0 +1,55 @@
+use anyhow::Context;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::{results::TestResult, settings::JiraSettings, state::AppState, storage::IssueRef};
+
+/// Issue keys mentioned in failure messages/traces, deduplicated and sorted.
+pub fn extract_issues(jira: &JiraSettings, results: &[TestResult]) -> anyhow::Result<Vec<IssueRef>> {
+    let re = Regex::new(&jira.issue_key_regex)
+        .with_context(|| format!("invalid jira issue_key_regex: {}", jira.issue_key_regex))?;
+
+    let mut keys = BTreeSet::new();
+    for r in results.iter().filter(|r| r.is_failure()) {
+        let Some(details) = &r.status_details else { continue };
+        for text in [&details.message, &details.trace].into_iter().flatten() {
+            keys.extend(re.find_iter(text).map(|m| m.as_str().to_string()));
+        }
+    }
+
+    let base = jira.base_url.trim_end_matches('/');
+    Ok(keys
+        .into_iter()
+        .map(|key| IssueRef {
+            url: format!("{}/browse/{}", base, key),
+            key,
+        })
+        .collect())
+}
+
+/// Adds a comment with the report link to the issue (Jira REST API v2).
+/// Credentials come from `JIRA_USER`/`JIRA_TOKEN`, never from project.json.
+pub async fn comment_issue(
+    state: &AppState,
+    jira: &JiraSettings,
+    key: &str,
+    report_url: &str,
+) -> anyhow::Result<()> {
+    let user = state.config.jira_user.as_deref().context("JIRA_USER is not set")?;
+    let token = &state.config.jira_token.as_ref().context("JIRA_TOKEN is not set")?.0;
+
+    let url = format!("{}/rest/api/2/issue/{}/comment", jira.base_url.trim_end_matches('/'), key);
+    state
+        .http
+        .post(&url)
+        .basic_auth(user, Some(token))
+        .timeout(Duration::from_secs(30))
+        .json(&serde_json::json!({ "body": format!("Failing tests reference this issue. Allure report: {report_url}") }))
+        .send()
+        .await
+        .context("send request")?
+        .error_for_status()
+        .context("bad response")?;
+    Ok(())
+}
@@ -0,0 +1,310 @@
+//! Report generations queued by `?async=true` uploads. The upload answers
+//! 202 as soon as the results are on disk; `JOB_WORKERS` background
+//! workers generate the reports and clients poll `GET /api/v1/jobs/{id}`.
+//! Jobs are kept in `DATA_DIR/jobs/<id>.json`, so a restart picks up
+//! queued and interrupted ones.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::{
+    pipeline::{self, Generated},
+    progress::Phase,
+    state::AppState,
+    storage::{self, Status},
+    trace,
+    util::now_unix,
+    volumes::Volumes,
+};
+
+/// Finished jobs are forgotten after a week.
+const JOB_TTL_SECS: u64 = 7 * 24 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Success,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub project: String,
+    pub run_id: u64,
+    pub status: JobStatus,
+    /// Generation error of a failed job.
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    /// `x-request-id` of the upload that queued the job.
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// Trace context of that upload; the generation continues its trace.
+    #[serde(default)]
+    pub traceparent: Option<String>,
+}
+
+impl Job {
+    fn is_finished(&self) -> bool {
+        matches!(self.status, JobStatus::Success | JobStatus::Failed)
+    }
+}
+
+#[derive(Default)]
+pub struct JobQueue {
+    jobs: DashMap<String, Job>,
+    pending: Mutex<VecDeque<String>>,
+    wake: Notify,
+}
+
+fn jobs_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("jobs")
+}
+
+fn job_path(data_dir: &Path, id: &str) -> PathBuf {
+    jobs_dir(data_dir).join(format!("{id}.json"))
+}
+
+impl JobQueue {
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.get(id).map(|j| j.clone())
+    }
+
+    /// Queues the generation of an uploaded run; the job is on disk before
+    /// this returns.
+    pub async fn enqueue(&self, volumes: &Volumes, project: &str, run_id: u64) -> anyhow::Result<Job> {
+        let data_dir = volumes.data_dir();
+        let origin = trace::current();
+        let job = Job {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            project: project.to_string(),
+            run_id,
+            status: JobStatus::Queued,
+            error: None,
+            created_at: now_unix(),
+            started_at: None,
+            finished_at: None,
+            request_id: origin.request_id,
+            traceparent: origin.traceparent,
+        };
+        tokio::fs::create_dir_all(jobs_dir(data_dir)).await?;
+        storage::write_json(&job_path(data_dir, &job.id), &job).await?;
+        storage::write_run_status(&storage::run_dir(volumes, project, run_id), Status::Queued, None).await;
+        self.push(job.clone());
+        Ok(job)
+    }
+
+    /// Queued or running jobs refer to the project's runs by id.
+    pub fn has_unfinished(&self, project: &str) -> bool {
+        self.jobs.iter().any(|j| j.project == project && !j.is_finished())
+    }
+
+    fn push(&self, job: Job) {
+        let id = job.id.clone();
+        self.jobs.insert(id.clone(), job);
+        self.pending.lock().unwrap().push_back(id);
+        self.wake.notify_one();
+    }
+
+    async fn next(&self) -> String {
+        loop {
+            let notified = self.wake.notified();
+            if let Some(id) = self.pending.lock().unwrap().pop_front() {
+                return id;
+            }
+            notified.await;
+        }
+    }
+
+    /// Applies `f` to the job and persists it.
+    async fn update(&self, data_dir: &Path, id: &str, f: impl FnOnce(&mut Job)) -> Option<Job> {
+        let job = {
+            let mut job = self.jobs.get_mut(id)?;
+            f(&mut job);
+            job.clone()
+        };
+        if let Err(e) = storage::write_json(&job_path(data_dir, id), &job).await {
+            warn!(job_id=%id, error=%format!("{e:#}"), "failed to persist job");
+        }
+        Some(job)
+    }
+
+    /// Drops finished jobs older than [`JOB_TTL_SECS`].
+    async fn prune(&self, data_dir: &Path) {
+        let cutoff = now_unix().saturating_sub(JOB_TTL_SECS);
+        let expired: Vec<String> = self
+            .jobs
+            .iter()
+            .filter(|j| j.is_finished() && j.finished_at.unwrap_or(j.created_at) < cutoff)
+            .map(|j| j.id.clone())
+            .collect();
+        for id in expired {
+            self.jobs.remove(&id);
+            let _ = tokio::fs::remove_file(job_path(data_dir, &id)).await;
+        }
+    }
+}
+
+/// Loads persisted jobs and starts the workers.
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        restore(&state).await;
+        mark_interrupted(&state).await;
+        for _ in 0..state.config.job_workers.max(1) {
+            let state = state.clone();
+            tokio::spawn(async move {
+                loop {
+                    let id = state.jobs.next().await;
+                    let Some(job) = state.jobs.get(&id) else { continue };
+                    let c = trace::Correlation { request_id: job.request_id, job_id: Some(id.clone()), traceparent: job.traceparent };
+                    trace::scope(c, run(&state, &id)).await;
+                }
+            });
+        }
+    });
+}
+
+async fn restore(state: &AppState) {
+    let mut entries = match tokio::fs::read_dir(jobs_dir(&state.data_dir)).await {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    let mut jobs = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let parsed = tokio::fs::read(&path)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice::<Job>(&bytes)?));
+        match parsed {
+            Ok(job) => jobs.push(job),
+            Err(e) => warn!(path=%path.display(), error=%format!("{e:#}"), "skipping broken job file"),
+        }
+    }
+
+    jobs.sort_unstable_by_key(|j| j.created_at);
+    let mut requeued = 0;
+    for mut job in jobs {
+        // после обновления бинаря задачи, поставленные уже этим процессом,
+        // в очереди: повторно их не ставим
+        if state.jobs.jobs.contains_key(&job.id) {
+            continue;
+        }
+        if job.is_finished() {
+            state.jobs.jobs.insert(job.id.clone(), job);
+            continue;
+        }
+        // прерванная рестартом генерация начинается заново
+        job.status = JobStatus::Queued;
+        job.started_at = None;
+        state.progress.publish(&job.project, job.run_id, Phase::Queued, None);
+        let run_dir = storage::run_dir(&state.volumes, &job.project, job.run_id);
+        if run_dir.is_dir() {
+            storage::write_run_status(&run_dir, Status::Queued, None).await;
+        }
+        state.jobs.push(job);
+        requeued += 1;
+    }
+    state.jobs.prune(&state.data_dir).await;
+    if requeued > 0 {
+        info!(jobs = requeued, "requeued unfinished generation jobs");
+    }
+}
+
+/// Runs left extracting or generating by a previous process and not
+/// requeued by [`restore`] will not finish any more.
+async fn mark_interrupted(state: &AppState) {
+    let projects = match storage::list_projects(&state.volumes).await {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(error=%format!("{e:#}"), "list projects failed, interrupted runs are not marked");
+            return;
+        }
+    };
+    let mut marked = 0;
+    for project in projects {
+        for run_id in storage::list_run_ids(&state.volumes, &project).await.unwrap_or_default() {
+            // загрузка, начатая уже этим процессом (после передачи сокетов), ещё идёт
+            if state.progress.subscribe(&project, run_id).is_some() {
+                continue;
+            }
+            let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+            let Some(st) = storage::read_run_status(&run_dir).await else { continue };
+            if matches!(st.status, Status::Extracting | Status::Generating) {
+                storage::write_run_status(&run_dir, Status::Interrupted, Some("service restarted".into())).await;
+                marked += 1;
+            }
+        }
+    }
+    if marked > 0 {
+        warn!(runs = marked, "runs cut short by a restart marked interrupted");
+    }
+}
+
+async fn run(state: &AppState, id: &str) {
+    let Some(job) = state.jobs.get(id) else {
+        return;
+    };
+    let project = job.project;
+    let run_id = job.run_id;
+
+    // при перегрузке задача ждёт в очереди, а не запускает ещё один allure
+    state.shedder.wait_healthy().await;
+    let _guard = state.lock_project(&project, "job").await;
+    state
+        .jobs
+        .update(&state.data_dir, id, |j| {
+            j.status = JobStatus::Running;
+            j.started_at = Some(now_unix());
+        })
+        .await;
+
+    let result = if storage::run_dir(&state.volumes, &project, run_id).is_dir() {
+        pipeline::generate_run(state, &project, run_id).await
+    } else {
+        // прогон удалили, пока задача ждала в очереди
+        let err = "run was deleted before generation".to_string();
+        state.progress.publish(&project, run_id, Phase::Failed, Some(err.clone()));
+        Err(err)
+    };
+    match &result {
+        Ok(Generated::Incomplete(reason)) => {
+            warn!(project=%project, run_id=run_id, job_id=%id, reason=%reason, "generation job finished, run is incomplete");
+        }
+        Ok(Generated::Complete) => {
+            let project_dir = storage::project_dir(&state.volumes, &project);
+            // задача могла ждать в очереди, пока загрузили прогон новее:
+            // latest назад не откатываем
+            let latest = storage::read_latest_run_id(&project_dir).await;
+            if latest.is_none_or(|latest| run_id > latest) {
+                if let Err(e) = storage::set_latest_run_id(&project_dir, run_id).await {
+                    warn!(project=%project, run_id=run_id, error=%e, "set latest_run_id failed");
+                }
+            }
+            info!(project=%project, run_id=run_id, job_id=%id, "generation job finished");
+        }
+        Err(e) => warn!(project=%project, run_id=run_id, job_id=%id, error=%e, "generation job failed"),
+    }
+
+    state
+        .jobs
+        .update(&state.data_dir, id, |j| {
+            j.status = if result.is_ok() { JobStatus::Success } else { JobStatus::Failed };
+            j.error = result.err();
+            j.finished_at = Some(now_unix());
+        })
+        .await;
+    state.jobs.prune(&state.data_dir).await;
+}
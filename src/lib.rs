@@ -0,0 +1,5 @@
+//! The service itself is the `allure3-docker-service` binary; the library
+//! only carries the API client (`--features client`).
+
+#[cfg(feature = "client")]
+pub mod client;
@@ -0,0 +1,90 @@
+//! Per-project locks (run_id allocation, generation, deletion) that know
+//! who holds them, so a wedged project can be inspected and force-unlocked.
+
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::util::now_unix;
+
+#[derive(Default)]
+struct Slot {
+    mutex: Arc<Mutex<()>>,
+    holder: std::sync::Mutex<Option<(&'static str, u64)>>,
+    waiting: AtomicUsize,
+}
+
+#[derive(Default)]
+pub struct ProjectLocks {
+    slots: DashMap<String, Arc<Slot>>,
+}
+
+/// Held project lock; released on drop.
+pub struct ProjectGuard {
+    slot: Arc<Slot>,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Drop for ProjectGuard {
+    fn drop(&mut self) {
+        *self.slot.holder.lock().unwrap() = None;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LockInfo {
+    pub project: String,
+    /// What holds the lock (`upload`, `regenerate`, ...); None when free.
+    pub holder: Option<&'static str>,
+    pub held_since: Option<u64>,
+    pub held_secs: Option<u64>,
+    pub waiting: usize,
+}
+
+impl ProjectLocks {
+    pub async fn lock(&self, project: &str, purpose: &'static str) -> ProjectGuard {
+        let slot = self.slots.entry(project.to_string()).or_default().clone();
+
+        slot.waiting.fetch_add(1, Ordering::Relaxed);
+        let guard = slot.mutex.clone().lock_owned().await;
+        slot.waiting.fetch_sub(1, Ordering::Relaxed);
+
+        *slot.holder.lock().unwrap() = Some((purpose, now_unix()));
+        ProjectGuard { slot, _guard: guard }
+    }
+
+    /// Held or contended locks, longest held first.
+    pub fn list(&self) -> Vec<LockInfo> {
+        let now = now_unix();
+        let mut out: Vec<LockInfo> = self
+            .slots
+            .iter()
+            .filter_map(|e| {
+                let holder = *e.holder.lock().unwrap();
+                let waiting = e.waiting.load(Ordering::Relaxed);
+                if holder.is_none() && waiting == 0 {
+                    return None;
+                }
+                Some(LockInfo {
+                    project: e.key().clone(),
+                    holder: holder.map(|h| h.0),
+                    held_since: holder.map(|h| h.1),
+                    held_secs: holder.map(|h| now.saturating_sub(h.1)),
+                    waiting,
+                })
+            })
+            .collect();
+        out.sort_by_key(|l| l.held_since.unwrap_or(u64::MAX));
+        out
+    }
+
+    /// Detaches the project's lock: new requests get a fresh one, the stuck
+    /// holder keeps running with the old one. Returns what held it.
+    pub fn force_unlock(&self, project: &str) -> Option<LockInfo> {
+        let info = self.list().into_iter().find(|l| l.project == project)?;
+        self.slots.remove(project);
+        Some(info)
+    }
+}
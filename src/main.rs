@@ -1,15 +1,69 @@
+mod aliases;
 mod app;
+mod config;
 mod state;
 mod util;
 mod storage;
-mod unzip;
+mod sync;
+mod archive;
 mod allure;
+mod analytics;
+mod artifacts;
+mod audit;
+mod backup;
+mod badge;
+mod breaker;
+mod categories;
+mod ci;
+mod digest;
+mod executor;
+mod export;
+mod favorites;
+mod federation;
 mod handlers;
+mod hold;
+mod hooks;
+mod idempotency;
+mod integrity;
+mod iosched;
+mod jira;
+mod jobs;
+mod locks;
+mod merge;
+mod metrics;
+mod notify;
+mod pipeline;
+mod preprocess;
+mod progress;
+mod purge;
+mod quota;
+mod renumber;
+mod resumable;
+mod seal;
+mod results;
+mod retention;
+mod run_keys;
+mod schema;
+mod settings;
+mod shed;
+mod sniff;
+mod slots;
+mod storage_classes;
+mod summary;
+mod systemd;
+mod trace;
+#[cfg(feature = "test-hooks")]
+mod test_hooks;
+mod uploads;
+mod upgrade;
+mod usage;
+mod volumes;
+mod worker;
 
+use crate::config::{Config, Role, StartupCheck};
 use crate::state::AppState;
 use std::net::SocketAddr;
-use std::path::PathBuf;
-use tracing::{info, debug};
+use tracing::{debug, error, info};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -22,25 +76,185 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     info!("starting allure3-docker-service");
+    #[cfg(feature = "test-hooks")]
+    tracing::warn!("built with test-hooks: /api/v1/test-hooks can inject failures, do not use in production");
 
+    let config = Config::from_env()?;
+    debug!(?config, "configuration");
 
-    let data_dir = std::env::var("DATA_DIR").unwrap_or_else(|_| "/data".to_string());
-    let listen = std::env::var("LISTEN").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
-    let allure_bin = std::env::var("ALLURE_BIN").unwrap_or_else(|_| "allure".to_string());
+    let addr: SocketAddr = config.listen.parse()?;
+    let flavor = match config.allure_flavor {
+        Some(f) => f,
+        None => allure::detect_flavor(&config.allure_bin).await,
+    };
+    let problems = match config.startup_check {
+        StartupCheck::Off => Vec::new(),
+        _ => integrity::check(&config).await,
+    };
+    for p in &problems {
+        error!(problem=%p, "data volume check failed");
+    }
+    let read_only = !problems.is_empty();
+    if read_only && (config.startup_check == StartupCheck::Strict || config.role == Role::Worker) {
+        anyhow::bail!(
+            "data volume check failed: {} (STARTUP_CHECK=read-only serves existing reports anyway)",
+            problems.join("; ")
+        );
+    }
 
-    debug!(%data_dir, %listen, %allure_bin, "configuration");
+    let mut state = AppState::new(config, flavor);
+    if read_only {
+        // отчёты отдаём, всё, что пишет на том, — 503
+        state.read_only = Some(problems.join("; ").into());
+    } else if upgrade::parent().is_none() {
+        // при обновлении бинаря в TMP_DIR ещё пишут загрузки старого
+        // процесса, а после его выхода --- уже наши; чистит следующий старт
+        util::clean_tmp_dir(&state.config.tmp_dir).await;
+    }
+    {
+        let generator = state.generator.clone();
+        tokio::spawn(async move { allure::warm_up(&generator).await });
+    }
+    // проекты со старыми именами открываются и удаляются, но новый с таким
+    // именем уже не создать; переименовать можно слиянием в новый проект
+    for project in storage::list_projects(&state.volumes).await.unwrap_or_default() {
+        if let Err(e) = util::validate_new_project_name(&project) {
+            tracing::warn!(project=%project, reason=e, "project name is no longer allowed for new projects");
+        }
+    }
+    if state.config.role == Role::Worker {
+        // воркер не поднимает HTTP — только забирает генерации у primary
+        return worker::run(state).await;
+    }
+    digest::spawn(state.clone());
+    retention::spawn_report(state.clone());
+    if !read_only {
+        match upgrade::parent() {
+            None => {
+                retention::spawn(state.clone());
+                jobs::spawn(state.clone());
+            }
+            Some(pid) => {
+                // очередь генераций и автоочистку ведёт старый процесс, пока не выйдет
+                let state = state.clone();
+                tokio::spawn(async move {
+                    upgrade::parent_exited(pid).await;
+                    retention::spawn(state.clone());
+                    jobs::spawn(state);
+                });
+            }
+        }
+    }
+    breaker::spawn_probe(state.clone(), std::time::Duration::from_secs(state.config.breaker_probe_secs.max(1)));
+    let upload_addr: Option<SocketAddr> = state.config.upload_listen.as_deref().map(str::parse).transpose()?;
 
-    let state = AppState::new(PathBuf::from(&data_dir), allure_bin);
-    let router = app::router(state);
-
-    let addr: SocketAddr = listen.parse()?;
-    info!(%addr, "binding listener");
-
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    info!("listener bound, starting HTTP server");
+    let mut inherited = systemd::listen_fds()?;
+    let (listener, upload_listener) = if inherited.is_empty() {
+        info!(%addr, ?upload_addr, "binding listeners");
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let upload_listener = match upload_addr {
+            Some(a) => Some(tokio::net::TcpListener::bind(a).await?),
+            None => None,
+        };
+        (listener, upload_listener)
+    } else {
+        // socket activation или обновление бинаря: сокет с
+        // FileDescriptorName=upload — для загрузок, первый из остальных —
+        // основной; LISTEN игнорируется
+        info!(sockets = inherited.len(), upgrade = upgrade::parent().is_some(), "using inherited sockets");
+        let upload = inherited.iter().position(|(name, _)| name == "upload").map(|i| inherited.remove(i).1);
+        let Some((_, main)) = inherited.into_iter().next() else {
+            anyhow::bail!("systemd passed only the upload socket");
+        };
+        (
+            tokio::net::TcpListener::from_std(main)?,
+            upload.map(tokio::net::TcpListener::from_std).transpose()?,
+        )
+    };
+    info!("listeners bound, starting HTTP server");
+    upgrade::ready();
+    systemd::notify("READY=1");
+    systemd::spawn_watchdog();
+    let sockets = handover_sockets(&listener, upload_listener.as_ref());
 
-    axum::serve(listener, router).await?;
+    match upload_listener {
+        None => {
+            axum::serve(listener, app::router(state))
+                .with_graceful_shutdown(stop_signal(sockets))
+                .await?;
+        }
+        Some(upload_listener) => {
+            // UI/отчёты и загрузки на разных адресах
+            let ui_router = app::read_router(state.clone());
+            let upload_router = app::write_router(state);
+            let (stop_tx, stop_rx) = tokio::sync::watch::channel(());
+            tokio::spawn(async move {
+                stop_signal(sockets).await;
+                let _ = stop_tx.send(());
+            });
+            let stopped = |mut rx: tokio::sync::watch::Receiver<()>| async move {
+                let _ = rx.changed().await;
+            };
+            let ui_stop = stopped(stop_rx.clone());
+            let upload_stop = stopped(stop_rx);
+            tokio::try_join!(
+                async { axum::serve(listener, ui_router).with_graceful_shutdown(ui_stop).await },
+                async { axum::serve(upload_listener, upload_router).with_graceful_shutdown(upload_stop).await },
+            )?;
+        }
+    }
 
     info!("server stopped");
     Ok(())
 }
+
+/// Listening sockets for a binary upgrade, named as systemd would pass them.
+#[cfg(unix)]
+fn handover_sockets(
+    listener: &tokio::net::TcpListener,
+    upload: Option<&tokio::net::TcpListener>,
+) -> Vec<(&'static str, upgrade::Fd)> {
+    use std::os::fd::AsRawFd;
+
+    let mut sockets = vec![("main", listener.as_raw_fd())];
+    sockets.extend(upload.map(|u| ("upload", u.as_raw_fd())));
+    sockets
+}
+
+#[cfg(not(unix))]
+fn handover_sockets(_listener: &tokio::net::TcpListener, _upload: Option<&tokio::net::TcpListener>) -> Vec<(&'static str, upgrade::Fd)> {
+    Vec::new()
+}
+
+/// Shutdown or a completed binary upgrade (SIGUSR2).
+async fn stop_signal(sockets: Vec<(&'static str, upgrade::Fd)>) {
+    tokio::select! {
+        _ = shutdown_signal() => {},
+        _ = upgrade::handover(sockets) => {},
+    }
+}
+
+/// SIGTERM / Ctrl-C: stop accepting, let in-flight requests finish.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("shutdown signal received, draining connections");
+    systemd::notify("STOPPING=1");
+}
@@ -0,0 +1,194 @@
+//! Near-duplicate project names (`my-app`, `my_app`, `MyApp`) and merging
+//! one project into another.
+
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::{btree_map::Entry, BTreeMap};
+use std::path::Path;
+use tracing::info;
+
+use crate::{artifacts, renumber, run_keys, state::AppState, storage};
+
+/// Run ids of merged projects: alias -> old id -> id in this project.
+pub const MERGED_RUNS_FILE: &str = "merged_runs.json";
+
+pub type MergedRuns = BTreeMap<String, BTreeMap<u64, u64>>;
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    /// Name with case and separators dropped.
+    pub key: String,
+    pub projects: Vec<DuplicateProject>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateProject {
+    pub project: String,
+    pub runs_count: usize,
+    pub last_upload_at: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MergeReport {
+    pub from: String,
+    pub into: String,
+    pub dry_run: bool,
+    /// In the order they are moved.
+    pub runs: Vec<MovedRun>,
+    /// Latest run of `into` after the merge: the most recently uploaded
+    /// successful run.
+    pub latest_run_id: Option<u64>,
+    /// `from` had its own `project.json`, which is not carried over.
+    pub discarded_settings: bool,
+    /// Run keys of `from` that `into` already uses; they keep naming the
+    /// run of `into`.
+    pub conflicting_run_keys: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MovedRun {
+    pub from_run_id: u64,
+    pub to_run_id: u64,
+}
+
+pub fn name_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Groups of two or more projects with the same [`name_key`].
+pub async fn duplicates(state: &AppState) -> anyhow::Result<Vec<DuplicateGroup>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for project in storage::list_projects(&state.volumes).await? {
+        groups.entry(name_key(&project)).or_default().push(project);
+    }
+
+    let mut out = Vec::new();
+    for (key, names) in groups.into_iter().filter(|(_, names)| names.len() > 1) {
+        let mut projects = Vec::with_capacity(names.len());
+        for project in names {
+            projects.push(DuplicateProject {
+                runs_count: storage::list_run_ids(&state.volumes, &project).await?.len(),
+                last_upload_at: storage::last_upload_at(&state.volumes, &project).await?,
+                project,
+            });
+        }
+        out.push(DuplicateGroup { key, projects });
+    }
+    Ok(out)
+}
+
+/// Moves the runs of `from` into `into` under new ids, removes `from` and
+/// leaves it as an alias of `into`. The caller holds both project locks
+/// and has checked that nothing in `from` is under legal hold.
+pub async fn merge(state: &AppState, from: &str, into: &str, dry_run: bool) -> anyhow::Result<MergeReport> {
+    let from_dir = storage::project_dir(&state.volumes, from);
+    let into_dir = storage::project_dir(&state.volumes, into);
+    let mut report = MergeReport {
+        from: from.to_string(),
+        into: into.to_string(),
+        dry_run,
+        runs: Vec::new(),
+        latest_run_id: None,
+        discarded_settings: from_dir.join("project.json").is_file(),
+        conflicting_run_keys: Vec::new(),
+    };
+
+    let from_ids = storage::list_run_ids(&state.volumes, from).await?;
+    let mut next_id = match tokio::fs::read_to_string(into_dir.join("next_run_id")).await {
+        Ok(s) => s.trim().parse().unwrap_or(1),
+        Err(_) => 1,
+    };
+    for &from_run_id in &from_ids {
+        let to_run_id = if dry_run {
+            next_id += 1;
+            next_id - 1
+        } else {
+            let to_run_id = storage::reserve_next_run_id(&into_dir).await?;
+            let dst = storage::run_dir(&state.volumes, into, to_run_id);
+            if let Err(e) = crate::util::move_dir(&storage::run_dir(&state.volumes, from, from_run_id), &dst).await {
+                // перенесённое до ошибки уже лежит в into — сообщаем, что именно
+                let moved: Vec<_> = report.runs.iter().map(|r| (r.from_run_id, r.to_run_id)).collect();
+                anyhow::bail!("move run {from}/{from_run_id}: {e:#} (moved before: {moved:?})");
+            }
+            // в кеше summary.json старые проект и номер
+            artifacts::invalidate(&dst).await;
+            to_run_id
+        };
+        report.runs.push(MovedRun { from_run_id, to_run_id });
+    }
+
+    // latest — последний загруженный, а не самый большой номер, и только
+    // с готовым отчётом: упавший прогон latest не становится
+    let mut latest: Option<(u64, u64)> = None;
+    let into_ids = storage::list_run_ids(&state.volumes, into).await?;
+    let moved = report.runs.iter().map(|r| (r.to_run_id, storage::run_dir(&state.volumes, from, r.from_run_id)));
+    let existing = into_ids.iter().map(|&id| (id, storage::run_dir(&state.volumes, into, id)));
+    let candidates: Vec<_> = if dry_run { existing.chain(moved).collect() } else { existing.collect() };
+    for (id, dir) in candidates {
+        if !storage::read_run_status(&dir).await.is_some_and(|s| s.status == storage::Status::Success) {
+            continue;
+        }
+        let created = storage::run_created_at(&dir).await.unwrap_or(0);
+        if latest.is_none_or(|(_, c)| created >= c) {
+            latest = Some((id, created));
+        }
+    }
+    report.latest_run_id = latest.map(|(id, _)| id);
+
+    let moved: BTreeMap<u64, u64> = report.runs.iter().map(|r| (r.from_run_id, r.to_run_id)).collect();
+    let mut keys = run_keys::read(&into_dir).await?;
+    for (key, id) in run_keys::read(&from_dir).await? {
+        let Some(&id) = moved.get(&id) else { continue };
+        match keys.entry(key) {
+            Entry::Occupied(e) => report.conflicting_run_keys.push(e.key().clone()),
+            Entry::Vacant(e) => {
+                e.insert(id);
+            }
+        }
+    }
+    if dry_run {
+        return Ok(report);
+    }
+
+    // ссылки /runs/{id} старого имени ведут на перенесённые прогоны, а не
+    // на прогоны into с теми же номерами
+    let mut merged = read_merged_runs(&into_dir).await?;
+    let mut own = moved.clone();
+    for (old, id) in renumber::read_run_aliases(&from_dir).await? {
+        if let Some(&to) = moved.get(&id) {
+            own.entry(old).or_insert(to);
+        }
+    }
+    for (alias, ids) in read_merged_runs(&from_dir).await? {
+        let ids = ids.into_iter().filter_map(|(old, id)| Some((old, *moved.get(&id)?)));
+        merged.entry(alias).or_default().extend(ids);
+    }
+    merged.insert(from.to_string(), own);
+    storage::write_json(&into_dir.join(MERGED_RUNS_FILE), &merged).await?;
+    if !keys.is_empty() {
+        storage::write_json(&into_dir.join(run_keys::RUN_KEYS_FILE), &keys).await?;
+    }
+
+    if let Some(id) = report.latest_run_id {
+        storage::set_latest_run_id(&into_dir, id).await?;
+    }
+    tokio::fs::remove_dir_all(&from_dir)
+        .await
+        .with_context(|| format!("remove {}", from_dir.display()))?;
+    state.aliases.set(&state.data_dir, from, into).await?;
+    info!(from=%from, into=%into, runs = report.runs.len(), "projects merged");
+    Ok(report)
+}
+
+
+pub async fn read_merged_runs(project_dir: &Path) -> anyhow::Result<MergedRuns> {
+    let p = project_dir.join(MERGED_RUNS_FILE);
+    match tokio::fs::read(&p).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("parse {}", p.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("read {}", p.display())),
+    }
+}
@@ -0,0 +1,118 @@
+//! Prometheus metrics (`GET /metrics`, text exposition format).
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::executor::ResourceUsage;
+
+struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    /// Upper bounds, ascending; `+Inf` is implicit.
+    buckets: &'static [f64],
+    /// Per-bucket (non-cumulative) counts, last one is `+Inf`.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(name: &'static str, help: &'static str, buckets: &'static [f64]) -> Self {
+        Self { name, help, buckets, counts: vec![0; buckets.len() + 1], sum: 0.0 }
+    }
+
+    fn observe(&mut self, v: f64) {
+        let idx = self.buckets.iter().position(|b| v <= *b).unwrap_or(self.buckets.len());
+        self.counts[idx] += 1;
+        self.sum += v;
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        let mut cumulative = 0;
+        for (bound, count) in self.buckets.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", self.name, bound, cumulative);
+        }
+        cumulative += self.counts[self.buckets.len()];
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", self.name, cumulative);
+        let _ = writeln!(out, "{}_sum {}", self.name, self.sum);
+        let _ = writeln!(out, "{}_count {}", self.name, cumulative);
+    }
+}
+
+const SECONDS: &[f64] = &[1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+const BYTES: &[f64] = &[
+    134_217_728.0,    // 128 MiB
+    268_435_456.0,    // 256 MiB
+    536_870_912.0,    // 512 MiB
+    1_073_741_824.0,  // 1 GiB
+    2_147_483_648.0,  // 2 GiB
+    4_294_967_296.0,  // 4 GiB
+    8_589_934_592.0,  // 8 GiB
+];
+
+pub struct Metrics {
+    generation: Mutex<[Histogram; 3]>,
+    retention_runs: AtomicU64,
+    retention_bytes: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            generation: Mutex::new([
+                Histogram::new("allure_generate_wall_seconds", "Wall time of allure generate.", SECONDS),
+                Histogram::new("allure_generate_cpu_seconds", "User+system CPU time of allure generate.", SECONDS),
+                Histogram::new("allure_generate_peak_rss_bytes", "Peak RSS of allure generate.", BYTES),
+            ]),
+            retention_runs: AtomicU64::new(0),
+            retention_bytes: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn observe_generation(&self, usage: &ResourceUsage) {
+        let mut h = self.generation.lock().unwrap();
+        h[0].observe(usage.wall_ms as f64 / 1000.0);
+        if let Some(cpu) = usage.cpu_ms {
+            h[1].observe(cpu as f64 / 1000.0);
+        }
+        if let Some(rss) = usage.peak_rss_bytes {
+            h[2].observe(rss as f64);
+        }
+    }
+
+    pub fn observe_retention(&self, runs: u64, bytes: u64) {
+        self.retention_runs.fetch_add(runs, Ordering::Relaxed);
+        self.retention_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for h in self.generation.lock().unwrap().iter() {
+            h.render(&mut out);
+        }
+        counter(&mut out, "allure_retention_runs_deleted_total", "Runs removed by retention.", &self.retention_runs);
+        counter(&mut out, "allure_retention_bytes_reclaimed_total", "Bytes freed by retention.", &self.retention_bytes);
+        out
+    }
+}
+
+pub fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: &AtomicU64) {
+    counter_value(out, name, help, value.load(Ordering::Relaxed));
+}
+
+pub fn counter_value(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    let _ = writeln!(out, "{name} {value}");
+}
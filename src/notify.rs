@@ -0,0 +1,83 @@
+use anyhow::Context;
+use lettre::{
+    message::{
+        header::{HeaderName, HeaderValue},
+        Mailbox,
+    },
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{state::AppState, trace};
+
+/// Where notifications are delivered: `notifications` in project.json, or
+/// the org-wide `NOTIFY_*` environment variables.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyTargets {
+    /// Slack incoming webhook URL.
+    pub slack_webhook: Option<String>,
+    pub email: Vec<String>,
+}
+
+impl NotifyTargets {
+    pub fn is_empty(&self) -> bool {
+        self.slack_webhook.is_none() && self.email.is_empty()
+    }
+}
+
+/// Sends `text` to every target; all targets are tried, the first error is returned.
+pub async fn send(state: &AppState, to: &NotifyTargets, subject: &str, text: &str) -> anyhow::Result<()> {
+    let mut first_err = None;
+
+    if let Some(url) = &to.slack_webhook {
+        if let Err(e) = send_slack(state, url, subject, text).await {
+            first_err.get_or_insert(e.context("slack"));
+        }
+    }
+    if !to.email.is_empty() {
+        if let Err(e) = send_email(state, &to.email, subject, text).await {
+            first_err.get_or_insert(e.context("email"));
+        }
+    }
+
+    match first_err {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+async fn send_slack(state: &AppState, url: &str, subject: &str, text: &str) -> anyhow::Result<()> {
+    let mut req = state.http.post(url).timeout(Duration::from_secs(30));
+    for (name, value) in trace::current().headers() {
+        req = req.header(name, value);
+    }
+    req.json(&serde_json::json!({ "text": format!("*{subject}*\n{text}") }))
+        .send()
+        .await
+        .context("send request")?
+        .error_for_status()
+        .context("bad response")?;
+    Ok(())
+}
+
+async fn send_email(state: &AppState, to: &[String], subject: &str, text: &str) -> anyhow::Result<()> {
+    let smtp_url = state.config.smtp_url.as_ref().context("SMTP_URL is not set")?;
+    let from: Mailbox = state.config.smtp_from.parse().context("invalid SMTP_FROM")?;
+
+    let mut builder = Message::builder().from(from).subject(subject);
+    for (name, value) in trace::current().headers() {
+        builder = builder.raw_header(HeaderValue::new(HeaderName::new_from_ascii_str(name), value));
+    }
+    for addr in to {
+        builder = builder.to(addr.parse().with_context(|| format!("invalid email: {addr}"))?);
+    }
+    let msg = builder.body(text.to_string()).context("build email")?;
+
+    let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(&smtp_url.0)
+        .context("invalid SMTP_URL")?
+        .build();
+    transport.send(msg).await.context("smtp send")?;
+    Ok(())
+}
@@ -0,0 +1,583 @@
+use std::path::Path;
+use std::time::Instant;
+use tracing::{debug, error, warn};
+
+use crate::{
+    allure::{self, Generation, ReportConfig},
+    analytics, categories, ci,
+    hooks::{self, HookContext, HookEvent},
+    jira, notify,
+    preprocess::{self, Outcome, TrimStats},
+    progress::Phase,
+    results,
+    settings::{self, ProjectSettings},
+    state::AppState,
+    seal,
+    storage::{self, Status},
+    summary, sync, usage,
+    util::sanitize_name,
+    worker,
+};
+
+/// How a successful generation ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Generated {
+    Complete,
+    /// The report is there, but the run has fewer tests than the project's
+    /// `completeness` gate expects and must not become `latest`.
+    Incomplete(String),
+}
+
+/// Generates the report of an already extracted run: pre-generate hooks,
+/// `allure generate`, status.json, post-generate hooks.
+/// Returns the error text when generation failed (status.json has it too).
+pub async fn generate_run(state: &AppState, project: &str, run_id: u64) -> Result<Generated, String> {
+    state.progress.publish(project, run_id, Phase::Generating, None);
+    storage::write_run_status(&storage::run_dir(&state.volumes, project, run_id), Status::Generating, None).await;
+    let result = generate(state, project, run_id).await;
+    match &result {
+        Ok(_) => state.progress.publish(project, run_id, Phase::Done, None),
+        Err(e) => state.progress.publish(project, run_id, Phase::Failed, Some(e.clone())),
+    }
+    result
+}
+
+async fn generate(state: &AppState, project: &str, run_id: u64) -> Result<Generated, String> {
+    let project_dir = storage::project_dir(&state.volumes, project);
+    let run_dir = storage::run_dir(&state.volumes, project, run_id);
+
+    let project_settings = match settings::load(&project_dir).await {
+        Ok(s) => s,
+        Err(e) => {
+            // битый project.json не должен молча отключать хуки
+            let err_text = format!("project settings: {e:#}");
+            write_status(&run_dir, Err(&err_text)).await;
+            return Err(err_text);
+        }
+    };
+
+    // секреты вычищаем до обрезки, чтобы они не уехали в attachments/
+    let redactions = match preprocess::redact(&run_dir, &project_settings.redaction).await {
+        Ok(n) => n,
+        Err(e) => {
+            let err_text = format!("redaction: {e:#}");
+            write_status(&run_dir, Err(&err_text)).await;
+            return Err(err_text);
+        }
+    };
+
+    let trimmed = match preprocess::trim_attachments(&run_dir, &project_settings.attachments).await {
+        Ok(t) => t,
+        Err(e) => {
+            warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "trimming attachments failed");
+            TrimStats::default()
+        }
+    };
+
+    match preprocess::carry_history(&state.volumes, project, run_id).await {
+        Ok(Some(from)) => debug!(project=%project, run_id=run_id, from_run_id=from, "report history carried over"),
+        Ok(None) => {}
+        // без истории отчёт всё равно строится, только без трендов
+        Err(e) => warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "carrying report history over failed"),
+    }
+
+    if let Some(meta) = storage::read_run_meta(&run_dir).await {
+        let results_dir = run_dir.join("allure-results");
+        if let Err(e) = ci::write_environment(&results_dir, &meta).await {
+            warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "writing environment.properties failed");
+        }
+        if let Err(e) = ci::write_results(&results_dir, &meta, project, run_id, &state.run_url(project, run_id)).await {
+            warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "writing CI context failed");
+        }
+    }
+
+    // битый файл проекта не мешает пресетам
+    let project_categories = match categories::read_project(&project_dir).await {
+        Ok(c) => c.unwrap_or_default(),
+        Err(e) => {
+            warn!(project=%project, error=%format!("{e:#}"), "project categories.json is broken, ignored");
+            Vec::new()
+        }
+    };
+    match categories::apply(&run_dir.join("allure-results"), &project_categories, &project_settings.categories.presets).await {
+        Ok((_, unknown)) if !unknown.is_empty() => {
+            let known: Vec<_> = categories::names().collect();
+            warn!(project=%project, ?unknown, ?known, "unknown category presets ignored");
+        }
+        Ok(_) => {}
+        // категории --- удобство, из-за них отчёт не теряем
+        Err(e) => warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "applying categories failed"),
+    }
+
+    let mut ctx = HookContext {
+        event: HookEvent::PreGenerate,
+        project: project.to_string(),
+        run_id: Some(run_id),
+        run_dir: Some(run_dir.clone()),
+        status: None,
+        error: None,
+        report_url: Some(state.run_url(project, run_id)),
+    };
+
+    let hook_settings = &project_settings.hooks;
+    let pre_generate = hooks::run_hooks(state, &hook_settings.pre_generate, &ctx).await;
+    #[cfg(feature = "test-hooks")]
+    let pre_generate = match pre_generate {
+        Ok(()) => crate::test_hooks::before_generation().await,
+        err => err,
+    };
+    let attempted = pre_generate.is_ok();
+    let mut started = Instant::now();
+    let result = match pre_generate {
+        Ok(()) if state.config.worker_token.is_some() => {
+            worker::dispatch(state, project, run_id, &project_settings).await
+        }
+        Ok(()) => match report_config(state, &project_settings) {
+            Ok(config) => {
+                // сначала общий лимит процессов, потом IO: иначе ждущие
+                // слота держали бы последовательный IO-слот
+                let _slot = state.generation_slots.acquire().await;
+                let _io_slot = state.io_scheduler.admit().await;
+                // ожидание слота в учёт использования не входит
+                started = Instant::now();
+                let generated = allure::generate_report(
+                    &state.generator,
+                    &run_dir.join("allure-results"),
+                    &run_dir.join("report"),
+                    &config,
+                )
+                .await
+                .map_err(|e| e.to_string());
+                state.breaker.record(&generated);
+                if let Some(usage) = generated.as_ref().ok().and_then(|g| g.usage) {
+                    state.metrics.observe_generation(&usage);
+                }
+                generated
+            }
+            Err(e) => Err(e.to_string()),
+        },
+        Err(e) => Err(format!("{e:#}")),
+    };
+    if attempted {
+        usage::record_generation(&state.data_dir, project, run_id, started.elapsed().as_secs_f64(), result.is_ok()).await;
+    }
+    let generation = result.as_ref().ok().cloned().unwrap_or_default();
+    let result = result.map(|_| ());
+
+    if let Err(err_text) = &result {
+        error!(project=%project, run_id=run_id, error=%err_text, "report generation failed");
+    }
+    write_status(&run_dir, result.as_ref().map(|_| ()).map_err(|e| e.as_str())).await;
+
+    // сбой выгрузки не портит прогон: отчёт всё равно отдаётся сервисом
+    let external_url = match (&result, &project_settings.sync) {
+        (Ok(()), Some(sync_settings)) => match sync::sync_report(state, project, run_id, sync_settings).await {
+            Ok(url) => url,
+            Err(e) => {
+                warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "report sync failed");
+                None
+            }
+        },
+        _ => None,
+    };
+
+    if result.is_ok() && project_settings.seal {
+        if let Err(e) = seal::seal(&run_dir).await {
+            error!(project=%project, run_id=run_id, error=%format!("{e:#}"), "sealing run failed");
+        }
+    }
+
+    let prepared = Outcome { redactions, trimmed };
+    if let Err(e) = analyze_run(state, project, run_id, &project_settings, generation, prepared, external_url).await {
+        warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "run analysis failed");
+    }
+    write_summary(&run_dir, result.is_ok()).await;
+
+    let result = match result {
+        Ok(()) => match incomplete_reason(state, project, run_id, &project_settings.completeness).await {
+            Some(reason) => {
+                warn!(project=%project, run_id=run_id, reason=%reason, "run is incomplete, latest is kept");
+                storage::write_run_status(&run_dir, Status::Incomplete, Some(reason.clone())).await;
+                Ok(Generated::Incomplete(reason))
+            }
+            None => Ok(Generated::Complete),
+        },
+        Err(e) => Err(e),
+    };
+
+    ctx.event = HookEvent::PostGenerate;
+    ctx.status = Some(
+        match &result {
+            Ok(Generated::Complete) => Status::Success,
+            Ok(Generated::Incomplete(_)) => Status::Incomplete,
+            Err(_) => Status::Failed,
+        }
+        .as_str()
+        .into(),
+    );
+    ctx.error = match &result {
+        Ok(Generated::Incomplete(reason)) => Some(reason.clone()),
+        other => other.as_ref().err().cloned(),
+    };
+    if let Err(e) = hooks::run_hooks(state, &hook_settings.post_generate, &ctx).await {
+        warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "post-generate hook failed");
+    }
+
+    result
+}
+
+/// Test counts of the fresh report, for clients that do not read the
+/// report itself; a failed generation drops the previous ones.
+async fn write_summary(run_dir: &Path, generated: bool) {
+    let summary = if generated {
+        let stats = storage::read_run_info(run_dir).await.stats;
+        summary::of_report(&run_dir.join("report"), stats.as_ref()).await
+    } else {
+        None
+    };
+    let Some(summary) = summary else {
+        summary::remove(run_dir).await;
+        return;
+    };
+    if let Err(e) = summary::write(run_dir, &summary).await {
+        warn!(run_dir=%run_dir.display(), error=%format!("{e:#}"), "writing report summary failed");
+    }
+}
+
+/// Why the run falls short of the project's `completeness` gate, if it
+/// does. The previous complete run is the last one with status `success`.
+async fn incomplete_reason(
+    state: &AppState,
+    project: &str,
+    run_id: u64,
+    gate: &settings::CompletenessSettings,
+) -> Option<String> {
+    if gate.min_tests.is_none() && gate.min_pct_of_previous.is_none() {
+        return None;
+    }
+    let total = storage::read_run_info(&storage::run_dir(&state.volumes, project, run_id)).await.stats?.total;
+    if let Some(min) = gate.min_tests.filter(|min| total < *min) {
+        return Some(format!("{total} tests, expected at least {min}"));
+    }
+    let pct = gate.min_pct_of_previous?;
+    let ids = storage::list_run_ids(&state.volumes, project).await.unwrap_or_default();
+    for id in ids.into_iter().rev().filter(|id| *id < run_id) {
+        let dir = storage::run_dir(&state.volumes, project, id);
+        if storage::read_run_status(&dir).await.is_none_or(|st| st.status != Status::Success) {
+            continue;
+        }
+        let Some(prev) = storage::read_run_info(&dir).await.stats else { continue };
+        let min = (prev.total as f64 * pct / 100.0).ceil() as usize;
+        return (total < min).then(|| format!("{total} tests, expected at least {min} ({pct}% of run #{id})"));
+    }
+    None
+}
+
+/// Parses the run's results and stores what we derive from them in run_info.json.
+async fn analyze_run(
+    state: &AppState,
+    project: &str,
+    run_id: u64,
+    ps: &ProjectSettings,
+    generation: Generation,
+    prepared: Outcome,
+    external_url: Option<String>,
+) -> anyhow::Result<()> {
+    let run_dir = storage::run_dir(&state.volumes, project, run_id);
+    let results = results::latest_attempts(results::read_results(&run_dir.join("allure-results")).await?);
+    // обрезка и редактирование необратимы: при регенерации к прошлой
+    // статистике добавляется только новое
+    let prev = storage::read_run_info(&run_dir).await;
+
+    let report_size = check_report_size(state, project, run_id, ps).await;
+
+    let mut info = storage::RunInfo {
+        stats: Some(results::stats(&results)),
+        health_score: analytics::health_score(&results, &ps.health),
+        remote_execution: generation.remote,
+        resources: generation.usage,
+        trimmed_attachments: prev.trimmed_attachments.add(prepared.trimmed),
+        redactions: prev.redactions + prepared.redactions,
+        external_url,
+        report_size,
+        ..Default::default()
+    };
+
+    if let Some(jira_settings) = &ps.jira {
+        info.issues = jira::extract_issues(jira_settings, &results)?;
+
+        info.jira_commented = prev.jira_commented;
+        let keys: Vec<String> = info
+            .issues
+            .iter()
+            .map(|i| i.key.clone())
+            .filter(|k| !info.jira_commented.contains(k))
+            .collect();
+        if jira_settings.comment && !keys.is_empty() {
+            // регенерация не комментирует те же задачи повторно; ответа Jira
+            // не ждём под локом проекта — комментарии уходят отдельной задачей
+            info.jira_commented.extend(keys.iter().cloned());
+            let (state, jira_settings, project) = (state.clone(), jira_settings.clone(), project.to_string());
+            tokio::spawn(async move {
+                let report_url = state.run_url(&project, run_id);
+                for key in keys {
+                    if let Err(e) = jira::comment_issue(&state, &jira_settings, &key, &report_url).await {
+                        warn!(project=%project, run_id=run_id, issue=%key, error=%format!("{e:#}"), "jira comment failed");
+                    }
+                }
+            });
+        }
+    }
+
+    if let Some(stats) = &info.stats {
+        let prev_stats = previous_stats(state, project, run_id, ps.anomalies.window).await;
+        info.anomalies = analytics::anomalies(stats, &prev_stats, &ps.anomalies);
+    }
+    if !info.anomalies.is_empty() {
+        warn!(project=%project, run_id=run_id, anomalies=?info.anomalies, "run statistics look anomalous");
+        if ps.anomalies.notify && !ps.notifications.is_empty() {
+            notify_anomalies(state, project, run_id, ps, &info.anomalies).await;
+        }
+    }
+
+    if ps.muted_budget.notify && !ps.notifications.is_empty() {
+        let muted = info.stats.as_ref().map_or(0, |s| s.muted);
+        notify_muted_budget(state, project, run_id, ps, muted).await;
+    }
+
+    if ps.duration_regression.notify && !ps.notifications.is_empty() {
+        notify_duration_regressions(state, project, run_id, ps, &results).await;
+    }
+
+    storage::write_json(&run_dir.join("run_info.json"), &info).await
+}
+
+/// Measures the generated report and checks it against the project's
+/// budget; notifies when a run crosses the budget or jumps in size.
+async fn check_report_size(state: &AppState, project: &str, run_id: u64, ps: &ProjectSettings) -> Option<storage::ReportSize> {
+    let report_dir = storage::run_dir(&state.volumes, project, run_id).join("report");
+    if !report_dir.is_dir() {
+        return None;
+    }
+    let bytes = storage::dir_size(&report_dir).await.ok()?;
+
+    let prev_id = storage::list_run_ids(&state.volumes, project)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .find(|id| *id < run_id);
+    let prev = match prev_id {
+        Some(id) => storage::read_run_info(&storage::run_dir(&state.volumes, project, id)).await.report_size,
+        None => None,
+    };
+
+    let limits = &ps.report_size;
+    let prev_bytes = prev.as_ref().map(|p| p.bytes);
+    let size = storage::ReportSize {
+        bytes,
+        prev_bytes,
+        over_budget: limits.max_bytes.is_some_and(|max| bytes > max),
+        jumped: match (limits.max_growth_pct, prev_bytes) {
+            (Some(pct), Some(p)) if p > 0 => bytes as f64 > p as f64 * (1.0 + pct / 100.0),
+            _ => false,
+        },
+    };
+
+    // о превышении бюджета сообщаем один раз, при пересечении
+    let crossed = size.over_budget && !prev.is_some_and(|p| p.over_budget);
+    if limits.notify && !ps.notifications.is_empty() && (crossed || size.jumped) {
+        let subject = format!("Report size warning in {project} #{run_id}");
+        let mut text = format!("Run #{run_id} report is {}.\n", human_bytes(bytes));
+        if let (true, Some(max)) = (crossed, limits.max_bytes) {
+            text.push_str(&format!("Budget is {}.\n", human_bytes(max)));
+        }
+        if let (true, Some(p)) = (size.jumped, prev_bytes) {
+            text.push_str(&format!("Previous run: {} (+{:.0}%).\n", human_bytes(p), (bytes as f64 / p as f64 - 1.0) * 100.0));
+        }
+        text.push_str(&format!("Report: {}", state.run_url(project, run_id)));
+        if let Err(e) = notify::send(state, &ps.notifications, &subject, &text).await {
+            warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "report size notification failed");
+        }
+    }
+
+    Some(size)
+}
+
+fn human_bytes(b: u64) -> String {
+    match b {
+        b if b >= 1 << 30 => format!("{:.1} GiB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1u64 << 20) as f64),
+        b => format!("{:.1} KiB", b as f64 / 1024.0),
+    }
+}
+
+/// Stats of up to `limit` runs before `run_id`, oldest first.
+async fn previous_stats(state: &AppState, project: &str, run_id: u64, limit: usize) -> Vec<storage::RunStats> {
+    let ids = storage::list_run_ids(&state.volumes, project).await.unwrap_or_default();
+    let mut out = Vec::new();
+    for id in ids.into_iter().rev().filter(|id| *id < run_id) {
+        if out.len() >= limit {
+            break;
+        }
+        // прогоны без статистики (упавшая генерация) в базу не попадают
+        if let Some(stats) = storage::read_run_info(&storage::run_dir(&state.volumes, project, id)).await.stats {
+            out.push(stats);
+        }
+    }
+    out.reverse();
+    out
+}
+
+async fn notify_anomalies(state: &AppState, project: &str, run_id: u64, ps: &ProjectSettings, anomalies: &[storage::Anomaly]) {
+    let mut text = format!("Run #{run_id} differs from the previous runs:\n");
+    for a in anomalies {
+        let line = match a.kind {
+            storage::AnomalyKind::TestCountDrop => format!("• {} tests, usually {}\n", a.value, a.baseline),
+            storage::AnomalyKind::SkippedJump => format!("• {}% skipped, usually {}%\n", a.value, a.baseline),
+            storage::AnomalyKind::DurationDrop => {
+                format!("• took {:.1}s, usually {:.1}s\n", a.value / 1000.0, a.baseline / 1000.0)
+            }
+        };
+        text.push_str(&line);
+    }
+    text.push_str(&format!("Report: {}", state.run_url(project, run_id)));
+
+    let subject = format!("Anomalous run in {project} #{run_id}");
+    if let Err(e) = notify::send(state, &ps.notifications, &subject, &text).await {
+        warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "anomaly notification failed");
+    }
+}
+
+/// Notifies when the muted count crosses the budget (not on every run above it).
+async fn notify_muted_budget(state: &AppState, project: &str, run_id: u64, ps: &ProjectSettings, muted: usize) {
+    let Some(max) = ps.muted_budget.max_muted else { return };
+    if muted <= max {
+        return;
+    }
+
+    let prev = storage::list_run_ids(&state.volumes, project)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .find(|id| *id < run_id);
+    if let Some(prev) = prev {
+        let prev_info = storage::read_run_info(&storage::run_dir(&state.volumes, project, prev)).await;
+        if prev_info.stats.is_some_and(|s| s.muted > max) {
+            return;
+        }
+    }
+
+    let subject = format!("Muted budget exceeded in {project}");
+    let text = format!(
+        "Run #{} has {} known/muted tests, budget is {}.\nReport: {}",
+        run_id,
+        muted,
+        max,
+        state.run_url(project, run_id)
+    );
+    if let Err(e) = notify::send(state, &ps.notifications, &subject, &text).await {
+        warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "muted budget notification failed");
+    }
+}
+
+async fn notify_duration_regressions(
+    state: &AppState,
+    project: &str,
+    run_id: u64,
+    ps: &ProjectSettings,
+    head: &[results::TestResult],
+) {
+    let base = match ps.baseline_run_id.filter(|b| *b != run_id) {
+        Some(b) => Some(b),
+        None => storage::list_run_ids(&state.volumes, project)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .find(|id| *id < run_id),
+    };
+    let Some(base) = base else { return };
+
+    let base_results = results::load_run(&state.volumes, project, base).await;
+    let cmp = analytics::compare(&base_results, head, &ps.duration_regression);
+    if cmp.duration_regressions.is_empty() {
+        return;
+    }
+
+    let mut text = format!(
+        "{} test(s) got slower than in run #{} by more than {}%:\n",
+        cmp.duration_regressions.len(),
+        base,
+        ps.duration_regression.threshold_pct
+    );
+    for d in cmp.duration_regressions.iter().take(20) {
+        text.push_str(&format!(
+            "• {}: {:.1}s → {:.1}s (+{:.0}%)\n",
+            d.name,
+            d.base_ms as f64 / 1000.0,
+            d.head_ms as f64 / 1000.0,
+            d.increase_pct
+        ));
+    }
+    text.push_str(&format!("Report: {}", state.run_url(project, run_id)));
+
+    let subject = format!("Duration regressions in {project} #{run_id}");
+    if let Err(e) = notify::send(state, &ps.notifications, &subject, &text).await {
+        warn!(project=%project, run_id=run_id, error=%format!("{e:#}"), "duration regression notification failed");
+    }
+}
+
+/// Builds the allurerc for a project from its settings.
+pub fn report_config(state: &AppState, ps: &ProjectSettings) -> anyhow::Result<ReportConfig> {
+    let mut config = ReportConfig::default();
+
+    for (id, plugin) in ps.plugins.iter().filter(|(_, p)| p.enabled) {
+        let id = sanitize_name(id).ok_or_else(|| anyhow::anyhow!("invalid plugin id: {id}"))?;
+
+        let mut entry = serde_json::json!({ "options": plugin.options });
+        // плагин из PLUGINS_DIR подключаем по пути, иначе allure резолвит встроенный по id
+        if let Some(dir) = state.config.plugins_dir.as_ref().map(|d| d.join(&id)) {
+            if dir.is_dir() {
+                entry["import"] = serde_json::Value::String(dir.display().to_string());
+            }
+        }
+        config.plugins.insert(id, entry);
+    }
+
+    if let Some(lang) = &ps.language {
+        let valid = lang.len() <= 8 && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            anyhow::bail!("invalid language: {lang}");
+        }
+        // язык отчёта — опция встроенного плагина awesome
+        let awesome = config
+            .plugins
+            .entry("awesome")
+            .or_insert_with(|| serde_json::json!({ "options": {} }));
+        if let Some(options) = awesome.get_mut("options").and_then(|o| o.as_object_mut()) {
+            options
+                .entry("reportLanguage")
+                .or_insert_with(|| serde_json::Value::String(lang.clone()));
+        }
+    }
+
+    if let Some(tz) = &ps.timezone {
+        let valid = tz.len() <= 64
+            && tz.chars().all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c));
+        if !valid {
+            anyhow::bail!("invalid timezone: {tz}");
+        }
+        config.timezone = Some(tz.clone());
+    }
+
+    Ok(config)
+}
+
+async fn write_status(run_dir: &std::path::Path, result: Result<(), &str>) {
+    match result {
+        Ok(()) => storage::write_run_status(run_dir, Status::Success, None).await,
+        Err(e) => storage::write_run_status(run_dir, Status::Failed, Some(e.to_string())).await,
+    }
+}
@@ -0,0 +1,290 @@
+//! Rewrites of stored allure-results before generation.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use regex::{NoExpand, Regex};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    settings::{AttachmentSettings, RedactionSettings},
+    storage::{self, Status},
+    util::copy_dir,
+    volumes::Volumes,
+};
+
+/// Attachment extensions treated as text (console logs and the like).
+const TEXT_EXTENSIONS: &[&str] = &["txt", "log", "out", "err", "csv", "json", "xml", "html", "yaml", "yml"];
+
+/// What preprocessing changed in a run, stored in run_info.json.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Outcome {
+    pub redactions: usize,
+    pub trimmed: TrimStats,
+}
+
+/// What attachment trimming did to a run, summed over (re)generations.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrimStats {
+    pub files: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl TrimStats {
+    pub fn add(self, other: TrimStats) -> TrimStats {
+        TrimStats {
+            files: self.files + other.files,
+            bytes_before: self.bytes_before + other.bytes_before,
+            bytes_after: self.bytes_after + other.bytes_after,
+        }
+    }
+}
+
+/// Copies `report/history` of the last successful run before `run_id`
+/// into the run's `allure-results`, so the report shows trends and retries
+/// history. Results that bring a `history` of their own keep it. Returns
+/// the run the history came from.
+pub async fn carry_history(volumes: &Volumes, project: &str, run_id: u64) -> anyhow::Result<Option<u64>> {
+    let target = storage::run_dir(volumes, project, run_id).join("allure-results").join("history");
+    if target.exists() {
+        return Ok(None);
+    }
+    let ids = storage::list_run_ids(volumes, project).await?;
+    for id in ids.into_iter().rev().filter(|id| *id < run_id) {
+        let dir = storage::run_dir(volumes, project, id);
+        if storage::read_run_status(&dir).await.is_none_or(|st| st.status != Status::Success) {
+            continue;
+        }
+        let source = dir.join("report").join("history");
+        if !source.is_dir() {
+            // берём только последний успешный: история старше дала бы тренд с дырой
+            return Ok(None);
+        }
+        tokio::task::spawn_blocking(move || copy_dir(&source, &target))
+            .await
+            .context("join history copy task")??;
+        return Ok(Some(id));
+    }
+    Ok(None)
+}
+
+/// Cuts text attachments over `max_text_bytes` down to their head and tail.
+/// With `externalize` the original goes to `<run>/attachments/` (outside
+/// the report) instead of being dropped.
+pub async fn trim_attachments(run_dir: &Path, settings: &AttachmentSettings) -> anyhow::Result<TrimStats> {
+    let Some(max) = settings.max_text_bytes else {
+        return Ok(TrimStats::default());
+    };
+    let run_dir = run_dir.to_path_buf();
+    let settings = settings.clone();
+    tokio::task::spawn_blocking(move || trim_blocking(&run_dir, max, &settings))
+        .await
+        .context("join trim task")?
+}
+
+fn trim_blocking(run_dir: &Path, max: u64, settings: &AttachmentSettings) -> anyhow::Result<TrimStats> {
+    let results_dir = run_dir.join("allure-results");
+    let mut stats = TrimStats::default();
+
+    for path in text_files(&results_dir, true)? {
+        let size = std::fs::metadata(&path)?.len();
+        if size <= max || size <= 2 * settings.keep_bytes {
+            continue;
+        }
+
+        let rel = path.strip_prefix(&results_dir).context("strip prefix")?;
+        let kept_at = settings.externalize.then(|| run_dir.join("attachments").join(rel));
+        let trimmed = trim_file(&path, size, settings.keep_bytes, kept_at.as_deref(), rel)
+            .with_context(|| format!("trim {}", path.display()))?;
+
+        stats.files += 1;
+        stats.bytes_before += size;
+        stats.bytes_after += trimmed;
+    }
+
+    Ok(stats)
+}
+
+/// Text files (by extension) under `dir`, recursively; with
+/// `attachments_only` just the `*-attachment.<ext>` ones.
+fn text_files(dir: &Path, attachments_only: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let Ok(rd) = std::fs::read_dir(&current) else { continue };
+        for entry in rd {
+            let path = entry?.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let ext = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            let text = TEXT_EXTENSIONS.contains(&ext.as_str()) || (!attachments_only && ext == "properties");
+            if text && (!attachments_only || name.contains("-attachment")) {
+                out.push(path);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Replaces `path` with head + marker + tail; returns the new size.
+fn trim_file(path: &Path, size: u64, keep: u64, kept_at: Option<&Path>, rel: &Path) -> anyhow::Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+
+    // +1 байт, чтобы видеть, не режем ли UTF-8 символ посередине
+    let mut head = vec![0u8; (keep + 1).min(size) as usize];
+    file.read_exact(&mut head)?;
+    let mut head_end = keep.min(size) as usize;
+    while head_end > 0 && head_end < head.len() && head[head_end] & 0xC0 == 0x80 {
+        head_end -= 1;
+    }
+    head.truncate(head_end);
+
+    let tail_len = keep.min(size.saturating_sub(head.len() as u64));
+    let mut tail = vec![0u8; tail_len as usize];
+    file.seek(SeekFrom::Start(size - tail_len))?;
+    file.read_exact(&mut tail)?;
+    let tail_start = tail.iter().position(|b| b & 0xC0 != 0x80).unwrap_or(tail.len());
+    let tail = &tail[tail_start..];
+    drop(file);
+
+    let omitted = size - head.len() as u64 - tail.len() as u64;
+    let marker = match kept_at {
+        Some(dest) => {
+            std::fs::create_dir_all(dest.parent().context("attachment parent dir")?)?;
+            std::fs::rename(path, dest)?;
+            format!(
+                "\n\n... [{omitted} bytes omitted, full attachment kept on the server as attachments/{}] ...\n\n",
+                rel.display()
+            )
+        }
+        None => format!("\n\n... [{omitted} bytes omitted] ...\n\n"),
+    };
+
+    let tmp = path.with_extension("trim.tmp");
+    {
+        let mut out = std::fs::File::create(&tmp)?;
+        out.write_all(&head)?;
+        out.write_all(marker.as_bytes())?;
+        out.write_all(tail)?;
+    }
+    std::fs::rename(&tmp, path)?;
+
+    Ok((head.len() + marker.len() + tail.len()) as u64)
+}
+
+/// Replaces matches of the project's redaction patterns in text result
+/// files and attachments; returns the number of replacements.
+pub async fn redact(run_dir: &Path, settings: &RedactionSettings) -> anyhow::Result<usize> {
+    if settings.patterns.is_empty() {
+        return Ok(0);
+    }
+    let patterns = settings
+        .patterns
+        .iter()
+        .map(|p| Regex::new(p).with_context(|| format!("invalid redaction pattern: {p}")))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let results_dir = run_dir.join("allure-results");
+    let replacement = settings.replacement.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut total = 0;
+        for path in text_files(&results_dir, false)? {
+            let is_json = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"));
+            let n = if is_json {
+                redact_json(&path, &patterns, &replacement)
+            } else {
+                redact_lines(&path, &patterns, &replacement)
+            }
+            .with_context(|| format!("redact {}", path.display()))?;
+            total += n;
+        }
+        Ok(total)
+    })
+    .await
+    .context("join redact task")?
+}
+
+fn redact_str(s: &str, patterns: &[Regex], replacement: &str) -> Option<(String, usize)> {
+    let mut count = 0;
+    let mut out = std::borrow::Cow::Borrowed(s);
+    for re in patterns {
+        let n = re.find_iter(&out).count();
+        if n > 0 {
+            count += n;
+            out = std::borrow::Cow::Owned(re.replace_all(&out, NoExpand(replacement)).into_owned());
+        }
+    }
+    (count > 0).then(|| (out.into_owned(), count))
+}
+
+/// JSON is redacted string by string, so the file stays valid JSON.
+fn redact_json(path: &Path, patterns: &[Regex], replacement: &str) -> anyhow::Result<usize> {
+    let raw = std::fs::read(path)?;
+    let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&raw) else {
+        return redact_lines(path, patterns, replacement);
+    };
+
+    fn walk(v: &mut serde_json::Value, patterns: &[Regex], replacement: &str) -> usize {
+        match v {
+            serde_json::Value::String(s) => match redact_str(s, patterns, replacement) {
+                Some((r, n)) => {
+                    *s = r;
+                    n
+                }
+                None => 0,
+            },
+            serde_json::Value::Array(a) => a.iter_mut().map(|x| walk(x, patterns, replacement)).sum(),
+            serde_json::Value::Object(o) => o.values_mut().map(|x| walk(x, patterns, replacement)).sum(),
+            _ => 0,
+        }
+    }
+
+    let count = walk(&mut value, patterns, replacement);
+    if count > 0 {
+        let tmp = path.with_extension("redact.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&value)?)?;
+        std::fs::rename(&tmp, path)?;
+    }
+    Ok(count)
+}
+
+/// Line by line, so multi-GB logs are not read into memory; lines that are
+/// not UTF-8 are kept as is.
+fn redact_lines(path: &Path, patterns: &[Regex], replacement: &str) -> anyhow::Result<usize> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    let tmp = path.with_extension("redact.tmp");
+    let mut writer = BufWriter::new(std::fs::File::create(&tmp)?);
+
+    let mut count = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        match std::str::from_utf8(&line).ok().and_then(|l| redact_str(l, patterns, replacement)) {
+            Some((redacted, n)) => {
+                count += n;
+                writer.write_all(redacted.as_bytes())?;
+            }
+            None => writer.write_all(&line)?,
+        }
+    }
+    writer.flush()?;
+    drop(writer);
+
+    if count > 0 {
+        std::fs::rename(&tmp, path)?;
+    } else {
+        std::fs::remove_file(&tmp)?;
+    }
+    Ok(count)
+}
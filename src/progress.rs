@@ -0,0 +1,118 @@
+//! Phases of a run on its way to a report, streamed by
+//! `GET /api/v1/projects/{project}/runs/{run_id}/events`. The upload, the
+//! job queue and the generation pipeline publish into a per-run watch
+//! channel; the channel goes away once the run is done or failed, after
+//! which `status.json` tells the outcome.
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::util::now_unix;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Receiving,
+    /// The archive is fully received.
+    Received,
+    Unzipping,
+    /// Waiting for a background worker (`?async=true`).
+    Queued,
+    Generating,
+    Done,
+    Failed,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Receiving => "receiving",
+            Self::Received => "received",
+            Self::Unzipping => "unzipping",
+            Self::Queued => "queued",
+            Self::Generating => "generating",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn is_final(self) -> bool {
+        matches!(self, Self::Done | Self::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunEvent {
+    pub project: String,
+    pub run_id: u64,
+    pub phase: Phase,
+    /// Error text of a failed run.
+    pub error: Option<String>,
+    pub at: u64,
+}
+
+#[derive(Default)]
+pub struct RunProgress {
+    runs: DashMap<(String, u64), watch::Sender<RunEvent>>,
+}
+
+impl RunProgress {
+    /// A final phase reaches the current subscribers and closes the channel.
+    pub fn publish(&self, project: &str, run_id: u64, phase: Phase, error: Option<String>) {
+        let event = RunEvent { project: project.to_string(), run_id, phase, error, at: now_unix() };
+        let key = (project.to_string(), run_id);
+        if phase.is_final() {
+            if let Some((_, tx)) = self.runs.remove(&key) {
+                tx.send_replace(event);
+            }
+            return;
+        }
+        match self.runs.entry(key) {
+            Entry::Occupied(e) => {
+                e.get().send_replace(event);
+            }
+            Entry::Vacant(e) => {
+                e.insert(watch::channel(event).0);
+            }
+        }
+    }
+
+    /// None when nothing is happening to the run right now.
+    pub fn subscribe(&self, project: &str, run_id: u64) -> Option<watch::Receiver<RunEvent>> {
+        self.runs.get(&(project.to_string(), run_id)).map(|tx| tx.subscribe())
+    }
+}
+
+/// Marks the run failed when an upload ends before handing the run over
+/// to generation (bad archive, quota, client gone).
+pub struct UploadTracker<'a> {
+    progress: &'a RunProgress,
+    project: &'a str,
+    run_id: u64,
+    armed: bool,
+}
+
+impl<'a> UploadTracker<'a> {
+    pub fn new(progress: &'a RunProgress, project: &'a str, run_id: u64) -> Self {
+        Self { progress, project, run_id, armed: true }
+    }
+
+    pub fn publish(&self, phase: Phase) {
+        self.progress.publish(self.project, self.run_id, phase, None);
+    }
+
+    /// Generation (or the job queue) reports the rest.
+    pub fn hand_over(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for UploadTracker<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.progress
+                .publish(self.project, self.run_id, Phase::Failed, Some("upload failed".to_string()));
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Permanent removal of stored data on request (GDPR-style purges).
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{archive, artifacts, hold, seal, settings, state::AppState, storage, util::glob_match};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PurgeRequest {
+    /// Project name patterns (`*`, `?`); required, use `["*"]` for all.
+    pub projects: Vec<String>,
+    /// Only projects whose settings carry one of these classifications.
+    pub classifications: Vec<String>,
+    /// Upload time range, unix seconds, inclusive.
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// Attachment extensions (`log`, `png`, ...) to remove; whole runs are
+    /// removed when empty.
+    pub attachment_types: Vec<String>,
+    /// Only report what would be removed.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgedRun {
+    pub project: String,
+    pub run_id: u64,
+    /// The whole run was removed (not just attachments).
+    pub whole_run: bool,
+    pub files: usize,
+    pub bytes: u64,
+    /// Kept original archives removed with the attachments; counted in
+    /// `files` and `bytes` too.
+    pub originals: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeReport {
+    pub dry_run: bool,
+    pub runs: Vec<PurgedRun>,
+    pub files: usize,
+    pub bytes: u64,
+    /// Sealed runs whose attachments were left in place.
+    pub skipped_sealed: usize,
+    /// Runs in scope that are under legal hold; never touched.
+    pub held: Vec<HeldRun>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeldRun {
+    pub project: String,
+    pub run_id: u64,
+}
+
+pub async fn run(state: &AppState, req: &PurgeRequest) -> anyhow::Result<PurgeReport> {
+    let types: Vec<String> = req
+        .attachment_types
+        .iter()
+        .map(|t| t.trim_start_matches('.').to_ascii_lowercase())
+        .collect();
+    let mut report = PurgeReport { dry_run: req.dry_run, ..Default::default() };
+
+    for project in storage::list_projects(&state.volumes).await? {
+        if !req.projects.iter().any(|p| glob_match(p, &project)) {
+            continue;
+        }
+        let pdir = storage::project_dir(&state.volumes, &project);
+        if !req.classifications.is_empty() {
+            let class = settings::load(&pdir).await.ok().and_then(|s| s.classification);
+            if !class.is_some_and(|c| req.classifications.contains(&c)) {
+                continue;
+            }
+        }
+
+        let _guard = state.lock_project(&project, "purge").await;
+
+        for run_id in storage::list_run_ids(&state.volumes, &project).await? {
+            let run_dir = storage::run_dir(&state.volumes, &project, run_id);
+            let created = storage::run_created_at(&run_dir).await.unwrap_or(0);
+            if req.from.is_some_and(|f| created < f) || req.to.is_some_and(|t| created > t) {
+                continue;
+            }
+            if hold::run_held(&state.volumes, &project, run_id) {
+                report.held.push(HeldRun { project: project.clone(), run_id });
+                continue;
+            }
+
+            let item = if types.is_empty() {
+                let files = list_files(&run_dir).await?;
+                let bytes = storage::dir_size(&run_dir).await?;
+                if !req.dry_run {
+                    storage::delete_run(&state.volumes, &project, run_id).await?;
+                }
+                PurgedRun { project: project.clone(), run_id, whole_run: true, files: files.len(), bytes, originals: 0 }
+            } else if seal::is_sealed(&run_dir) {
+                report.skipped_sealed += 1;
+                continue;
+            } else {
+                let mut files: Vec<PathBuf> = list_files(&run_dir)
+                    .await?
+                    .into_iter()
+                    .filter(|f| is_attachment_of(&run_dir, f, &types))
+                    .collect();
+                // вложения лежат и в сохранённом исходном архиве; выборочно
+                // их оттуда не убрать, поэтому архив удаляется целиком
+                let originals: Vec<PathBuf> = archive::originals(&run_dir).into_iter().map(|(p, _)| p).collect();
+                if files.is_empty() && originals.is_empty() {
+                    continue;
+                }
+                files.retain(|f| !originals.contains(f));
+                let originals_count = originals.len();
+                files.extend(originals);
+                let mut bytes = 0;
+                for f in &files {
+                    bytes += tokio::fs::metadata(f).await.map(|m| m.len()).unwrap_or(0);
+                    if !req.dry_run {
+                        tokio::fs::remove_file(f)
+                            .await
+                            .with_context(|| format!("remove {}", f.display()))?;
+                    }
+                }
+                if !req.dry_run {
+                    // архивы в artifacts/ ещё содержат удалённые вложения
+                    artifacts::invalidate(&run_dir).await;
+                }
+                PurgedRun {
+                    project: project.clone(),
+                    run_id,
+                    whole_run: false,
+                    files: files.len(),
+                    bytes,
+                    originals: originals_count,
+                }
+            };
+
+            report.files += item.files;
+            report.bytes += item.bytes;
+            report.runs.push(item);
+        }
+    }
+
+    Ok(report)
+}
+
+async fn list_files(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let root = root.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut out = Vec::new();
+        let mut stack = vec![root];
+        while let Some(dir) = stack.pop() {
+            let Ok(rd) = std::fs::read_dir(&dir) else { continue };
+            for entry in rd {
+                let path = entry?.path();
+                if path.is_dir() {
+                    stack.push(path);
+                } else {
+                    out.push(path);
+                }
+            }
+        }
+        Ok(out)
+    })
+    .await
+    .context("join list task")?
+}
+
+/// Allure attachments: `*-attachment.*` in results, anything under an
+/// `attachments/` dir (report data, externalized logs).
+fn is_attachment_of(run_dir: &Path, file: &Path, types: &[String]) -> bool {
+    let ext = file
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    if !types.contains(&ext) {
+        return false;
+    }
+    let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    name.contains("-attachment")
+        || file
+            .strip_prefix(run_dir)
+            .map(|rel| rel.components().any(|c| c.as_os_str() == "attachments"))
+            .unwrap_or(false)
+}
@@ -0,0 +1,121 @@
+//! Per-project disk quota, checked on upload before the extracted results
+//! are moved into the project.
+
+use serde::Serialize;
+use std::path::Path;
+use tracing::info;
+
+use crate::{hold, settings, state::AppState, storage};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub max_bytes: u64,
+    /// Delete the oldest runs to make room instead of rejecting the upload.
+    pub evict_oldest: bool,
+}
+
+/// Body of the 507 response.
+#[derive(Debug, Serialize)]
+pub struct Exceeded {
+    pub error: &'static str,
+    pub project: String,
+    pub quota_bytes: u64,
+    /// Project size without the rejected upload.
+    pub used_bytes: u64,
+    /// Extracted size; the request's `Content-Length` when the upload is
+    /// refused before its archive is transferred.
+    pub upload_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Room {
+    /// Runs deleted to make room, oldest first.
+    pub evicted: Vec<u64>,
+    pub exceeded: Option<Exceeded>,
+}
+
+/// Project settings first, then `QUOTA_MAX_BYTES` / `QUOTA_EVICT_OLDEST`.
+pub async fn quota_for(state: &AppState, project: &str) -> Option<Quota> {
+    let ps = settings::load(&storage::project_dir(&state.volumes, project))
+        .await
+        .unwrap_or_default();
+    Some(Quota {
+        max_bytes: ps.quota.max_bytes.or(state.config.quota_max_bytes)?,
+        evict_oldest: ps.quota.evict_oldest.unwrap_or(state.config.quota_evict_oldest),
+    })
+}
+
+/// The project is at its quota and may not evict runs, so any upload
+/// would be refused: it is refused before its archive is transferred.
+pub async fn full(state: &AppState, project: &str, upload_bytes: u64) -> anyhow::Result<Option<Exceeded>> {
+    let Some(quota) = quota_for(state, project).await.filter(|q| !q.evict_oldest) else {
+        return Ok(None);
+    };
+    let project_dir = storage::project_dir(&state.volumes, project);
+    if !project_dir.is_dir() {
+        return Ok(None);
+    }
+    let used = storage::dir_size(&project_dir).await?;
+    Ok((used >= quota.max_bytes).then(|| Exceeded {
+        error: "Project disk quota exceeded",
+        project: project.to_string(),
+        quota_bytes: quota.max_bytes,
+        used_bytes: used,
+        upload_bytes,
+    }))
+}
+
+/// Checks that `staging` (the new run's extracted results) fits into the
+/// project's quota, evicting old runs when the project allows it. The
+/// caller holds the project lock; `run_id` is the run being uploaded.
+/// Latest is never evicted, and nothing is when evicting every other run
+/// would still not make room.
+pub async fn make_room(state: &AppState, project: &str, run_id: u64, staging: &Path) -> anyhow::Result<Room> {
+    let mut room = Room::default();
+    let Some(quota) = quota_for(state, project).await else {
+        return Ok(room);
+    };
+
+    let upload_bytes = storage::dir_size(staging).await?;
+    let project_dir = storage::project_dir(&state.volumes, project);
+    let mut used = storage::dir_size(&project_dir).await?;
+
+    if used + upload_bytes > quota.max_bytes && quota.evict_oldest {
+        // latest не вытесняем: новый прогон ещё может не сгенерироваться,
+        // и проект остался бы без рабочего отчёта
+        let latest = storage::read_latest_run_id(&project_dir).await;
+        let mut candidates = Vec::new();
+        for id in storage::list_run_ids(&state.volumes, project).await? {
+            if id == run_id || Some(id) == latest || hold::run_held(&state.volumes, project, id) {
+                continue;
+            }
+            let size = storage::dir_size(&storage::run_dir(&state.volumes, project, id)).await.unwrap_or(0);
+            candidates.push((id, size));
+        }
+        let freeable: u64 = candidates.iter().map(|&(_, size)| size).sum();
+        // не хватит даже после вытеснения всех --- ничего не удаляем зря
+        if used.saturating_sub(freeable) + upload_bytes > quota.max_bytes {
+            candidates.clear();
+        }
+        for (id, size) in candidates {
+            if used + upload_bytes <= quota.max_bytes {
+                break;
+            }
+            storage::delete_run(&state.volumes, project, id).await?;
+            info!(project=%project, run_id=id, bytes=size, "run evicted by quota");
+            used = used.saturating_sub(size);
+            room.evicted.push(id);
+        }
+    }
+
+    if used + upload_bytes > quota.max_bytes {
+        room.exceeded = Some(Exceeded {
+            error: "Project disk quota exceeded",
+            project: project.to_string(),
+            quota_bytes: quota.max_bytes,
+            used_bytes: used,
+            upload_bytes,
+        });
+    }
+    Ok(room)
+}
@@ -0,0 +1,139 @@
+//! Compacting sparse run ids (`9000..9050` left after years of retention)
+//! into a contiguous range, and rebuilding `next_run_id` from the run
+//! directories. Old ids stay reachable through the project's
+//! `run_aliases.json` (see [`crate::aliases`]); external run keys follow
+//! their runs.
+
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::info;
+
+use crate::{
+    artifacts,
+    merge::{self, MovedRun},
+    run_keys,
+    state::AppState,
+    storage,
+};
+
+pub const RUN_ALIASES_FILE: &str = "run_aliases.json";
+
+#[derive(Debug, Serialize)]
+pub struct RenumberReport {
+    pub project: String,
+    pub dry_run: bool,
+    /// Only the runs whose id changes, oldest first.
+    pub runs: Vec<MovedRun>,
+    pub latest_run_id: Option<u64>,
+    pub next_run_id: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NextRunIdReport {
+    pub project: String,
+    /// None when the file was missing or unreadable.
+    pub previous: Option<u64>,
+    pub next_run_id: u64,
+}
+
+/// Old id -> new id for the runs numbered from `start` in their current
+/// order. Fails when an id that changes would become the id of another
+/// run: its old URLs could not be told apart.
+pub fn plan(run_ids: &[u64], start: u64) -> Result<Vec<MovedRun>, String> {
+    let end = start + run_ids.len() as u64;
+    let moves: Vec<MovedRun> = run_ids
+        .iter()
+        .zip(start..end)
+        .filter(|(old, new)| *old != new)
+        .map(|(&from_run_id, to_run_id)| MovedRun { from_run_id, to_run_id })
+        .collect();
+    if let Some(clash) = moves.iter().find(|m| (start..end).contains(&m.from_run_id)) {
+        return Err(format!(
+            "run {} would be renumbered while its id goes to another run; use \"start\": {} or higher",
+            clash.from_run_id,
+            run_ids.last().map_or(start, |max| max + 1)
+        ));
+    }
+    Ok(moves)
+}
+
+pub async fn read_run_aliases(project_dir: &Path) -> anyhow::Result<BTreeMap<u64, u64>> {
+    let p = project_dir.join(RUN_ALIASES_FILE);
+    match tokio::fs::read(&p).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("parse {}", p.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("read {}", p.display())),
+    }
+}
+
+/// Moves the runs according to [`plan`]; the caller holds the project
+/// lock and has checked that nothing in the project is under legal hold.
+pub async fn apply(state: &AppState, project: &str, moves: Vec<MovedRun>, dry_run: bool) -> anyhow::Result<RenumberReport> {
+    let project_dir = storage::project_dir(&state.volumes, project);
+    let run_ids = storage::list_run_ids(&state.volumes, project).await?;
+    let renamed = |id: u64| moves.iter().find(|m| m.from_run_id == id).map_or(id, |m| m.to_run_id);
+
+    let latest_run_id = storage::read_latest_run_id(&project_dir).await.map(renamed);
+    let next_run_id = run_ids.iter().copied().map(renamed).max().map_or(1, |max| max + 1);
+    let mut report = RenumberReport { project: project.to_string(), dry_run, runs: Vec::new(), latest_run_id, next_run_id };
+    if dry_run {
+        report.runs = moves;
+        return Ok(report);
+    }
+
+    // старые адреса сохраняем до переноса: при сбое посередине по ним
+    // уже доступны перенесённые прогоны, а остальные ещё на месте
+    let mut aliases = read_run_aliases(&project_dir).await?;
+    for target in aliases.values_mut() {
+        *target = renamed(*target);
+    }
+    for m in &moves {
+        aliases.insert(m.from_run_id, m.to_run_id);
+    }
+    aliases.retain(|old, new| old != new);
+    storage::write_json(&project_dir.join(RUN_ALIASES_FILE), &aliases).await?;
+    let mut keys = run_keys::read(&project_dir).await?;
+    if !keys.is_empty() {
+        keys.values_mut().for_each(|id| *id = renamed(*id));
+        storage::write_json(&project_dir.join(run_keys::RUN_KEYS_FILE), &keys).await?;
+    }
+    let mut merged = merge::read_merged_runs(&project_dir).await?;
+    if !merged.is_empty() {
+        merged.values_mut().flat_map(|ids| ids.values_mut()).for_each(|id| *id = renamed(*id));
+        storage::write_json(&project_dir.join(merge::MERGED_RUNS_FILE), &merged).await?;
+    }
+
+    for m in moves {
+        let src = storage::run_dir(&state.volumes, project, m.from_run_id);
+        let dst = storage::run_dir(&state.volumes, project, m.to_run_id);
+        tokio::fs::rename(&src, &dst)
+            .await
+            .with_context(|| format!("move run {project}/{} to {}", m.from_run_id, m.to_run_id))?;
+        // в кеше summary.json старый номер
+        artifacts::invalidate(&dst).await;
+        report.runs.push(m);
+    }
+
+    if let Some(id) = latest_run_id {
+        storage::set_latest_run_id(&project_dir, id).await?;
+    }
+    storage::set_next_run_id(&project_dir, next_run_id).await?;
+    info!(project=%project, runs = report.runs.len(), next_run_id, "runs renumbered");
+    Ok(report)
+}
+
+/// Sets `next_run_id` past the highest run directory. It never goes down:
+/// ids of deleted runs are not handed out again.
+pub async fn rebuild_next_run_id(state: &AppState, project: &str) -> anyhow::Result<NextRunIdReport> {
+    let project_dir = storage::project_dir(&state.volumes, project);
+    let previous = storage::read_next_run_id(&project_dir).await;
+    let from_dirs = storage::list_run_ids(&state.volumes, project).await?.last().map_or(1, |max| max + 1);
+    let next_run_id = previous.unwrap_or(1).max(from_dirs);
+    if previous != Some(next_run_id) {
+        storage::set_next_run_id(&project_dir, next_run_id).await?;
+        info!(project=%project, ?previous, next_run_id, "next_run_id rebuilt");
+    }
+    Ok(NextRunIdReport { project: project.to_string(), previous, next_run_id })
+}
@@ -0,0 +1,256 @@
+//! Reading of raw allure-results (`*-result.json`).
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::{storage::RunStats, volumes::Volumes};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TestResult {
+    pub uuid: String,
+    pub history_id: Option<String>,
+    pub name: String,
+    pub full_name: Option<String>,
+    /// passed | failed | broken | skipped | unknown
+    pub status: Option<String>,
+    pub status_details: Option<StatusDetails>,
+    pub start: Option<u64>, // unix millis
+    pub stop: Option<u64>,
+    pub labels: Vec<Label>,
+    pub attachments: Vec<Attachment>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Step {
+    pub attachments: Vec<Attachment>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Attachment {
+    pub name: String,
+    /// File name in the results dir.
+    pub source: String,
+    #[serde(rename = "type")]
+    pub mime: Option<String>,
+}
+
+/// An attachment file of a run with the test that references it.
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachmentUsage {
+    pub source: String,
+    pub name: Option<String>,
+    /// None for files no result refers to (fixtures, leftovers).
+    pub test: Option<String>,
+    #[serde(rename = "type")]
+    pub mime: Option<String>,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StatusDetails {
+    pub message: Option<String>,
+    pub trace: Option<String>,
+    pub flaky: bool,
+    pub known: bool,
+    pub muted: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Label {
+    pub name: String,
+    pub value: String,
+}
+
+impl TestResult {
+    pub fn is_failure(&self) -> bool {
+        matches!(self.status.as_deref(), Some("failed") | Some("broken"))
+    }
+
+    pub fn is_flaky(&self) -> bool {
+        self.status_details.as_ref().is_some_and(|d| d.flaky)
+    }
+
+    /// Known or muted (quarantined) test.
+    pub fn is_muted(&self) -> bool {
+        self.status_details.as_ref().is_some_and(|d| d.known || d.muted)
+    }
+
+    /// Stable identity of a test across runs.
+    pub fn key(&self) -> &str {
+        self.history_id
+            .as_deref()
+            .or(self.full_name.as_deref())
+            .unwrap_or(&self.name)
+    }
+
+    /// Values of all labels with the given name.
+    pub fn labels<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.labels
+            .iter()
+            .filter(move |l| l.name == name)
+            .map(|l| l.value.as_str())
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.full_name.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn duration_ms(&self) -> Option<u64> {
+        Some(self.stop?.saturating_sub(self.start?))
+    }
+}
+
+/// Keeps only the last attempt of every test (retries share the key),
+/// which is what the report counts as well.
+pub fn latest_attempts(results: Vec<TestResult>) -> Vec<TestResult> {
+    let mut by_key: HashMap<String, TestResult> = HashMap::new();
+    for r in results {
+        match by_key.get(r.key()) {
+            Some(prev) if prev.stop >= r.stop => {}
+            _ => {
+                by_key.insert(r.key().to_string(), r);
+            }
+        }
+    }
+    let mut out: Vec<TestResult> = by_key.into_values().collect();
+    out.sort_by(|a, b| a.key().cmp(b.key()));
+    out
+}
+
+pub fn stats(results: &[TestResult]) -> RunStats {
+    let mut st = RunStats::default();
+    let (mut first_start, mut last_stop) = (u64::MAX, 0u64);
+
+    for r in results {
+        st.total += 1;
+        match r.status.as_deref() {
+            Some("passed") => st.passed += 1,
+            Some("failed") => st.failed += 1,
+            Some("broken") => st.broken += 1,
+            Some("skipped") => st.skipped += 1,
+            _ => st.unknown += 1,
+        }
+        if r.is_flaky() {
+            st.flaky += 1;
+        }
+        if r.is_muted() {
+            st.muted += 1;
+        }
+        if let (Some(a), Some(b)) = (r.start, r.stop) {
+            first_start = first_start.min(a);
+            last_stop = last_stop.max(b);
+        }
+    }
+
+    st.duration_ms = last_stop.saturating_sub(first_start.min(last_stop));
+    st
+}
+
+/// Last attempts of all tests of a stored run; empty when unreadable.
+pub async fn load_run(volumes: &Volumes, project: &str, run_id: u64) -> Vec<TestResult> {
+    let dir = crate::storage::run_dir(volumes, project, run_id).join("allure-results");
+    read_results(&dir).await.map(latest_attempts).unwrap_or_default()
+}
+
+/// Reads all `*-result.json` files under `results_dir` (recursively, since
+/// archives often keep a top-level `allure-results/` folder).
+/// Unparsable files are skipped: allure itself tolerates them as well.
+pub async fn read_results(results_dir: &Path) -> anyhow::Result<Vec<TestResult>> {
+    let dir = results_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || read_results_blocking(&dir))
+        .await
+        .context("join read results task")?
+}
+
+fn read_results_blocking(results_dir: &Path) -> anyhow::Result<Vec<TestResult>> {
+    let mut out = Vec::new();
+    let mut stack: Vec<PathBuf> = vec![results_dir.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        for ent in std::fs::read_dir(&dir).with_context(|| format!("read dir {}", dir.display()))? {
+            let ent = ent?;
+            let path = ent.path();
+            if ent.file_type()?.is_dir() {
+                stack.push(path);
+                continue;
+            }
+
+            let is_result = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.ends_with("-result.json"));
+            if !is_result {
+                continue;
+            }
+
+            if let Ok(bytes) = std::fs::read(&path) {
+                if let Ok(r) = serde_json::from_slice::<TestResult>(&bytes) {
+                    out.push(r);
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// All `*-attachment*` files under `results_dir` with their size and the
+/// test referencing them, largest first.
+pub async fn attachment_usage(results_dir: &Path) -> anyhow::Result<Vec<AttachmentUsage>> {
+    let results = read_results(results_dir).await?;
+    let mut refs: HashMap<String, (String, Attachment)> = HashMap::new();
+    for r in &results {
+        let mut stack: Vec<&[Attachment]> = vec![&r.attachments];
+        let mut steps: Vec<&Step> = r.steps.iter().collect();
+        while let Some(step) = steps.pop() {
+            stack.push(&step.attachments);
+            steps.extend(&step.steps);
+        }
+        for a in stack.into_iter().flatten() {
+            refs.entry(a.source.clone()).or_insert_with(|| (r.display_name().to_string(), a.clone()));
+        }
+    }
+
+    let dir = results_dir.to_path_buf();
+    let mut out = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<AttachmentUsage>> {
+        let mut out = Vec::new();
+        let mut stack = vec![dir];
+        while let Some(dir) = stack.pop() {
+            for ent in std::fs::read_dir(&dir).with_context(|| format!("read dir {}", dir.display()))? {
+                let ent = ent?;
+                if ent.file_type()?.is_dir() {
+                    stack.push(ent.path());
+                    continue;
+                }
+                let source = ent.file_name().to_string_lossy().into_owned();
+                if !source.contains("-attachment") {
+                    continue;
+                }
+                let size = ent.metadata()?.len();
+                let r = refs.get(&source);
+                out.push(AttachmentUsage {
+                    name: r.map(|(_, a)| a.name.clone()),
+                    test: r.map(|(t, _)| t.clone()),
+                    mime: r.and_then(|(_, a)| a.mime.clone()),
+                    source,
+                    size,
+                });
+            }
+        }
+        Ok(out)
+    })
+    .await
+    .context("join attachments task")??;
+
+    out.sort_unstable_by(|a, b| b.size.cmp(&a.size).then_with(|| a.source.cmp(&b.source)));
+    Ok(out)
+}
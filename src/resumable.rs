@@ -0,0 +1,109 @@
+//! Resumable uploads, a subset of tus: the client creates a session, then
+//! PATCHes the archive in chunks at `Upload-Offset` and finalizes it. The
+//! run is reserved on creation and the session lives in its directory —
+//! `upload.json` and the bytes received so far in `upload.part` — so an
+//! upload interrupted by a network error or a restart resumes from the
+//! last byte written to disk.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{state::AppState, storage, util::now_unix};
+
+const SESSION: &str = "upload.json";
+const PART: &str = "upload.part";
+
+/// `upload.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    /// Archive size declared on creation.
+    pub length: u64,
+    pub meta: storage::Meta,
+    pub created_at: u64,
+}
+
+/// `<run_id>-<random>`: the run is found by the id, the random part keeps
+/// other clients from writing into the session.
+pub fn parse_id(id: &str) -> Option<u64> {
+    let (run_id, secret) = id.split_once('-')?;
+    if secret.is_empty() {
+        return None;
+    }
+    run_id.parse().ok()
+}
+
+/// Reserves a run and starts an empty session in it.
+pub async fn create(state: &AppState, project: &str, length: u64, meta: storage::Meta) -> anyhow::Result<(u64, Session)> {
+    let _guard = state.lock_project(project, "upload").await;
+    storage::ensure_project_dirs(&state.volumes, project).await.context("ensure project dirs")?;
+    let project_dir = storage::project_dir(&state.volumes, project);
+    let run_id = storage::reserve_next_run_id(&project_dir).await.context("reserve next_run_id")?;
+
+    let run_dir = storage::run_dir(&state.volumes, project, run_id);
+    tokio::fs::create_dir_all(&run_dir).await.context("mkdir run_dir")?;
+    let session = Session {
+        id: format!("{run_id}-{}", uuid::Uuid::new_v4().simple()),
+        length,
+        meta,
+        created_at: now_unix(),
+    };
+    tokio::fs::File::create(run_dir.join(PART)).await.context("create upload.part")?;
+    storage::write_json(&run_dir.join(SESSION), &session).await?;
+    Ok((run_id, session))
+}
+
+/// The session with this id, if it is still open.
+pub async fn load(run_dir: &Path, id: &str) -> Option<Session> {
+    let text = tokio::fs::read_to_string(run_dir.join(SESSION)).await.ok()?;
+    let session: Session = serde_json::from_str(&text).ok()?;
+    (session.id == id).then_some(session)
+}
+
+/// The run still holds an open session (not yet taken or discarded).
+pub fn is_open(run_dir: &Path) -> bool {
+    run_dir.join(SESSION).is_file()
+}
+
+/// Bytes received so far.
+pub async fn offset(run_dir: &Path) -> u64 {
+    tokio::fs::metadata(run_dir.join(PART)).await.map(|m| m.len()).unwrap_or(0)
+}
+
+/// `upload.part` opened for appending a chunk.
+pub async fn open_part(run_dir: &Path) -> anyhow::Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .append(true)
+        .open(run_dir.join(PART))
+        .await
+        .context("open upload.part")
+}
+
+/// Moves the complete archive to `dst` and closes the session.
+pub async fn take(run_dir: &Path, dst: &Path) -> anyhow::Result<(u64, storage::Meta)> {
+    let text = tokio::fs::read_to_string(run_dir.join(SESSION)).await.context("read upload.json")?;
+    let session: Session = serde_json::from_str(&text).context("parse upload.json")?;
+    let part = run_dir.join(PART);
+    let len = tokio::fs::metadata(&part).await.context("read upload.part")?.len();
+    if len != session.length {
+        anyhow::bail!("upload incomplete: {} of {} bytes", len, session.length);
+    }
+    match tokio::fs::rename(&part, dst).await {
+        Ok(()) => {}
+        // TMP_DIR на другом диске
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            tokio::fs::copy(&part, dst).await.context("copy upload.part")?;
+        }
+        Err(e) => return Err(e).context("move upload.part"),
+    }
+    discard(run_dir).await;
+    Ok((len, session.meta))
+}
+
+/// Drops the session files, leaving the run dir.
+pub async fn discard(run_dir: &Path) {
+    for name in [PART, SESSION] {
+        let _ = tokio::fs::remove_file(run_dir.join(name)).await;
+    }
+}
@@ -0,0 +1,277 @@
+//! Background retention: periodically removes runs older than the age
+//! limit or beyond the run count limit of each project. Limits come from
+//! the project's `retention` settings, falling back to
+//! `RETENTION_MAX_AGE_DAYS` / `RETENTION_MAX_RUNS`.
+//!
+//! With `RETENTION_DRY_RUN` the sweep only logs what it would remove, and
+//! `RETENTION_REPORT` mails the same as a report on a schedule, so limits
+//! can be tuned before anything is deleted.
+
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{config::DigestSchedule, digest, hold, notify, settings, state::AppState, storage, util::now_unix};
+
+const DAY: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Limits {
+    pub max_runs: Option<usize>,
+    pub max_age_days: Option<u64>,
+}
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        self.max_runs.is_none() && self.max_age_days.is_none()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Pruned {
+    /// Oldest first.
+    pub removed: Vec<u64>,
+    /// Runs past the limits kept because of a legal hold.
+    pub held: Vec<u64>,
+    pub bytes: u64,
+}
+
+pub fn spawn(state: AppState) {
+    if !state.config.retention_enabled {
+        return;
+    }
+    info!(
+        max_age_days = ?state.config.retention_max_age_days,
+        max_runs = ?state.config.retention_max_runs,
+        interval_secs = state.config.retention_interval_secs,
+        dry_run = state.config.retention_dry_run,
+        "retention enabled"
+    );
+
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(state.config.retention_interval_secs.max(60));
+        loop {
+            sweep(&state).await;
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Project settings first, then the service-wide defaults.
+pub async fn limits_for(state: &AppState, project: &str) -> Limits {
+    let ps = settings::load(&storage::project_dir(&state.volumes, project))
+        .await
+        .unwrap_or_default();
+    Limits {
+        max_runs: ps.retention.max_runs.or(state.config.retention_max_runs),
+        max_age_days: ps.retention.max_age_days.or(state.config.retention_max_age_days),
+    }
+}
+
+async fn sweep(state: &AppState) {
+    if state.config.retention_dry_run {
+        let report = dry_run(state).await;
+        for p in &report.projects {
+            info!(project=%p.project, runs=?p.runs, bytes=p.bytes, "retention dry run: would prune");
+        }
+        return;
+    }
+    let projects = match storage::list_projects(&state.volumes).await {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(error=%e, "retention: list projects failed");
+            return;
+        }
+    };
+
+    let (mut runs, mut bytes) = (0, 0);
+    for project in projects {
+        let limits = limits_for(state, &project).await;
+        if limits.is_empty() {
+            continue;
+        }
+        let _guard = state.lock_project(&project, "retention").await;
+        match prune(state, &project, limits).await {
+            Ok(p) => {
+                runs += p.removed.len() as u64;
+                bytes += p.bytes;
+            }
+            Err(e) => warn!(project=%project, error=%format!("{e:#}"), "retention failed"),
+        }
+    }
+    state.metrics.observe_retention(runs, bytes);
+    if runs > 0 {
+        info!(runs, bytes, "retention sweep done");
+    }
+}
+
+/// Removes the project's runs past `limits`; the caller holds the project
+/// lock. The newest run, latest and runs under legal hold always stay.
+pub async fn prune(state: &AppState, project: &str, limits: Limits) -> anyhow::Result<Pruned> {
+    let planned = plan(state, project, limits).await?;
+    let mut out = Pruned { held: planned.held, ..Default::default() };
+    for run_id in planned.removed {
+        let size = storage::dir_size(&storage::run_dir(&state.volumes, project, run_id)).await.unwrap_or(0);
+        if let Err(e) = storage::delete_run(&state.volumes, project, run_id).await {
+            // удалённое до ошибки уже не вернуть — сообщаем, на чём остановились
+            anyhow::bail!("delete run {run_id}: {e:#} (removed before: {:?})", out.removed);
+        }
+        info!(project=%project, run_id=run_id, bytes=size, "run pruned");
+        out.removed.push(run_id);
+        out.bytes += size;
+    }
+    Ok(out)
+}
+
+/// What [`prune`] would remove now, with sizes; deletes nothing.
+/// `max_runs` counts runs with a report: runs older than the oldest of
+/// the newest `max_runs` reports go.
+pub async fn plan(state: &AppState, project: &str, limits: Limits) -> anyhow::Result<Pruned> {
+    let ids = storage::list_run_ids(&state.volumes, project).await?;
+    let latest = storage::read_latest_run_id(&storage::project_dir(&state.volumes, project)).await;
+    let cutoff = limits.max_age_days.map(|d| now_unix().saturating_sub(d * DAY));
+
+    // упавшие прогоны не вытесняют последние рабочие отчёты
+    let mut reports = Vec::new();
+    if limits.max_runs.is_some() {
+        for &run_id in &ids {
+            let rdir = storage::run_dir(&state.volumes, project, run_id);
+            if storage::read_run_status(&rdir).await.is_some_and(|s| s.status.has_report()) {
+                reports.push(run_id);
+            }
+        }
+    }
+    let keep_from = limits.max_runs.and_then(|max| match reports.len().checked_sub(max) {
+        None | Some(0) => None,
+        Some(over) => Some(reports.get(over).copied().unwrap_or(u64::MAX)),
+    });
+
+    let mut out = Pruned::default();
+    // самый новый прогон и latest не трогаем: у проекта всегда остаётся отчёт
+    for &run_id in ids.iter().take(ids.len().saturating_sub(1)) {
+        if Some(run_id) == latest {
+            continue;
+        }
+        let rdir = storage::run_dir(&state.volumes, project, run_id);
+        let expired = match cutoff {
+            Some(cutoff) => storage::run_created_at(&rdir).await.is_some_and(|c| c < cutoff),
+            None => false,
+        };
+        let over_count = keep_from.is_some_and(|first_kept| run_id < first_kept);
+        if !over_count && !expired {
+            continue;
+        }
+        if hold::run_held(&state.volumes, project, run_id) {
+            out.held.push(run_id);
+            continue;
+        }
+
+        out.removed.push(run_id);
+        out.bytes += storage::dir_size(&rdir).await.unwrap_or(0);
+    }
+    Ok(out)
+}
+
+/// Runs one project would lose to retention.
+#[derive(Debug, Serialize)]
+pub struct ProjectPlan {
+    pub project: String,
+    pub limits: Limits,
+    /// Oldest first.
+    pub runs: Vec<u64>,
+    /// Runs past the limits kept because of a legal hold.
+    pub held: Vec<u64>,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DryRunReport {
+    pub generated_at: u64,
+    /// Whether the background sweep deletes (false) or only reports.
+    pub dry_run_mode: bool,
+    pub runs: usize,
+    pub bytes: u64,
+    /// Projects that would lose runs, most space first.
+    pub projects: Vec<ProjectPlan>,
+}
+
+/// What retention would remove across all projects right now.
+pub async fn dry_run(state: &AppState) -> DryRunReport {
+    let projects = storage::list_projects(&state.volumes).await.unwrap_or_else(|e| {
+        warn!(error=%e, "retention dry run: list projects failed");
+        Vec::new()
+    });
+    let mut plans = Vec::new();
+    for project in projects {
+        let limits = limits_for(state, &project).await;
+        if limits.is_empty() {
+            continue;
+        }
+        match plan(state, &project, limits).await {
+            Ok(p) if !p.removed.is_empty() || !p.held.is_empty() => plans.push(ProjectPlan {
+                project,
+                limits,
+                runs: p.removed,
+                held: p.held,
+                bytes: p.bytes,
+            }),
+            Ok(_) => {}
+            Err(e) => warn!(project=%project, error=%format!("{e:#}"), "retention dry run failed"),
+        }
+    }
+    plans.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.project.cmp(&b.project)));
+    DryRunReport {
+        generated_at: now_unix(),
+        dry_run_mode: state.config.retention_dry_run,
+        runs: plans.iter().map(|p| p.runs.len()).sum(),
+        bytes: plans.iter().map(|p| p.bytes).sum(),
+        projects: plans,
+    }
+}
+
+/// Sends the dry-run report to the org-wide `NOTIFY_*` targets on the
+/// `RETENTION_REPORT` schedule, at `DIGEST_HOUR`.
+pub fn spawn_report(state: AppState) {
+    let schedule = state.config.retention_report;
+    if schedule == DigestSchedule::Off {
+        return;
+    }
+    if state.config.notify.is_empty() {
+        warn!("RETENTION_REPORT is set but no NOTIFY_* target is configured");
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            let wait = digest::secs_until_next(now_unix(), schedule, state.config.digest_hour);
+            info!(?schedule, in_secs = wait, "next retention report scheduled");
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+
+            let report = dry_run(&state).await;
+            let text = report_text(&report);
+            if let Err(e) = notify::send(&state, &state.config.notify, "Allure retention report", &text).await {
+                warn!(error=%format!("{e:#}"), "retention report: send failed");
+            }
+        }
+    });
+}
+
+fn report_text(report: &DryRunReport) -> String {
+    let verb = if report.dry_run_mode { "would remove" } else { "will remove" };
+    if report.projects.is_empty() {
+        return format!("Retention {verb} nothing with the current limits.");
+    }
+    let mut text = format!(
+        "Retention {verb} {} run(s), {:.1} MiB:\n",
+        report.runs,
+        report.bytes as f64 / (1u64 << 20) as f64
+    );
+    for p in &report.projects {
+        text.push_str(&format!("• {}: {} run(s), {:.1} MiB", p.project, p.runs.len(), p.bytes as f64 / (1u64 << 20) as f64));
+        if !p.held.is_empty() {
+            text.push_str(&format!(", {} held", p.held.len()));
+        }
+        text.push('\n');
+    }
+    text
+}
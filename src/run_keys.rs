@@ -0,0 +1,70 @@
+//! External run keys: a CI build number (or any id of the uploader's
+//! choosing) sent as `meta.run_key` names the run next to its internal
+//! id. The project's `run_keys.json` maps key -> run id, and
+//! `/ui/{project}/builds/{key}/...` and `/api/v1/projects/{project}/builds/{key}/...`
+//! are served as the run's `/runs/{id}/...` (see [`crate::aliases`]).
+//!
+//! A key names one run at a time. A key whose run is gone or ended
+//! without a report may be taken by a new upload, so a CI job can retry
+//! with the same build number.
+
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{storage, volumes::Volumes};
+
+pub const RUN_KEYS_FILE: &str = "run_keys.json";
+
+/// 1..=64 ASCII letters, digits, `-`, `_` or `.`, not only dots.
+pub fn validate_key(key: &str) -> Result<(), &'static str> {
+    let valid = (1..=64).contains(&key.len())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !key.chars().all(|c| c == '.');
+    if !valid {
+        return Err("Invalid run_key: 1-64 ASCII letters, digits, '-', '_' or '.'");
+    }
+    Ok(())
+}
+
+pub async fn read(project_dir: &Path) -> anyhow::Result<BTreeMap<String, u64>> {
+    let p = project_dir.join(RUN_KEYS_FILE);
+    match tokio::fs::read(&p).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("parse {}", p.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("read {}", p.display())),
+    }
+}
+
+/// Run id of `key`, for path rewriting before routing.
+pub async fn resolve(project_dir: &Path, key: &str) -> Option<u64> {
+    read(project_dir).await.ok()?.get(key).copied()
+}
+
+/// Gives `key` to `run_id`, or returns the run that holds it. The caller
+/// holds the project lock.
+pub async fn claim(volumes: &Volumes, project: &str, key: &str, run_id: u64) -> anyhow::Result<Result<(), u64>> {
+    let project_dir = storage::project_dir(volumes, project);
+    let mut keys = read(&project_dir).await?;
+    if let Some(&holder) = keys.get(key).filter(|&&id| id != run_id) {
+        let holder_dir = storage::run_dir(volumes, project, holder);
+        let failed = storage::read_run_status(&holder_dir).await.is_some_and(|s| s.status.is_failure());
+        if holder_dir.is_dir() && !failed {
+            return Ok(Err(holder));
+        }
+    }
+    keys.insert(key.to_string(), run_id);
+    storage::write_json(&project_dir.join(RUN_KEYS_FILE), &keys).await?;
+    Ok(Ok(()))
+}
+
+/// Key of each run that has one.
+pub async fn by_run(project_dir: &Path) -> BTreeMap<u64, String> {
+    match read(project_dir).await {
+        Ok(keys) => keys.into_iter().map(|(key, id)| (id, key)).collect(),
+        Err(e) => {
+            tracing::warn!(error=%format!("{e:#}"), "run_keys.json is broken, run keys are not shown");
+            BTreeMap::new()
+        }
+    }
+}
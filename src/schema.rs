@@ -0,0 +1,408 @@
+//! JSON Schemas of the API bodies, served at `/api/schemas/{name}` for
+//! client generators and contract tests.
+//!
+//! Every described type also gets a compile-time check that destructures
+//! it with the listed fields and types, so adding, removing or retyping a
+//! field without updating its schema here breaks the build.
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::{
+    handlers::api::{
+        DeleteResp, DeleteRunResp, ErrorResp, ProjectConfigRequest, ProjectConfigResp, ProjectsSummaryResp, PruneRequest,
+        PruneResp, RegenerateResp, RunDetailResp, RunItem, RunsResp, TopAttachmentsResp, UploadAcceptedResp, UploadResp,
+    },
+    executor::{RemoteExecution, ResourceUsage},
+    jobs::{Job, JobStatus},
+    preprocess::TrimStats,
+    quota::Exceeded as QuotaExceeded,
+    results::AttachmentUsage,
+    retention::Limits,
+    settings::{ProjectLink, RetentionSettings},
+    storage::{Anomaly, AnomalyKind, DiskUsage, IssueRef, Meta, ProjectSummary, ProjectUsage, ReportSize, RunInfo, RunStats, RunUsage, RunViews, Status},
+    summary::{RunSummary, Source as SummarySource},
+    uploads::{Stage, UploadInfo},
+};
+
+/// Named schemas referenced as `#/$defs/<name>`.
+pub type Defs = BTreeMap<&'static str, Value>;
+
+pub trait JsonSchema {
+    /// May be omitted or null in the body.
+    const OPTIONAL: bool = false;
+
+    /// Inline schema or a `$ref`; named types register themselves in `defs`.
+    fn schema(defs: &mut Defs) -> Value;
+}
+
+macro_rules! primitive {
+    ($($t:ty => $schema:tt),* $(,)?) => {
+        $(impl JsonSchema for $t {
+            fn schema(_: &mut Defs) -> Value {
+                json!($schema)
+            }
+        })*
+    };
+}
+
+primitive!(
+    String => {"type": "string"},
+    &'static str => {"type": "string"},
+    bool => {"type": "boolean"},
+    u32 => {"type": "integer", "minimum": 0},
+    u64 => {"type": "integer", "minimum": 0},
+    usize => {"type": "integer", "minimum": 0},
+    f64 => {"type": "number"},
+);
+
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn schema(defs: &mut Defs) -> Value {
+        json!({"anyOf": [T::schema(defs), {"type": "null"}]})
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeMap<String, T> {
+    fn schema(defs: &mut Defs) -> Value {
+        json!({"type": "object", "additionalProperties": T::schema(defs)})
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn schema(defs: &mut Defs) -> Value {
+        json!({"type": "array", "items": T::schema(defs)})
+    }
+}
+
+/// `object!(Type { field: FieldType, renamed as "json_name": FieldType })`;
+/// a leading `#[serde(default)]` mirrors the serde attribute: no field is
+/// required. `#[default] field: FieldType` does the same for one field.
+macro_rules! object {
+    (#[serde(default)] $ty:ident { $($body:tt)* }) => {
+        object!(@impl true, $ty { $($body)* });
+    };
+    ($ty:ident { $($body:tt)* }) => {
+        object!(@impl false, $ty { $($body)* });
+    };
+    (@impl $all_default:literal, $ty:ident { $($(#[$default:ident])? $field:ident $(as $key:literal)?: $fty:ty),* $(,)? }) => {
+        impl JsonSchema for $ty {
+            fn schema(defs: &mut Defs) -> Value {
+                let name = stringify!($ty);
+                if !defs.contains_key(name) {
+                    // заглушка на случай рекурсивных типов
+                    defs.insert(name, Value::Null);
+                    let mut properties = Map::new();
+                    let mut required: Vec<&str> = Vec::new();
+                    $(
+                        let key = object!(@key $field $($key)?);
+                        properties.insert(key.to_string(), <$fty as JsonSchema>::schema(defs));
+                        if !$all_default && !object!(@default $($default)?) && !<$fty as JsonSchema>::OPTIONAL {
+                            required.push(key);
+                        }
+                    )*
+                    defs.insert(name, json!({
+                        "type": "object",
+                        "properties": properties,
+                        "required": required,
+                    }));
+                }
+                json!({"$ref": format!("#/$defs/{name}")})
+            }
+        }
+
+        const _: fn($ty) = |v| {
+            let $ty { $($field),* } = v;
+            $(let _: $fty = $field;)*
+        };
+    };
+    (@default) => { false };
+    (@default default) => { true };
+    (@key $field:ident) => { stringify!($field) };
+    (@key $field:ident $key:literal) => { $key };
+}
+
+/// Unit enum serialized as strings.
+macro_rules! string_enum {
+    ($ty:ident { $($variant:ident => $value:literal),* $(,)? }) => {
+        impl JsonSchema for $ty {
+            fn schema(defs: &mut Defs) -> Value {
+                defs.insert(stringify!($ty), json!({"type": "string", "enum": [$($value),*]}));
+                json!({"$ref": format!("#/$defs/{}", stringify!($ty))})
+            }
+        }
+
+        const _: fn($ty) = |v| match v {
+            $($ty::$variant => {})*
+        };
+    };
+}
+
+object!(Meta {
+    branch: Option<String>,
+    commit: Option<String>,
+    trigger: Option<String>,
+    started_at: Option<String>,
+    launch: Option<String>,
+    run_key: Option<String>,
+    ci: Option<String>,
+    build_url: Option<String>,
+    #[default] ci_env: BTreeMap<String, String>,
+    #[default] environment: BTreeMap<String, String>,
+    #[default] sha256: Vec<String>,
+});
+object!(UploadResp {
+    project: String,
+    run_id: u64,
+    ui_url: String,
+    latest_url: String,
+    status: Status,
+    error: Option<String>,
+    run_key: Option<String>,
+    summary: Option<RunSummary>,
+    retryable: bool,
+    evicted_runs: Vec<u64>,
+});
+object!(ErrorResp { error: String, retryable: bool });
+object!(UploadAcceptedResp {
+    project: String,
+    run_id: u64,
+    job_id: String,
+    job_url: String,
+    ui_url: String,
+    latest_url: String,
+    status: JobStatus,
+    run_key: Option<String>,
+    evicted_runs: Vec<u64>,
+});
+object!(Job {
+    id: String,
+    project: String,
+    run_id: u64,
+    status: JobStatus,
+    error: Option<String>,
+    created_at: u64,
+    started_at: Option<u64>,
+    finished_at: Option<u64>,
+    request_id: Option<String>,
+    traceparent: Option<String>,
+});
+string_enum!(JobStatus {
+    Queued => "queued",
+    Running => "running",
+    Success => "success",
+    Failed => "failed",
+});
+object!(QuotaExceeded {
+    error: &'static str,
+    project: String,
+    quota_bytes: u64,
+    used_bytes: u64,
+    upload_bytes: u64,
+});
+object!(RegenerateResp { project: String, run_id: u64, status: Status, error: Option<String>, retryable: bool });
+object!(RunsResp { project: String, runs: Vec<RunItem> });
+object!(RunItem {
+    run_id: u64,
+    status: Option<Status>,
+    error: Option<String>,
+    ui_url: String,
+    run_key: Option<String>,
+    external_url: Option<String>,
+    views: u64,
+    last_viewed_at: Option<u64>,
+    issues: Vec<IssueRef>,
+    health_score: Option<f64>,
+    summary: Option<RunSummary>,
+    deleted_at: Option<u64>,
+});
+string_enum!(Status {
+    Queued => "queued",
+    Extracting => "extracting",
+    Generating => "generating",
+    Success => "success",
+    Failed => "failed",
+    Interrupted => "interrupted",
+    Rejected => "rejected",
+    Incomplete => "incomplete",
+    Pruned => "pruned",
+});
+object!(IssueRef { key: String, url: String });
+object!(RunDetailResp {
+    project: String,
+    run_id: u64,
+    status: Option<Status>,
+    error: Option<String>,
+    ui_url: String,
+    meta: Option<Meta>,
+    info: RunInfo,
+    views: RunViews,
+    sealed: bool,
+});
+object!(#[serde(default)] RunInfo {
+    stats: Option<RunStats>,
+    health_score: Option<f64>,
+    issues: Vec<IssueRef>,
+    jira_commented: Vec<String>,
+    remote_execution: Option<RemoteExecution>,
+    resources: Option<ResourceUsage>,
+    trimmed_attachments: TrimStats,
+    redactions: usize,
+    external_url: Option<String>,
+    report_size: Option<ReportSize>,
+    anomalies: Vec<Anomaly>,
+});
+object!(Anomaly { kind: AnomalyKind, value: f64, baseline: f64 });
+string_enum!(AnomalyKind {
+    TestCountDrop => "test_count_drop",
+    SkippedJump => "skipped_jump",
+    DurationDrop => "duration_drop",
+});
+object!(#[serde(default)] RunStats {
+    total: usize,
+    passed: usize,
+    failed: usize,
+    broken: usize,
+    skipped: usize,
+    unknown: usize,
+    flaky: usize,
+    muted: usize,
+    duration_ms: u64,
+});
+object!(#[serde(default)] RunSummary {
+    total: u64,
+    passed: u64,
+    failed: u64,
+    broken: u64,
+    skipped: u64,
+    unknown: u64,
+    duration_ms: u64,
+    pass_rate: Option<f64>,
+    source: SummarySource,
+});
+string_enum!(SummarySource {
+    Report => "report",
+    Results => "results",
+});
+object!(RemoteExecution { backend: String, name: String, started_at: u64, finished_at: u64 });
+object!(ResourceUsage { wall_ms: u64, cpu_ms: Option<u64>, peak_rss_bytes: Option<u64> });
+object!(#[serde(default)] TrimStats { files: usize, bytes_before: u64, bytes_after: u64 });
+object!(#[serde(default)] ReportSize { bytes: u64, prev_bytes: Option<u64>, over_budget: bool, jumped: bool });
+object!(RunViews { views: u64, last_viewed_at: Option<u64> });
+object!(ProjectsSummaryResp { total_projects: usize, total_runs: usize, projects: Vec<ProjectSummary> });
+object!(ProjectSummary {
+    project: String,
+    runs_count: usize,
+    latest_run_id: Option<u64>,
+    latest_status: Option<Status>,
+    latest_error: Option<String>,
+    latest_summary: Option<RunSummary>,
+    total_views: u64,
+    last_viewed_at: Option<u64>,
+    description: Option<String>,
+    repository_url: Option<String>,
+    links: Vec<ProjectLink>,
+    storage_class: Option<String>,
+    starred: bool,
+});
+object!(ProjectLink { title: String, url: String });
+object!(DeleteResp { deleted: bool, project: String });
+object!(DeleteRunResp { deleted: bool, project: String, run_id: u64, bytes: u64, latest_run_id: Option<u64> });
+object!(PruneRequest { keep_last: Option<usize>, #[default] dry_run: bool });
+object!(PruneResp {
+    project: String,
+    removed: Vec<u64>,
+    held: Vec<u64>,
+    bytes: u64,
+    latest_run_id: Option<u64>,
+    dry_run: bool,
+});
+object!(#[serde(default)] ProjectConfigRequest { retention: RetentionSettings });
+object!(ProjectConfigResp { project: String, retention: RetentionSettings, effective: Limits });
+object!(#[serde(default)] RetentionSettings { max_runs: Option<usize>, max_age_days: Option<u64> });
+object!(Limits { max_runs: Option<usize>, max_age_days: Option<u64> });
+object!(UploadInfo {
+    id: String,
+    project: String,
+    run_id: Option<u64>,
+    stage: Stage,
+    bytes_total: Option<u64>,
+    bytes_received: u64,
+    bytes_to_extract: Option<u64>,
+    bytes_extracted: u64,
+    started_at: u64,
+    elapsed_secs: u64,
+});
+string_enum!(Stage {
+    WaitingLock => "waiting_lock",
+    Receiving => "receiving",
+    Extracting => "extracting",
+    Generating => "generating",
+});
+object!(DiskUsage {
+    computed_at: u64,
+    total_bytes: u64,
+    other_bytes: u64,
+    projects: Vec<ProjectUsage>,
+    largest_runs: Vec<RunUsage>,
+});
+object!(TopAttachmentsResp {
+    project: String,
+    run_id: u64,
+    total_files: usize,
+    total_bytes: u64,
+    attachments: Vec<AttachmentUsage>,
+});
+object!(AttachmentUsage {
+    source: String,
+    name: Option<String>,
+    test: Option<String>,
+    mime as "type": Option<String>,
+    size: u64,
+});
+object!(ProjectUsage { project: String, bytes: u64, runs_count: usize });
+object!(RunUsage { project: String, run_id: u64, bytes: u64 });
+
+type SchemaFn = fn(&mut Defs) -> Value;
+
+/// Bodies with a schema of their own, by name.
+const SCHEMAS: &[(&str, SchemaFn)] = &[
+    ("Meta", Meta::schema),
+    ("UploadResp", UploadResp::schema),
+    ("UploadAcceptedResp", UploadAcceptedResp::schema),
+    ("ErrorResp", ErrorResp::schema),
+    ("Job", Job::schema),
+    ("QuotaExceeded", QuotaExceeded::schema),
+    ("RegenerateResp", RegenerateResp::schema),
+    ("RunsResp", RunsResp::schema),
+    ("RunDetailResp", RunDetailResp::schema),
+    ("ProjectsSummaryResp", ProjectsSummaryResp::schema),
+    ("DeleteResp", DeleteResp::schema),
+    ("DeleteRunResp", DeleteRunResp::schema),
+    ("PruneRequest", PruneRequest::schema),
+    ("PruneResp", PruneResp::schema),
+    ("ProjectConfigRequest", ProjectConfigRequest::schema),
+    ("ProjectConfigResp", ProjectConfigResp::schema),
+    ("UploadInfo", UploadInfo::schema),
+    ("DiskUsage", DiskUsage::schema),
+    ("TopAttachmentsResp", TopAttachmentsResp::schema),
+];
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    SCHEMAS.iter().map(|(name, _)| *name)
+}
+
+/// Standalone document: the body as the root plus all types it uses.
+pub fn document(name: &str) -> Option<Value> {
+    let (name, schema) = SCHEMAS.iter().find(|(n, _)| *n == name)?;
+    let mut defs = Defs::new();
+    let root = schema(&mut defs);
+    Some(json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("/api/schemas/{name}"),
+        "title": name,
+        "$ref": root["$ref"],
+        "$defs": defs,
+    }))
+}
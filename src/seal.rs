@@ -0,0 +1,144 @@
+//! Sealed runs: after generation the results and the report are hashed into
+//! `manifest.json`; the run can no longer be regenerated or trimmed, and
+//! the manifest can be re-checked against the files on disk.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::{storage, util::now_unix};
+
+/// Hashed parts of a run, relative to the run dir.
+const SEALED_DIRS: [&str; 2] = ["allure-results", "report"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub algorithm: String,
+    pub sealed_at: u64,
+    /// sha256 over the sorted `"<sha256>  <path>\n"` lines of `files`.
+    pub root: String,
+    /// Relative path (`/`-separated) -> sha256.
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Verification {
+    pub ok: bool,
+    pub expected_root: String,
+    pub actual_root: String,
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    pub added: Vec<String>,
+}
+
+fn manifest_path(run_dir: &Path) -> PathBuf {
+    run_dir.join("manifest.json")
+}
+
+pub fn is_sealed(run_dir: &Path) -> bool {
+    manifest_path(run_dir).is_file()
+}
+
+pub async fn read_manifest(run_dir: &Path) -> anyhow::Result<Option<Manifest>> {
+    let p = manifest_path(run_dir);
+    match tokio::fs::read(&p).await {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes).with_context(|| format!("parse {}", p.display()))?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("read {}", p.display())),
+    }
+}
+
+/// Hashes the run and writes its manifest.
+pub async fn seal(run_dir: &Path) -> anyhow::Result<Manifest> {
+    let files = hash_run(run_dir.to_path_buf()).await?;
+    let manifest = Manifest {
+        algorithm: "sha256".into(),
+        sealed_at: now_unix(),
+        root: root_of(&files),
+        files,
+    };
+    storage::write_json(&manifest_path(run_dir), &manifest).await?;
+    Ok(manifest)
+}
+
+/// Re-hashes the run and compares it with the manifest.
+pub async fn verify(run_dir: &Path, manifest: &Manifest) -> anyhow::Result<Verification> {
+    let actual = hash_run(run_dir.to_path_buf()).await?;
+
+    let mut modified = Vec::new();
+    let mut missing = Vec::new();
+    for (path, expected) in &manifest.files {
+        match actual.get(path) {
+            Some(h) if h == expected => {}
+            Some(_) => modified.push(path.clone()),
+            None => missing.push(path.clone()),
+        }
+    }
+    let added: Vec<String> = actual.keys().filter(|p| !manifest.files.contains_key(*p)).cloned().collect();
+    let actual_root = root_of(&actual);
+
+    Ok(Verification {
+        // корень сверяем отдельно: правка самого manifest.json тоже должна всплыть
+        ok: modified.is_empty() && missing.is_empty() && added.is_empty() && actual_root == manifest.root,
+        expected_root: manifest.root.clone(),
+        actual_root,
+        modified,
+        missing,
+        added,
+    })
+}
+
+fn root_of(files: &BTreeMap<String, String>) -> String {
+    let mut hasher = Sha256::new();
+    for (path, hash) in files {
+        hasher.update(format!("{hash}  {path}\n").as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+async fn hash_run(run_dir: PathBuf) -> anyhow::Result<BTreeMap<String, String>> {
+    tokio::task::spawn_blocking(move || {
+        let mut out = BTreeMap::new();
+        for part in SEALED_DIRS {
+            let mut stack = vec![run_dir.join(part)];
+            while let Some(dir) = stack.pop() {
+                let Ok(rd) = std::fs::read_dir(&dir) else { continue };
+                for entry in rd {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        stack.push(path);
+                        continue;
+                    }
+                    let rel = path
+                        .strip_prefix(&run_dir)
+                        .unwrap_or(&path)
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    out.insert(rel, hash_file(&path)?);
+                }
+            }
+        }
+        Ok(out)
+    })
+    .await
+    .context("join hash task")?
+}
+
+fn hash_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,332 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::fs;
+
+use crate::{hooks::HookSettings, notify::NotifyTargets};
+
+/// Per-project settings, stored in `projects/<project>/project.json`.
+/// All fields have defaults, so a missing file or missing keys are fine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    /// Short text shown on the dashboard card.
+    pub description: Option<String>,
+    /// Where the project's code lives.
+    pub repository_url: Option<String>,
+    /// Extra links (docs, runbooks) shown on the dashboard card.
+    pub links: Vec<ProjectLink>,
+    /// Data classification, e.g. `public`, `internal`, `pii`; purges can
+    /// target projects by it.
+    pub classification: Option<String>,
+    /// Report UI language, e.g. `en`, `ru`, `de`.
+    pub language: Option<String>,
+    /// IANA timezone for the generator process, e.g. `Europe/Berlin`.
+    pub timezone: Option<String>,
+    pub hooks: HookSettings,
+    /// Allure report plugins by id: built-in ones (e.g. `awesome`) or
+    /// directories under `PLUGINS_DIR`.
+    pub plugins: BTreeMap<String, PluginSettings>,
+    pub jira: Option<JiraSettings>,
+    /// Targets for this project's notifications.
+    pub notifications: NotifyTargets,
+    /// Send the scheduled digest of this project to `notifications`.
+    pub digest: bool,
+    /// Run to compare against by default instead of the previous one.
+    pub baseline_run_id: Option<u64>,
+    pub duration_regression: DurationRegressionSettings,
+    pub anomalies: AnomalySettings,
+    pub completeness: CompletenessSettings,
+    pub health: HealthSettings,
+    pub muted_budget: MutedBudgetSettings,
+    pub report_size: ReportSizeSettings,
+    /// Overrides `RETENTION_MAX_RUNS` / `RETENTION_MAX_AGE_DAYS`.
+    pub retention: RetentionSettings,
+    /// Overrides `QUOTA_MAX_BYTES` / `QUOTA_EVICT_OLDEST`.
+    pub quota: QuotaSettings,
+    pub attachments: AttachmentSettings,
+    pub redaction: RedactionSettings,
+    pub categories: CategorySettings,
+    pub embed: EmbedSettings,
+    /// What `/ui/{project}/` opens.
+    pub landing: Landing,
+    /// Copy generated reports to an object bucket.
+    pub sync: Option<SyncSettings>,
+    /// Hash each run into `manifest.json` after generation and refuse
+    /// later changes to it.
+    pub seal: bool,
+    /// Named bundle of retention, quota, sync and attachment settings, see
+    /// [`crate::storage_classes`].
+    pub storage_class: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncSettings {
+    /// `s3://bucket/prefix` or `gs://bucket/prefix`; reports go to
+    /// `<target>/<project>/<run_id>/`.
+    pub target: String,
+    /// Public URL of `target` (CDN / website endpoint); recorded per run.
+    #[serde(default)]
+    pub public_url: Option<String>,
+}
+
+/// Embedding reports into other sites' iframes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbedSettings {
+    /// CSP `frame-ancestors` sources besides the service itself, e.g.
+    /// `https://portal.example.com`.
+    pub frame_ancestors: Vec<String>,
+}
+
+/// Project home page behavior.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Landing {
+    /// The runs list.
+    #[default]
+    Runs,
+    /// Redirect to the latest report.
+    Latest,
+    /// The projects dashboard filtered to this project.
+    Dashboard,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectLink {
+    pub title: String,
+    pub url: String,
+}
+
+/// Defect categories added to every run of the project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CategorySettings {
+    /// Built-in presets: `junit`, `pytest`, `selenium`.
+    pub presets: Vec<String>,
+}
+
+/// Secrets scrubbed from stored results before generation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionSettings {
+    /// Regexes; every match is replaced with `replacement`.
+    pub patterns: Vec<String>,
+    pub replacement: String,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            replacement: "***".into(),
+        }
+    }
+}
+
+/// Trimming of oversized text attachments (console logs) before generation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttachmentSettings {
+    /// Text attachments above this size are cut; off when unset.
+    pub max_text_bytes: Option<u64>,
+    /// Bytes kept from the start and from the end of a cut attachment.
+    pub keep_bytes: u64,
+    /// Keep the full file in `<run>/attachments/` instead of dropping the middle.
+    pub externalize: bool,
+}
+
+impl Default for AttachmentSettings {
+    fn default() -> Self {
+        Self {
+            max_text_bytes: None,
+            keep_bytes: 64 * 1024,
+            externalize: false,
+        }
+    }
+}
+
+/// Limit on known/muted tests, so quarantine does not grow unnoticed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MutedBudgetSettings {
+    pub max_muted: Option<usize>,
+    /// Notify `notifications` when a run goes over the budget.
+    pub notify: bool,
+}
+
+/// How many runs the project keeps, see `retention`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    pub max_runs: Option<usize>,
+    pub max_age_days: Option<u64>,
+}
+
+/// Disk quota of the project, checked on upload.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaSettings {
+    pub max_bytes: Option<u64>,
+    /// Delete the oldest runs instead of rejecting uploads over the quota.
+    pub evict_oldest: Option<bool>,
+}
+
+/// Limits on the generated report size; oversized reports usually mean
+/// videos or dumps attached to every test.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportSizeSettings {
+    pub max_bytes: Option<u64>,
+    /// Flag a run whose report grew by more than this many percent over
+    /// the previous run.
+    pub max_growth_pct: Option<f64>,
+    /// Notify `notifications` when a run goes over the budget or jumps.
+    pub notify: bool,
+}
+
+/// Severity-weighted health score and its quality gate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthSettings {
+    /// Weight per Allure severity; tests without severity count as `normal`.
+    pub weights: BTreeMap<String, f64>,
+    /// Gate threshold (0..=100); no gate when unset.
+    pub min_score: Option<f64>,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        let weights = [
+            ("blocker", 8.0),
+            ("critical", 4.0),
+            ("normal", 2.0),
+            ("minor", 1.0),
+            ("trivial", 0.5),
+        ];
+        Self {
+            weights: weights.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            min_score: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DurationRegressionSettings {
+    /// A test is flagged when it got slower by more than this many percent.
+    pub threshold_pct: f64,
+    /// Tests faster than this (in head) are never flagged.
+    pub min_duration_ms: u64,
+    /// Notify `notifications` about regressions after each generation.
+    pub notify: bool,
+}
+
+impl Default for DurationRegressionSettings {
+    fn default() -> Self {
+        Self {
+            threshold_pct: 50.0,
+            min_duration_ms: 1000,
+            notify: false,
+        }
+    }
+}
+
+/// Checks of each run's counts against the median of the previous runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalySettings {
+    /// Previous runs the baseline is taken from; 0 turns the checks off.
+    pub window: usize,
+    /// Flag a run whose test count fell by more than this many percent.
+    pub test_drop_pct: f64,
+    /// Flag a run whose skipped share rose by more than this many points.
+    pub skipped_jump_pct: f64,
+    /// Flag a run whose duration fell by more than this many percent.
+    pub duration_drop_pct: f64,
+    /// Notify `notifications` about flagged runs.
+    pub notify: bool,
+}
+
+impl Default for AnomalySettings {
+    fn default() -> Self {
+        Self {
+            window: 10,
+            test_drop_pct: 30.0,
+            skipped_jump_pct: 20.0,
+            duration_drop_pct: 50.0,
+            notify: false,
+        }
+    }
+}
+
+/// A run with fewer tests than expected is marked `incomplete` and does
+/// not become `latest`; no gate when both are unset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompletenessSettings {
+    /// Expected minimum number of tests.
+    pub min_tests: Option<usize>,
+    /// Minimum share of the previous complete run's tests, in percent.
+    pub min_pct_of_previous: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JiraSettings {
+    /// e.g. `https://jira.example.com`
+    pub base_url: String,
+    #[serde(default = "default_issue_key_regex")]
+    pub issue_key_regex: String,
+    /// Comment on referenced issues with the report link.
+    #[serde(default)]
+    pub comment: bool,
+}
+
+fn default_issue_key_regex() -> String {
+    r"\b[A-Z][A-Z0-9]+-\d+\b".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginSettings {
+    pub enabled: bool,
+    pub options: serde_json::Value,
+}
+
+impl Default for PluginSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            options: serde_json::Value::Object(Default::default()),
+        }
+    }
+}
+
+pub async fn load(project_dir: &Path) -> anyhow::Result<ProjectSettings> {
+    let p = project_dir.join("project.json");
+    let s = match fs::read_to_string(&p).await {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ProjectSettings::default()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", p.display())),
+    };
+    let mut doc: serde_json::Value = serde_json::from_str(&s).with_context(|| format!("parse {}", p.display()))?;
+    crate::storage_classes::apply(&mut doc)?;
+    serde_json::from_value(doc).with_context(|| format!("parse {}", p.display()))
+}
+
+/// Replaces one top-level section of `project.json`, keeping the other
+/// keys as written (including ones this version does not know).
+pub async fn update_section<T: Serialize>(project_dir: &Path, key: &str, value: &T) -> anyhow::Result<()> {
+    let p = project_dir.join("project.json");
+    let mut doc = match fs::read_to_string(&p).await {
+        Ok(s) => serde_json::from_str::<serde_json::Value>(&s).with_context(|| format!("parse {}", p.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => serde_json::json!({}),
+        Err(e) => return Err(e).with_context(|| format!("read {}", p.display())),
+    };
+    let Some(obj) = doc.as_object_mut() else {
+        anyhow::bail!("{} is not a JSON object", p.display());
+    };
+    obj.insert(key.to_string(), serde_json::to_value(value)?);
+    crate::storage::write_json(&p, &doc).await
+}
@@ -0,0 +1,122 @@
+//! Load shedding: while the host is short on memory or its load average
+//! is high, uploads are answered 202 and queued instead of starting
+//! another allure process, and queued generations wait for the host to
+//! recover (`SHED_MAX_LOAD`, `SHED_MIN_AVAILABLE_MB`).
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How often a deferred generation looks at the host again.
+const RECHECK: Duration = Duration::from_secs(5);
+
+pub struct LoadShedder {
+    /// 1-minute load average.
+    max_load: Option<f64>,
+    /// `MemAvailable` of `/proc/meminfo`, bytes.
+    min_available: Option<u64>,
+    overloaded: AtomicBool,
+    deferred: AtomicUsize,
+    shed_uploads: AtomicU64,
+}
+
+/// Keeps `deferred` right when the waiting job is dropped.
+struct Deferred<'a>(&'a AtomicUsize);
+
+impl Drop for Deferred<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl LoadShedder {
+    pub fn new(max_load: Option<f64>, min_available_mb: Option<u64>) -> Self {
+        Self {
+            max_load,
+            min_available: min_available_mb.map(|mb| mb * 1024 * 1024),
+            overloaded: AtomicBool::new(false),
+            deferred: AtomicUsize::new(0),
+            shed_uploads: AtomicU64::new(0),
+        }
+    }
+
+    /// Why the host is overloaded, or None. Thresholds that cannot be
+    /// measured (no `/proc`) never trigger.
+    pub async fn check(&self) -> Option<String> {
+        if self.max_load.is_none() && self.min_available.is_none() {
+            return None;
+        }
+        let mut reason = None;
+        if let (Some(max), Some(load)) = (self.max_load, load_average().await) {
+            if load >= max {
+                reason = Some(format!("load average {load:.2} >= {max}"));
+            }
+        }
+        if let (Some(min), Some(available)) = (self.min_available, available_memory().await) {
+            if available < min {
+                reason = Some(format!("available memory {} MiB < {} MiB", available >> 20, min >> 20));
+            }
+        }
+
+        let was = self.overloaded.swap(reason.is_some(), Ordering::Relaxed);
+        match (&reason, was) {
+            (Some(r), false) => warn!(reason=%r, "host overloaded, shedding generations"),
+            (None, true) => info!("host recovered, generations resume"),
+            _ => {}
+        }
+        reason
+    }
+
+    /// Waits until the host is not overloaded.
+    pub async fn wait_healthy(&self) {
+        if self.check().await.is_none() {
+            return;
+        }
+        self.deferred.fetch_add(1, Ordering::Relaxed);
+        let _deferred = Deferred(&self.deferred);
+        loop {
+            tokio::time::sleep(RECHECK).await;
+            if self.check().await.is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Counts an upload queued instead of generated in place.
+    pub fn shed_upload(&self) {
+        self.shed_uploads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// As of the last check.
+    pub fn overloaded(&self) -> bool {
+        self.overloaded.load(Ordering::Relaxed)
+    }
+
+    pub fn deferred(&self) -> usize {
+        self.deferred.load(Ordering::Relaxed)
+    }
+
+    pub fn shed_uploads(&self) -> u64 {
+        self.shed_uploads.load(Ordering::Relaxed)
+    }
+}
+
+/// 1-minute load average from `/proc/loadavg`.
+async fn load_average() -> Option<f64> {
+    let text = tokio::fs::read_to_string("/proc/loadavg").await.ok()?;
+    text.split_whitespace().next()?.parse().ok()
+}
+
+/// `MemAvailable` from `/proc/meminfo`, bytes.
+async fn available_memory() -> Option<u64> {
+    let text = tokio::fs::read_to_string("/proc/meminfo").await.ok()?;
+    let kb: u64 = text
+        .lines()
+        .find_map(|l| l.strip_prefix("MemAvailable:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
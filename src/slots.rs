@@ -0,0 +1,54 @@
+//! Cap on concurrently running `allure generate` processes
+//! (`MAX_CONCURRENT_GENERATIONS`): a burst of uploads waits for a slot
+//! instead of starting a Node/JVM process each and running the container
+//! out of memory.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::info;
+
+pub struct GenerationSlots {
+    size: usize,
+    slots: Semaphore,
+    waiting: AtomicUsize,
+}
+
+/// Keeps `waiting` right when the waiting upload is aborted.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl GenerationSlots {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        Self { size, slots: Semaphore::new(size), waiting: AtomicUsize::new(0) }
+    }
+
+    /// Waits in FIFO order until fewer than `size` generations run.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        if let Ok(permit) = self.slots.try_acquire() {
+            return permit;
+        }
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let _waiting = Waiting(&self.waiting);
+        info!(running = self.size, waiting = self.waiting(), "all generation slots busy, queueing");
+        // семафор не закрывается, ошибки здесь быть не может
+        self.slots.acquire().await.expect("generation slots closed")
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn running(&self) -> usize {
+        self.size - self.slots.available_permits()
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,97 @@
+//! Content types of report attachments. `ServeDir` goes by the file
+//! extension, so a screenshot saved as `.bin` or a log as `.out` is
+//! downloaded instead of shown. Attachments are sniffed by their first
+//! bytes; an allowlist of types renders inline, anything else — HTML and
+//! SVG included, which would run script in the service's origin — is
+//! served as a download.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Bytes read for sniffing.
+const HEAD: usize = 8192;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+const TEXT: &str = "text/plain; charset=utf-8";
+
+/// Types a browser may show inline.
+const INLINE: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/avif",
+    "video/mp4",
+    "video/webm",
+    "video/ogg",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/x-wav",
+    "audio/x-flac",
+    "application/pdf",
+    "application/json",
+    "text/plain",
+    "text/csv",
+];
+
+/// Attachments of both Allure 2 and Allure 3 reports.
+pub fn is_attachment(tail: &str) -> bool {
+    tail.starts_with("data/attachments/")
+}
+
+/// Sets `Content-Type` (sniffed when the extension says nothing),
+/// `Content-Disposition` and `nosniff` on an attachment response.
+pub async fn apply(path: &Path, headers: &mut HeaderMap) {
+    let guessed = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|ct| !ct.starts_with(OCTET_STREAM))
+        .map(str::to_string);
+    let mime = match guessed {
+        Some(ct) => ct,
+        None => sniff(path).await,
+    };
+
+    let essence = mime.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let disposition = if INLINE.contains(&essence.as_str()) { "inline" } else { "attachment" };
+
+    if let Ok(v) = HeaderValue::from_str(&mime) {
+        headers.insert(header::CONTENT_TYPE, v);
+    }
+    headers.insert(header::CONTENT_DISPOSITION, HeaderValue::from_static(disposition));
+    // иначе браузер сам «угадает» HTML в том, что мы отдали как текст
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+}
+
+/// By magic bytes, then UTF-8 text, then an opaque binary.
+async fn sniff(path: &Path) -> String {
+    let mut buf = Vec::with_capacity(HEAD);
+    let read = match tokio::fs::File::open(path).await {
+        Ok(f) => f.take(HEAD as u64).read_to_end(&mut buf).await,
+        Err(e) => Err(e),
+    };
+    if read.is_err() {
+        return OCTET_STREAM.to_string();
+    }
+
+    if let Some(kind) = infer::get(&buf) {
+        return kind.mime_type().to_string();
+    }
+    if is_text(&buf) {
+        return TEXT.to_string();
+    }
+    OCTET_STREAM.to_string()
+}
+
+/// UTF-8 without NULs; the head may cut a multibyte character.
+fn is_text(buf: &[u8]) -> bool {
+    if buf.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
@@ -1,28 +1,109 @@
-use dashmap::DashMap;
 use std::{path::PathBuf, sync::Arc};
 use tokio::sync::Mutex;
 
+use crate::{
+    aliases::Aliases,
+    allure::{Flavor, Generator},
+    breaker::Breaker,
+    config::Config,
+    locks::{ProjectGuard, ProjectLocks},
+    iosched::IoScheduler,
+    jobs::JobQueue,
+    metrics::Metrics,
+    progress::RunProgress,
+    shed::LoadShedder,
+    slots::GenerationSlots,
+    storage::DiskUsage,
+    uploads::UploadRegistry,
+    volumes::Volumes,
+    worker::WorkQueue,
+};
+
 #[derive(Clone)]
 pub struct AppState {
+    pub config: Arc<Config>,
     pub data_dir: PathBuf,
-    pub allure_bin: String,
+    /// Where each project lives; resolves every project path.
+    pub volumes: Arc<Volumes>,
+    pub generator: Arc<Generator>,
+    pub breaker: Arc<Breaker>,
+    pub io_scheduler: Arc<IoScheduler>,
+    pub generation_slots: Arc<GenerationSlots>,
+    pub shedder: Arc<LoadShedder>,
+    /// Shared client for outbound HTTP (hooks).
+    pub http: reqwest::Client,
     /// Lock per project to avoid race on run_id and latest.
-    pub project_locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
+    pub project_locks: Arc<ProjectLocks>,
+    /// Serializes read-modify-write of views.json (kept apart from project
+    /// locks so viewing a report never waits for a running generation).
+    pub views_lock: Arc<Mutex<()>>,
+    pub favorites_lock: Arc<Mutex<()>>,
+    /// Generations waiting for remote workers (`WORKER_TOKEN`).
+    pub work_queue: Arc<WorkQueue>,
+    pub uploads: Arc<UploadRegistry>,
+    /// Generations of `?async=true` uploads.
+    pub jobs: Arc<JobQueue>,
+    /// Phases of runs being uploaded or generated.
+    pub progress: Arc<RunProgress>,
+    pub metrics: Arc<Metrics>,
+    /// Names left behind by project merges.
+    pub aliases: Arc<Aliases>,
+    /// Last `storage::disk_usage` result for `GET /api/v1/stats`; the lock
+    /// also keeps concurrent requests from walking the volume twice.
+    pub disk_usage: Arc<Mutex<Option<DiskUsage>>>,
+    /// Set when the startup check failed with `STARTUP_CHECK=read-only`;
+    /// write routes answer 503 with this reason.
+    pub read_only: Option<Arc<str>>,
 }
 
 impl AppState {
-    pub fn new(data_dir: PathBuf, allure_bin: String) -> Self {
+    pub fn new(config: Config, allure_flavor: Flavor) -> Self {
+        crate::storage_classes::init(&config.data_dir);
         Self {
-            data_dir,
-            allure_bin,
-            project_locks: Arc::new(DashMap::new()),
+            data_dir: config.data_dir.clone(),
+            volumes: Arc::new(Volumes::new(&config.data_dir, &config.data_volumes)),
+            aliases: Arc::new(Aliases::load(&config.data_dir)),
+            generator: Arc::new(Generator {
+                bin: config.allure_bin.clone(),
+                flavor: allure_flavor,
+                backend: config.generator_backend.clone(),
+                ionice: config.generator_ionice,
+                timeout: std::time::Duration::from_secs(config.generator_timeout_secs.max(1)),
+                warm_cache: config
+                    .generator_warm_start
+                    .then(|| config.data_dir.join("cache").join("allure")),
+            }),
+            breaker: Arc::new(Breaker::new(config.breaker_threshold)),
+            io_scheduler: Arc::new(IoScheduler::new(config.io_pressure_threshold)),
+            generation_slots: Arc::new(GenerationSlots::new(config.max_concurrent_generations)),
+            shedder: Arc::new(LoadShedder::new(config.shed_max_load, config.shed_min_available_mb)),
+            http: reqwest::Client::new(),
+            config: Arc::new(config),
+            project_locks: Arc::new(ProjectLocks::default()),
+            views_lock: Arc::new(Mutex::new(())),
+            favorites_lock: Arc::new(Mutex::new(())),
+            work_queue: Arc::new(WorkQueue::default()),
+            uploads: Arc::new(UploadRegistry::default()),
+            jobs: Arc::new(JobQueue::default()),
+            progress: Arc::new(RunProgress::default()),
+            metrics: Arc::new(Metrics::default()),
+            disk_usage: Arc::new(Mutex::new(None)),
+            read_only: None,
         }
     }
 
-    pub fn project_lock(&self, project: &str) -> Arc<Mutex<()>> {
-        self.project_locks
-            .entry(project.to_string())
-            .or_insert_with(|| Arc::new(Mutex::new(())))
-            .clone()
+    /// Report URL of a run, absolute when `PUBLIC_URL` is configured.
+    pub fn run_url(&self, project: &str, run_id: u64) -> String {
+        format!(
+            "{}/ui/{}/runs/{}/",
+            self.config.public_url.as_deref().unwrap_or(""),
+            project,
+            run_id
+        )
+    }
+
+    /// `purpose` is shown by `GET /api/v1/admin/locks`.
+    pub async fn lock_project(&self, project: &str, purpose: &'static str) -> ProjectGuard {
+        self.project_locks.lock(project, purpose).await
     }
 }
@@ -1,27 +1,34 @@
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use tokio::{fs, io::AsyncWriteExt};
+use tracing::warn;
 
-pub fn project_dir(data_dir: &Path, project: &str) -> PathBuf {
-    data_dir.join("projects").join(project)
+use crate::{audit, settings, summary, volumes::Volumes};
+
+/// On the project's data volume (see [`crate::volumes`]).
+pub fn project_dir(volumes: &Volumes, project: &str) -> PathBuf {
+    volumes.project_root(project).join("projects").join(project)
 }
 
-pub fn runs_dir(data_dir: &Path, project: &str) -> PathBuf {
-    project_dir(data_dir, project).join("runs")
+pub fn runs_dir(volumes: &Volumes, project: &str) -> PathBuf {
+    project_dir(volumes, project).join("runs")
 }
 
-pub fn run_dir(data_dir: &Path, project: &str, run_id: u64) -> PathBuf {
-    runs_dir(data_dir, project).join(run_id.to_string())
+pub fn run_dir(volumes: &Volumes, project: &str, run_id: u64) -> PathBuf {
+    runs_dir(volumes, project).join(run_id.to_string())
 }
 
-pub async fn ensure_project_dirs(data_dir: &Path, project: &str) -> anyhow::Result<()> {
-    let runs = runs_dir(data_dir, project);
+pub async fn ensure_project_dirs(volumes: &Volumes, project: &str) -> anyhow::Result<()> {
+    let runs = runs_dir(volumes, project);
     fs::create_dir_all(&runs).await.context("create runs dir")?;
+    volumes.remember(project);
     Ok(())
 }
 
 pub async fn reserve_next_run_id(project_dir: &Path) -> anyhow::Result<u64> {
+    let _lock = lock_counter(project_dir).await?;
     let p = project_dir.join("next_run_id");
 
     let current: u64 = match fs::read_to_string(&p).await {
@@ -39,6 +46,53 @@ pub async fn reserve_next_run_id(project_dir: &Path) -> anyhow::Result<u64> {
     Ok(current)
 }
 
+/// Cross-process lock of `next_run_id`: while a binary upgrade drains,
+/// the old and the new process both take uploads. The project lock
+/// covers the rest within one process.
+#[cfg(unix)]
+async fn lock_counter(project_dir: &Path) -> anyhow::Result<std::fs::File> {
+    use std::os::fd::AsRawFd;
+
+    let path = project_dir.join("next_run_id.lock");
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("lock next_run_id");
+        }
+        Ok(file)
+    })
+    .await
+    .context("join lock task")?
+}
+
+#[cfg(not(unix))]
+async fn lock_counter(_project_dir: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+pub async fn read_next_run_id(project_dir: &Path) -> Option<u64> {
+    let s = fs::read_to_string(project_dir.join("next_run_id")).await.ok()?;
+    s.trim().parse::<u64>().ok()
+}
+
+pub async fn set_next_run_id(project_dir: &Path, run_id: u64) -> anyhow::Result<()> {
+    let p = project_dir.join("next_run_id");
+    let tmp = project_dir.join("next_run_id.tmp");
+
+    let mut f = fs::File::create(&tmp).await?;
+    f.write_all(run_id.to_string().as_bytes()).await?;
+    f.flush().await?;
+    drop(f);
+
+    fs::rename(&tmp, &p).await?;
+    Ok(())
+}
+
 pub async fn set_latest_run_id(project_dir: &Path, run_id: u64) -> anyhow::Result<()> {
     let p = project_dir.join("latest_run_id");
     let tmp = project_dir.join("latest_run_id.tmp");
@@ -71,47 +125,283 @@ pub async fn write_json<T: Serialize>(path: &Path, v: &T) -> anyhow::Result<()>
     Ok(())
 }
 
-pub async fn list_projects(data_dir: &Path) -> anyhow::Result<Vec<String>> {
-    let root = data_dir.join("projects");
+pub async fn list_projects(volumes: &Volumes) -> anyhow::Result<Vec<String>> {
     let mut out = Vec::new();
 
-    let mut rd = match fs::read_dir(&root).await {
-        Ok(r) => r,
-        Err(e) => {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                return Ok(out);
+    for root in volumes.roots() {
+        let mut rd = match fs::read_dir(root.join("projects")).await {
+            Ok(r) => r,
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    continue;
+                }
+                return Err(e.into());
             }
-            return Err(e.into());
-        }
-    };
-
-    while let Some(ent) = rd.next_entry().await? {
-        let ft = ent.file_type().await?;
-        if ft.is_dir() {
-            if let Some(name) = ent.file_name().to_str() {
-                out.push(name.to_string());
+        };
+
+        while let Some(ent) = rd.next_entry().await? {
+            let ft = ent.file_type().await?;
+            if ft.is_dir() {
+                if let Some(name) = ent.file_name().to_str() {
+                    // на других томах бывают скрытые каталоги незаконченного переноса
+                    if !name.starts_with('.') {
+                        out.push(name.to_string());
+                    }
+                }
             }
         }
     }
 
     out.sort();
+    out.dedup();
     Ok(out)
 }
 
+/// Upload metadata (`meta.json`), as sent by the client.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct Meta {
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    pub trigger: Option<String>,
+    pub started_at: Option<String>,
+    /// Cross-project launch id (e.g. a release train).
+    pub launch: Option<String>,
+    /// External key of the run, e.g. the CI build number; unique per project.
+    pub run_key: Option<String>,
+    /// CI the run came from: `github`, `gitlab` or `jenkins`.
+    pub ci: Option<String>,
+    /// Link of the CI job in the report's Executors widget; wins over
+    /// the one built from `ci_env`.
+    pub build_url: Option<String>,
+    /// Standard variables of that CI (see `ci::Provider::variables`).
+    #[serde(default)]
+    pub ci_env: BTreeMap<String, String>,
+    /// Entries of the report's Environment widget (`environment.properties`).
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+    /// SHA-256 of each uploaded archive, computed by the service.
+    #[serde(default)]
+    pub sha256: Vec<String>,
+}
+
+pub async fn read_run_meta(run_dir: &Path) -> Option<Meta> {
+    let p = run_dir.join("meta.json");
+    let s = fs::read_to_string(&p).await.ok()?;
+    serde_json::from_str::<Meta>(&s).ok()
+}
+
+/// Where a run is on its way to a report; `status` of `status.json` and
+/// of the API, serialized in snake_case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Waiting for a background generation job.
+    Queued,
+    Extracting,
+    Generating,
+    Success,
+    Failed,
+    /// The upload was aborted, or a restart cut the run short.
+    Interrupted,
+    /// The archive was refused: broken, unsafe or over a limit.
+    Rejected,
+    /// Generated, but below the project's `completeness` gate.
+    Incomplete,
+    /// Deleted since; only seen in `as_of` listings.
+    Pruned,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Extracting => "extracting",
+            Self::Generating => "generating",
+            Self::Success => "success",
+            Self::Failed => "failed",
+            Self::Interrupted => "interrupted",
+            Self::Rejected => "rejected",
+            Self::Incomplete => "incomplete",
+            Self::Pruned => "pruned",
+        }
+    }
+
+    /// The run will not change on its own any more.
+    pub fn is_final(self) -> bool {
+        !matches!(self, Self::Queued | Self::Extracting | Self::Generating)
+    }
+
+    /// Ended without a report.
+    pub fn is_failure(self) -> bool {
+        matches!(self, Self::Failed | Self::Interrupted | Self::Rejected)
+    }
+
+    /// The run has a generated report.
+    pub fn has_report(self) -> bool {
+        matches!(self, Self::Success | Self::Incomplete)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunStatus {
-    pub status: String,            // "success" | "failed"
+    pub status: Status,
+    /// Why the run failed or is incomplete.
     pub error: Option<String>,
 }
 
+impl RunStatus {
+    pub fn new(status: Status, error: Option<String>) -> Self {
+        Self { status, error }
+    }
+
+    /// Older releases wrote `failed` for refused archives and aborted
+    /// uploads too; they are told apart by the error text they left.
+    fn migrated(mut self) -> Self {
+        if self.status == Status::Failed {
+            match self.error.as_deref() {
+                // до поддержки tar.gz/zstd отказ писался как "bad zip: ..."
+                Some(e) if e.starts_with("bad archive:") || e.starts_with("bad zip:") => self.status = Status::Rejected,
+                Some("upload aborted") => self.status = Status::Interrupted,
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
 pub async fn read_run_status(run_dir: &Path) -> Option<RunStatus> {
     let p = run_dir.join("status.json");
     let s = fs::read_to_string(&p).await.ok()?;
-    serde_json::from_str::<RunStatus>(&s).ok()
+    serde_json::from_str::<RunStatus>(&s).ok().map(RunStatus::migrated)
+}
+
+pub async fn write_run_status(run_dir: &Path, status: Status, error: Option<String>) {
+    if let Err(e) = write_json(&run_dir.join("status.json"), &RunStatus::new(status, error)).await {
+        warn!(run_dir=%run_dir.display(), error=%e, "write status.json failed");
+    }
+}
+
+/// Data derived from a run's results after generation (`run_info.json`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunInfo {
+    pub stats: Option<RunStats>,
+    /// Severity-weighted pass rate, see `analytics::health_score`.
+    pub health_score: Option<f64>,
+    /// Jira issues referenced from failure messages.
+    pub issues: Vec<IssueRef>,
+    /// Issues already given a comment with this run's report link;
+    /// regenerating the run does not comment them again.
+    pub jira_commented: Vec<String>,
+    /// Container/job that generated the report (remote generator backends).
+    pub remote_execution: Option<crate::executor::RemoteExecution>,
+    /// Wall/CPU time and peak RSS of `allure generate`.
+    pub resources: Option<crate::executor::ResourceUsage>,
+    /// Oversized text attachments cut before generation.
+    pub trimmed_attachments: crate::preprocess::TrimStats,
+    /// Secrets replaced by the project's redaction patterns.
+    pub redactions: usize,
+    /// Report copy in the project's sync bucket.
+    pub external_url: Option<String>,
+    pub report_size: Option<ReportSize>,
+    /// Counts far off the project's recent runs, see `analytics::anomalies`.
+    pub anomalies: Vec<Anomaly>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    /// The run's value: tests, percent skipped or milliseconds.
+    pub value: f64,
+    /// Median of the same value over the previous runs.
+    pub baseline: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    TestCountDrop,
+    SkippedJump,
+    DurationDrop,
+}
+
+/// Size of the generated report against the project's `report_size` limits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportSize {
+    pub bytes: u64,
+    /// Report size of the previous run.
+    pub prev_bytes: Option<u64>,
+    pub over_budget: bool,
+    /// Grew by more than `max_growth_pct` since the previous run.
+    pub jumped: bool,
+}
+
+/// Test counts of a run (last attempt of every test).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunStats {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub broken: usize,
+    pub skipped: usize,
+    pub unknown: usize,
+    pub flaky: usize,
+    /// Known/muted (quarantined) tests.
+    pub muted: usize,
+    /// Wall time from the first test start to the last test stop.
+    pub duration_ms: u64,
+}
+
+impl RunStats {
+    /// passed / (total - skipped), in percent.
+    pub fn pass_rate(&self) -> Option<f64> {
+        let executed = self.total.saturating_sub(self.skipped);
+        (executed > 0).then(|| self.passed as f64 * 100.0 / executed as f64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueRef {
+    pub key: String,
+    pub url: String,
+}
+
+pub async fn read_run_info(run_dir: &Path) -> RunInfo {
+    let p = run_dir.join("run_info.json");
+    match fs::read_to_string(&p).await {
+        Ok(s) => serde_json::from_str::<RunInfo>(&s).unwrap_or_default(),
+        Err(_) => RunInfo::default(),
+    }
+}
+
+/// Report view counters of a run. Only a counter and a timestamp are kept,
+/// no client addresses or other identifying data.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunViews {
+    pub views: u64,
+    pub last_viewed_at: Option<u64>, // unix seconds
+}
+
+pub async fn read_run_views(run_dir: &Path) -> RunViews {
+    let p = run_dir.join("views.json");
+    match fs::read_to_string(&p).await {
+        Ok(s) => serde_json::from_str::<RunViews>(&s).unwrap_or_default(),
+        Err(_) => RunViews::default(),
+    }
+}
+
+pub async fn record_run_view(run_dir: &Path) -> anyhow::Result<RunViews> {
+    let mut v = read_run_views(run_dir).await;
+    v.views += 1;
+    v.last_viewed_at = Some(crate::util::now_unix());
+    write_json(&run_dir.join("views.json"), &v).await?;
+    Ok(v)
 }
 
-pub async fn list_run_ids(data_dir: &Path, project: &str) -> anyhow::Result<Vec<u64>> {
-    let runs_root = runs_dir(data_dir, project);
+pub async fn list_run_ids(volumes: &Volumes, project: &str) -> anyhow::Result<Vec<u64>> {
+    let runs_root = runs_dir(volumes, project);
     let mut out = Vec::new();
 
     let mut rd = match fs::read_dir(&runs_root).await {
@@ -139,54 +429,336 @@ pub async fn list_run_ids(data_dir: &Path, project: &str) -> anyhow::Result<Vec<
     Ok(out)
 }
 
+/// Runs tagged with the given launch id, grouped by project (sorted).
+pub async fn list_launch_runs(
+    volumes: &Volumes,
+    launch: &str,
+) -> anyhow::Result<Vec<(String, Vec<u64>)>> {
+    let mut out = Vec::new();
+
+    for project in list_projects(volumes).await? {
+        let mut ids = Vec::new();
+        for id in list_run_ids(volumes, &project).await? {
+            let meta = read_run_meta(&run_dir(volumes, &project, id)).await;
+            if meta.and_then(|m| m.launch).as_deref() == Some(launch) {
+                ids.push(id);
+            }
+        }
+        if !ids.is_empty() {
+            out.push((project, ids));
+        }
+    }
+
+    Ok(out)
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ProjectSummary {
     pub project: String,
     pub runs_count: usize,
     pub latest_run_id: Option<u64>,
-    pub latest_status: Option<String>, // "success" | "failed"
+    pub latest_status: Option<Status>,
     pub latest_error: Option<String>,
+    /// Test counts of the latest run's report.
+    pub latest_summary: Option<summary::RunSummary>,
+    pub total_views: u64,
+    pub last_viewed_at: Option<u64>,
+    /// From the project settings.
+    pub description: Option<String>,
+    pub repository_url: Option<String>,
+    pub links: Vec<settings::ProjectLink>,
+    pub storage_class: Option<String>,
+    /// Starred by the requesting user; filled in by the API.
+    pub starred: bool,
 }
 
-pub async fn project_summary(data_dir: &Path, project: &str) -> anyhow::Result<ProjectSummary> {
-    let pdir = project_dir(data_dir, project);
+pub async fn project_summary(volumes: &Volumes, project: &str) -> anyhow::Result<ProjectSummary> {
+    let pdir = project_dir(volumes, project);
     let latest = read_latest_run_id(&pdir).await;
 
-    let run_ids = list_run_ids(data_dir, project).await?;
+    let run_ids = list_run_ids(volumes, project).await?;
     let runs_count = run_ids.len();
 
-    let (latest_status, latest_error) = if let Some(id) = latest {
-        let rdir = run_dir(data_dir, project, id);
-        if let Some(st) = read_run_status(&rdir).await {
-            (Some(st.status), st.error)
-        } else {
-            (None, None)
-        }
+    let mut total_views = 0;
+    let mut last_viewed_at: Option<u64> = None;
+    for id in &run_ids {
+        let v = read_run_views(&run_dir(volumes, project, *id)).await;
+        total_views += v.views;
+        last_viewed_at = last_viewed_at.max(v.last_viewed_at);
+    }
+
+    let (latest_status, latest_error, latest_summary) = if let Some(id) = latest {
+        let rdir = run_dir(volumes, project, id);
+        let (status, error) = match read_run_status(&rdir).await {
+            Some(st) => (Some(st.status), st.error),
+            None => (None, None),
+        };
+        (status, error, summary::read(&rdir).await)
     } else {
-        (None, None)
+        (None, None, None)
     };
 
+    // битый project.json не должен ронять дашборд
+    let settings = settings::load(&pdir).await.unwrap_or_default();
+
     Ok(ProjectSummary {
         project: project.to_string(),
         runs_count,
         latest_run_id: latest,
         latest_status,
         latest_error,
+        latest_summary,
+        total_views,
+        last_viewed_at,
+        description: settings.description,
+        repository_url: settings.repository_url,
+        links: settings.links,
+        storage_class: settings.storage_class,
+        starred: false,
     })
 }
 
-pub async fn list_project_summaries(data_dir: &Path) -> anyhow::Result<Vec<ProjectSummary>> {
-    let projects = list_projects(data_dir).await?;
+pub async fn list_project_summaries(volumes: &Volumes) -> anyhow::Result<Vec<ProjectSummary>> {
+    let projects = list_projects(volumes).await?;
     let mut out = Vec::with_capacity(projects.len());
     for p in projects {
-        out.push(project_summary(data_dir, &p).await?);
+        out.push(project_summary(volumes, &p).await?);
     }
     out.sort_by(|a, b| a.project.cmp(&b.project));
     Ok(out)
 }
 
-pub async fn delete_project(data_dir: &Path, project: &str) -> anyhow::Result<()> {
-    let pdir = project_dir(data_dir, project);
+/// Total size in bytes of regular files under `root` (0 if it does not exist).
+pub async fn dir_size(root: &Path) -> anyhow::Result<u64> {
+    let mut total: u64 = 0;
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let mut rd = match fs::read_dir(&dir).await {
+            Ok(r) => r,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("read dir {}", dir.display())),
+        };
+
+        while let Some(ent) = rd.next_entry().await? {
+            let ft = ent.file_type().await?;
+            if ft.is_dir() {
+                stack.push(ent.path());
+            } else if ft.is_file() {
+                total += ent.metadata().await?.len();
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+/// Disk usage of the data volume, as served by `GET /api/v1/stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskUsage {
+    pub computed_at: u64,
+    pub total_bytes: u64,
+    /// Everything outside `projects/` (tmp, caches, audit log).
+    pub other_bytes: u64,
+    /// Largest first.
+    pub projects: Vec<ProjectUsage>,
+    /// Largest first, across all projects.
+    pub largest_runs: Vec<RunUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectUsage {
+    pub project: String,
+    pub bytes: u64,
+    pub runs_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunUsage {
+    pub project: String,
+    pub run_id: u64,
+    pub bytes: u64,
+}
+
+/// Walks the whole data dir once; keeps the `top_runs` largest runs.
+pub async fn disk_usage(volumes: &Volumes, top_runs: usize) -> anyhow::Result<DiskUsage> {
+    let mut projects = Vec::new();
+    let mut runs = Vec::new();
+    for project in list_projects(volumes).await? {
+        let pdir = project_dir(volumes, &project);
+        let mut bytes = dir_size_except(&pdir, "runs").await?;
+        let ids = list_run_ids(volumes, &project).await?;
+        for &run_id in &ids {
+            let size = dir_size(&run_dir(volumes, &project, run_id)).await?;
+            bytes += size;
+            runs.push(RunUsage { project: project.clone(), run_id, bytes: size });
+        }
+        projects.push(ProjectUsage { project, bytes, runs_count: ids.len() });
+    }
+
+    let other_bytes = dir_size_except(volumes.data_dir(), "projects").await?;
+    projects.sort_unstable_by_key(|p| std::cmp::Reverse(p.bytes));
+    runs.sort_unstable_by_key(|r| std::cmp::Reverse(r.bytes));
+    runs.truncate(top_runs);
+    Ok(DiskUsage {
+        computed_at: crate::util::now_unix(),
+        total_bytes: other_bytes + projects.iter().map(|p| p.bytes).sum::<u64>(),
+        other_bytes,
+        projects,
+        largest_runs: runs,
+    })
+}
+
+/// Like [`dir_size`], without the top-level entry `skip`.
+async fn dir_size_except(root: &Path, skip: &str) -> anyhow::Result<u64> {
+    let mut rd = match fs::read_dir(root).await {
+        Ok(r) => r,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e).with_context(|| format!("read dir {}", root.display())),
+    };
+    let mut total = 0;
+    while let Some(ent) = rd.next_entry().await? {
+        if ent.file_name() == skip {
+            continue;
+        }
+        let ft = ent.file_type().await?;
+        if ft.is_dir() {
+            total += dir_size(&ent.path()).await?;
+        } else if ft.is_file() {
+            total += ent.metadata().await?.len();
+        }
+    }
+    Ok(total)
+}
+
+fn mtime_unix(md: &std::fs::Metadata) -> Option<u64> {
+    md.modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// Upload time of a run: mtime of meta.json (written once on upload and
+/// never touched by regeneration), falling back to the run dir.
+pub async fn run_created_at(run_dir: &Path) -> Option<u64> {
+    for p in [run_dir.join("meta.json"), run_dir.to_path_buf()] {
+        if let Ok(md) = fs::metadata(&p).await {
+            return mtime_unix(&md);
+        }
+    }
+    None
+}
+
+/// Time of the last upload into the project: creation of the newest run,
+/// or mtime of the project dir itself when there are no runs yet.
+pub async fn last_upload_at(volumes: &Volumes, project: &str) -> anyhow::Result<Option<u64>> {
+    let run_ids = list_run_ids(volumes, project).await?;
+    Ok(match run_ids.last() {
+        Some(id) => run_created_at(&run_dir(volumes, project, *id)).await,
+        None => fs::metadata(project_dir(volumes, project))
+            .await
+            .ok()
+            .and_then(|md| mtime_unix(&md)),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleProject {
+    pub project: String,
+    pub runs_count: usize,
+    pub last_upload_at: Option<u64>,
+    pub idle_days: u64,
+    pub disk_bytes: u64,
+}
+
+/// Projects with no uploads for at least `min_idle_days`, most idle first.
+pub async fn list_stale_projects(
+    volumes: &Volumes,
+    min_idle_days: u64,
+) -> anyhow::Result<Vec<StaleProject>> {
+    let now = crate::util::now_unix();
+    let mut out = Vec::new();
+
+    for p in list_projects(volumes).await? {
+        let last = last_upload_at(volumes, &p).await?;
+        let idle_days = now.saturating_sub(last.unwrap_or(0)) / 86_400;
+        if idle_days < min_idle_days {
+            continue;
+        }
+
+        out.push(StaleProject {
+            runs_count: list_run_ids(volumes, &p).await?.len(),
+            last_upload_at: last,
+            idle_days,
+            disk_bytes: dir_size(&project_dir(volumes, &p)).await?,
+            project: p,
+        });
+    }
+
+    out.sort_by(|a, b| b.idle_days.cmp(&a.idle_days).then_with(|| a.project.cmp(&b.project)));
+    Ok(out)
+}
+
+/// A deleted run as recorded in the audit log; lets `as_of` listings
+/// show runs that existed back then.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub project: String,
+    pub run_id: u64,
+    pub created_at: Option<u64>,
+}
+
+/// Newest run with a finished report (status `success`): the only kind of
+/// run latest may point at.
+pub async fn newest_successful_run(volumes: &Volumes, project: &str) -> anyhow::Result<Option<u64>> {
+    for id in list_run_ids(volumes, project).await?.into_iter().rev() {
+        if read_run_status(&run_dir(volumes, project, id)).await.is_some_and(|s| s.status == Status::Success) {
+            return Ok(Some(id));
+        }
+    }
+    Ok(None)
+}
+
+/// Removes a run; when it was the latest one, latest moves to the newest
+/// remaining successful run (or is cleared).
+pub async fn delete_run(volumes: &Volumes, project: &str, run_id: u64) -> anyhow::Result<()> {
+    let rdir = run_dir(volumes, project, run_id);
+    if rdir.is_dir() {
+        // надгробие пишем до удаления: без записи прогон пропал бы из истории
+        let tomb = Tombstone { project: project.to_string(), run_id, created_at: run_created_at(&rdir).await };
+        audit::record(volumes.data_dir(), "run_deleted", serde_json::json!(tomb)).await?;
+    }
+    match fs::remove_dir_all(&rdir).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let pdir = project_dir(volumes, project);
+    if read_latest_run_id(&pdir).await == Some(run_id) {
+        // упавший, отклонённый или ещё загружаемый прогон отчёта не имеет
+        match newest_successful_run(volumes, project).await? {
+            Some(id) => set_latest_run_id(&pdir, id).await?,
+            None => {
+                let _ = fs::remove_file(pdir.join("latest_run_id")).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn delete_project(volumes: &Volumes, project: &str) -> anyhow::Result<()> {
+    let pdir = project_dir(volumes, project);
+    let mut runs = Vec::new();
+    for run_id in list_run_ids(volumes, project).await? {
+        let created_at = run_created_at(&run_dir(volumes, project, run_id)).await;
+        runs.push(Tombstone { project: project.to_string(), run_id, created_at });
+    }
+    if !runs.is_empty() {
+        audit::record(volumes.data_dir(), "project_deleted", serde_json::json!({ "project": project, "runs": runs })).await?;
+    }
+    volumes.forget(project);
     match fs::remove_dir_all(&pdir).await {
         Ok(_) => Ok(()),
         Err(e) => {
@@ -0,0 +1,100 @@
+//! Named storage classes (`DATA_DIR/storage_classes.json`), e.g. `hot-30d`
+//! or `archive-1y`: retention, quota, bucket sync and attachment trimming
+//! bundled under one name. A project opts in with `storage_class` in its
+//! `project.json`; every section the project does not set itself comes
+//! from the class, so changing a class changes all of its projects.
+//!
+//! The classes are applied in [`crate::settings::load`], so the rest of
+//! the service only ever sees the effective settings.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use tracing::warn;
+
+use crate::{
+    settings::{AttachmentSettings, QuotaSettings, RetentionSettings, SyncSettings},
+    storage,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageClass {
+    pub description: Option<String>,
+    pub retention: Option<RetentionSettings>,
+    pub quota: Option<QuotaSettings>,
+    pub sync: Option<SyncSettings>,
+    pub attachments: Option<AttachmentSettings>,
+}
+
+static CLASSES: OnceLock<RwLock<BTreeMap<String, StorageClass>>> = OnceLock::new();
+
+fn path(data_dir: &Path) -> PathBuf {
+    data_dir.join("storage_classes.json")
+}
+
+fn classes() -> &'static RwLock<BTreeMap<String, StorageClass>> {
+    CLASSES.get_or_init(Default::default)
+}
+
+/// Startup only; a broken file is logged and ignored.
+pub fn init(data_dir: &Path) {
+    let map = match std::fs::read(path(data_dir)) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!(error=%e, "storage_classes.json is broken, storage classes are ignored");
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    };
+    *classes().write().unwrap() = map;
+}
+
+pub fn list() -> BTreeMap<String, StorageClass> {
+    classes().read().unwrap().clone()
+}
+
+pub fn exists(name: &str) -> bool {
+    classes().read().unwrap().contains_key(name)
+}
+
+/// Defines `name`, or removes it with None. Projects still naming a
+/// removed class keep only their own settings.
+pub async fn set(data_dir: &Path, name: &str, class: Option<StorageClass>) -> anyhow::Result<()> {
+    let snapshot = {
+        let mut map = classes().write().unwrap();
+        match class {
+            Some(c) => map.insert(name.to_string(), c),
+            None => map.remove(name),
+        };
+        map.clone()
+    };
+    storage::write_json(&path(data_dir), &snapshot).await
+}
+
+/// Fills the sections of a `project.json` document that it leaves out
+/// from the class it names. A section the project sets wins as a whole.
+pub fn apply(doc: &mut serde_json::Value) -> anyhow::Result<()> {
+    let Some(obj) = doc.as_object_mut() else {
+        return Ok(());
+    };
+    let Some(name) = obj.get("storage_class").and_then(|v| v.as_str()).map(str::to_string) else {
+        return Ok(());
+    };
+    let Some(class) = classes().read().unwrap().get(&name).cloned() else {
+        warn!(storage_class=%name, "unknown storage class, project settings are used as they are");
+        return Ok(());
+    };
+    let sections = [
+        ("retention", class.retention.map(serde_json::to_value).transpose()?),
+        ("quota", class.quota.map(serde_json::to_value).transpose()?),
+        ("sync", class.sync.map(serde_json::to_value).transpose()?),
+        ("attachments", class.attachments.map(serde_json::to_value).transpose()?),
+    ];
+    for (key, value) in sections {
+        if let Some(value) = value {
+            obj.entry(key).or_insert(value);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! Test counts of a generated report (`report_summary.json` next to
+//! `status.json`), so a client knows whether the tests passed without
+//! scraping the HTML report. They come from the report itself --- Allure 2
+//! `widgets/summary.json`, Allure 3 `summary.json` --- and from the run's
+//! results when the report carries neither.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::storage::{self, RunStats};
+
+pub const FILE: &str = "report_summary.json";
+
+/// Summaries the report may carry, Allure 2 first.
+const REPORT_FILES: [&str; 2] = ["widgets/summary.json", "summary.json"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunSummary {
+    pub total: u64,
+    pub passed: u64,
+    pub failed: u64,
+    pub broken: u64,
+    pub skipped: u64,
+    pub unknown: u64,
+    pub duration_ms: u64,
+    /// passed / (total - skipped), in percent; None when nothing ran.
+    pub pass_rate: Option<f64>,
+    pub source: Source,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    #[default]
+    Report,
+    /// The report has no summary; counted from `allure-results`.
+    Results,
+}
+
+impl RunSummary {
+    fn with_pass_rate(mut self) -> Self {
+        let executed = self.total.saturating_sub(self.skipped);
+        self.pass_rate = (executed > 0).then(|| (self.passed as f64 * 10000.0 / executed as f64).round() / 100.0);
+        self
+    }
+
+    fn from_stats(stats: &RunStats) -> Self {
+        Self {
+            total: stats.total as u64,
+            passed: stats.passed as u64,
+            failed: stats.failed as u64,
+            broken: stats.broken as u64,
+            skipped: stats.skipped as u64,
+            unknown: stats.unknown as u64,
+            duration_ms: stats.duration_ms,
+            pass_rate: None,
+            source: Source::Results,
+        }
+        .with_pass_rate()
+    }
+
+    /// Allure 2 keeps the counts in `statistic` and the time in
+    /// `time.duration`, Allure 3 in `stats` and `duration`.
+    fn from_report(doc: &Value) -> Option<Self> {
+        let counts = doc.get("statistic").or_else(|| doc.get("stats")).filter(|c| c.is_object())?;
+        let count = |key: &str| counts.get(key).and_then(Value::as_u64).unwrap_or(0);
+        let duration_ms = doc
+            .pointer("/time/duration")
+            .or_else(|| doc.get("duration"))
+            .and_then(Value::as_f64)
+            .map_or(0, |d| d.max(0.0) as u64);
+        let mut summary = Self {
+            passed: count("passed"),
+            failed: count("failed"),
+            broken: count("broken"),
+            skipped: count("skipped"),
+            unknown: count("unknown"),
+            duration_ms,
+            pass_rate: None,
+            source: Source::Report,
+            ..Default::default()
+        };
+        summary.total = counts.get("total").and_then(Value::as_u64).unwrap_or(
+            summary.passed + summary.failed + summary.broken + summary.skipped + summary.unknown,
+        );
+        Some(summary.with_pass_rate())
+    }
+}
+
+/// Summary of the report in `report_dir`; `stats` (from the results)
+/// when the report has none.
+pub async fn of_report(report_dir: &Path, stats: Option<&RunStats>) -> Option<RunSummary> {
+    for name in REPORT_FILES {
+        let Ok(bytes) = tokio::fs::read(report_dir.join(name)).await else {
+            continue;
+        };
+        if let Some(summary) = serde_json::from_slice::<Value>(&bytes).ok().as_ref().and_then(RunSummary::from_report) {
+            return Some(summary);
+        }
+    }
+    stats.map(RunSummary::from_stats)
+}
+
+pub async fn read(run_dir: &Path) -> Option<RunSummary> {
+    let bytes = tokio::fs::read(run_dir.join(FILE)).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+pub async fn write(run_dir: &Path, summary: &RunSummary) -> anyhow::Result<()> {
+    storage::write_json(&run_dir.join(FILE), summary).await
+}
+
+/// A failed generation leaves no report to summarize.
+pub async fn remove(run_dir: &Path) {
+    let _ = tokio::fs::remove_file(run_dir.join(FILE)).await;
+}
@@ -0,0 +1,60 @@
+//! Copies generated reports to an object bucket (S3/GCS) after generation,
+//! so heavy report traffic can be served from a CDN instead of this
+//! instance. Uses the `aws` / `gsutil` CLIs and their usual credentials.
+
+use anyhow::Context;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::{settings::SyncSettings, state::AppState, storage, trace, util::clip};
+
+/// Uploads the run's report to `<target>/<project>/<run_id>/`; returns the
+/// external URL when the project has a `public_url`.
+pub async fn sync_report(
+    state: &AppState,
+    project: &str,
+    run_id: u64,
+    settings: &SyncSettings,
+) -> anyhow::Result<Option<String>> {
+    let report_dir = storage::run_dir(&state.volumes, project, run_id).join("report");
+    let target = format!("{}/{}/{}", settings.target.trim_end_matches('/'), project, run_id);
+
+    let mut cmd = if target.starts_with("s3://") {
+        let mut c = Command::new(&state.config.aws_bin);
+        c.args(["s3", "sync", "--delete", "--only-show-errors"])
+            .arg(&report_dir)
+            .arg(format!("{target}/"));
+        c
+    } else if target.starts_with("gs://") {
+        let mut c = Command::new(&state.config.gsutil_bin);
+        c.args(["-m", "-q", "rsync", "-r", "-d"]).arg(&report_dir).arg(&target);
+        c
+    } else {
+        anyhow::bail!("unsupported sync target (expected s3:// or gs://): {}", settings.target);
+    };
+    cmd.envs(trace::current().env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let child = cmd.spawn().context("spawn sync")?;
+    let timeout = Duration::from_secs(state.config.sync_timeout_secs);
+    let out = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {}s", timeout.as_secs()))?
+        .context("wait sync")?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "exit_code={} stderr={}",
+            out.status.code().unwrap_or(-1),
+            clip(&String::from_utf8_lossy(&out.stderr), 2000)
+        );
+    }
+
+    Ok(settings
+        .public_url
+        .as_deref()
+        .map(|base| format!("{}/{}/{}/index.html", base.trim_end_matches('/'), project, run_id)))
+}
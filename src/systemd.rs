@@ -0,0 +1,91 @@
+//! systemd integration for bare-metal installs: socket activation
+//! (`LISTEN_FDS`) and `sd_notify` readiness/watchdog. Everything here is a
+//! no-op when the service is not started by systemd.
+
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// First fd passed by systemd (`SD_LISTEN_FDS_START`).
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Sockets passed via socket activation or by the old process of a
+/// binary upgrade, with their `FileDescriptorName=` (empty when unnamed).
+/// Empty when not socket-activated.
+#[cfg(unix)]
+pub fn listen_fds() -> anyhow::Result<Vec<(String, std::net::TcpListener)>> {
+    use std::os::fd::FromRawFd;
+
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|p| p.parse::<u32>().ok())
+        .is_some_and(|p| p == std::process::id())
+        || crate::upgrade::parent().is_some();
+    if !for_us {
+        return Ok(Vec::new());
+    }
+    let count: i32 = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse().ok()).unwrap_or(0);
+    let names: Vec<String> = std::env::var("LISTEN_FDNAMES")
+        .map(|n| n.split(':').map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let mut out = Vec::new();
+    for i in 0..count {
+        let fd = LISTEN_FDS_START + i;
+        // systemd передаёт fd без CLOEXEC — не отдаём их процессам allure
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        listener.set_nonblocking(true)?;
+        out.push((names.get(i as usize).cloned().unwrap_or_default(), listener));
+    }
+    Ok(out)
+}
+
+#[cfg(not(unix))]
+pub fn listen_fds() -> anyhow::Result<Vec<(String, std::net::TcpListener)>> {
+    Ok(Vec::new())
+}
+
+/// Sends a state line (`READY=1`, `STOPPING=1`, ...) to `NOTIFY_SOCKET`.
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else { return };
+    let sent = UnixDatagram::unbound().and_then(|sock| match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            sock.send_to_addr(state.as_bytes(), &addr)
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => Err(std::io::Error::from(std::io::ErrorKind::Unsupported)),
+        None => sock.send_to(state.as_bytes(), &path),
+    });
+    match sent {
+        Ok(_) => debug!(state, "sd_notify sent"),
+        Err(e) => warn!(error=%e, state, "sd_notify failed"),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+/// Pings the systemd watchdog at half of `WATCHDOG_USEC`, if enabled.
+pub fn spawn_watchdog() {
+    let for_us = std::env::var("WATCHDOG_PID")
+        .ok()
+        .and_then(|p| p.parse::<u32>().ok())
+        .is_none_or(|p| p == std::process::id());
+    let usec = std::env::var("WATCHDOG_USEC").ok().and_then(|u| u.parse::<u64>().ok());
+    let (true, Some(usec)) = (for_us, usec) else { return };
+
+    let interval = Duration::from_micros(usec / 2).max(Duration::from_millis(100));
+    tokio::spawn(async move {
+        loop {
+            notify("WATCHDOG=1");
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
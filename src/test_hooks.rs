@@ -0,0 +1,67 @@
+//! Fault injection for e2e suites of CI plugins, built only with
+//! `--features test-hooks`: artificial generation delays and failures and
+//! a skewed service clock, so timeout and retry paths can be exercised
+//! deterministically.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+static GENERATION_DELAY_MS: AtomicU64 = AtomicU64::new(0);
+static FAIL_GENERATIONS: AtomicU32 = AtomicU32::new(0);
+static FAILURE_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
+static CLOCK_SKEW_SECS: AtomicI64 = AtomicI64::new(0);
+
+const DEFAULT_FAILURE: &str = "injected generation failure (test-hooks)";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Faults {
+    /// Added before every generation.
+    #[serde(default)]
+    pub generation_delay_ms: u64,
+    /// The next N generations fail with `failure_message`.
+    #[serde(default)]
+    pub fail_generations: u32,
+    #[serde(default)]
+    pub failure_message: Option<String>,
+    /// Shifts every timestamp the service takes (`util::now_unix`).
+    #[serde(default)]
+    pub clock_skew_secs: i64,
+}
+
+pub fn current() -> Faults {
+    Faults {
+        generation_delay_ms: GENERATION_DELAY_MS.load(Ordering::Relaxed),
+        fail_generations: FAIL_GENERATIONS.load(Ordering::Relaxed),
+        failure_message: FAILURE_MESSAGE.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        clock_skew_secs: CLOCK_SKEW_SECS.load(Ordering::Relaxed),
+    }
+}
+
+pub fn set(faults: Faults) {
+    GENERATION_DELAY_MS.store(faults.generation_delay_ms, Ordering::Relaxed);
+    FAIL_GENERATIONS.store(faults.fail_generations, Ordering::Relaxed);
+    *FAILURE_MESSAGE.lock().unwrap_or_else(|e| e.into_inner()) = faults.failure_message;
+    CLOCK_SKEW_SECS.store(faults.clock_skew_secs, Ordering::Relaxed);
+}
+
+pub fn clock_skew() -> i64 {
+    CLOCK_SKEW_SECS.load(Ordering::Relaxed)
+}
+
+/// Called right before a generation starts.
+pub async fn before_generation() -> anyhow::Result<()> {
+    let delay = GENERATION_DELAY_MS.load(Ordering::Relaxed);
+    if delay > 0 {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+    if FAIL_GENERATIONS
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+        .is_ok()
+    {
+        let msg = FAILURE_MESSAGE.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        anyhow::bail!("{}", msg.as_deref().unwrap_or(DEFAULT_FAILURE));
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Correlation ids of the work in progress: the `x-request-id` of the
+//! request that started it, the generation job and a W3C trace context.
+//! They live in a task-local set for every request (and every job) and go
+//! out with hooks, notifications and the processes the service starts, so
+//! a downstream consumer can tie its work back to the CI upload.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Correlation {
+    pub request_id: Option<String>,
+    pub job_id: Option<String>,
+    /// W3C `traceparent`: the caller's trace id with a span id of ours.
+    pub traceparent: Option<String>,
+}
+
+tokio::task_local! {
+    static CURRENT: Correlation;
+}
+
+impl Correlation {
+    /// HTTP headers for an outbound request.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut out = Vec::new();
+        if let Some(v) = &self.traceparent {
+            out.push(("traceparent", v.clone()));
+        }
+        if let Some(v) = &self.request_id {
+            out.push(("x-request-id", v.clone()));
+        }
+        if let Some(v) = &self.job_id {
+            out.push(("x-allure-job-id", v.clone()));
+        }
+        out
+    }
+
+    /// Environment of a child process.
+    pub fn env(&self) -> Vec<(String, String)> {
+        [("TRACEPARENT", &self.traceparent), ("REQUEST_ID", &self.request_id), ("JOB_ID", &self.job_id)]
+            .into_iter()
+            .filter_map(|(k, v)| v.clone().map(|v| (k.to_string(), v)))
+            .collect()
+    }
+}
+
+/// Ids of the current request or job; empty outside of both.
+pub fn current() -> Correlation {
+    CURRENT.try_with(Clone::clone).unwrap_or_default()
+}
+
+pub async fn scope<F: Future>(c: Correlation, f: F) -> F::Output {
+    CURRENT.scope(c, f).await
+}
+
+/// Continues the caller's trace with a span id of ours, or starts a new one.
+pub fn traceparent(incoming: Option<&str>) -> String {
+    let span = uuid::Uuid::new_v4().simple().to_string();
+    let span = &span[..16];
+    match incoming.and_then(parse) {
+        Some((trace_id, flags)) => format!("00-{trace_id}-{span}-{flags}"),
+        None => format!("00-{}-{span}-01", uuid::Uuid::new_v4().simple()),
+    }
+}
+
+/// Trace id and flags of a valid version 00 `traceparent`.
+fn parse(s: &str) -> Option<(&str, &str)> {
+    let parts: Vec<&str> = s.trim().split('-').collect();
+    let [version, trace_id, parent_id, flags] = parts[..] else {
+        return None;
+    };
+    let hex = |p: &str, len: usize| p.len() == len && p.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    let zero = |p: &str| p.bytes().all(|b| b == b'0');
+    let valid = version == "00"
+        && hex(trace_id, 32)
+        && hex(parent_id, 16)
+        && hex(flags, 2)
+        && !zero(trace_id)
+        && !zero(parent_id);
+    valid.then_some((trace_id, flags))
+}
+
+/// Middleware: every request runs with its `x-request-id` (set by the
+/// request id layer) and the caller's `traceparent` continued.
+pub async fn propagate(req: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+    let c = {
+        let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+        Correlation {
+            request_id: header("x-request-id").map(str::to_string),
+            job_id: None,
+            traceparent: Some(traceparent(header("traceparent"))),
+        }
+    };
+    scope(c, next.run(req)).await
+}
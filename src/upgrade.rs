@@ -0,0 +1,190 @@
+//! Zero-downtime binary upgrade. On SIGUSR2 the running process starts
+//! the binary at its `argv[0]` again and hands it the listening sockets
+//! the way systemd does (`LISTEN_FDS`). Once the new process is serving
+//! it says so over a pipe, and the old one stops accepting, lets report
+//! downloads and uploads in flight finish and exits. Both hold the same
+//! sockets meanwhile, so no connection is refused.
+//!
+//! A new binary that fails to start, or does not get ready within
+//! [`READY_TIMEOUT`], is killed and the old process keeps serving.
+
+use std::time::Duration;
+
+#[cfg(unix)]
+pub type Fd = std::os::fd::RawFd;
+#[cfg(not(unix))]
+pub type Fd = i32;
+
+/// How long the new process may take to start serving.
+#[cfg(unix)]
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Set for the new process: pid of the process handing over.
+const PARENT_ENV: &str = "UPGRADE_PARENT_PID";
+/// Set for the new process: fd to report readiness on.
+const READY_FD_ENV: &str = "UPGRADE_READY_FD";
+
+/// Pid of the old process when this one was started by an upgrade.
+#[cfg(unix)]
+pub fn parent() -> Option<u32> {
+    let pid: u32 = std::env::var(PARENT_ENV).ok()?.parse().ok()?;
+    // переменная могла достаться по наследству от процесса, запустившего нас
+    (pid == std::os::unix::process::parent_id()).then_some(pid)
+}
+
+#[cfg(not(unix))]
+pub fn parent() -> Option<u32> {
+    None
+}
+
+/// Tells the old process that this one serves; from now on systemd
+/// watches this pid.
+#[cfg(unix)]
+pub fn ready() {
+    use std::io::Write;
+    use std::os::fd::FromRawFd;
+
+    if parent().is_none() {
+        return;
+    }
+    let Some(fd) = std::env::var(READY_FD_ENV).ok().and_then(|v| v.parse::<i32>().ok()) else {
+        return;
+    };
+    let mut pipe = unsafe { std::fs::File::from_raw_fd(fd) };
+    if let Err(e) = pipe.write_all(b"1") {
+        tracing::warn!(error=%e, "report readiness to the old process failed");
+    }
+    crate::systemd::notify(&format!("MAINPID={}", std::process::id()));
+}
+
+#[cfg(not(unix))]
+pub fn ready() {}
+
+/// Resolves once the old process has exited. Queued generations and
+/// retention start only then, so a job is never run by both.
+#[cfg(unix)]
+pub async fn parent_exited(pid: u32) {
+    while std::os::unix::process::parent_id() == pid {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    tracing::info!(pid, "old process exited, background work starts");
+}
+
+#[cfg(not(unix))]
+pub async fn parent_exited(_pid: u32) {}
+
+/// Waits for SIGUSR2 and hands the sockets (name, fd) to a new process;
+/// resolves once it serves. A failed attempt is logged and the next
+/// signal tries again.
+#[cfg(unix)]
+pub async fn handover(sockets: Vec<(&'static str, Fd)>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    use tracing::{error, info};
+
+    let mut sig = match signal(SignalKind::user_defined2()) {
+        Ok(s) => s,
+        Err(e) => {
+            error!(error=%e, "cannot listen for SIGUSR2, binary upgrade is disabled");
+            return std::future::pending().await;
+        }
+    };
+    loop {
+        sig.recv().await;
+        info!("SIGUSR2 received, starting the new binary");
+        match spawn_successor(&sockets).await {
+            Ok(pid) => {
+                info!(pid, "new process is serving, draining connections");
+                return;
+            }
+            Err(e) => error!(error=%format!("{e:#}"), "binary upgrade failed, keep serving"),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn handover(_sockets: Vec<(&'static str, Fd)>) {
+    std::future::pending().await
+}
+
+#[cfg(unix)]
+async fn spawn_successor(sockets: &[(&'static str, Fd)]) -> anyhow::Result<u32> {
+    use anyhow::Context;
+    use std::io::Read;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    if args.is_empty() {
+        anyhow::bail!("no argv[0]");
+    }
+    let program = args.remove(0);
+
+    // дубли выше нужных номеров: dup2 в pre_exec не затрёт ещё не перенесённый сокет
+    let mut fds: Vec<OwnedFd> = Vec::with_capacity(sockets.len() + 1);
+    for (_, fd) in sockets {
+        let dup = unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, 64) };
+        if dup < 0 {
+            return Err(std::io::Error::last_os_error()).context("dup listening socket");
+        }
+        fds.push(unsafe { OwnedFd::from_raw_fd(dup) });
+    }
+    let mut pipe = [0; 2];
+    if unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(std::io::Error::last_os_error()).context("create ready pipe");
+    }
+    let (read_end, write_end) = unsafe { (std::fs::File::from_raw_fd(pipe[0]), OwnedFd::from_raw_fd(pipe[1])) };
+    let high = unsafe { libc::fcntl(write_end.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 64) };
+    if high < 0 {
+        return Err(std::io::Error::last_os_error()).context("dup ready pipe");
+    }
+    fds.push(unsafe { OwnedFd::from_raw_fd(high) });
+    drop(write_end);
+
+    let ready_fd = 3 + sockets.len() as i32;
+    let names: Vec<&str> = sockets.iter().map(|(name, _)| *name).collect();
+    let raw: Vec<i32> = fds.iter().map(|fd| fd.as_raw_fd()).collect();
+    let mut cmd = std::process::Command::new(program);
+    cmd.args(args)
+        .env(PARENT_ENV, std::process::id().to_string())
+        .env(READY_FD_ENV, ready_fd.to_string())
+        .env("LISTEN_FDS", sockets.len().to_string())
+        .env("LISTEN_FDNAMES", names.join(":"))
+        .env_remove("LISTEN_PID");
+    unsafe {
+        cmd.pre_exec(move || {
+            // dup2 снимает CLOEXEC: сокеты и pipe переживут exec под номерами 3..
+            for (i, fd) in raw.iter().enumerate() {
+                if libc::dup2(*fd, 3 + i as i32) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn().context("spawn new binary")?;
+    // свой конец pipe закрыт — EOF придёт, если новый процесс умрёт
+    drop(fds);
+    let pid = child.id();
+
+    let wait = tokio::task::spawn_blocking(move || {
+        let mut read_end = read_end;
+        let mut buf = [0u8; 1];
+        read_end.read(&mut buf).map(|n| n == 1)
+    });
+    match tokio::time::timeout(READY_TIMEOUT, wait).await {
+        Ok(Ok(Ok(true))) => {
+            // процесс больше не наш: после нашего выхода его подберёт init
+            std::thread::spawn(move || child.wait());
+            Ok(pid)
+        }
+        Ok(Ok(Ok(false))) | Ok(Ok(Err(_))) | Ok(Err(_)) => {
+            let status = child.wait().context("wait new binary")?;
+            anyhow::bail!("new process {pid} exited before serving ({status})")
+        }
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("new process {pid} did not get ready in {}s", READY_TIMEOUT.as_secs())
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! In-flight uploads, so operators can see what holds a project lock and
+//! abort a stuck one.
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::{archive, util::now_unix};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    WaitingLock,
+    Receiving,
+    Extracting,
+    Generating,
+}
+
+pub struct UploadSession {
+    pub id: String,
+    pub project: String,
+    pub started_at: u64,
+    run_id: AtomicU64,
+    /// `Content-Length` of the upload request, multipart framing included.
+    bytes_total: Option<u64>,
+    bytes_received: AtomicU64,
+    extraction: Arc<archive::Progress>,
+    stage: Mutex<Stage>,
+    aborted: AtomicBool,
+    abort: Notify,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadInfo {
+    pub id: String,
+    pub project: String,
+    pub run_id: Option<u64>,
+    pub stage: Stage,
+    pub bytes_total: Option<u64>,
+    pub bytes_received: u64,
+    /// Uncompressed size of the archive, known once extraction starts.
+    pub bytes_to_extract: Option<u64>,
+    pub bytes_extracted: u64,
+    pub started_at: u64,
+    pub elapsed_secs: u64,
+}
+
+impl UploadSession {
+    pub fn set_stage(&self, stage: Stage) {
+        *self.stage.lock().unwrap() = stage;
+    }
+
+    pub fn set_run_id(&self, run_id: u64) {
+        self.run_id.store(run_id, Ordering::Relaxed);
+    }
+
+    /// Reserved run id (0 = not yet).
+    pub fn run_id(&self) -> Option<u64> {
+        Some(self.run_id.load(Ordering::Relaxed)).filter(|id| *id != 0)
+    }
+
+    /// Archive bytes received so far.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub fn add_bytes(&self, n: u64) {
+        self.bytes_received.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Progress handle for `archive::extract_with_progress`.
+    pub fn extraction(&self) -> Arc<archive::Progress> {
+        self.extraction.clone()
+    }
+
+    /// Resolves once the upload is aborted via the API.
+    pub async fn aborted(&self) {
+        loop {
+            let notified = self.abort.notified();
+            if self.aborted.load(Ordering::Acquire) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn info(&self) -> UploadInfo {
+        UploadInfo {
+            id: self.id.clone(),
+            project: self.project.clone(),
+            run_id: self.run_id(),
+            stage: *self.stage.lock().unwrap(),
+            bytes_total: self.bytes_total,
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_to_extract: Some(self.extraction.total.load(Ordering::Relaxed)).filter(|n| *n != 0),
+            bytes_extracted: self.extraction.done.load(Ordering::Relaxed),
+            started_at: self.started_at,
+            elapsed_secs: now_unix().saturating_sub(self.started_at),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UploadRegistry {
+    sessions: DashMap<String, Arc<UploadSession>>,
+}
+
+/// Removes the session from the registry when the upload ends (or its
+/// future is dropped).
+pub struct UploadGuard {
+    registry: Arc<UploadRegistry>,
+    pub session: Arc<UploadSession>,
+}
+
+impl Drop for UploadGuard {
+    fn drop(&mut self) {
+        self.registry.sessions.remove(&self.session.id);
+    }
+}
+
+impl UploadRegistry {
+    /// None when an upload with this id is already in flight.
+    pub fn start(self: &Arc<Self>, id: String, project: &str, bytes_total: Option<u64>) -> Option<UploadGuard> {
+        let Entry::Vacant(slot) = self.sessions.entry(id.clone()) else {
+            return None;
+        };
+        let session = Arc::new(UploadSession {
+            id,
+            project: project.to_string(),
+            started_at: now_unix(),
+            run_id: AtomicU64::new(0),
+            bytes_total,
+            bytes_received: AtomicU64::new(0),
+            extraction: Arc::default(),
+            stage: Mutex::new(Stage::WaitingLock),
+            aborted: AtomicBool::new(false),
+            abort: Notify::new(),
+        });
+        slot.insert(session.clone());
+        Some(UploadGuard { registry: self.clone(), session })
+    }
+
+    pub fn get(&self, id: &str) -> Option<UploadInfo> {
+        self.sessions.get(id).map(|s| s.info())
+    }
+
+    /// Oldest first.
+    pub fn list(&self) -> Vec<UploadInfo> {
+        let mut out: Vec<UploadInfo> = self.sessions.iter().map(|s| s.info()).collect();
+        out.sort_by_key(|u| u.started_at);
+        out
+    }
+
+    /// Signals the upload to stop; false if there is no such upload.
+    pub fn abort(&self, id: &str) -> bool {
+        let Some(session) = self.sessions.get(id).map(|s| s.clone()) else {
+            return false;
+        };
+        session.aborted.store(true, Ordering::Release);
+        session.abort.notify_waiters();
+        true
+    }
+}
@@ -0,0 +1,205 @@
+//! Usage ledger for chargeback on shared instances
+//! (`DATA_DIR/usage.log`, one JSON object per line): every accepted upload
+//! with its size and every report generation with its duration.
+//!
+//! Uploads are attributed to the bearer token they came with — the
+//! service does not check it, an auth proxy in front of it does — by a
+//! fingerprint, so the log never holds the token itself. A generation
+//! counts against the token that uploaded the run.
+
+use anyhow::Context;
+use axum::http::{header, HeaderMap};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::util::{format_date, now_unix};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Event {
+    Upload { at: u64, project: String, run_id: u64, token: Option<String>, bytes: u64 },
+    Generation { at: u64, project: String, run_id: u64, secs: f64, ok: bool },
+}
+
+fn log_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("usage.log")
+}
+
+/// `sha256:` and the first 12 hex digits of the `Authorization: Bearer`
+/// token; `printf %s "$TOKEN" | sha256sum | cut -c1-12` gives the same.
+pub fn token_of(headers: &HeaderMap) -> Option<String> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())?;
+    let digest = format!("{:x}", Sha256::digest(token.as_bytes()));
+    Some(format!("sha256:{}", &digest[..12]))
+}
+
+async fn append(data_dir: &Path, event: &Event) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    let path = log_path(data_dir);
+    let mut f = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .with_context(|| format!("open {}", path.display()))?;
+    f.write_all(&line).await?;
+    f.flush().await?;
+    Ok(())
+}
+
+pub async fn record_upload(data_dir: &Path, project: &str, run_id: u64, token: Option<String>, bytes: u64) {
+    let event = Event::Upload { at: now_unix(), project: project.to_string(), run_id, token, bytes };
+    if let Err(e) = append(data_dir, &event).await {
+        tracing::warn!(error=%format!("{e:#}"), "write usage log failed");
+    }
+}
+
+pub async fn record_generation(data_dir: &Path, project: &str, run_id: u64, secs: f64, ok: bool) {
+    let secs = (secs * 1000.0).round() / 1000.0;
+    let event = Event::Generation { at: now_unix(), project: project.to_string(), run_id, secs, ok };
+    if let Err(e) = append(data_dir, &event).await {
+        tracing::warn!(error=%format!("{e:#}"), "write usage log failed");
+    }
+}
+
+/// `30d`, `12h`; at most a year.
+pub fn parse_window(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (n, unit) = s.split_at(s.len().checked_sub(1)?);
+    let n: u64 = n.parse().ok().filter(|n| *n > 0)?;
+    let secs = match unit {
+        "d" => n.checked_mul(86400)?,
+        "h" => n.checked_mul(3600)?,
+        _ => return None,
+    };
+    (secs <= 366 * 86400).then_some(secs)
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Counters {
+    pub uploads: u64,
+    pub upload_bytes: u64,
+    pub generations: u64,
+    pub failed_generations: u64,
+    pub generation_minutes: f64,
+}
+
+impl Counters {
+    fn add(&mut self, event: &Event) {
+        match event {
+            Event::Upload { bytes, .. } => {
+                self.uploads += 1;
+                self.upload_bytes += bytes;
+            }
+            Event::Generation { secs, ok, .. } => {
+                self.generations += 1;
+                self.failed_generations += u64::from(!ok);
+                self.generation_minutes += secs / 60.0;
+            }
+        }
+    }
+
+    fn round(&mut self) {
+        self.generation_minutes = (self.generation_minutes * 100.0).round() / 100.0;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenUsage {
+    /// None for uploads without a bearer token.
+    pub token: Option<String>,
+    #[serde(flatten)]
+    pub counters: Counters,
+    pub projects: BTreeMap<String, Counters>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DayUsage {
+    /// `YYYY-MM-DD`, UTC.
+    pub date: String,
+    #[serde(flatten)]
+    pub counters: Counters,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub window: String,
+    pub from: u64,
+    pub to: u64,
+    pub totals: Counters,
+    pub tokens: Vec<TokenUsage>,
+    pub projects: BTreeMap<String, Counters>,
+    pub daily: Vec<DayUsage>,
+}
+
+/// Usage of the last `window_secs`, by token, by project and by day.
+pub async fn report(data_dir: &Path, window: &str, window_secs: u64) -> anyhow::Result<Usage> {
+    let text = match fs::read_to_string(log_path(data_dir)).await {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context("read usage log"),
+    };
+    let events: Vec<Event> = text.lines().filter_map(|l| serde_json::from_str(l).ok()).collect();
+
+    // загрузка прогона могла случиться раньше окна, поэтому по всему журналу
+    let uploader: HashMap<(&str, u64), Option<&str>> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Upload { project, run_id, token, .. } => Some(((project.as_str(), *run_id), token.as_deref())),
+            Event::Generation { .. } => None,
+        })
+        .collect();
+
+    let to = now_unix();
+    let from = to.saturating_sub(window_secs);
+    let mut totals = Counters::default();
+    let mut tokens: BTreeMap<Option<String>, TokenUsage> = BTreeMap::new();
+    let mut projects: BTreeMap<String, Counters> = BTreeMap::new();
+    let mut daily: BTreeMap<u64, Counters> = BTreeMap::new();
+    for event in &events {
+        let (at, project, token) = match event {
+            Event::Upload { at, project, token, .. } => (*at, project, token.as_deref()),
+            Event::Generation { at, project, run_id, .. } => {
+                (*at, project, uploader.get(&(project.as_str(), *run_id)).copied().flatten())
+            }
+        };
+        if at < from {
+            continue;
+        }
+        totals.add(event);
+        projects.entry(project.clone()).or_default().add(event);
+        daily.entry(at / 86400).or_default().add(event);
+        let t = tokens.entry(token.map(str::to_string)).or_insert_with(|| TokenUsage {
+            token: token.map(str::to_string),
+            counters: Counters::default(),
+            projects: BTreeMap::new(),
+        });
+        t.counters.add(event);
+        t.projects.entry(project.clone()).or_default().add(event);
+    }
+
+    totals.round();
+    let mut tokens: Vec<TokenUsage> = tokens.into_values().collect();
+    for t in &mut tokens {
+        t.counters.round();
+        t.projects.values_mut().for_each(Counters::round);
+    }
+    projects.values_mut().for_each(Counters::round);
+    let daily = daily
+        .into_iter()
+        .map(|(day, mut counters)| {
+            counters.round();
+            DayUsage { date: format_date(day * 86400)[..10].to_string(), counters }
+        })
+        .collect();
+    Ok(Usage { window: window.to_string(), from, to, totals, tokens, projects, daily })
+}
@@ -1,12 +1,355 @@
+use anyhow::Context;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+
+/// Names that would collide with routes (`/api/v1/projects/summary`,
+/// `/ui/{project}/latest/`) or with the data layout.
+const RESERVED_NAMES: &[&str] = &[
+    "api", "cache", "latest", "projects", "runs", "stale", "summary", "ui", "worker",
+];
+
 pub fn sanitize_name(s: &str) -> Option<String> {
+    validate_name(s).ok()
+}
+
+/// `sanitize_name` with the reason for a rejection.
+pub fn validate_name(s: &str) -> Result<String, &'static str> {
     if s.is_empty() || s.len() > 80 {
-        return None;
+        return Err("must be 1-80 characters long");
     }
-    if s.chars()
+    if !s
+        .chars()
         .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
     {
-        Some(s.to_string())
+        return Err("only ASCII letters, digits, '-', '_' and '.' are allowed");
+    }
+    // ".", "..", "..." и скрытые каталоги; Windows молча срезает точку в конце
+    if s.starts_with('.') || s.ends_with('.') {
+        return Err("must not start or end with a dot");
+    }
+    // имена проектов — это каталоги; держим их переносимыми на Windows
+    if is_windows_reserved(s) {
+        return Err("reserved device name");
+    }
+    Ok(s.to_string())
+}
+
+/// Names of new projects and of launches: `validate_name` plus the
+/// reserved words.
+pub fn validate_new_project_name(s: &str) -> Result<String, &'static str> {
+    let name = validate_name(s)?;
+    if RESERVED_NAMES.contains(&name.to_ascii_lowercase().as_str()) {
+        return Err("reserved name");
+    }
+    Ok(name)
+}
+
+/// Project named in a request: [`validate_new_project_name`], except that
+/// a project created before those rules (`latest`, `con`, `foo.`) can
+/// still be opened, regenerated and deleted.
+pub fn validate_project_name(volumes: &crate::volumes::Volumes, s: &str) -> Result<String, &'static str> {
+    let err = match validate_new_project_name(s) {
+        Ok(name) => return Ok(name),
+        Err(e) => e,
+    };
+    if is_legacy_project_name(s) && crate::storage::project_dir(volumes, s).is_dir() {
+        return Ok(s.to_string());
+    }
+    Err(err)
+}
+
+/// Rules project names followed before the reserved words and the
+/// Windows checks; never `.` or `..`.
+pub fn is_legacy_project_name(s: &str) -> bool {
+    (1..=80).contains(&s.len())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && s != "."
+        && s != ".."
+}
+
+/// Device names Windows will not create as files, with any extension
+/// (`con`, `NUL.txt`, `com1.json`).
+pub fn is_windows_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or("").trim_end().to_ascii_uppercase();
+    match stem.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" | "CONIN$" | "CONOUT$" => true,
+        _ => {
+            stem.len() == 4
+                && (stem.starts_with("COM") || stem.starts_with("LPT"))
+                && matches!(stem.as_bytes()[3], b'1'..=b'9')
+        }
+    }
+}
+
+/// Extended-length form of an absolute Windows path (`\\?\C:\data`), so
+/// deep report trees are not cut off by `MAX_PATH`. Unchanged elsewhere.
+pub fn long_path(p: PathBuf) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let abs = std::path::absolute(&p).unwrap_or(p);
+        let s = abs.to_string_lossy().replace('/', "\\");
+        if s.starts_with(r"\\?\") {
+            return abs;
+        }
+        if let Some(unc) = s.strip_prefix(r"\\") {
+            return PathBuf::from(format!(r"\\?\UNC\{unc}"));
+        }
+        PathBuf::from(format!(r"\\?\{s}"))
+    }
+    #[cfg(not(windows))]
+    p
+}
+
+/// Escapes text for HTML/SVG content and attribute values.
+pub fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn clip(s: &str, max: usize) -> String {
+    if s.len() <= max {
+        s.to_string()
     } else {
-        None
+        let mut end = max;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}...[truncated {} bytes]", &s[..end], s.len().saturating_sub(end))
+    }
+}
+
+/// Current time as unix seconds.
+pub fn now_unix() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    #[cfg(feature = "test-hooks")]
+    let now = now.saturating_add_signed(crate::test_hooks::clock_skew());
+    now
+}
+
+/// Shell-style match with `*` (any run of chars) and `?` (one char).
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    let (p, s): (Vec<char>, Vec<char>) = (pattern.chars().collect(), s.chars().collect());
+    let (mut pi, mut si) = (0, 0);
+    // позиция последней '*' и сколько символов она уже съела
+    let mut star: Option<(usize, usize)> = None;
+
+    while si < s.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == s[si]) {
+            pi += 1;
+            si += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, si));
+            pi += 1;
+        } else if let Some((sp, ss)) = star {
+            pi = sp + 1;
+            si = ss + 1;
+            star = Some((sp, ss + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+/// Unix seconds from `1700000000`, `2024-01-31` (midnight UTC) or
+/// `2024-01-31T12:30[:00][Z]` (UTC).
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        return s.parse().ok();
+    }
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((d, t)) => (d, Some(t.trim_end_matches('Z'))),
+        None => (s, None),
+    };
+
+    let mut parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    let secs = match time {
+        Some(t) => {
+            let mut hms = t.splitn(3, ':').map(|p| p.parse::<u64>().ok());
+            let h = hms.next()??;
+            let min = hms.next()??;
+            let sec = hms.next().unwrap_or(Some(0))?;
+            if h > 23 || min > 59 || sec > 60 {
+                return None;
+            }
+            h * 3600 + min * 60 + sec
+        }
+        None => 0,
+    };
+
+    // days_from_civil (Howard Hinnant)
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = if m > 2 { m - 3 } else { m + 9 };
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u64::try_from(days).ok().map(|d| d * 86400 + secs)
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_date(ts: u64) -> String {
+    let days = (ts / 86400) as i64;
+    let secs = ts % 86400;
+    // civil_from_days (Howard Hinnant)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{y:04}-{m:02}-{d:02} {:02}:{:02}", secs / 3600, secs % 3600 / 60)
+}
+
+/// Moves a directory into place. Across filesystems (`TMP_DIR` on tmpfs or
+/// another disk) it is copied next to `dst` first, so `dst` still appears
+/// in one rename.
+pub async fn move_dir(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    match tokio::fs::rename(src, dst).await {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e).with_context(|| format!("rename {} -> {}", src.display(), dst.display())),
+    }
+
+    let incoming = dst.with_extension("incoming");
+    let _ = tokio::fs::remove_dir_all(&incoming).await;
+    let (from, to) = (src.to_path_buf(), incoming.clone());
+    tokio::task::spawn_blocking(move || copy_dir(&from, &to))
+        .await
+        .context("join copy task")??;
+    tokio::fs::rename(&incoming, dst)
+        .await
+        .with_context(|| format!("rename {} -> {}", incoming.display(), dst.display()))?;
+    let _ = tokio::fs::remove_dir_all(src).await;
+    Ok(())
+}
+
+pub fn copy_dir(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dst).with_context(|| format!("create {}", dst.display()))?;
+    for entry in std::fs::read_dir(src).with_context(|| format!("read dir {}", src.display()))? {
+        let entry = entry?;
+        let to = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            std::fs::copy(entry.path(), &to).with_context(|| format!("copy to {}", to.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Fresh staging dir under `TMP_DIR`; the caller removes it.
+pub async fn staging_dir(tmp_dir: &Path, prefix: &str) -> anyhow::Result<PathBuf> {
+    static SEQ: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let seq = SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let dir = tmp_dir.join(format!("{prefix}-{}-{}-{seq}", now_unix(), std::process::id()));
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// Staging dir removed on drop, also when the future holding it is
+/// dropped half-way (an aborted upload).
+pub struct StagingGuard(PathBuf);
+
+impl StagingGuard {
+    pub fn new(dir: PathBuf) -> Self {
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for StagingGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Removes staging leftovers of a previous process (crash, kill -9).
+/// Only our own prefixes: `TMP_DIR` may be shared, e.g. `/tmp`.
+pub async fn clean_tmp_dir(tmp_dir: &Path) {
+    let Ok(mut rd) = tokio::fs::read_dir(tmp_dir).await else { return };
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !["upload-", "report-", "export-"].iter().any(|p| name.starts_with(p)) {
+            continue;
+        }
+        let path = entry.path();
+        let _ = match entry.file_type().await {
+            Ok(t) if t.is_dir() => tokio::fs::remove_dir_all(&path).await,
+            _ => tokio::fs::remove_file(&path).await,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_reserved_names() {
+        for name in ["con", "CON", "nul.txt", "Aux.tar.gz", "com1", "LPT9.json", "conin$", "prn "] {
+            assert!(is_windows_reserved(name), "{name}");
+        }
+        for name in ["console", "com0", "com10", "lpt", "nul-report", "my.con", "c0m1"] {
+            assert!(!is_windows_reserved(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn names() {
+        for name in ["backend", "my-app_2", "v1.2", &"a".repeat(80)] {
+            assert_eq!(validate_name(name).as_deref(), Ok(name));
+        }
+        assert!(validate_name("").is_err());
+        assert!(validate_name(&"a".repeat(81)).is_err());
+        for name in ["a/b", "a b", "имя", "a\\b", "a:b"] {
+            assert!(validate_name(name).is_err(), "{name}");
+        }
+        for name in [".", "..", ".hidden", "trailing."] {
+            assert_eq!(validate_name(name), Err("must not start or end with a dot"), "{name}");
+        }
+        assert_eq!(validate_name("COM3"), Err("reserved device name"));
+        assert_eq!(validate_name("nul.json"), Err("reserved device name"));
+    }
+
+    #[test]
+    fn existing_projects_keep_old_names() {
+        let data_dir = std::env::temp_dir().join(format!("allure-names-test-{}", uuid::Uuid::new_v4()));
+        let volumes = crate::volumes::Volumes::new(&data_dir, &[]);
+        for name in ["latest", "foo."] {
+            assert!(validate_project_name(&volumes, name).is_err(), "{name}");
+            std::fs::create_dir_all(crate::storage::project_dir(&volumes, name)).unwrap();
+            assert_eq!(validate_project_name(&volumes, name).as_deref(), Ok(name));
+            assert!(validate_new_project_name(name).is_err(), "{name}");
+        }
+        assert!(!is_legacy_project_name(".."));
+        assert!(validate_project_name(&volumes, "..").is_err());
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_paths() {
+        assert_eq!(long_path(PathBuf::from(r"C:\data")), PathBuf::from(r"\\?\C:\data"));
+        assert_eq!(long_path(PathBuf::from("C:/data/projects")), PathBuf::from(r"\\?\C:\data\projects"));
+        assert_eq!(long_path(PathBuf::from(r"\\?\C:\data")), PathBuf::from(r"\\?\C:\data"));
+        assert_eq!(long_path(PathBuf::from(r"\\server\share\data")), PathBuf::from(r"\\?\UNC\server\share\data"));
+        assert!(long_path(PathBuf::from("data")).to_string_lossy().starts_with(r"\\?\"));
     }
 }
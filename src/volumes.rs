@@ -0,0 +1,293 @@
+//! Projects spread over several data volumes (`DATA_VOLUMES`). Each
+//! project lives wholly on one volume as `<volume>/projects/<project>`;
+//! everything else (jobs, audit log, aliases, `TMP_DIR`) stays on
+//! `DATA_DIR`, which is itself the `default` volume.
+//!
+//! A project is found where it already is; a new one goes to the volume
+//! it is pinned to in `DATA_DIR/volumes.json`, otherwise to the one picked
+//! by rendezvous hashing of its name. Adding a volume changes the hash
+//! target of only the projects that now prefer it, and they move only on
+//! an explicit rebalance. [`crate::storage::project_dir`] resolves all of
+//! this, so the rest of the service never sees volumes.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::{storage, util};
+
+pub const DEFAULT_VOLUME: &str = "default";
+
+/// An extra volume from `DATA_VOLUMES`.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    pub name: String,
+    pub root: PathBuf,
+}
+
+impl FromStr for Volume {
+    type Err = anyhow::Error;
+
+    /// `ssd=/mnt/ssd/allure`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, root)) = s.split_once('=') else {
+            anyhow::bail!("expected name=path, got {s:?}");
+        };
+        let name = util::validate_name(name.trim()).map_err(|e| anyhow::anyhow!("volume name {name:?}: {e}"))?;
+        if name == DEFAULT_VOLUME {
+            anyhow::bail!("{DEFAULT_VOLUME:?} is DATA_DIR itself");
+        }
+        let root = PathBuf::from(root.trim());
+        if !root.is_absolute() {
+            anyhow::bail!("volume path must be absolute, got {root:?}");
+        }
+        Ok(Self { name, root: util::long_path(root) })
+    }
+}
+
+/// `DATA_DIR/volumes.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct PinsFile {
+    /// project → volume name.
+    pins: BTreeMap<String, String>,
+}
+
+pub struct Volumes {
+    /// `[0]` is `DATA_DIR`.
+    volumes: Vec<Volume>,
+    /// Where each known project is now (index into `volumes`).
+    placed: RwLock<HashMap<String, usize>>,
+    pins: RwLock<BTreeMap<String, String>>,
+}
+
+fn pins_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("volumes.json")
+}
+
+/// Project dirs under `<root>/projects`.
+fn scan(root: &Path) -> Vec<String> {
+    let Ok(rd) = std::fs::read_dir(root.join("projects")) else {
+        return Vec::new();
+    };
+    rd.filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|e| e.file_name().into_string().ok())
+        // скрытые — недокопированные переносы, проектов с точкой в начале не бывает
+        .filter(|n| !n.starts_with('.'))
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct VolumeInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub projects: usize,
+    pub free_bytes: Option<u64>,
+}
+
+/// A project not on its target volume.
+#[derive(Debug, Clone, Serialize)]
+pub struct Move {
+    pub project: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl Volumes {
+    /// Startup only: finds the projects on every volume. Without extra
+    /// volumes `DATA_DIR` is the only one and nothing is scanned.
+    pub fn new(data_dir: &Path, extra: &[Volume]) -> Self {
+        let mut volumes = vec![Volume { name: DEFAULT_VOLUME.to_string(), root: data_dir.to_path_buf() }];
+        if extra.is_empty() {
+            return Self { volumes, placed: RwLock::default(), pins: RwLock::default() };
+        }
+        volumes.extend(extra.iter().cloned());
+
+        let pins = match std::fs::read(pins_path(data_dir)) {
+            Ok(bytes) => serde_json::from_slice::<PinsFile>(&bytes).map(|f| f.pins).unwrap_or_else(|e| {
+                warn!(error=%e, "volumes.json is broken, volume pins are ignored");
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+
+        let mut placed: HashMap<String, usize> = HashMap::new();
+        for (i, v) in volumes.iter().enumerate() {
+            for project in scan(&v.root) {
+                if let Some(&first) = placed.get(&project) {
+                    // прерванный перенос: остаётся копия, найденная первой
+                    warn!(project=%project, kept=%volumes[first].name, ignored=%v.name, "project found on two volumes");
+                    continue;
+                }
+                placed.insert(project, i);
+            }
+        }
+        info!(volumes = volumes.len(), projects = placed.len(), "data volumes");
+
+        Self { volumes, placed: RwLock::new(placed), pins: RwLock::new(pins) }
+    }
+
+    /// `DATA_DIR`, the `default` volume.
+    pub fn data_dir(&self) -> &Path {
+        &self.volumes[0].root
+    }
+
+    /// False when only `DATA_DIR` is in use.
+    pub fn configured(&self) -> bool {
+        self.volumes.len() > 1
+    }
+
+    /// Root holding `projects/<project>`.
+    pub fn project_root(&self, project: &str) -> &Path {
+        &self.volumes[self.index_of(project)].root
+    }
+
+    /// Every root that may hold projects.
+    pub fn roots(&self) -> impl Iterator<Item = &Path> {
+        self.volumes.iter().map(|v| v.root.as_path())
+    }
+
+    /// Records where a newly created project went.
+    pub fn remember(&self, project: &str) {
+        if self.configured() {
+            let i = self.index_of(project);
+            self.placed.write().unwrap().insert(project.to_string(), i);
+        }
+    }
+
+    /// A deleted project is placed anew when it comes back.
+    pub fn forget(&self, project: &str) {
+        self.placed.write().unwrap().remove(project);
+    }
+
+    fn index_of(&self, project: &str) -> usize {
+        match self.placed.read().unwrap().get(project) {
+            Some(&i) => i,
+            None => self.target(project),
+        }
+    }
+
+    fn index_by_name(&self, name: &str) -> Option<usize> {
+        self.volumes.iter().position(|v| v.name == name)
+    }
+
+    /// Pinned volume, or the rendezvous-hash winner.
+    fn target(&self, project: &str) -> usize {
+        if let Some(i) = self.pins.read().unwrap().get(project).and_then(|n| self.index_by_name(n)) {
+            return i;
+        }
+        let score = |v: &Volume| {
+            let digest = Sha256::digest(format!("{}\0{project}", v.name).as_bytes());
+            u64::from_be_bytes(digest[..8].try_into().expect("sha256 is 32 bytes"))
+        };
+        (0..self.volumes.len()).max_by_key(|&i| score(&self.volumes[i])).unwrap_or(0)
+    }
+
+    pub fn name_of(&self, project: &str) -> &str {
+        &self.volumes[self.index_of(project)].name
+    }
+
+    pub fn list(&self) -> Vec<VolumeInfo> {
+        let placed = self.placed.read().unwrap();
+        self.volumes
+            .iter()
+            .enumerate()
+            .map(|(i, v)| VolumeInfo {
+                name: v.name.clone(),
+                path: v.root.clone(),
+                projects: placed.values().filter(|&&p| p == i).count(),
+                free_bytes: crate::integrity::free_bytes(&v.root),
+            })
+            .collect()
+    }
+
+    pub fn pins(&self) -> BTreeMap<String, String> {
+        self.pins.read().unwrap().clone()
+    }
+
+    /// Pins `project` to `volume`, or unpins it with None. Does not move it.
+    pub async fn set_pin(&self, data_dir: &Path, project: &str, volume: Option<&str>) -> anyhow::Result<()> {
+        if let Some(name) = volume {
+            if self.index_by_name(name).is_none() {
+                anyhow::bail!("unknown volume {name:?}");
+            }
+        }
+        let snapshot = {
+            let mut pins = self.pins.write().unwrap();
+            match volume {
+                Some(name) => pins.insert(project.to_string(), name.to_string()),
+                None => pins.remove(project),
+            };
+            pins.clone()
+        };
+        storage::write_json(&pins_path(data_dir), &PinsFile { pins: snapshot }).await
+    }
+
+    /// Projects that are not where their pin or hash says.
+    pub fn plan(&self) -> Vec<Move> {
+        let placed = self.placed.read().unwrap().clone();
+        let mut moves: Vec<Move> = placed
+            .into_iter()
+            .filter_map(|(project, from)| {
+                let to = self.target(&project);
+                (from != to).then(|| Move {
+                    from: self.volumes[from].name.clone(),
+                    to: self.volumes[to].name.clone(),
+                    project,
+                })
+            })
+            .collect();
+        moves.sort_by(|a, b| a.project.cmp(&b.project));
+        moves
+    }
+
+    /// Moves `project` to its target volume; false when it is there
+    /// already. The caller holds the project lock. Readers keep using the
+    /// old copy until the new one is complete.
+    pub async fn relocate(&self, project: &str) -> anyhow::Result<bool> {
+        let (from, to) = (self.index_of(project), self.target(project));
+        if from == to {
+            return Ok(false);
+        }
+        let src = self.volumes[from].root.join("projects").join(project);
+        let parent = self.volumes[to].root.join("projects");
+        let dst = parent.join(project);
+        tokio::fs::create_dir_all(&parent).await.with_context(|| format!("create {}", parent.display()))?;
+        if dst.exists() {
+            anyhow::bail!("{} already exists", dst.display());
+        }
+
+        if src.is_dir() {
+            match tokio::fs::rename(&src, &dst).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                    let incoming = parent.join(format!(".{project}.incoming"));
+                    let _ = tokio::fs::remove_dir_all(&incoming).await;
+                    let (a, b) = (src.clone(), incoming.clone());
+                    let copied = tokio::task::spawn_blocking(move || util::copy_dir(&a, &b))
+                        .await
+                        .context("join copy task")?;
+                    if let Err(e) = copied {
+                        let _ = tokio::fs::remove_dir_all(&incoming).await;
+                        return Err(e);
+                    }
+                    tokio::fs::rename(&incoming, &dst)
+                        .await
+                        .with_context(|| format!("rename {} -> {}", incoming.display(), dst.display()))?;
+                }
+                Err(e) => return Err(e).with_context(|| format!("rename {} -> {}", src.display(), dst.display())),
+            }
+        }
+        self.placed.write().unwrap().insert(project.to_string(), to);
+        // копия на старом томе больше не видна — удаляем после переключения
+        let _ = tokio::fs::remove_dir_all(&src).await;
+        info!(project=%project, from=%self.volumes[from].name, to=%self.volumes[to].name, "project moved to another volume");
+        Ok(true)
+    }
+}
@@ -0,0 +1,284 @@
+//! Remote generation workers. With `WORKER_TOKEN` set the primary does not
+//! run allure itself: generations are queued and workers (`ROLE=worker`)
+//! claim them over HTTP, download the results, generate locally and upload
+//! the report back.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{oneshot, Notify};
+use tracing::{info, warn};
+
+use crate::{
+    allure::{self, Generation},
+    executor::RemoteExecution,
+    pipeline,
+    settings::ProjectSettings,
+    state::AppState,
+    storage,
+    archive::{self, Limits},
+    util::{self, now_unix},
+};
+
+/// How long a worker's claim request waits for a job before returning 204.
+const CLAIM_WAIT_SECS: u64 = 25;
+/// Pause after a failed poll (primary unreachable etc.).
+const RETRY_SECS: u64 = 5;
+
+type JobResult = Result<RemoteExecution, String>;
+
+struct Job {
+    id: String,
+    project: String,
+    run_id: u64,
+    settings: ProjectSettings,
+    done: oneshot::Sender<JobResult>,
+}
+
+struct Claimed {
+    job: Job,
+    worker: String,
+    started_at: u64,
+    lease_until: u64,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    pending: VecDeque<Job>,
+    claimed: HashMap<String, Claimed>,
+}
+
+/// Generation jobs waiting for (or held by) remote workers. In-memory: the
+/// uploads waiting on them do not survive a restart either.
+#[derive(Default)]
+pub struct WorkQueue {
+    inner: Mutex<QueueInner>,
+    notify: Notify,
+    seq: AtomicU64,
+}
+
+/// What a worker gets from `POST /api/v1/worker/claim`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClaimedJob {
+    pub job_id: String,
+    pub project: String,
+    pub run_id: u64,
+    /// Worker builds the report config with its own `PLUGINS_DIR`.
+    pub settings: ProjectSettings,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FailReq {
+    pub error: String,
+}
+
+impl WorkQueue {
+    fn push(&self, project: &str, run_id: u64, settings: ProjectSettings) -> (String, oneshot::Receiver<JobResult>) {
+        let id = format!("{}-{}", now_unix(), self.seq.fetch_add(1, Ordering::Relaxed));
+        let (done, rx) = oneshot::channel();
+        self.inner.lock().unwrap().pending.push_back(Job {
+            id: id.clone(),
+            project: project.to_string(),
+            run_id,
+            settings,
+            done,
+        });
+        self.notify.notify_waiters();
+        (id, rx)
+    }
+
+    fn try_claim(&self, worker: &str, lease_secs: u64) -> Option<ClaimedJob> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = now_unix();
+
+        // молчащий воркер теряет задачу — её заберёт следующий
+        let expired: Vec<String> = inner
+            .claimed
+            .iter()
+            .filter(|(_, c)| c.lease_until < now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            if let Some(c) = inner.claimed.remove(&id) {
+                warn!(job_id=%id, worker=%c.worker, project=%c.job.project, run_id=c.job.run_id, "worker lease expired, requeueing");
+                inner.pending.push_front(c.job);
+            }
+        }
+
+        let job = inner.pending.pop_front()?;
+        let claimed = ClaimedJob {
+            job_id: job.id.clone(),
+            project: job.project.clone(),
+            run_id: job.run_id,
+            settings: job.settings.clone(),
+        };
+        inner.claimed.insert(
+            job.id.clone(),
+            Claimed { job, worker: worker.to_string(), started_at: now, lease_until: now + lease_secs },
+        );
+        Some(claimed)
+    }
+
+    /// Waits up to `wait` for a pending job.
+    pub async fn claim(&self, worker: &str, lease_secs: u64, wait: Duration) -> Option<ClaimedJob> {
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            let notified = self.notify.notified();
+            if let Some(job) = self.try_claim(worker, lease_secs) {
+                return Some(job);
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return None;
+            }
+        }
+    }
+
+    /// Project and run of a job currently held by a worker.
+    pub fn claimed_run(&self, job_id: &str) -> Option<(String, u64)> {
+        let inner = self.inner.lock().unwrap();
+        inner.claimed.get(job_id).map(|c| (c.job.project.clone(), c.job.run_id))
+    }
+
+    /// Finishes a claimed job; false when it is unknown (e.g. lease expired).
+    pub fn complete(&self, job_id: &str, result: Result<(), String>) -> bool {
+        let Some(c) = self.inner.lock().unwrap().claimed.remove(job_id) else {
+            return false;
+        };
+        let result = result.map(|()| RemoteExecution {
+            backend: "worker".into(),
+            name: c.worker,
+            started_at: c.started_at,
+            finished_at: now_unix(),
+        });
+        let _ = c.job.done.send(result);
+        true
+    }
+
+    fn cancel(&self, job_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.pending.retain(|j| j.id != job_id);
+        inner.claimed.remove(job_id);
+    }
+}
+
+/// Primary side: queues the run and waits until a worker uploads its report.
+pub async fn dispatch(
+    state: &AppState,
+    project: &str,
+    run_id: u64,
+    settings: &ProjectSettings,
+) -> Result<Generation, String> {
+    let (job_id, rx) = state.work_queue.push(project, run_id, settings.clone());
+    info!(project=%project, run_id=run_id, job_id=%job_id, "generation queued for remote worker");
+
+    let timeout = state.config.worker_job_timeout_secs;
+    match tokio::time::timeout(Duration::from_secs(timeout), rx).await {
+        Ok(Ok(result)) => result.map(|remote| Generation { remote: Some(remote), usage: None }),
+        Ok(Err(_)) => Err("worker job dropped".into()),
+        Err(_) => {
+            state.work_queue.cancel(&job_id);
+            Err(format!("no worker finished the generation within {timeout}s"))
+        }
+    }
+}
+
+/// Worker main loop (`ROLE=worker`).
+pub async fn run(state: AppState) -> anyhow::Result<()> {
+    info!(primary=?state.config.primary_url, worker=%state.config.worker_name, "worker started");
+    loop {
+        if let Err(e) = work_once(&state).await {
+            warn!(error=%format!("{e:#}"), "worker poll failed");
+            tokio::time::sleep(Duration::from_secs(RETRY_SECS)).await;
+        }
+    }
+}
+
+async fn work_once(state: &AppState) -> anyhow::Result<()> {
+    let primary = state.config.primary_url.as_deref().context("PRIMARY_URL is not set")?;
+    let token = state.config.worker_token.as_ref().map(|t| t.0.as_str()).unwrap_or_default();
+
+    let resp = state
+        .http
+        .post(format!("{primary}/api/v1/worker/claim?wait={CLAIM_WAIT_SECS}"))
+        .bearer_auth(token)
+        .header("x-worker-name", &state.config.worker_name)
+        .send()
+        .await?
+        .error_for_status()?;
+    if resp.status() == reqwest::StatusCode::NO_CONTENT {
+        return Ok(());
+    }
+    let job: ClaimedJob = resp.json().await.context("parse claimed job")?;
+    let job_url = format!("{primary}/api/v1/worker/jobs/{}", job.job_id);
+    info!(job_id=%job.job_id, project=%job.project, run_id=job.run_id, "claimed generation job");
+
+    // data_dir/worker/<project>/runs/<id>: повторяет раскладку, чтобы метка
+    // контейнера генератора была "<project>-<id>"
+    let work_dir = state
+        .data_dir
+        .join("worker")
+        .join(&job.project)
+        .join("runs")
+        .join(job.run_id.to_string());
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+
+    let result = generate(state, &job, &job_url, token, &work_dir).await;
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+
+    let req = match result {
+        Ok(report_zip) => state.http.put(format!("{job_url}/report")).body(report_zip),
+        Err(e) => {
+            let error = format!("{e:#}");
+            warn!(job_id=%job.job_id, error=%error, "generation failed on worker");
+            state.http.post(format!("{job_url}/fail")).json(&FailReq { error })
+        }
+    };
+    req.bearer_auth(token).send().await?.error_for_status()?;
+    info!(job_id=%job.job_id, "generation job finished");
+    Ok(())
+}
+
+/// Downloads the results, generates and returns the zipped report.
+async fn generate(
+    state: &AppState,
+    job: &ClaimedJob,
+    job_url: &str,
+    token: &str,
+    work_dir: &std::path::Path,
+) -> anyhow::Result<Vec<u8>> {
+    let results_zip = state
+        .http
+        .get(format!("{job_url}/results"))
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    let results_dir = work_dir.join("allure-results");
+    let report_dir = work_dir.join("report");
+    archive::unzip_safely(results_zip.to_vec(), results_dir.clone(), state.config.unzip_limits).await?;
+
+    let config = pipeline::report_config(state, &job.settings)?;
+    allure::generate_report(&state.generator, &results_dir, &report_dir, &config).await?;
+
+    archive::zip_dir(report_dir).await
+}
+
+/// Replaces the run's report with the one uploaded by a worker.
+pub async fn store_report(state: &AppState, project: &str, run_id: u64, zip_bytes: Vec<u8>) -> anyhow::Result<()> {
+    let report_dir = storage::run_dir(&state.volumes, project, run_id).join("report");
+    let staging = util::staging_dir(&state.config.tmp_dir, "report").await?;
+    let result = async {
+        archive::unzip_safely(zip_bytes, staging.clone(), Limits::default()).await?;
+        let _ = tokio::fs::remove_dir_all(&report_dir).await;
+        util::move_dir(&staging, &report_dir).await
+    }
+    .await;
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    result
+}